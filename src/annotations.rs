@@ -0,0 +1,138 @@
+use crate::manifest::RunManifest;
+
+// Images annotated at most, the least severe images being left out
+pub const MAX_ANNOTATIONS: usize = 1000;
+
+// Image with findings, annotated on the GitHub check run, the Bitbucket
+// report or the Azure DevOps job
+#[derive(Debug, Clone)]
+pub struct ImageAnnotation {
+    pub repository_name: String,
+    pub image_digest: String,
+    pub image_tags: Vec<String>,
+    pub counts: Vec<i64>, // Finding count per level of the severity scheme
+    pub failing: bool,    // Over its thresholds and not exempted
+}
+
+impl ImageAnnotation {
+    // Name of the image, e.g. payments-api:v2.3.1, by digest when untagged
+    pub fn image_name(&self) -> String {
+        match self.image_tags.first() {
+            Some(tag) => format!("{}:{}", self.repository_name, tag),
+            None => format!("{}@{}", self.repository_name, self.image_digest),
+        }
+    }
+
+    // Finding counts of the image, e.g. "Critical: 2, High: 5"
+    pub fn counts_text(&self, levels: &[String]) -> String {
+        let counts: Vec<String> = levels
+            .iter()
+            .zip(&self.counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(level, count)| format!("{}: {}", level, count))
+            .collect();
+        counts.join(", ")
+    }
+
+    // Most severe level with findings of the image
+    pub fn top_level<'a>(&self, levels: &'a [String]) -> Option<&'a String> {
+        levels
+            .iter()
+            .zip(&self.counts)
+            .find(|(_, count)| **count > 0)
+            .map(|(level, _)| level)
+    }
+}
+
+// Function to parse a commit given as owner/repo@sha, e.g.
+// "$GITHUB_REPOSITORY@$GITHUB_SHA" in GitHub Actions, into the repository
+// and the lowercase commit SHA
+pub fn parse_commit(value: &str) -> Result<(String, String), String> {
+    let Some((repository, commit)) = value.split_once('@') else {
+        return Err(format!("expected 'owner/repo@sha', got '{}'", value));
+    };
+    let valid_repository = repository
+        .split_once('/')
+        .is_some_and(|(owner, name)| !owner.is_empty() && !name.is_empty() && !name.contains('/'));
+    if !valid_repository {
+        return Err(format!("'{}' is not an owner/repo repository", repository));
+    }
+    if !matches!(commit.len(), 40 | 64) || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a full commit SHA", commit));
+    }
+    Ok((repository.to_string(), commit.to_lowercase()))
+}
+
+// Function to order the images failing first, then the most severe ones,
+// keeping MAX_ANNOTATIONS of them. Returns them with the number left out
+pub fn ordered(annotations: &[ImageAnnotation]) -> (Vec<&ImageAnnotation>, usize) {
+    let mut images: Vec<&ImageAnnotation> = annotations.iter().collect();
+    images.sort_by(|a, b| b.failing.cmp(&a.failing).then(b.counts.cmp(&a.counts)));
+    let omitted = images.len().saturating_sub(MAX_ANNOTATIONS);
+    images.truncate(MAX_ANNOTATIONS);
+    (images, omitted)
+}
+
+// Title of the run, e.g. "3 image(s) over the thresholds"
+pub fn title(manifest: &RunManifest) -> String {
+    match manifest.threshold_violations {
+        0 => format!(
+            "{} finding(s) in {} image(s)",
+            manifest.findings.values().sum::<i64>(),
+            manifest.images
+        ),
+        violations => format!("{} image(s) over the thresholds", violations),
+    }
+}
+
+// Markdown summary of the run, its finding counts per level and what failed
+pub fn summary(manifest: &RunManifest, omitted: usize) -> String {
+    let mut lines = vec![
+        format!(
+            "**{} image(s)** reported by run `{}`",
+            manifest.images, manifest.ids.run_id
+        ),
+        String::new(),
+        "| Level | Findings |".to_string(),
+        "| --- | ---: |".to_string(),
+    ];
+    lines.extend(
+        manifest
+            .findings
+            .iter()
+            .map(|(level, count)| format!("| {} | {} |", level, count)),
+    );
+    lines.push(String::new());
+    for (count, label) in outcome_counts(manifest)
+        .iter()
+        .filter(|(count, _)| *count > 0)
+    {
+        lines.push(format!("- {} {}", count, label));
+    }
+    if omitted > 0 {
+        lines.push(format!(
+            "- {} image(s) with findings not annotated, over the {} annotations of a run",
+            omitted, MAX_ANNOTATIONS
+        ));
+    }
+    lines.join("\n")
+}
+
+// Counts of what gates the run, with their label
+pub fn outcome_counts(manifest: &RunManifest) -> [(u64, &'static str); 4] {
+    [
+        (
+            manifest.threshold_violations,
+            "image(s) over the thresholds",
+        ),
+        (
+            manifest.exempted_images,
+            "image(s) exempted by --exempt-images",
+        ),
+        (
+            manifest.sla_breaches,
+            "finding(s) over their remediation SLA",
+        ),
+        (manifest.errors.len() as u64, "error(s)"),
+    ]
+}
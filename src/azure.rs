@@ -0,0 +1,113 @@
+use crate::annotations::{ordered, summary, title, ImageAnnotation};
+use crate::errors::EXIT_OK;
+use crate::manifest::RunManifest;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+// Type of the report attached to the Azure Pipelines job
+const ATTACHMENT_TYPE: &str = "ecr-scan-report";
+
+// Whether the run is a job of Azure Pipelines, whose agent reads the logging commands
+pub fn in_azure_pipeline() -> bool {
+    std::env::var("TF_BUILD").is_ok_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+// Function to write the Azure Pipelines logging commands of the run on stderr,
+// which the agent reads like stdout so that they do not mix with a report on
+// stdout: one issue per image with findings, the markdown summary of the run
+// uploaded to the build summary, the local report file attached to the job,
+// and the result of the task. Returns the path of the summary
+pub fn write_logging_commands(
+    levels: &[String],
+    annotations: &[ImageAnnotation],
+    manifest: &RunManifest,
+    exit_code: u8,
+    report_path: Option<&str>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let (images, omitted) = ordered(annotations);
+    for image in &images {
+        eprintln!(
+            "##vso[task.logissue type={}]{}",
+            if image.failing { "error" } else { "warning" },
+            escape_message(&format!(
+                "{}: {}",
+                image.image_name(),
+                image.counts_text(levels)
+            ))
+        );
+    }
+
+    // Summary of the run with its images, in the Extensions tab of the build
+    let mut lines = vec![
+        "### ECR image scan".to_string(),
+        String::new(),
+        summary(manifest, omitted),
+    ];
+    if !images.is_empty() {
+        lines.extend([
+            String::new(),
+            "| Image | Findings | Over the thresholds |".to_string(),
+            "| --- | --- | :---: |".to_string(),
+        ]);
+        lines.extend(images.iter().map(|image| {
+            format!(
+                "| {} | {} | {} |",
+                image.image_name(),
+                image.counts_text(levels),
+                if image.failing { "✗" } else { "" }
+            )
+        }));
+    }
+    let directory = std::env::var("AGENT_TEMPDIRECTORY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| std::env::temp_dir());
+    let summary_path = directory.join(format!("ecr-scan-{}.md", manifest.ids.run_id));
+    std::fs::write(&summary_path, lines.join("\n") + "\n")?;
+    eprintln!(
+        "##vso[task.uploadsummary]{}",
+        escape_message(&summary_path.to_string_lossy())
+    );
+
+    // Report file of --output-file, S3 reports stay where they are
+    if let Some(path) = report_path.filter(|path| !path.starts_with("s3://")) {
+        let path = std::fs::canonicalize(path)?;
+        let name = Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        eprintln!(
+            "##vso[task.addattachment type={};name={}]{}",
+            ATTACHMENT_TYPE,
+            escape_property(&name),
+            escape_message(&path.to_string_lossy())
+        );
+    }
+
+    // Images with findings leave the task succeeded with issues
+    let result = match exit_code {
+        EXIT_OK if images.is_empty() => "Succeeded",
+        EXIT_OK => "SucceededWithIssues",
+        _ => "Failed",
+    };
+    eprintln!(
+        "##vso[task.complete result={};]{}",
+        result,
+        escape_message(&title(manifest))
+    );
+    Ok(summary_path)
+}
+
+// Escape the message of a logging command, which ends at the end of the line
+fn escape_message(value: &str) -> String {
+    value
+        .replace('%', "%AZP25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// Escape a property of a logging command, which also ends at ';' or ']'
+fn escape_property(value: &str) -> String {
+    escape_message(value)
+        .replace(';', "%3B")
+        .replace(']', "%5D")
+}
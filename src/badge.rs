@@ -0,0 +1,112 @@
+use crate::runid::RunIds;
+use crate::telemetry::s3_client;
+use aws_config::SdkConfig;
+use aws_sdk_ecr::primitives::DateTime;
+use std::error::Error;
+use std::path::Path;
+
+// Colors of the badge value, as used by shields.io
+const COLOR_CLEAN: &str = "#4c1";
+const COLOR_FINDINGS: &str = "#e05d44";
+const COLOR_UNKNOWN: &str = "#9f9f9f";
+
+// Badge of one repository, from its most recently pushed image
+#[derive(Debug, Clone)]
+pub struct RepositoryBadge {
+    pub repository_name: String,
+    pub pushed_at: Option<DateTime>,
+    // Findings at the badge level, None when the image has no scan findings
+    pub count: Option<i64>,
+}
+
+impl RepositoryBadge {
+    // Render the badge as a flat shields-like SVG image
+    pub fn svg(&self, label: &str) -> String {
+        let (value, color) = match self.count {
+            Some(0) => ("0".to_string(), COLOR_CLEAN),
+            Some(count) => (count.to_string(), COLOR_FINDINGS),
+            None => ("unknown".to_string(), COLOR_UNKNOWN),
+        };
+        // Approximate the text width, Verdana 11px averages 7px per character
+        let label_width = 10 + 7 * label.chars().count();
+        let value_width = 10 + 7 * value.chars().count();
+        let width = label_width + value_width;
+        let label = escape_xml(label);
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+<title>{label}: {value}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="14">{label}</text>
+<text x="{value_x}" y="14">{value}</text>
+</g>
+</svg>
+"##,
+            label_x = label_width / 2,
+            value_x = label_width + value_width / 2,
+        )
+    }
+}
+
+// Function to write one badge per repository to a directory or to an
+// s3://bucket/prefix location, returns the written locations
+pub async fn write_badges(
+    sdk_config: &SdkConfig,
+    destination: &str,
+    label: &str,
+    badges: &[RepositoryBadge],
+    ids: &RunIds,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut locations = Vec::new();
+    if let Some(location) = destination.strip_prefix("s3://") {
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        let client = s3_client(sdk_config);
+        for badge in badges {
+            let key = badge_path(prefix, &badge.repository_name);
+            client
+                .put_object()
+                .bucket(bucket)
+                .key(&key)
+                .body(badge.svg(label).into_bytes().into())
+                .content_type("image/svg+xml")
+                .set_metadata(Some(ids.object_metadata()))
+                // Badges must not be cached by READMEs renderers for too long
+                .cache_control("max-age=300")
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+            locations.push(format!("s3://{}/{}", bucket, key));
+        }
+    } else {
+        for badge in badges {
+            // Repository namespaces become sub-directories
+            let path = Path::new(destination).join(badge_path("", &badge.repository_name));
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, badge.svg(label))?;
+            locations.push(path.display().to_string());
+        }
+    }
+    Ok(locations)
+}
+
+// Relative path of the badge of a repository
+fn badge_path(prefix: &str, repository_name: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        format!("{}.svg", repository_name)
+    } else {
+        format!("{}/{}.svg", prefix, repository_name)
+    }
+}
+
+// Escape the characters with a meaning in XML
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
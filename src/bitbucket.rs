@@ -0,0 +1,178 @@
+use crate::annotations::{ordered, outcome_counts, parse_commit, title, ImageAnnotation};
+use crate::errors::EXIT_OK;
+use crate::manifest::RunManifest;
+use crate::update::http_client;
+use serde_json::{json, Value};
+use std::error::Error;
+
+// Identifier of the Code Insights report, replaced by every run
+const REPORT_ID: &str = "ecr-image-scan";
+
+// Annotations sent per request, the maximum of the Code Insights API
+const ANNOTATIONS_PER_REQUEST: usize = 100;
+
+// Data fields of a report at most
+const MAX_DATA_FIELDS: usize = 10;
+
+// Characters of an annotation summary at most
+const MAX_SUMMARY: usize = 450;
+
+// Proxy of the Bitbucket API in Pipelines, authenticating the build
+const PIPELINES_PROXY: &str = "http://localhost:29418";
+
+// Commit of a Bitbucket repository given with --bitbucket-report workspace/repo@sha,
+// e.g. "$BITBUCKET_REPO_FULL_NAME@$BITBUCKET_COMMIT" in Pipelines
+#[derive(Debug, Clone)]
+pub struct ReportTarget {
+    pub repository: String,
+    pub commit: String,
+}
+
+impl ReportTarget {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (repository, commit) = parse_commit(value)?;
+        Ok(ReportTarget { repository, commit })
+    }
+}
+
+// Whether the Bitbucket API can be called, with an access token or through
+// the proxy of Pipelines
+pub fn has_bitbucket_credentials() -> bool {
+    std::env::var("BITBUCKET_ACCESS_TOKEN").is_ok()
+        || std::env::var("BITBUCKET_BUILD_NUMBER").is_ok()
+}
+
+// Function to create the Code Insights security report of the commit, with
+// the finding counts of the run and one annotation per image with findings,
+// failed when the run fails. Returns the number of annotations
+pub async fn create_report(
+    target: &ReportTarget,
+    levels: &[String],
+    annotations: &[ImageAnnotation],
+    manifest: &RunManifest,
+    exit_code: u8,
+) -> Result<usize, Box<dyn Error>> {
+    let token = std::env::var("BITBUCKET_ACCESS_TOKEN").ok();
+    let (client, api) = match &token {
+        Some(_) => (http_client(), bitbucket_api()),
+        // Pipelines builds call the API over plain HTTP through their proxy
+        None => (
+            reqwest::Client::builder()
+                .proxy(reqwest::Proxy::http(PIPELINES_PROXY)?)
+                .build()?,
+            "http://api.bitbucket.org".to_string(),
+        ),
+    };
+    let authorize = |request: reqwest::RequestBuilder| match &token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+    let url = format!(
+        "{}/2.0/repositories/{}/commit/{}/reports/{}",
+        api, target.repository, target.commit, REPORT_ID
+    );
+
+    // The report of a previous run is deleted with its annotations
+    let response = authorize(client.delete(&url)).send().await?;
+    if response.status() != reqwest::StatusCode::NOT_FOUND {
+        response.error_for_status()?;
+    }
+    let (images, omitted) = ordered(annotations);
+    let body = json!({
+        "title": "ECR image scan",
+        "details": details(manifest, omitted),
+        "report_type": "SECURITY",
+        "reporter": env!("CARGO_PKG_NAME"),
+        "result": if exit_code == EXIT_OK { "PASSED" } else { "FAILED" },
+        "data": data(manifest),
+    });
+    authorize(client.put(&url))
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let annotations: Vec<Value> = images
+        .iter()
+        .enumerate()
+        .map(|(index, image)| annotation(index, image, levels))
+        .collect();
+    for batch in annotations.chunks(ANNOTATIONS_PER_REQUEST) {
+        authorize(client.post(format!("{}/annotations", url)))
+            .header("Content-Type", "application/json")
+            .body(Value::from(batch).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+    Ok(annotations.len())
+}
+
+// URL of the Bitbucket API, BITBUCKET_API_URL when set
+fn bitbucket_api() -> String {
+    std::env::var("BITBUCKET_API_URL")
+        .unwrap_or("https://api.bitbucket.org".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+// Plain text details of the report, e.g. "3 image(s) over the thresholds, 2 error(s)"
+fn details(manifest: &RunManifest, omitted: usize) -> String {
+    let mut details = vec![format!(
+        "{} by run {}",
+        title(manifest),
+        manifest.ids.run_id
+    )];
+    for (count, label) in outcome_counts(manifest)
+        .iter()
+        .filter(|(count, _)| *count > 0)
+    {
+        details.push(format!("{} {}", count, label));
+    }
+    if omitted > 0 {
+        details.push(format!("{} image(s) with findings not annotated", omitted));
+    }
+    details.join(", ")
+}
+
+// Data fields of the report, the images then the findings per level
+fn data(manifest: &RunManifest) -> Vec<Value> {
+    let mut data = vec![
+        json!({ "title": "Images", "type": "NUMBER", "value": manifest.images }),
+        json!({
+            "title": "Over the thresholds",
+            "type": "NUMBER",
+            "value": manifest.threshold_violations,
+        }),
+    ];
+    data.extend(
+        manifest
+            .findings
+            .iter()
+            .map(|(level, count)| json!({ "title": level, "type": "NUMBER", "value": count })),
+    );
+    data.truncate(MAX_DATA_FIELDS);
+    data
+}
+
+// Vulnerability annotation of an image, at the severity of its most severe
+// level with findings
+fn annotation(index: usize, image: &ImageAnnotation, levels: &[String]) -> Value {
+    let severity = image
+        .top_level(levels)
+        .map(|level| level.to_uppercase())
+        .filter(|level| ["CRITICAL", "HIGH", "MEDIUM", "LOW"].contains(&level.as_str()))
+        .unwrap_or("LOW".to_string());
+    json!({
+        "external_id": format!("{}-{}", REPORT_ID, index + 1),
+        "annotation_type": "VULNERABILITY",
+        "summary": format!("{}: {}", image.image_name(), image.counts_text(levels))
+            .chars()
+            .take(MAX_SUMMARY)
+            .collect::<String>(),
+        "details": format!("{} {}", image.image_digest, image.image_tags.join(", ")),
+        "severity": severity,
+        "result": if image.failing { "FAILED" } else { "PASSED" },
+    })
+}
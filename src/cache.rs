@@ -0,0 +1,197 @@
+use crate::findings::{Finding, Package, ScanFindings};
+use aws_sdk_ecr::primitives::DateTime;
+use aws_sdk_ecr::types::FindingSeverity;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+// Version of the cache entry layout, entries of other versions are ignored
+const CACHE_VERSION: u32 = 6;
+
+// Detailed scan findings kept on disk across runs, given with --findings-cache.
+// The findings of a scan never change, so entries are keyed by the image digest
+// and the scan completion date and never need to be invalidated
+#[derive(Debug, Clone)]
+pub struct FindingsCache {
+    dir: PathBuf,
+}
+
+// Cache file content, dates as seconds since the epoch and nanoseconds
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    version: u32,
+    // False when only the first page of findings was fetched
+    complete: bool,
+    scan_completed_at: Option<(i64, u32)>,
+    vulnerability_source_updated_at: Option<(i64, u32)>,
+    severity_counts: BTreeMap<String, i32>,
+    findings: Vec<CachedFinding>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedFinding {
+    name: String,
+    finding_arn: Option<String>,
+    description: Option<String>,
+    severity: String,
+    vendor_severity: Option<String>,
+    vendor_created_at: Option<(i64, u32)>,
+    vendor_updated_at: Option<(i64, u32)>,
+    cvss_score: Option<f64>,
+    cvss_vector: Option<String>,
+    first_observed_at: Option<(i64, u32)>,
+    packages: Vec<CachedPackage>,
+    references: Vec<String>,
+    platform: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedPackage {
+    name: String,
+    version: String,
+    package_manager: Option<String>,
+    fixed_in_version: Option<String>,
+}
+
+impl FindingsCache {
+    pub fn new(dir: &str) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(FindingsCache {
+            dir: PathBuf::from(dir),
+        })
+    }
+
+    // Findings of a scan, when cached with every page if all of them are needed
+    pub fn load(
+        &self,
+        image_digest: &str,
+        scan_completed_at: &DateTime,
+        all_pages: bool,
+    ) -> Option<ScanFindings> {
+        let content = std::fs::read(self.path(image_digest, scan_completed_at)).ok()?;
+        // Unreadable entries are fetched again and overwritten
+        let entry: CacheEntry = serde_json::from_slice(&content).ok()?;
+        if entry.version != CACHE_VERSION || (all_pages && !entry.complete) {
+            return None;
+        }
+        Some(ScanFindings {
+            scan_completed_at: entry.scan_completed_at.map(from_parts),
+            vulnerability_source_updated_at: entry.vulnerability_source_updated_at.map(from_parts),
+            severity_counts: entry
+                .severity_counts
+                .into_iter()
+                .map(|(severity, count)| (FindingSeverity::from(severity.as_str()), count))
+                .collect(),
+            findings: entry.findings.into_iter().map(Finding::from).collect(),
+            truncated: false,
+        })
+    }
+
+    // Store the findings of a scan, written to a temporary file first so that
+    // concurrent runs never read a partial entry
+    pub fn store(
+        &self,
+        image_digest: &str,
+        scan: &ScanFindings,
+        all_pages: bool,
+    ) -> io::Result<()> {
+        let Some(scan_completed_at) = &scan.scan_completed_at else {
+            // Without a completion date the scan cannot be told from the next one
+            return Ok(());
+        };
+        let entry = CacheEntry {
+            version: CACHE_VERSION,
+            complete: all_pages && !scan.truncated,
+            scan_completed_at: scan.scan_completed_at.map(to_parts),
+            vulnerability_source_updated_at: scan.vulnerability_source_updated_at.map(to_parts),
+            severity_counts: scan
+                .severity_counts
+                .iter()
+                .map(|(severity, count)| (severity.as_str().to_string(), *count))
+                .collect(),
+            findings: scan.findings.iter().map(CachedFinding::from).collect(),
+        };
+        let path = self.path(image_digest, scan_completed_at);
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&temporary, serde_json::to_vec(&entry)?)?;
+        std::fs::rename(&temporary, &path)
+    }
+
+    // Entry of a scan, e.g. sha256-3f2a...-1718000000.json
+    fn path(&self, image_digest: &str, scan_completed_at: &DateTime) -> PathBuf {
+        let digest: String = image_digest
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        self.dir
+            .join(format!("{}-{}.json", digest, scan_completed_at.secs()))
+    }
+}
+
+impl From<&Finding> for CachedFinding {
+    fn from(finding: &Finding) -> Self {
+        CachedFinding {
+            name: finding.name.clone(),
+            finding_arn: finding.finding_arn.clone(),
+            description: finding.description.clone(),
+            severity: finding.severity.clone(),
+            vendor_severity: finding.vendor_severity.clone(),
+            vendor_created_at: finding.vendor_created_at.map(to_parts),
+            vendor_updated_at: finding.vendor_updated_at.map(to_parts),
+            cvss_score: finding.cvss_score,
+            cvss_vector: finding.cvss_vector.clone(),
+            first_observed_at: finding.first_observed_at.map(to_parts),
+            packages: finding
+                .packages
+                .iter()
+                .map(|p| CachedPackage {
+                    name: p.name.clone(),
+                    version: p.version.clone(),
+                    package_manager: p.package_manager.clone(),
+                    fixed_in_version: p.fixed_in_version.clone(),
+                })
+                .collect(),
+            references: finding.references.clone(),
+            platform: finding.platform.clone(),
+        }
+    }
+}
+
+impl From<CachedFinding> for Finding {
+    fn from(finding: CachedFinding) -> Self {
+        Finding {
+            name: finding.name,
+            finding_arn: finding.finding_arn,
+            description: finding.description,
+            severity: finding.severity,
+            vendor_severity: finding.vendor_severity,
+            vendor_created_at: finding.vendor_created_at.map(from_parts),
+            vendor_updated_at: finding.vendor_updated_at.map(from_parts),
+            cvss_score: finding.cvss_score,
+            cvss_vector: finding.cvss_vector,
+            first_observed_at: finding.first_observed_at.map(from_parts),
+            packages: finding
+                .packages
+                .into_iter()
+                .map(|p| Package {
+                    name: p.name,
+                    version: p.version,
+                    package_manager: p.package_manager,
+                    fixed_in_version: p.fixed_in_version,
+                })
+                .collect(),
+            references: finding.references,
+            platform: finding.platform,
+        }
+    }
+}
+
+// Dates are kept to the nanosecond so that cached reports match fetched ones
+fn to_parts(date: DateTime) -> (i64, u32) {
+    (date.secs(), date.subsec_nanos())
+}
+
+fn from_parts((secs, nanos): (i64, u32)) -> DateTime {
+    DateTime::from_secs_and_nanos(secs, nanos)
+}
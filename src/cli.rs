@@ -0,0 +1,1151 @@
+use crate::azure::in_azure_pipeline;
+use crate::bitbucket::{has_bitbucket_credentials, ReportTarget};
+use crate::cache::FindingsCache;
+use crate::config::Config;
+use crate::continuation::ContinueToken;
+use crate::cvss::{AttackVector, FindingFilter};
+use crate::dataset::PartitionKey;
+use crate::dates::{parse_date, parse_duration};
+use crate::encoding::{Encoding, LineEnding};
+use crate::environments::EnvironmentMapping;
+use crate::evidence::EvidenceOptions;
+use crate::exemptions::ImageExemptions;
+use crate::github::CheckTarget;
+use crate::labels::Labels;
+use crate::output::Compression;
+use crate::partition::check_region;
+use crate::pull::PullTool;
+use crate::query::Query;
+use crate::report::{GroupBy, OutputFormat, ReportSettings};
+use crate::repos::read_repository_list;
+use crate::retry::RetryTargets;
+use crate::runid::check_correlation_id;
+use crate::schedule::Schedule;
+use crate::sink::SinkTarget;
+use crate::sla::SlaPolicy;
+use crate::spill::DEFAULT_MAX_IMAGES_IN_MEMORY;
+use crate::split::SplitBy;
+use crate::sso::SsoPortal;
+use crate::tags::TagFilter;
+use crate::targets::Target;
+use crate::thresholds::{FailurePolicy, Thresholds};
+use crate::upstream::UpstreamImages;
+use crate::workloads::{read_image_references, RunningImages, UsageSource, WorkloadUsage};
+use aws_sdk_ecr::types::TagStatus;
+use indexmap::IndexMap;
+use std::env;
+use std::time::Duration;
+
+// Command-line options expecting a value right after them
+const VALUE_OPTIONS: [&str; 74] = [
+    "--run-manifest",
+    "--errors-json",
+    "--config",
+    "--sla",
+    "--fail-on",
+    "--group-by",
+    "--repo-tag",
+    "--tag-columns",
+    "--output",
+    "--max-images-per-repo",
+    "--sample",
+    "--from",
+    "--out-dir",
+    "--badge-level",
+    "--listen",
+    "--cache-ttl",
+    "--otlp-endpoint",
+    "--max-images-in-memory",
+    "--encoding",
+    "--line-endings",
+    "--output-file",
+    "--compress",
+    "--emit-pull-commands",
+    "--pull-tool",
+    "--cloudwatch-namespace",
+    "--findings-sink",
+    "--partition-by",
+    "--quicksight-manifest",
+    "--lang",
+    "--repos-file",
+    "--env",
+    "--max-db-age",
+    "--severities",
+    "--summary-json",
+    "--findings-cache",
+    "--max-findings-per-image",
+    "--continue-token",
+    "--attack-vector",
+    "--cvss-min",
+    "--verify-counts",
+    "--formatter",
+    "--schedule",
+    "--schedule-jitter",
+    "--health-listen",
+    "--images-file",
+    "--usage",
+    "--region",
+    "--scan-type",
+    "--role-arn",
+    "--role-session",
+    "--sso-start-url",
+    "--sso-region",
+    "--sso-session",
+    "--sso-account-id",
+    "--sso-role-name",
+    "--mfa-serial",
+    "--mfa-token",
+    "--retry-from",
+    "--pin-tags",
+    "--correlation-id",
+    "--web-identity-token-file",
+    "--split-by",
+    "--query",
+    "--scanned-after",
+    "--scanned-before",
+    "--gpg-key",
+    "--sign-kms-key-id",
+    "--verify-kms-key-id",
+    "--signature",
+    "--exempt-images",
+    "--github-check",
+    "--github-check-path",
+    "--bitbucket-report",
+    "--image-digest",
+];
+
+// Options narrowing what a report covers, listed in its metadata
+const FILTER_OPTIONS: [&str; 21] = [
+    "--repos-file",
+    "--retry-from",
+    "--images-file",
+    "--repo-tag",
+    "--env",
+    "--severities",
+    "--attack-vector",
+    "--cvss-min",
+    "--fixable-only",
+    "--tagged-only",
+    "--untagged-only",
+    "--skip-expired",
+    "--scanned-after",
+    "--scanned-before",
+    "--include-unsupported",
+    "--max-images-per-repo",
+    "--max-findings-per-image",
+    "--sample",
+    "--continue-token",
+    "--pin-tags",
+    "--image-digest",
+];
+
+// Subcommands run instead of a report
+const COMMANDS: [&str; 17] = [
+    "doctor",
+    "whoami",
+    "stats",
+    "render",
+    "merge",
+    "badge",
+    "evidence",
+    "verify",
+    "serve",
+    "cross-check",
+    "replication",
+    "simulate-scan-rules",
+    "self-update",
+    "schema",
+    "k8s-correlate",
+    "gen-iam-policy",
+    "login",
+];
+
+// Options of a report run
+pub struct Options {
+    pub repository_name: Option<String>, // Single repository to report, all repositories if not set
+    pub repository_names: Option<Vec<String>>, // Repositories listed in --repos-file
+    pub format: OutputFormat,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    pub output_file: Option<String>, // File or S3 location of the report, stdout if not set
+    pub compression: Option<Compression>,
+    pub quicksight_manifest: Option<String>, // File or S3 location of the QuickSight manifest of the report
+    pub sign_kms_key_id: Option<String>,     // KMS asymmetric key signing the report file
+    pub manifest_path: Option<String>,
+    pub errors_path: Option<String>,
+    pub summary_path: Option<String>, // JSON file of the fields of the summary line
+    pub correlation_id: Option<String>, // Identifier of the caller given to every output of the run
+    pub out_dir: Option<String>,      // Directory or S3 location of the badges or Parquet files
+    pub partition_by: Vec<PartitionKey>, // Partition columns of the Parquet files
+    pub pull_commands_path: Option<String>, // Script pulling the flagged images
+    pub pull_tool: PullTool,
+    pub cloudwatch_namespace: Option<String>, // Namespace of the published finding metrics
+    pub github_check: Option<CheckTarget>,    // Commit given the check run of the findings
+    pub bitbucket_report: Option<ReportTarget>, // Commit given the Code Insights report of the findings
+    pub azure_devops: bool, // Logging commands of the findings for the Azure Pipelines agent
+    pub attach_report: bool, // Push the report as an OCI artifact referring to the image of --image-digest
+    pub findings_sink: Option<SinkTarget>, // CloudWatch Logs or Firehose destination of the findings
+    pub serve: Option<ServeOptions>,       // HTTP API settings of the serve subcommand
+    pub evidence: Option<EvidenceOptions>, // Zip archive of the evidence subcommand
+    pub dry_run: bool,                     // List what would be processed instead of reporting
+    pub schedule: Option<Schedule>,        // Runs of the report when it runs as a daemon
+    pub health_listen: Option<String>,     // Address of the health endpoints of --schedule
+    pub settings: ReportSettings,
+}
+
+// Options of the serve subcommand
+pub struct ServeOptions {
+    pub listen: String,
+    pub cache_ttl: Duration,
+}
+
+// Usage line printed on invalid arguments
+pub fn usage(program: &str) -> String {
+    format!(
+        "Usage: {} doctor [<repository_name>]\n       {} stats [<repository_name>] [report options]\n       {} render --from <report.json> [--output <format> | --formatter <module.wasm>] [--query <jmespath>] [--lang <lang>] [--encoding <encoding>] [--line-endings <endings>]\n       {} merge <report.json>... [--output <format> | --formatter <module.wasm>] [--query <jmespath>] [--lang <lang>] [--encoding <encoding>] [--line-endings <endings>]\n       {} badge [<repository_name>] --out-dir <dir|s3://bucket/prefix> [--badge-level <level>] [report options]\n       {} evidence [<repository_name>] --output-file <pack.zip|s3://bucket/key> [--gpg-key <key-id>] [--sign-kms-key-id <key-id>] [report options]\n       {} verify <file|s3://bucket/key> --verify-kms-key-id <key-id> [--signature <file.sig|s3://bucket/key.sig>]\n       {} serve [--listen <address>] [--cache-ttl <duration>] [report options]\n       {} cross-check <repository_name> <image_digest|tag> --from <trivy-or-grype.json> [--output csv|json]\n       {} replication [<repository_name>] [--failed-only] [--config <file>] [--output csv|json]\n       {} simulate-scan-rules [<repository_name>] [--output csv|json]\n       {} self-update [--check-update]\n       {} schema [report|findings]\n       {} k8s-correlate --images-file <pods.json|images.txt|-> [report options]\n       {} gen-iam-policy [<repository_name>] [--scan-type basic|enhanced] [report options]\n       {} login --sso-start-url <url> [--sso-region <region>] [--sso-session <name>]\n       {} [--all | <repository_name> | --repos-file <file|-> | --retry-from <errors.json> | --version | --check-update] [--config <file>] [--region <region>] [--sso-start-url <url> --sso-account-id <id> --sso-role-name <name> [--sso-region <region>] [--sso-session <name>]] [--role-arn <arn> [--role-session <duration>] [--mfa-serial <arn> [--mfa-token <code>]]] [--web-identity-token-file <file> [--role-arn <arn>]] [--severities <level>,...] [--sla <level>=<age>,...] [--max-db-age <age>] [--fail-on <level>[=<max>],...] [--exempt-images <file>] [--group-by package|ecosystem] [--repo-tag <key>=<value>]... [--tag-columns <key>,...] [--env <environment>,...] [--output csv|json|table|markdown|html|sarif|defectdojo|in-toto|parquet|heatmap|heatmap-html] [--lang en|fr|de|es] [--redact] [--out-dir <dir|s3://bucket/prefix> [--partition-by date,account,region | --split-by repo-tag:<key>|prefix]] [--query <jmespath>] [--encoding utf-8|utf-8-bom|utf-16le] [--line-endings lf|crlf] [--output-file <file|s3://bucket/key> [--compress gzip|zstd] [--quicksight-manifest <file|s3://bucket/key>] [--sign-kms-key-id <key-id>]] [--emit-pull-commands <file> [--pull-tool docker|crane]] [--cloudwatch-namespace <namespace>] [--github-check <owner/repo@sha> [--github-check-path <file>]] [--bitbucket-report <workspace/repo@sha>] [--azure-devops] [--findings-sink logs:<group>[:<stream>]|firehose:<stream>|plugin:<name>] [--strict] [--fail-on-empty] [--keep-going] [--max-images-per-repo <n>] [--max-findings-per-image <n>] [--verify-counts <n>] [--attack-vector network|adjacent|local|physical,...] [--cvss-min <score>] [--fixable-only] [--sample <n> [--continue-token <token>]] [--tagged-only | --untagged-only] [--skip-expired] [--scanned-after <date>] [--scanned-before <date>] [--include-unsupported] [--age-columns] [--tag-count] [--vulnerable-since-push] [--usage ecs,lambda] [--per-tag] [--pin-tags <tag>,...] [--image-digest <sha256:digest> [--attach-report]] [--max-images-in-memory <n>] [--findings-cache <dir>] [--run-manifest <file>] [--errors-json <file>] [--summary-json <file>] [--correlation-id <id>] [--otlp-endpoint <url>] [--schedule <cron> [--schedule-jitter <duration>] [--health-listen <address>]] [--nice] [--dry-run]",
+        program, program, program, program, program, program, program, program, program,
+        program, program, program, program, program, program, program, program
+    )
+}
+
+// Parse the command-line arguments of a report run
+pub fn parse_options(args: &[String], region: &str) -> Result<Options, String> {
+    // Load the configuration file if provided
+    let config = match option_value(args, "--config") {
+        Some(path) => Config::load(path)
+            .map_err(|e| format!("Error loading configuration file '{}': {}", path, e))?,
+        None => Config::default(),
+    };
+    let mut scheme = config.severity_scheme.unwrap_or_default();
+    // Keep the severity columns of --severities, or of the configuration file
+    let severities: Vec<String> = match option_value(args, "--severities") {
+        Some(value) => value.split(',').map(|l| l.trim().to_string()).collect(),
+        None => config.severities,
+    };
+    if !severities.is_empty() {
+        scheme = scheme
+            .select(&severities)
+            .map_err(|e| format!("Invalid --severities value: {}", e))?;
+    }
+
+    // Check the compliance targets of the configuration file
+    let targets = config
+        .targets
+        .iter()
+        .map(|target| {
+            Target::parse(target, &scheme)
+                .map_err(|e| format!("Invalid target '{}': {}", target.name, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Parse remediation SLA per severity level if provided
+    let sla = option_value(args, "--sla")
+        .map(|value| SlaPolicy::parse(value, &scheme))
+        .transpose()
+        .map_err(|e| format!("Invalid --sla value: {}", e))?;
+    // Age of the vulnerability database of a scan flagging the image as degraded
+    let max_db_age = option_value(args, "--max-db-age")
+        .map(|value| match parse_duration(value) {
+            Ok(age) if age > 0 => Ok(age),
+            Ok(_) => Err(format!("'{}' is not a positive duration", value)),
+            Err(e) => Err(e),
+        })
+        .transpose()
+        .map_err(|e| format!("Invalid --max-db-age value: {}", e))?;
+    // Window of the scan completion dates of the reported images
+    let scanned_after = option_value(args, "--scanned-after")
+        .map(|value| parse_date(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --scanned-after value: {}", e))?;
+    let scanned_before = option_value(args, "--scanned-before")
+        .map(|value| parse_date(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --scanned-before value: {}", e))?;
+    if let (Some(after), Some(before)) = (&scanned_after, &scanned_before) {
+        if after.secs() >= before.secs() {
+            return Err(
+                "Invalid --scanned-before value: the date must be after the --scanned-after date"
+                    .to_string(),
+            );
+        }
+    }
+
+    // Parse finding thresholds failing the run if provided
+    let fail_on = FailurePolicy {
+        global: option_value(args, "--fail-on")
+            .map(|value| Thresholds::parse(value, &scheme))
+            .transpose()
+            .map_err(|e| format!("Invalid --fail-on value: {}", e))?,
+        // Repositories with thresholds of their own in the configuration file
+        repositories: config
+            .repository_thresholds
+            .iter()
+            .map(|(repository_name, levels)| {
+                Thresholds::from_levels(levels, &scheme)
+                    .map(|thresholds| (repository_name.clone(), thresholds))
+                    .map_err(|e| {
+                        format!(
+                            "Invalid thresholds of repository '{}': {}",
+                            repository_name, e
+                        )
+                    })
+            })
+            .collect::<Result<_, _>>()?,
+        // Release channels with thresholds of their own, e.g. "release-*"
+        tags: config
+            .tag_thresholds
+            .iter()
+            .map(|(pattern, levels)| {
+                if pattern.is_empty() {
+                    return Err("Invalid tag thresholds: empty tag pattern".to_string());
+                }
+                Thresholds::from_levels(levels, &scheme)
+                    .map(|thresholds| (pattern.clone(), thresholds))
+                    .map_err(|e| format!("Invalid thresholds of tag pattern '{}': {}", pattern, e))
+            })
+            .collect::<Result<_, _>>()?,
+        // Time-boxed risk acceptances of images, still reported
+        exemptions: match option_value(args, "--exempt-images") {
+            Some(path) => {
+                let exemptions = ImageExemptions::load(path)
+                    .map_err(|e| format!("Error reading exemptions file '{}': {}", path, e))?;
+                if exemptions.is_empty() {
+                    return Err(format!("No exemptions in exemptions file '{}'", path));
+                }
+                exemptions
+            }
+            None => ImageExemptions::default(),
+        },
+    };
+
+    // Check if findings should be aggregated instead of listed per image
+    let group_by = option_value(args, "--group-by")
+        .map(|value| GroupBy::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --group-by value: {}", e))?;
+
+    // Parse repository resource tag filters and columns
+    let tag_filters = option_values(args, "--repo-tag")
+        .into_iter()
+        .map(|value| TagFilter::parse(value))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid --repo-tag value: {}", e))?;
+    let tag_columns = option_value(args, "--tag-columns")
+        .map(|value| value.split(',').map(|key| key.trim().to_string()).collect())
+        .unwrap_or_default();
+
+    // Map the image tags to the environments of the configuration file
+    let environments = EnvironmentMapping::new(config.environments)
+        .map_err(|e| format!("Invalid environments in configuration file: {}", e))?;
+    let environment_filter: Vec<String> = option_value(args, "--env")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    // Compare the mirrored repositories with their upstream images
+    let upstream = UpstreamImages::new(config.upstream_images)
+        .map_err(|e| format!("Invalid upstream_images in configuration file: {}", e))?;
+    if !environment_filter.is_empty() && environments.is_empty() {
+        return Err("--env needs the environments of a --config file".to_string());
+    }
+    if let Some(name) = environment_filter
+        .iter()
+        .find(|n| !environments.contains(n))
+    {
+        return Err(format!(
+            "Invalid --env value: unknown environment '{}'",
+            name
+        ));
+    }
+
+    // Check the report format, CSV by default
+    let format = option_value(args, "--output")
+        .map(|value| OutputFormat::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --output value: {}", e))?
+        .unwrap_or(OutputFormat::Csv);
+    let command = subcommand(args);
+    // Evidence pack of the run, its reports rendered into a zip archive
+    let evidence = match command {
+        Some(("evidence", _)) => {
+            let Some(path) = option_value(args, "--output-file") else {
+                return Err("evidence needs --output-file <pack.zip|s3://bucket/key>".to_string());
+            };
+            if [
+                "--output",
+                "--compress",
+                "--quicksight-manifest",
+                "--group-by",
+            ]
+            .iter()
+            .any(|option| option_value(args, option).is_some())
+            {
+                return Err(
+                    "evidence writes an HTML and a JSON report into the zip archive of --output-file, it cannot be used with --output, --compress, --quicksight-manifest or --group-by"
+                        .to_string(),
+                );
+            }
+            Some(EvidenceOptions {
+                path: path.clone(),
+                gpg_key: option_value(args, "--gpg-key").cloned(),
+                kms_key_id: option_value(args, "--sign-kms-key-id").cloned(),
+            })
+        }
+        _ if option_value(args, "--gpg-key").is_some() => {
+            return Err("--gpg-key needs evidence".to_string())
+        }
+        _ => None,
+    };
+    let format = match evidence {
+        Some(_) => OutputFormat::Json,
+        None => format,
+    };
+    let (encoding, line_ending) = output_encoding(args)?;
+    let labels = report_labels(args, &config.labels)?;
+    // NDJSON findings sent to CloudWatch Logs, Firehose or a plugin during the run
+    let findings_sink = option_value(args, "--findings-sink")
+        .map(|value| SinkTarget::parse(value, &config.sink_plugins))
+        .transpose()
+        .map_err(|e| format!("Invalid --findings-sink value: {}", e))?;
+    // DefectDojo, in-toto and the findings sink list findings image by image
+    let list_findings = matches!(format, OutputFormat::DefectDojo | OutputFormat::InToto)
+        || findings_sink.is_some();
+
+    // Compress the report file on the fly if asked to
+    let output_file = option_value(args, "--output-file")
+        .filter(|_| evidence.is_none())
+        .cloned();
+    let compression = option_value(args, "--compress")
+        .map(|value| Compression::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --compress value: {}", e))?;
+    if compression.is_some() && output_file.is_none() {
+        return Err("--compress needs --output-file <file|s3://bucket/key>".to_string());
+    }
+    // KMS key signing the report file, the evidence pack signs its checksum file
+    let sign_kms_key_id = option_value(args, "--sign-kms-key-id")
+        .filter(|_| evidence.is_none())
+        .cloned();
+    if sign_kms_key_id.is_some() && output_file.is_none() {
+        return Err(
+            "--sign-kms-key-id needs --output-file <file|s3://bucket/key> or evidence".to_string(),
+        );
+    }
+    // Script reproducing the flagged images locally, with docker by default
+    let pull_commands_path = option_value(args, "--emit-pull-commands").cloned();
+    let pull_tool = option_value(args, "--pull-tool")
+        .map(|value| PullTool::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --pull-tool value: {}", e))?;
+    if pull_tool.is_some() && pull_commands_path.is_none() {
+        return Err("--pull-tool needs --emit-pull-commands <file>".to_string());
+    }
+
+    // Parse the limits bounding the number of reported images
+    let max_images_per_repo = option_value(args, "--max-images-per-repo")
+        .map(|value| parse_limit(value, "images"))
+        .transpose()
+        .map_err(|e| format!("Invalid --max-images-per-repo value: {}", e))?;
+    let sample = option_value(args, "--sample")
+        .map(|value| parse_limit(value, "images"))
+        .transpose()
+        .map_err(|e| format!("Invalid --sample value: {}", e))?;
+    // Detailed findings kept per image, the next pages not being fetched
+    let max_findings_per_image = option_value(args, "--max-findings-per-image")
+        .map(|value| parse_limit(value, "findings"))
+        .transpose()
+        .map_err(|e| format!("Invalid --max-findings-per-image value: {}", e))?;
+    // Images whose summary counts are checked against every finding page
+    let verify_counts = option_value(args, "--verify-counts")
+        .map(|value| parse_limit(value, "images"))
+        .transpose()
+        .map_err(|e| format!("Invalid --verify-counts value: {}", e))?;
+    // Findings kept by their CVSS vector and score, the others not being counted
+    let attack_vectors = option_value(args, "--attack-vector")
+        .map(|value| {
+            value
+                .split(',')
+                .map(AttackVector::parse)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(|e| format!("Invalid --attack-vector value: {}", e))?
+        .unwrap_or_default();
+    let cvss_min = option_value(args, "--cvss-min")
+        .map(|value| match value.trim().parse::<f64>() {
+            Ok(score) if (0.0..=10.0).contains(&score) => Ok(score),
+            _ => Err(format!("'{}' is not a CVSS score between 0 and 10", value)),
+        })
+        .transpose()
+        .map_err(|e| format!("Invalid --cvss-min value: {}", e))?;
+    // Image records buffered before spilling to disk, for formats written at the end
+    let max_images_in_memory = option_value(args, "--max-images-in-memory")
+        .map(|value| parse_limit(value, "images"))
+        .transpose()
+        .map_err(|e| format!("Invalid --max-images-in-memory value: {}", e))?
+        .map_or(DEFAULT_MAX_IMAGES_IN_MEMORY, |limit| limit as usize);
+    // Detailed findings kept across runs, the findings of a scan never changing
+    let findings_cache = option_value(args, "--findings-cache")
+        .map(|dir| {
+            FindingsCache::new(dir)
+                .map_err(|e| format!("Invalid --findings-cache value: '{}': {}", dir, e))
+        })
+        .transpose()?;
+
+    // Check if only tagged or untagged images are reported
+    let tag_status = match (
+        has_flag(args, "--tagged-only"),
+        has_flag(args, "--untagged-only"),
+    ) {
+        (true, true) => {
+            return Err("--tagged-only and --untagged-only cannot be used together".to_string())
+        }
+        (true, false) => Some(TagStatus::Tagged),
+        (false, true) => Some(TagStatus::Untagged),
+        (false, false) => None,
+    };
+
+    // Check if repository name argument is provided
+    let stats = matches!(command, Some(("stats", _)));
+
+    // Parquet files of the image records, in Hive-style partition directories
+    let partition_by = option_value(args, "--partition-by")
+        .map(|value| PartitionKey::parse_list(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --partition-by value: {}", e))?
+        .unwrap_or_default();
+    if format == OutputFormat::Parquet {
+        if option_value(args, "--out-dir").is_none() || output_file.is_some() {
+            return Err("--output parquet needs --out-dir <dir|s3://bucket/prefix>".to_string());
+        }
+        if group_by.is_some() || matches!(command, Some(("stats" | "badge" | "serve", _))) {
+            return Err(
+                "--output parquet writes image rows to --out-dir, it cannot be used with --group-by, stats, badge or serve"
+                    .to_string(),
+            );
+        }
+    } else if !partition_by.is_empty() {
+        return Err("--partition-by needs --output parquet".to_string());
+    }
+    // QuickSight reads CSV files from S3, gzip compressed or not
+    let quicksight_manifest = option_value(args, "--quicksight-manifest").cloned();
+    if quicksight_manifest.is_some()
+        && (format != OutputFormat::Csv
+            || !output_file
+                .as_deref()
+                .is_some_and(|f| f.starts_with("s3://"))
+            || compression == Some(Compression::Zstd)
+            || matches!(command, Some(("stats" | "badge" | "serve", _))))
+    {
+        return Err(
+            "--quicksight-manifest needs a CSV report uploaded with --output-file s3://bucket/key, not compressed with zstd"
+                .to_string(),
+        );
+    }
+    if format == OutputFormat::DefectDojo && (group_by.is_some() || stats) {
+        return Err(
+            "--output defectdojo lists findings, it cannot be used with --group-by or stats"
+                .to_string(),
+        );
+    }
+    if matches!(format, OutputFormat::Heatmap | OutputFormat::HeatmapHtml)
+        && (group_by.is_some() || stats)
+    {
+        return Err(format!(
+            "--output {} sums the findings of each repository, it cannot be used with --group-by or stats",
+            format.as_str()
+        ));
+    }
+
+    // One report file per team, written to --out-dir
+    let split_by = option_value(args, "--split-by")
+        .map(|value| SplitBy::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --split-by value: {}", e))?;
+    if split_by.is_some() {
+        if option_value(args, "--out-dir").is_none() {
+            return Err("--split-by needs --out-dir <dir|s3://bucket/prefix>".to_string());
+        }
+        if matches!(format, OutputFormat::DefectDojo | OutputFormat::Parquet)
+            || group_by.is_some()
+            || matches!(command, Some(("stats" | "badge" | "evidence" | "serve", _)))
+        {
+            return Err(
+                "--split-by writes the image lines of each group to --out-dir, it cannot be used with --output defectdojo or parquet, --group-by, stats, badge, evidence or serve"
+                    .to_string(),
+            );
+        }
+    }
+
+    // JMESPath expression applied to the JSON document before it is written
+    let query = option_value(args, "--query")
+        .map(|value| Query::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --query value: {}", e))?;
+    if query.is_some() {
+        if format != OutputFormat::Json {
+            return Err(
+                "--query selects from the JSON document, it needs --output json".to_string(),
+            );
+        }
+        if split_by.is_some() || matches!(command, Some(("badge" | "evidence" | "serve", _))) {
+            return Err(
+                "--query applies to the report written to the output, it cannot be used with --split-by, badge, evidence or serve"
+                    .to_string(),
+            );
+        }
+    }
+
+    // HTTP API address and answer cache lifetime, 5 minutes by default
+    let serve = match command {
+        Some(("serve", _)) => Some(ServeOptions {
+            listen: option_value(args, "--listen")
+                .cloned()
+                .unwrap_or_else(|| "127.0.0.1:8080".to_string()),
+            cache_ttl: Duration::from_secs(
+                option_value(args, "--cache-ttl")
+                    .map(|value| parse_duration(value))
+                    .transpose()
+                    .map_err(|e| format!("Invalid --cache-ttl value: {}", e))?
+                    .unwrap_or(300)
+                    .max(0) as u64,
+            ),
+        }),
+        _ => None,
+    };
+
+    // Badges show the findings of one level, the most severe by default
+    let badge_level = match command {
+        Some(("badge", _)) => {
+            if option_value(args, "--out-dir").is_none() {
+                return Err("badge needs --out-dir <dir|s3://bucket/prefix>".to_string());
+            }
+            match option_value(args, "--badge-level") {
+                Some(level) => Some(scheme.level_position(level).ok_or_else(|| {
+                    format!(
+                        "Invalid --badge-level value: unknown severity level '{}'",
+                        level
+                    )
+                })?),
+                None => Some(0),
+            }
+        }
+        _ => None,
+    };
+    let dry_run = has_flag(args, "--dry-run");
+    if dry_run && serve.is_some() {
+        return Err("--dry-run cannot be used with serve".to_string());
+    }
+    // Report again at each run of the schedule instead of exiting
+    let schedule = match option_value(args, "--schedule") {
+        Some(_) if serve.is_some() || dry_run => {
+            return Err("--schedule cannot be used with serve or --dry-run".to_string())
+        }
+        Some(_) if option_value(args, "--continue-token").is_some() => {
+            return Err("--schedule cannot be used with --continue-token".to_string())
+        }
+        Some(expression) => {
+            let jitter = option_value(args, "--schedule-jitter")
+                .map(|value| parse_duration(value))
+                .transpose()
+                .map_err(|e| format!("Invalid --schedule-jitter value: {}", e))?
+                .unwrap_or(0)
+                .max(0) as u64;
+            Some(
+                Schedule::parse(expression, Duration::from_secs(jitter))
+                    .map_err(|e| format!("Invalid --schedule value: {}", e))?,
+            )
+        }
+        None if option_value(args, "--schedule-jitter").is_some() => {
+            return Err("--schedule-jitter needs --schedule".to_string())
+        }
+        None => None,
+    };
+    let health_listen = match option_value(args, "--health-listen") {
+        Some(_) if serve.is_some() => {
+            return Err(
+                "--health-listen cannot be used with serve, which answers /healthz on --listen"
+                    .to_string(),
+            )
+        }
+        Some(_) if schedule.is_none() => return Err("--health-listen needs --schedule".to_string()),
+        listen => listen.cloned(),
+    };
+    // Images running in a cluster, whose repositories are the only ones reported
+    let running_images = match (&command, option_value(args, "--images-file")) {
+        (Some(("k8s-correlate", rest)), _) if !rest.is_empty() => {
+            return Err(
+                "k8s-correlate reports the repositories of the running images, it cannot be given a repository name"
+                    .to_string(),
+            )
+        }
+        (Some(("k8s-correlate", _)), _) if has_flag(args, "--all") || option_value(args, "--repos-file").is_some() => {
+            return Err("k8s-correlate cannot be used with --all or --repos-file".to_string())
+        }
+        (Some(("k8s-correlate", _)), Some(path)) => {
+            let references = read_image_references(path)
+                .map_err(|e| format!("Error reading images file '{}': {}", path, e))?;
+            let running = RunningImages::new(&references, region);
+            if running.repositories().is_empty() {
+                return Err(format!(
+                    "No ECR image of region '{}' in images file '{}'",
+                    region, path
+                ));
+            }
+            Some(running)
+        }
+        (Some(("k8s-correlate", _)), None) => {
+            return Err("k8s-correlate needs --images-file <file|->".to_string())
+        }
+        (_, Some(_)) => return Err("--images-file needs k8s-correlate".to_string()),
+        _ => None,
+    };
+    // Services looked up for the workloads running each image
+    let usage_sources = match option_value(args, "--usage") {
+        Some(_) if serve.is_some() => return Err("--usage cannot be used with serve".to_string()),
+        Some(value) => {
+            UsageSource::parse_list(value).map_err(|e| format!("Invalid --usage value: {}", e))?
+        }
+        None => Vec::new(),
+    };
+    // Moving tags resolved to their digests before the images are listed
+    let pin_tags = match option_value(args, "--pin-tags") {
+        Some(value) => {
+            let tags: Vec<String> = value
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+            if tags.is_empty() {
+                return Err(format!("Invalid --pin-tags value: '{}' has no tag", value));
+            }
+            tags
+        }
+        None => Vec::new(),
+    };
+    // Repositories and images that failed in the run of an errors file
+    let retry = match option_value(args, "--retry-from") {
+        Some(_) if serve.is_some() => {
+            return Err("--retry-from cannot be used with serve".to_string())
+        }
+        Some(_)
+            if has_flag(args, "--all")
+                || option_value(args, "--repos-file").is_some()
+                || running_images.is_some() =>
+        {
+            return Err(
+                "--retry-from cannot be used with --all, --repos-file or k8s-correlate".to_string(),
+            )
+        }
+        Some(path) => Some(
+            RetryTargets::load(path)
+                .map_err(|e| format!("Error reading errors file '{}': {}", path, e))?,
+        ),
+        None => None,
+    };
+    // Check run of the findings on a commit, e.g. the one the images were built from
+    let github_check = option_value(args, "--github-check")
+        .map(|value| {
+            CheckTarget::parse(
+                value,
+                option_value(args, "--github-check-path").map(|p| p.as_str()),
+            )
+        })
+        .transpose()
+        .map_err(|e| format!("Invalid --github-check value: {}", e))?;
+    match &github_check {
+        Some(_) if env::var("GITHUB_TOKEN").is_err() => {
+            return Err("--github-check needs a GITHUB_TOKEN allowed to write checks".to_string())
+        }
+        Some(_) if serve.is_some() => {
+            return Err("--github-check cannot be used with serve".to_string())
+        }
+        None if option_value(args, "--github-check-path").is_some() => {
+            return Err("--github-check-path needs --github-check <owner/repo@sha>".to_string())
+        }
+        _ => {}
+    }
+
+    // Code Insights report of the findings on a Bitbucket commit
+    let bitbucket_report = option_value(args, "--bitbucket-report")
+        .map(|value| ReportTarget::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --bitbucket-report value: {}", e))?;
+    match &bitbucket_report {
+        Some(_) if !has_bitbucket_credentials() => return Err(
+            "--bitbucket-report needs a BITBUCKET_ACCESS_TOKEN, or to run in Bitbucket Pipelines"
+                .to_string(),
+        ),
+        Some(_) if serve.is_some() => {
+            return Err("--bitbucket-report cannot be used with serve".to_string())
+        }
+        _ => {}
+    }
+    // Logging commands of the findings for the Azure Pipelines agent
+    let azure_devops = has_flag(args, "--azure-devops");
+    if azure_devops && !in_azure_pipeline() {
+        return Err("--azure-devops needs to run in an Azure Pipelines job".to_string());
+    }
+    if azure_devops && serve.is_some() {
+        return Err("--azure-devops cannot be used with serve".to_string());
+    }
+
+    // Curated list of repositories, e.g. those changed by a deployment
+    let repository_names = match option_value(args, "--repos-file") {
+        Some(_) if serve.is_some() => {
+            return Err("--repos-file cannot be used with serve".to_string())
+        }
+        Some(_) if has_flag(args, "--all") => {
+            return Err("--repos-file cannot be used with --all".to_string())
+        }
+        Some(path) => {
+            let names = read_repository_list(path)
+                .map_err(|e| format!("Error reading repositories file '{}': {}", path, e))?;
+            if names.is_empty() {
+                return Err(format!(
+                    "No repository names in repositories file '{}'",
+                    path
+                ));
+            }
+            Some(names)
+        }
+        None => retry
+            .as_ref()
+            .map(|retry| retry.repositories())
+            .or_else(|| {
+                running_images
+                    .as_ref()
+                    .map(|running| running.repositories())
+            }),
+    };
+    let repository_name = if let Some((_, rest)) = &command {
+        // Subcommands are registry-wide unless given a repository
+        rest.first().map(|repo| repo.to_string())
+    } else if has_flag(args, "--all") {
+        None
+    } else if let Some(repo) = positional_args(args).first() {
+        Some(repo.to_string())
+    } else if repository_names.is_some() {
+        None
+    } else {
+        return Err(usage(&args[0]));
+    };
+
+    if repository_name.is_some() && retry.is_some() {
+        return Err("--retry-from cannot be used with a repository name".to_string());
+    }
+    if repository_name.is_some() && repository_names.is_some() {
+        return Err("--repos-file cannot be used with a repository name".to_string());
+    }
+    // Single image of the repository to report, e.g. the one attested with --output in-toto
+    let image_digest = option_value(args, "--image-digest").cloned();
+    if let Some(digest) = &image_digest {
+        let valid = digest
+            .strip_prefix("sha256:")
+            .is_some_and(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        if !valid {
+            return Err(format!(
+                "Invalid --image-digest value: '{}' is not a sha256:<digest> image digest",
+                digest
+            ));
+        }
+        if repository_name.is_none() {
+            return Err("--image-digest needs the repository name of the image".to_string());
+        }
+    }
+    if format == OutputFormat::InToto {
+        if image_digest.is_none() {
+            return Err(
+                "--output in-toto attests one image, it needs a repository name and --image-digest sha256:<digest>"
+                    .to_string(),
+            );
+        }
+        if group_by.is_some()
+            || split_by.is_some()
+            || has_flag(args, "--per-tag")
+            || matches!(command, Some(("stats" | "badge" | "serve", _)))
+        {
+            return Err(
+                "--output in-toto attests the findings of one image, it cannot be used with --group-by, --split-by, --per-tag, stats, badge or serve"
+                    .to_string(),
+            );
+        }
+    }
+    // Report pushed to the repository as an artifact of the image
+    let attach_report = has_flag(args, "--attach-report");
+    if attach_report {
+        if image_digest.is_none() {
+            return Err(
+                "--attach-report needs a repository name and --image-digest sha256:<digest>"
+                    .to_string(),
+            );
+        }
+        if !matches!(format, OutputFormat::Json | OutputFormat::InToto)
+            || group_by.is_some()
+            || has_flag(args, "--per-tag")
+            || matches!(command, Some(("stats" | "badge" | "evidence" | "serve", _)))
+        {
+            return Err(
+                "--attach-report pushes the JSON report or the in-toto statement of the image, it needs --output json or in-toto and cannot be used with --group-by, --per-tag, stats, badge, evidence or serve"
+                    .to_string(),
+            );
+        }
+    }
+    // Resume a report stopped by --sample, from the token printed by the previous run
+    let resume = match option_value(args, "--continue-token") {
+        Some(_) if serve.is_some() => {
+            return Err(
+                "--continue-token cannot be used with serve, use the token query parameter"
+                    .to_string(),
+            )
+        }
+        Some(token) => {
+            let token = ContinueToken::parse(token)
+                .map_err(|e| format!("Invalid --continue-token value: {}", e))?;
+            match &repository_name {
+                Some(repo) if *repo != token.repository => {
+                    return Err(format!(
+                        "Invalid --continue-token value: token of repository '{}', not '{}'",
+                        token.repository, repo
+                    ))
+                }
+                _ => Some(token),
+            }
+        }
+        None => None,
+    };
+    // Identifier of the caller, e.g. a pipeline or ticket id, next to the run id
+    let correlation_id = option_value(args, "--correlation-id").cloned();
+    if let Some(Err(e)) = correlation_id.as_deref().map(check_correlation_id) {
+        return Err(format!("Invalid --correlation-id value: {}", e));
+    }
+
+    Ok(Options {
+        repository_name,
+        repository_names,
+        format,
+        encoding,
+        line_ending,
+        output_file,
+        compression,
+        quicksight_manifest,
+        sign_kms_key_id,
+        manifest_path: option_value(args, "--run-manifest").cloned(),
+        errors_path: option_value(args, "--errors-json").cloned(),
+        summary_path: option_value(args, "--summary-json").cloned(),
+        correlation_id,
+        out_dir: option_value(args, "--out-dir").cloned(),
+        partition_by,
+        pull_commands_path,
+        pull_tool: pull_tool.unwrap_or(PullTool::Docker),
+        cloudwatch_namespace: option_value(args, "--cloudwatch-namespace").cloned(),
+        github_check,
+        bitbucket_report,
+        azure_devops,
+        attach_report,
+        findings_sink,
+        serve,
+        evidence,
+        dry_run,
+        schedule,
+        health_listen,
+        settings: ReportSettings {
+            scheme,
+            sla,
+            max_db_age,
+            fail_on,
+            group_by,
+            tag_filters,
+            tag_columns,
+            // Check if rows with degraded data should fail the run
+            strict: has_flag(args, "--strict"),
+            // Check if a run reporting no image should fail
+            fail_on_empty: has_flag(args, "--fail-on-empty"),
+            // Check if the run should go on after a repository or image fails
+            keep_going: has_flag(args, "--keep-going"),
+            max_images_per_repo,
+            sample,
+            resume,
+            tag_status,
+            stats,
+            targets,
+            badge_level,
+            max_images_in_memory,
+            per_tag: has_flag(args, "--per-tag"),
+            pin_tags,
+            list_findings,
+            labels,
+            redact: has_flag(args, "--redact"),
+            environments,
+            environment_filter,
+            skip_expired: has_flag(args, "--skip-expired"),
+            scanned_after,
+            scanned_before,
+            include_unsupported: has_flag(args, "--include-unsupported"),
+            age_columns: has_flag(args, "--age-columns"),
+            tag_count: has_flag(args, "--tag-count"),
+            vulnerable_since_push: has_flag(args, "--vulnerable-since-push"),
+            findings_cache,
+            max_findings_per_image,
+            verify_counts,
+            finding_filter: FindingFilter {
+                attack_vectors,
+                cvss_min,
+                fixable_only: has_flag(args, "--fixable-only"),
+            },
+            upstream,
+            running_images,
+            retry,
+            filters: report_filters(args),
+            usage: usage_sources,
+            workloads: WorkloadUsage::default(),
+            split_by,
+            query,
+            image_digest,
+        },
+    })
+}
+
+// Get the labels of a rendered report, from --lang and the labels of the --config file
+pub fn output_labels(args: &[String]) -> Result<Labels, String> {
+    let config = match option_value(args, "--config") {
+        Some(path) => Config::load(path)
+            .map_err(|e| format!("Error loading configuration file '{}': {}", path, e))?,
+        None => Config::default(),
+    };
+    report_labels(args, &config.labels)
+}
+
+fn report_labels(args: &[String], overrides: &IndexMap<String, String>) -> Result<Labels, String> {
+    Labels::new(
+        option_value(args, "--lang").map(|lang| lang.as_str()),
+        overrides,
+    )
+    .map_err(|e| format!("Invalid --lang value: {}", e))
+}
+
+// Parse the character encoding and line endings of the report, UTF-8 and LF by default
+pub fn output_encoding(args: &[String]) -> Result<(Encoding, LineEnding), String> {
+    let encoding = option_value(args, "--encoding")
+        .map(|value| Encoding::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --encoding value: {}", e))?
+        .unwrap_or(Encoding::Utf8);
+    let line_ending = option_value(args, "--line-endings")
+        .map(|value| LineEnding::parse(value))
+        .transpose()
+        .map_err(|e| format!("Invalid --line-endings value: {}", e))?
+        .unwrap_or(LineEnding::Lf);
+    Ok((encoding, line_ending))
+}
+
+// IAM Identity Center portal of --sso-start-url, in the region of the run unless
+// given with --sso-region
+pub fn sso_portal(args: &[String], region: &str) -> Result<Option<SsoPortal>, String> {
+    let Some(start_url) = option_value(args, "--sso-start-url") else {
+        if option_value(args, "--sso-region").is_some()
+            || option_value(args, "--sso-session").is_some()
+        {
+            return Err("--sso-region and --sso-session need --sso-start-url".to_string());
+        }
+        return Ok(None);
+    };
+    if !start_url.starts_with("https://") {
+        return Err(format!(
+            "Invalid --sso-start-url value: '{}' is not the https:// URL of an AWS access portal",
+            start_url
+        ));
+    }
+    let region = match option_value(args, "--sso-region") {
+        Some(sso_region) => {
+            check_region(sso_region).map_err(|e| format!("Invalid --sso-region value: {}", e))?;
+            sso_region.to_string()
+        }
+        None if region.is_empty() => {
+            return Err(
+                "--sso-start-url needs --sso-region <region> without a region for the run"
+                    .to_string(),
+            )
+        }
+        None => region.to_string(),
+    };
+    Ok(Some(SsoPortal {
+        start_url: start_url.to_string(),
+        region,
+        session: option_value(args, "--sso-session").cloned(),
+    }))
+}
+
+// Get the subcommand and its arguments, if the first positional argument is one
+pub fn subcommand(args: &[String]) -> Option<(&str, Vec<&String>)> {
+    let positionals = positional_args(args);
+    let (command, rest) = positionals.split_first()?;
+    COMMANDS
+        .contains(&command.as_str())
+        .then(|| (command.as_str(), rest.to_vec()))
+}
+
+// Parse a strictly positive count of images or findings
+fn parse_limit(value: &str, unit: &str) -> Result<u64, String> {
+    match value.trim().parse::<u64>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(format!("'{}' is not a positive number of {}", value, unit)),
+    }
+}
+
+// Check if a flag is present
+pub fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+// Get the filter options of the command line with their values, in their order
+fn report_filters(args: &[String]) -> Vec<String> {
+    let mut filters = Vec::new();
+    for (index, arg) in args.iter().enumerate() {
+        if !FILTER_OPTIONS.contains(&arg.as_str()) {
+            continue;
+        }
+        match args.get(index + 1) {
+            Some(value) if VALUE_OPTIONS.contains(&arg.as_str()) => {
+                filters.push(format!("{} {}", arg, value))
+            }
+            _ => filters.push(arg.clone()),
+        }
+    }
+    filters
+}
+
+// Get every value given to a repeatable command-line option
+fn option_values<'a>(args: &'a [String], name: &str) -> Vec<&'a String> {
+    args.iter()
+        .zip(args.iter().skip(1))
+        .filter(|(arg, _)| *arg == name)
+        .map(|(_, value)| value)
+        .collect()
+}
+
+// Get the value following a command-line option, if present
+pub fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+}
+
+// Get the positional arguments, skipping the program name, flags and option values
+fn positional_args(args: &[String]) -> Vec<&String> {
+    let mut positionals = Vec::new();
+    let mut skip_next = false;
+    for arg in args.iter().skip(1) {
+        if skip_next {
+            skip_next = false;
+        } else if VALUE_OPTIONS.contains(&arg.as_str()) {
+            skip_next = true;
+        } else if !arg.starts_with("--") {
+            positionals.push(arg);
+        }
+    }
+    positionals
+}
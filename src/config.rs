@@ -0,0 +1,50 @@
+use crate::severity::SeverityScheme;
+use crate::sink::SinkPlugin;
+use crate::targets::TargetConfig;
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+// Settings loaded from the JSON file given with --config
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    // Custom severity levels used for the report columns
+    #[serde(default)]
+    pub severity_scheme: Option<SeverityScheme>,
+    // Levels of the scheme shown as columns, in this order, all of them if empty
+    #[serde(default)]
+    pub severities: Vec<String>,
+    // Compliance targets evaluated in the run summary
+    #[serde(default)]
+    pub targets: Vec<TargetConfig>,
+    // Maximum finding counts per severity level of some repositories, replacing --fail-on
+    #[serde(default)]
+    pub repository_thresholds: IndexMap<String, IndexMap<String, i64>>,
+    // Maximum finding counts per severity level of the images with a matching tag,
+    // replacing the repository thresholds and --fail-on
+    #[serde(default)]
+    pub tag_thresholds: IndexMap<String, IndexMap<String, i64>>,
+    // Column headers and severity labels of the text reports, replacing the --lang ones
+    #[serde(default)]
+    pub labels: IndexMap<String, String>,
+    // Image tag patterns of each environment, for the environment column and --env
+    #[serde(default)]
+    pub environments: IndexMap<String, Vec<String>>,
+    // Upstream image of the mirrored repositories, for the upstream column
+    #[serde(default)]
+    pub upstream_images: IndexMap<String, String>,
+    // Executables reading the finding lines, given to --findings-sink plugin:<name>
+    #[serde(default)]
+    pub sink_plugins: IndexMap<String, SinkPlugin>,
+}
+
+impl Config {
+    // Load and validate the configuration file
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let config: Config = serde_json::from_str(&content)?;
+        if let Some(scheme) = &config.severity_scheme {
+            scheme.validate()?;
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,45 @@
+// Version of the token layout, tokens of other versions are rejected
+const TOKEN_VERSION: &str = "v1";
+
+// Position where a listing stopped by --sample resumes, given back with
+// --continue-token or the token query parameter of the HTTP API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContinueToken {
+    // Repository of the next image to report
+    pub repository: String,
+    // Images of the repository already reported
+    pub skip: u64,
+}
+
+impl ContinueToken {
+    // Opaque form of the token, hex encoded so that it is safe in URLs and shells
+    pub fn encode(&self) -> String {
+        format!("{}:{}:{}", TOKEN_VERSION, self.skip, self.repository)
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn parse(token: &str) -> Result<Self, String> {
+        let invalid = || format!("'{}' is not a continuation token", token);
+        if !token.len().is_multiple_of(2) || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let decoded = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let mut parts = decoded.splitn(3, ':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(TOKEN_VERSION), Some(skip), Some(repository)) if !repository.is_empty() => {
+                Ok(ContinueToken {
+                    repository: repository.to_string(),
+                    skip: skip.parse().map_err(|_| invalid())?,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::dates::parse_duration;
+use crate::errors::error_chain;
+use crate::telemetry::sts_client;
+use aws_config::provider_config::ProviderConfig;
+use aws_config::sts::AssumeRoleProvider;
+use aws_config::web_identity_token::{StaticConfiguration, WebIdentityTokenCredentialsProvider};
+use aws_config::SdkConfig;
+use aws_sdk_ecr::config::{Credentials, SharedCredentialsProvider};
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Name of the sessions of the role assumed with --role-arn, in CloudTrail
+const ROLE_SESSION_NAME: &str = env!("CARGO_PKG_NAME");
+
+// Session lengths accepted by STS, from 15 minutes up to the 12 hours maximum
+// a role may allow
+const MIN_ROLE_SESSION: i64 = 900;
+const MAX_ROLE_SESSION: i64 = 43_200;
+
+// Cached MFA sessions closer to their expiry are not reused
+const MIN_CACHED_SESSION_LEFT: i64 = 300;
+
+// MFA device of a role requiring MFA, given with --mfa-serial, and its code
+// given with --mfa-token, prompted for otherwise
+pub struct Mfa<'a> {
+    pub serial: &'a str,
+    pub token: Option<&'a str>,
+}
+
+// Function to make every call of the run with the credentials of a role given
+// with --role-arn, assumed with the credentials of the environment. The SDK
+// assumes the role again shortly before the session expires, so that runs
+// lasting longer than a session, such as registry-wide sweeps or --schedule
+// daemons, do not fail with ExpiredToken halfway through
+pub async fn assume_role(
+    sdk_config: &SdkConfig,
+    role_arn: &str,
+    session_length: Option<&str>,
+    mfa: Option<Mfa<'_>>,
+) -> Result<SdkConfig, String> {
+    check_role_arn(role_arn)?;
+    let session_length = session_length
+        .map(|value| {
+            parse_duration(value)
+                .and_then(|seconds| match seconds {
+                    MIN_ROLE_SESSION..=MAX_ROLE_SESSION => Ok(seconds),
+                    _ => Err(format!("'{}' is not between 15m and 12h", value)),
+                })
+                .map_err(|e| format!("Invalid --role-session value: {}", e))
+        })
+        .transpose()?;
+    let credentials_provider = match mfa {
+        Some(mfa) => SharedCredentialsProvider::new(
+            assume_role_with_mfa(sdk_config, role_arn, session_length, mfa).await?,
+        ),
+        None => {
+            let mut provider = AssumeRoleProvider::builder(role_arn)
+                .session_name(ROLE_SESSION_NAME)
+                .configure(sdk_config);
+            if let Some(seconds) = session_length {
+                provider = provider.session_length(Duration::from_secs(seconds as u64));
+            }
+            SharedCredentialsProvider::new(provider.build().await)
+        }
+    };
+    Ok(sdk_config
+        .to_builder()
+        .credentials_provider(credentials_provider)
+        .build())
+}
+
+// Function to make every call with the credentials of a role assumed with the
+// web identity token of --web-identity-token-file, e.g. the token of an EKS
+// service account (IRSA) mounted at another path than the one of
+// AWS_WEB_IDENTITY_TOKEN_FILE. The file is read again at each refresh of the
+// session, as Kubernetes rotates the token
+pub fn assume_role_with_web_identity(
+    sdk_config: &SdkConfig,
+    token_file: &str,
+    role_arn: &str,
+) -> Result<SdkConfig, String> {
+    check_role_arn(role_arn)?;
+    if let Err(e) = std::fs::File::open(token_file) {
+        return Err(format!(
+            "Invalid --web-identity-token-file value: '{}' cannot be read: {}",
+            token_file, e
+        ));
+    }
+    let provider = WebIdentityTokenCredentialsProvider::builder()
+        .configure(&ProviderConfig::default().with_region(sdk_config.region().cloned()))
+        .static_configuration(StaticConfiguration {
+            web_identity_token_file: token_file.into(),
+            role_arn: role_arn.to_string(),
+            session_name: ROLE_SESSION_NAME.to_string(),
+        })
+        .build();
+    Ok(sdk_config
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(provider))
+        .build())
+}
+
+fn check_role_arn(role_arn: &str) -> Result<(), String> {
+    let valid_arn = role_arn
+        .split_once(":role/")
+        .is_some_and(|(prefix, name)| prefix.starts_with("arn:") && !name.is_empty());
+    if !valid_arn {
+        return Err(format!(
+            "Invalid --role-arn value: '{}' is not a role ARN, e.g. arn:aws:iam::123456789012:role/ecr-scan-reader",
+            role_arn
+        ));
+    }
+    Ok(())
+}
+
+// Function to assume a role requiring MFA. A code is only valid once, so the
+// session cannot be refreshed during the run: it is cached instead, and reused
+// by the next runs until it expires, so that the code is asked once per session
+async fn assume_role_with_mfa(
+    sdk_config: &SdkConfig,
+    role_arn: &str,
+    session_length: Option<i64>,
+    mfa: Mfa<'_>,
+) -> Result<Credentials, String> {
+    let cache_path = session_cache_path(role_arn, mfa.serial);
+    let now = DateTime::from(SystemTime::now()).secs();
+    if let Some(credentials) = cache_path
+        .as_ref()
+        .and_then(|path| read_cached_session(path, now + MIN_CACHED_SESSION_LEFT))
+    {
+        return Ok(credentials);
+    }
+
+    let token = match mfa.token {
+        Some(token) => token.trim().to_string(),
+        None => prompt_mfa_token(mfa.serial)?,
+    };
+    if token.len() != 6 || !token.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!(
+            "Invalid --mfa-token value: '{}' is not a 6-digit code",
+            token
+        ));
+    }
+    let response = sts_client(sdk_config)
+        .assume_role()
+        .role_arn(role_arn)
+        .role_session_name(ROLE_SESSION_NAME)
+        .serial_number(mfa.serial)
+        .token_code(&token)
+        .set_duration_seconds(session_length.map(|seconds| seconds as i32))
+        .send()
+        .await
+        .map_err(|e| {
+            format!(
+                "Error assuming role '{}' with MFA: {}",
+                role_arn,
+                error_chain(&aws_sdk_sts::Error::from(e))
+            )
+        })?;
+    let session = response.credentials().ok_or_else(|| {
+        format!(
+            "Error assuming role '{}': no credentials returned",
+            role_arn
+        )
+    })?;
+    let cached = json!({
+        "AccessKeyId": session.access_key_id(),
+        "SecretAccessKey": session.secret_access_key(),
+        "SessionToken": session.session_token(),
+        "Expiration": session.expiration().fmt(DateTimeFormat::DateTime).unwrap_or_default(),
+    });
+    if let Some(path) = &cache_path {
+        if let Err(e) = write_cached_session(path, &cached) {
+            eprintln!(
+                "Warning: MFA session not cached in {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    session_credentials(&cached).ok_or_else(|| "invalid role session".to_string())
+}
+
+// Ask for the code of the MFA device on the terminal
+fn prompt_mfa_token(serial: &str) -> Result<String, String> {
+    if !io::stdin().is_terminal() {
+        return Err(format!(
+            "--mfa-serial needs --mfa-token <code> without a terminal to ask for the code of {}",
+            serial
+        ));
+    }
+    eprint!("MFA code of {}: ", serial);
+    let _ = io::stderr().flush();
+    let mut token = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut token)
+        .map_err(|e| format!("Error reading the MFA code: {}", e))?;
+    Ok(token.trim().to_string())
+}
+
+// Cache file of the MFA sessions of a role and device, in the user cache
+// directory, e.g. ~/.cache/aws-ecr-scan-detail/sessions/<sha1>.json
+fn session_cache_path(role_arn: &str, serial: &str) -> Option<PathBuf> {
+    let cache_home = std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .ok()?;
+    let digest = Sha1::digest(format!("{}|{}", role_arn, serial).as_bytes());
+    let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    Some(
+        cache_home
+            .join(env!("CARGO_PKG_NAME"))
+            .join("sessions")
+            .join(format!("{}.json", name)),
+    )
+}
+
+// Cached session still valid at the given time, None when missing or expired
+fn read_cached_session(path: &Path, valid_at: i64) -> Option<Credentials> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let cached: Value = serde_json::from_str(&content).ok()?;
+    let credentials = session_credentials(&cached)?;
+    let expiry = credentials.expiry()?;
+    (DateTime::from(expiry).secs() > valid_at).then_some(credentials)
+}
+
+fn write_cached_session(path: &Path, cached: &Value) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // The session gives the permissions of the role until it expires
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    writeln!(options.open(path)?, "{:#}", cached)
+}
+
+fn session_credentials(cached: &Value) -> Option<Credentials> {
+    let field = |name: &str| cached.get(name).and_then(|v| v.as_str());
+    let expiration = DateTime::from_str(field("Expiration")?, DateTimeFormat::DateTime).ok()?;
+    Some(Credentials::new(
+        field("AccessKeyId")?,
+        field("SecretAccessKey")?,
+        field("SessionToken").map(|token| token.to_string()),
+        SystemTime::try_from(expiration).ok(),
+        "mfa-role-session",
+    ))
+}
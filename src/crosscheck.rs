@@ -0,0 +1,355 @@
+use crate::findings::describe_findings;
+use crate::report::OutputFormat;
+use aws_sdk_ecr::types::ImageIdentifier;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::io::{self, Write};
+
+// Scanners whose JSON reports can be cross-checked against ECR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scanner {
+    Trivy,
+    Grype,
+}
+
+impl Scanner {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scanner::Trivy => "trivy",
+            Scanner::Grype => "grype",
+        }
+    }
+}
+
+// Vulnerabilities of an image found by another scanner
+pub struct ScannerReport {
+    pub scanner: Scanner,
+    // Image digests the report was written for, when the scanner records them
+    pub digests: Vec<String>,
+    pub vulnerabilities: BTreeMap<String, Vulnerability>,
+}
+
+// Vulnerability found by one scanner, with the packages it affects
+#[derive(Default)]
+pub struct Vulnerability {
+    pub severity: String,
+    pub packages: BTreeSet<String>,
+}
+
+// Vulnerabilities found by both scanners or by only one of them
+#[derive(Serialize)]
+pub struct CrossCheck {
+    pub repository_name: String,
+    pub image_digest: String,
+    pub scanner: &'static str,
+    pub common: Vec<CrossCheckEntry>,
+    pub ecr_only: Vec<CrossCheckEntry>,
+    pub scanner_only: Vec<CrossCheckEntry>,
+}
+
+// One vulnerability of the cross-check, with the severity given by each scanner
+#[derive(Serialize)]
+pub struct CrossCheckEntry {
+    pub vulnerability_id: String,
+    pub ecr_severity: Option<String>,
+    pub scanner_severity: Option<String>,
+    pub packages: Vec<String>,
+}
+
+// Fields of a Trivy JSON report (trivy image --format json)
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrivyReport {
+    #[serde(default)]
+    metadata: TrivyMetadata,
+    #[serde(default)]
+    results: Option<Vec<TrivyResult>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrivyMetadata {
+    #[serde(default)]
+    repo_digests: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrivyResult {
+    #[serde(default)]
+    vulnerabilities: Option<Vec<TrivyVulnerability>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrivyVulnerability {
+    #[serde(rename = "VulnerabilityID")]
+    vulnerability_id: String,
+    #[serde(default)]
+    pkg_name: String,
+    #[serde(default)]
+    installed_version: String,
+    #[serde(default)]
+    severity: String,
+}
+
+// Fields of a Grype JSON report (grype <image> -o json)
+#[derive(Deserialize)]
+struct GrypeReport {
+    matches: Vec<GrypeMatch>,
+    #[serde(default)]
+    source: Option<GrypeSource>,
+}
+
+#[derive(Deserialize)]
+struct GrypeMatch {
+    vulnerability: GrypeVulnerability,
+    #[serde(default, rename = "relatedVulnerabilities")]
+    related_vulnerabilities: Vec<GrypeVulnerability>,
+    artifact: GrypeArtifact,
+}
+
+#[derive(Deserialize)]
+struct GrypeVulnerability {
+    id: String,
+    #[serde(default)]
+    severity: String,
+}
+
+#[derive(Deserialize)]
+struct GrypeArtifact {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct GrypeSource {
+    // Image details for image sources, a path for directory sources
+    #[serde(default)]
+    target: Value,
+}
+
+impl ScannerReport {
+    // Load a Trivy or Grype JSON report, telling them apart by their top-level fields
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&content)?;
+        let mut report = ScannerReport {
+            scanner: Scanner::Trivy,
+            digests: Vec::new(),
+            vulnerabilities: BTreeMap::new(),
+        };
+        if value.get("matches").is_some() {
+            let grype: GrypeReport = serde_json::from_value(value)?;
+            report.scanner = Scanner::Grype;
+            if let Some(target) = grype.source.map(|s| s.target) {
+                let repo_digests = target["repoDigests"].as_array().into_iter().flatten();
+                report.digests = repo_digests
+                    .chain(target.get("manifestDigest"))
+                    .filter_map(|d| d.as_str())
+                    .map(image_digest)
+                    .collect();
+                report.digests.dedup();
+            }
+            for grype_match in grype.matches {
+                // Grype reports GitHub advisories first, with their CVE as a related vulnerability
+                let id = std::iter::once(&grype_match.vulnerability)
+                    .chain(&grype_match.related_vulnerabilities)
+                    .map(|v| v.id.as_str())
+                    .find(|id| id.starts_with("CVE-"))
+                    .unwrap_or(&grype_match.vulnerability.id);
+                let artifact = &grype_match.artifact;
+                let severity = &grype_match.vulnerability.severity;
+                report.add(id, severity, package(&artifact.name, &artifact.version));
+            }
+        } else if value.get("SchemaVersion").is_some() || value.get("Results").is_some() {
+            let trivy: TrivyReport = serde_json::from_value(value)?;
+            report.digests = trivy
+                .metadata
+                .repo_digests
+                .unwrap_or_default()
+                .iter()
+                .map(|d| image_digest(d))
+                .collect();
+            let vulnerabilities = trivy
+                .results
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|r| r.vulnerabilities.unwrap_or_default());
+            for v in vulnerabilities {
+                report.add(
+                    &v.vulnerability_id,
+                    &v.severity,
+                    package(&v.pkg_name, &v.installed_version),
+                );
+            }
+        } else {
+            return Err("not a Trivy or Grype JSON report".into());
+        }
+        Ok(report)
+    }
+
+    // Add a vulnerability and the packages it affects, keeping the first severity seen for it
+    fn add(&mut self, id: &str, severity: &str, packages: impl IntoIterator<Item = String>) {
+        let vulnerability = self.vulnerabilities.entry(id.to_string()).or_default();
+        if vulnerability.severity.is_empty() {
+            vulnerability.severity = severity.to_uppercase();
+        }
+        vulnerability.packages.extend(packages);
+    }
+}
+
+// Function to compare the ECR findings of an image, given by digest or tag,
+// with the vulnerabilities found by another scanner
+pub async fn cross_check(
+    client: &aws_sdk_ecr::Client,
+    repository_name: &str,
+    image: &str,
+    report: ScannerReport,
+) -> Result<CrossCheck, Box<dyn Error>> {
+    let image_id = if image.starts_with("sha256:") {
+        ImageIdentifier::builder().image_digest(image).build()
+    } else {
+        ImageIdentifier::builder().image_tag(image).build()
+    };
+    let response = client
+        .describe_images()
+        .repository_name(repository_name)
+        .image_ids(image_id)
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    let Some(detail) = response.image_details().first() else {
+        return Err(format!("image '{}' not found in '{}'", image, repository_name).into());
+    };
+    let digest = detail.image_digest().unwrap_or_default().to_string();
+    if !report.digests.is_empty() && !report.digests.contains(&digest) {
+        eprintln!(
+            "Warning: the {} report was written for {}, not for {}",
+            report.scanner.as_str(),
+            report.digests.join(", "),
+            digest
+        );
+    }
+    let scan = describe_findings(
+        client,
+        detail.registry_id().unwrap_or_default(),
+        repository_name,
+        &digest,
+        true,
+        None,
+    )
+    .await?
+    .ok_or_else(|| format!("no scan findings available for {}", digest))?;
+
+    // Group the ECR findings by vulnerability, as in the scanner report
+    let mut ecr = ScannerReport {
+        scanner: report.scanner,
+        digests: vec![digest.clone()],
+        vulnerabilities: BTreeMap::new(),
+    };
+    for finding in &scan.findings {
+        let packages = finding
+            .packages
+            .iter()
+            .filter_map(|p| package(&p.name, &p.version));
+        ecr.add(&finding.name, &finding.severity, packages);
+    }
+
+    let mut check = CrossCheck {
+        repository_name: repository_name.to_string(),
+        image_digest: digest,
+        scanner: report.scanner.as_str(),
+        common: Vec::new(),
+        ecr_only: Vec::new(),
+        scanner_only: Vec::new(),
+    };
+    let mut scanner_vulnerabilities = report.vulnerabilities;
+    for (id, found) in ecr.vulnerabilities {
+        match scanner_vulnerabilities.remove(&id) {
+            Some(other) => {
+                let mut packages = found.packages;
+                packages.extend(other.packages);
+                check.common.push(CrossCheckEntry {
+                    vulnerability_id: id,
+                    ecr_severity: Some(found.severity),
+                    scanner_severity: Some(other.severity),
+                    packages: packages.into_iter().collect(),
+                });
+            }
+            None => check.ecr_only.push(CrossCheckEntry {
+                vulnerability_id: id,
+                ecr_severity: Some(found.severity),
+                scanner_severity: None,
+                packages: found.packages.into_iter().collect(),
+            }),
+        }
+    }
+    for (id, other) in scanner_vulnerabilities {
+        check.scanner_only.push(CrossCheckEntry {
+            vulnerability_id: id,
+            ecr_severity: None,
+            scanner_severity: Some(other.severity),
+            packages: other.packages.into_iter().collect(),
+        });
+    }
+    Ok(check)
+}
+
+// Write the cross-check, one CSV line per vulnerability (unique ones first) or a JSON document
+pub fn write_cross_check(
+    check: &CrossCheck,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    if format == OutputFormat::Json {
+        serde_json::to_writer_pretty(&mut *out, check)?;
+        writeln!(out)?;
+        return out.flush();
+    }
+    writeln!(
+        out,
+        "vulnerability_id;found_by;ecr_severity;{}_severity;packages",
+        check.scanner
+    )?;
+    let sections = [
+        ("ecr", &check.ecr_only),
+        (check.scanner, &check.scanner_only),
+        ("both", &check.common),
+    ];
+    for (found_by, entries) in sections {
+        for entry in entries {
+            writeln!(
+                out,
+                "{};{};{};{};{}",
+                entry.vulnerability_id,
+                found_by,
+                entry.ecr_severity.as_deref().unwrap_or_default(),
+                entry.scanner_severity.as_deref().unwrap_or_default(),
+                entry.packages.join(",")
+            )?;
+        }
+    }
+    out.flush()
+}
+
+// Get the digest of an image reference such as repository@sha256:...
+fn image_digest(reference: &str) -> String {
+    match reference.rsplit_once('@') {
+        Some((_, digest)) => digest.to_string(),
+        None => reference.to_string(),
+    }
+}
+
+// Name a package version, when the package is known
+fn package(name: &str, version: &str) -> Option<String> {
+    match (name, version) {
+        ("", _) => None,
+        (name, "") => Some(name.to_string()),
+        (name, version) => Some(format!("{}@{}", name, version)),
+    }
+}
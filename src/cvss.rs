@@ -0,0 +1,70 @@
+use crate::findings::Finding;
+use crate::remediation::is_fixable;
+
+// Attack vector metric (AV) of a CVSS vector, from the most to the least exposed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackVector {
+    Network,
+    Adjacent,
+    Local,
+    Physical,
+}
+
+impl AttackVector {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "network" | "n" => Ok(AttackVector::Network),
+            "adjacent" | "adjacent_network" | "a" => Ok(AttackVector::Adjacent),
+            "local" | "l" => Ok(AttackVector::Local),
+            "physical" | "p" => Ok(AttackVector::Physical),
+            _ => Err(format!(
+                "unknown attack vector '{}', expected 'network', 'adjacent', 'local' or 'physical'",
+                value
+            )),
+        }
+    }
+
+    // Attack vector of a CVSS v2 or v3 vector, e.g. CVSS:3.1/AV:N/AC:L/...
+    pub fn from_vector(vector: &str) -> Option<Self> {
+        let value = vector
+            .split('/')
+            .find_map(|metric| metric.strip_prefix("AV:"))?;
+        match value {
+            "N" => Some(AttackVector::Network),
+            "A" => Some(AttackVector::Adjacent),
+            "L" => Some(AttackVector::Local),
+            "P" => Some(AttackVector::Physical),
+            _ => None,
+        }
+    }
+}
+
+// Findings kept by --attack-vector, --cvss-min and --fixable-only, the others
+// being left out of the counts, every finding when none is given
+#[derive(Debug, Clone, Default)]
+pub struct FindingFilter {
+    pub attack_vectors: Vec<AttackVector>,
+    pub cvss_min: Option<f64>,
+    pub fixable_only: bool, // Only findings with a fixed version of an affected package
+}
+
+impl FindingFilter {
+    pub fn is_empty(&self) -> bool {
+        self.attack_vectors.is_empty() && self.cvss_min.is_none() && !self.fixable_only
+    }
+
+    // Check a finding passes the filters, findings without a CVSS vector or
+    // score never pass the filters needing them
+    pub fn matches(&self, finding: &Finding) -> bool {
+        let vector_ok = self.attack_vectors.is_empty()
+            || finding
+                .cvss_vector
+                .as_deref()
+                .and_then(AttackVector::from_vector)
+                .is_some_and(|vector| self.attack_vectors.contains(&vector));
+        let score_ok = self
+            .cvss_min
+            .is_none_or(|min| finding.cvss_score.is_some_and(|score| score >= min));
+        vector_ok && score_ok && (!self.fixable_only || is_fixable(finding))
+    }
+}
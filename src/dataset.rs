@@ -0,0 +1,389 @@
+use crate::dates::{format_day, now};
+use crate::report::ImageRecord;
+use crate::runid::RunIds;
+use crate::spill::{temporary_path, ImageSource};
+use crate::telemetry::s3_client;
+use aws_config::SdkConfig;
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::primitives::ByteStream;
+use parquet::basic::Compression;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use parquet::schema::parser::parse_message_type;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Image rows buffered per row group of a Parquet file
+const ROW_GROUP_ROWS: usize = 10_000;
+
+// Columns available with --partition-by, as Hive-style key=value directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKey {
+    Date,
+    Account,
+    Region,
+}
+
+impl PartitionKey {
+    // Parse a comma-separated list of partition columns, e.g. date,account,region
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, String> {
+        let mut keys = Vec::new();
+        for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let key = match name {
+                "date" => PartitionKey::Date,
+                "account" => PartitionKey::Account,
+                "region" => PartitionKey::Region,
+                _ => {
+                    return Err(format!(
+                        "unknown partition column '{}', expected 'date', 'account' or 'region'",
+                        name
+                    ))
+                }
+            };
+            if keys.contains(&key) {
+                return Err(format!("partition column '{}' given twice", name));
+            }
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartitionKey::Date => "date",
+            PartitionKey::Account => "account",
+            PartitionKey::Region => "region",
+        }
+    }
+
+    // Value of the partition column for an image of the run of the given date
+    fn value(&self, image: &ImageRecord, date: &str) -> String {
+        match self {
+            PartitionKey::Date => date.to_string(),
+            PartitionKey::Account => image.account.clone(),
+            PartitionKey::Region => image.region.clone(),
+        }
+    }
+}
+
+// Column of the Parquet files, partition columns are only in the directory names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Partition(PartitionKey),
+    RepositoryName,
+    ImageDigest,
+    ImageTags,
+    ImageScanCompletedAt,
+    VulnerabilitySourceUpdatedAt,
+    ScanStatus,
+    Level(usize),
+    SlaBreaches,
+    ScanAgeDays,
+    ImageAgeDays,
+    TagCount,
+    VulnerableSincePush,
+    VulnerableAfterDays,
+}
+
+// Function to write the image records as Parquet files, one per partition, in
+// a directory or an s3://bucket/prefix location, returns the written locations
+pub async fn write_dataset(
+    sdk_config: &SdkConfig,
+    destination: &str,
+    partition_by: &[PartitionKey],
+    levels: &[String],
+    images: &dyn ImageSource,
+    ids: &RunIds,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let date = format_day(now()).unwrap_or_default();
+    // Every file of a run has the same name, so that runs never overwrite each other
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let file_name = format!("{}-{}.parquet", env!("CARGO_PKG_NAME"), started);
+
+    // Partitions of the run, in directory order
+    let mut partitions = BTreeSet::new();
+    images.try_for_each(&mut |image| {
+        partitions.insert(partition_values(partition_by, image, &date));
+        Ok(())
+    })?;
+
+    let (columns, schema) = schema(partition_by, levels);
+    let schema = Arc::new(parse_message_type(&schema)?);
+    let properties = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let s3_location = destination
+        .strip_prefix("s3://")
+        .map(|location| location.split_once('/').unwrap_or((location, "")));
+    let mut locations = Vec::new();
+    for values in partitions {
+        let directory: Vec<String> = partition_by
+            .iter()
+            .zip(&values)
+            .map(|(key, value)| format!("{}={}", key.as_str(), value))
+            .collect();
+        let relative = directory
+            .iter()
+            .chain(std::iter::once(&file_name))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("/");
+        // S3 files are written to a temporary file, then uploaded
+        let path = match s3_location {
+            Some(_) => temporary_path("parquet"),
+            None => Path::new(destination).join(&relative),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer =
+            SerializedFileWriter::new(File::create(&path)?, schema.clone(), properties.clone())?;
+        let mut rows = Vec::new();
+        images.try_for_each(&mut |image| {
+            if partition_values(partition_by, image, &date) == values {
+                rows.push(image.clone());
+            }
+            if rows.len() == ROW_GROUP_ROWS {
+                write_row_group(&mut writer, &columns, &std::mem::take(&mut rows), &date)
+                    .map_err(io::Error::other)?;
+            }
+            Ok(())
+        })?;
+        if !rows.is_empty() {
+            write_row_group(&mut writer, &columns, &rows, &date)?;
+        }
+        writer.close()?;
+
+        match s3_location {
+            Some((bucket, prefix)) => {
+                let key = match prefix.trim_end_matches('/') {
+                    "" => relative,
+                    prefix => format!("{}/{}", prefix, relative),
+                };
+                let uploaded = upload(sdk_config, bucket, &key, &path, ids).await;
+                let _ = std::fs::remove_file(&path);
+                uploaded?;
+                locations.push(format!("s3://{}/{}", bucket, key));
+            }
+            None => locations.push(path.display().to_string()),
+        }
+    }
+    Ok(locations)
+}
+
+// Values of the partition columns of an image
+fn partition_values(partition_by: &[PartitionKey], image: &ImageRecord, date: &str) -> Vec<String> {
+    partition_by
+        .iter()
+        .map(|key| key.value(image, date))
+        .collect()
+}
+
+// Columns and Parquet schema of the image rows, one count column per severity
+// level, named after the level in lower case
+fn schema(partition_by: &[PartitionKey], levels: &[String]) -> (Vec<Column>, String) {
+    let mut columns: Vec<Column> = [
+        PartitionKey::Date,
+        PartitionKey::Account,
+        PartitionKey::Region,
+    ]
+    .into_iter()
+    .filter(|key| !partition_by.contains(key))
+    .map(Column::Partition)
+    .collect();
+    columns.extend([
+        Column::RepositoryName,
+        Column::ImageDigest,
+        Column::ImageTags,
+        Column::ImageScanCompletedAt,
+        Column::VulnerabilitySourceUpdatedAt,
+        Column::ScanStatus,
+    ]);
+    columns.extend((0..levels.len()).map(Column::Level));
+    columns.extend([
+        Column::SlaBreaches,
+        Column::ScanAgeDays,
+        Column::ImageAgeDays,
+        Column::TagCount,
+        Column::VulnerableSincePush,
+        Column::VulnerableAfterDays,
+    ]);
+    let fields: Vec<String> = columns
+        .iter()
+        .map(|column| match column {
+            Column::Partition(key) => format!("REQUIRED BYTE_ARRAY {} (UTF8);", key.as_str()),
+            Column::RepositoryName => "REQUIRED BYTE_ARRAY repository_name (UTF8);".to_string(),
+            Column::ImageDigest => "REQUIRED BYTE_ARRAY image_digest (UTF8);".to_string(),
+            Column::ImageTags => "REQUIRED BYTE_ARRAY image_tags (UTF8);".to_string(),
+            Column::ImageScanCompletedAt => {
+                "OPTIONAL INT64 image_scan_completed_at (TIMESTAMP(MILLIS,true));".to_string()
+            }
+            Column::VulnerabilitySourceUpdatedAt => {
+                "OPTIONAL INT64 vulnerability_source_updated_at (TIMESTAMP(MILLIS,true));"
+                    .to_string()
+            }
+            Column::ScanStatus => "OPTIONAL BYTE_ARRAY scan_status (UTF8);".to_string(),
+            Column::Level(index) => format!("REQUIRED INT64 {};", column_name(&levels[*index])),
+            Column::SlaBreaches => "OPTIONAL INT64 sla_breaches;".to_string(),
+            Column::ScanAgeDays => "OPTIONAL INT64 scan_age_days;".to_string(),
+            Column::ImageAgeDays => "OPTIONAL INT64 image_age_days;".to_string(),
+            Column::TagCount => "OPTIONAL INT64 tag_count;".to_string(),
+            Column::VulnerableSincePush => "OPTIONAL BOOLEAN vulnerable_since_push;".to_string(),
+            Column::VulnerableAfterDays => "OPTIONAL INT64 vulnerable_after_days;".to_string(),
+        })
+        .collect();
+    (columns, format!("message image {{ {} }}", fields.join(" ")))
+}
+
+// Write one row group, column by column
+fn write_row_group(
+    writer: &mut SerializedFileWriter<File>,
+    columns: &[Column],
+    rows: &[ImageRecord],
+    date: &str,
+) -> parquet::errors::Result<()> {
+    let mut row_group = writer.next_row_group()?;
+    for column in columns {
+        let Some(mut writer) = row_group.next_column()? else {
+            break;
+        };
+        match column {
+            Column::Partition(key) => {
+                write_strings(&mut writer, rows.iter().map(|r| key.value(r, date)))?
+            }
+            Column::RepositoryName => {
+                write_strings(&mut writer, rows.iter().map(|r| r.repository_name.clone()))?
+            }
+            Column::ImageDigest => {
+                write_strings(&mut writer, rows.iter().map(|r| r.image_digest.clone()))?
+            }
+            // Comma-separated, as in the CSV report
+            Column::ImageTags => {
+                write_strings(&mut writer, rows.iter().map(|r| r.image_tags.join(",")))?
+            }
+            Column::ImageScanCompletedAt => {
+                let values = rows.iter().map(|r| timestamp(&r.image_scan_completed_at));
+                write_column::<Int64Type>(&mut writer, values.collect(), true)?
+            }
+            Column::VulnerabilitySourceUpdatedAt => {
+                let values = rows
+                    .iter()
+                    .map(|r| timestamp(&r.vulnerability_source_updated_at));
+                write_column::<Int64Type>(&mut writer, values.collect(), true)?
+            }
+            Column::ScanStatus => {
+                let values = rows.iter().map(|r| {
+                    r.scan_status
+                        .as_ref()
+                        .map(|s| ByteArray::from(s.clone().into_bytes()))
+                });
+                write_column::<ByteArrayType>(&mut writer, values.collect(), true)?
+            }
+            Column::Level(index) => {
+                let values = rows
+                    .iter()
+                    .map(|r| Some(r.severity_counts.get_index(*index).map_or(0, |(_, c)| *c)));
+                write_column::<Int64Type>(&mut writer, values.collect(), false)?
+            }
+            Column::SlaBreaches => {
+                let values = rows.iter().map(|r| r.sla_breaches.map(|b| b as i64));
+                write_column::<Int64Type>(&mut writer, values.collect(), true)?
+            }
+            Column::ScanAgeDays => {
+                let values = rows.iter().map(|r| r.scan_age_days);
+                write_column::<Int64Type>(&mut writer, values.collect(), true)?
+            }
+            Column::ImageAgeDays => {
+                let values = rows.iter().map(|r| r.image_age_days);
+                write_column::<Int64Type>(&mut writer, values.collect(), true)?
+            }
+            Column::TagCount => {
+                let values = rows.iter().map(|r| r.tag_count.map(|c| c as i64));
+                write_column::<Int64Type>(&mut writer, values.collect(), true)?
+            }
+            Column::VulnerableSincePush => {
+                let values = rows.iter().map(|r| r.vulnerable_since_push);
+                write_column::<BoolType>(&mut writer, values.collect(), true)?
+            }
+            Column::VulnerableAfterDays => {
+                let values = rows.iter().map(|r| r.vulnerable_after_days);
+                write_column::<Int64Type>(&mut writer, values.collect(), true)?
+            }
+        }
+        writer.close()?;
+    }
+    row_group.close()?;
+    Ok(())
+}
+
+// Write the values of a required text column
+fn write_strings(
+    writer: &mut SerializedColumnWriter,
+    values: impl Iterator<Item = String>,
+) -> parquet::errors::Result<()> {
+    let values = values
+        .map(|v| Some(ByteArray::from(v.into_bytes())))
+        .collect();
+    write_column::<ByteArrayType>(writer, values, false)
+}
+
+// Write the values of a column, missing values need an optional column
+fn write_column<T: DataType>(
+    writer: &mut SerializedColumnWriter,
+    values: Vec<Option<T::T>>,
+    optional: bool,
+) -> parquet::errors::Result<()> {
+    let definition_levels: Vec<i16> = values.iter().map(|v| i16::from(v.is_some())).collect();
+    let values: Vec<T::T> = values.into_iter().flatten().collect();
+    writer
+        .typed::<T>()
+        .write_batch(&values, optional.then_some(&definition_levels[..]), None)?;
+    Ok(())
+}
+
+// Milliseconds since the epoch of a report date
+fn timestamp(date: &Option<String>) -> Option<i64> {
+    let date = DateTime::from_str(date.as_deref()?, DateTimeFormat::DateTime).ok()?;
+    date.to_millis().ok()
+}
+
+// Name of the count column of a severity level, e.g. "critical" or "p1"
+fn column_name(level: &str) -> String {
+    level
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+// Upload a Parquet file to S3
+async fn upload(
+    sdk_config: &SdkConfig,
+    bucket: &str,
+    key: &str,
+    path: &Path,
+    ids: &RunIds,
+) -> Result<(), Box<dyn Error>> {
+    s3_client(sdk_config)
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from_path(path).await?)
+        .content_type("application/vnd.apache.parquet")
+        .set_metadata(Some(ids.object_metadata()))
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use std::time::SystemTime;
+
+// Parse a duration such as "90m", "12h", "7d" or "2w" into seconds
+pub fn parse_duration(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" | "" => 86400,
+        "w" => 7 * 86400,
+        _ => return Err(format!("invalid duration unit in '{}'", value)),
+    };
+    Ok(number * unit_seconds)
+}
+
+// Parse a date such as "2026-09-01", midnight UTC, or a date time such as
+// "2026-09-01T12:00:00Z"
+pub fn parse_date(value: &str) -> Result<DateTime, String> {
+    let value = value.trim();
+    let date_time = match value.len() == 10 {
+        true => format!("{}T00:00:00Z", value),
+        false => value.to_string(),
+    };
+    DateTime::from_str(&date_time, DateTimeFormat::DateTime).map_err(|_| {
+        format!(
+            "invalid date '{}', expected YYYY-MM-DD or YYYY-MM-DDThh:mm:ssZ",
+            value
+        )
+    })
+}
+
+// Current time as an AWS date time
+pub fn now() -> DateTime {
+    DateTime::from(SystemTime::now())
+}
+
+// Whole days elapsed since a date, None for missing dates and for the epoch
+// placeholder some APIs return instead, dates ahead of the local clock being
+// counted as today
+pub fn age_days(date: Option<DateTime>, now: &DateTime) -> Option<i64> {
+    let date = date.filter(|d| d.secs() > 0)?;
+    Some(((now.secs() - date.secs()) / 86400).max(0))
+}
+
+// Format a date as YYYY-MM-DD
+pub fn format_day(date: DateTime) -> Option<String> {
+    let date = date.fmt(DateTimeFormat::DateTime).ok()?;
+    date.get(..10).map(|day| day.to_string())
+}
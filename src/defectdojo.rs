@@ -0,0 +1,105 @@
+use crate::dates::format_day;
+use crate::findings::Finding;
+use crate::pull::registry_host;
+use crate::remediation::package_hint;
+use crate::report::ImageReport;
+use serde::Serialize;
+
+// Finding of the DefectDojo "Generic Findings Import" JSON format
+#[derive(Serialize)]
+pub struct DojoFinding {
+    pub title: String,
+    pub severity: &'static str,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cve: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component_version: Option<String>,
+    pub endpoints: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    // Reference URLs, one per line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub references: Option<String>,
+    // Remediation hint of the affected package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mitigation: Option<String>,
+    pub vuln_id_from_tool: String,
+    // Same finding on the same image and package across imports, for deduplication
+    pub unique_id_from_tool: String,
+    pub static_finding: bool,
+    pub dynamic_finding: bool,
+}
+
+// DefectDojo findings of an image, one per finding and affected package, with
+// the image URI as endpoint
+pub fn image_findings(image: &ImageReport, region: &str) -> Vec<DojoFinding> {
+    let Some(scan) = &image.scan else {
+        return Vec::new();
+    };
+    // Redacted images have no account, so no registry
+    let uri = match image.registry_id.as_str() {
+        "" => format!("{}@{}", image.repository_name, image.image_digest),
+        registry_id => format!(
+            "{}/{}@{}",
+            registry_host(registry_id, region),
+            image.repository_name,
+            image.image_digest
+        ),
+    };
+    let mut findings = Vec::new();
+    for finding in &scan.findings {
+        let date = finding.first_observed_at.or(scan.scan_completed_at);
+        let packages: Vec<_> = if finding.packages.is_empty() {
+            vec![None]
+        } else {
+            finding.packages.iter().map(Some).collect()
+        };
+        for package in packages {
+            let title = match package {
+                Some(package) => {
+                    format!("{} in {} {}", finding.name, package.name, package.version)
+                }
+                None => finding.name.clone(),
+            };
+            findings.push(DojoFinding {
+                title: title.trim_end().to_string(),
+                severity: severity(finding),
+                description: finding
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| finding.name.clone()),
+                cve: Some(finding.name.clone()).filter(|name| name.starts_with("CVE-")),
+                component_name: package.map(|p| p.name.clone()),
+                component_version: package.map(|p| p.version.clone()).filter(|v| !v.is_empty()),
+                endpoints: vec![uri.clone()],
+                date: date.and_then(format_day),
+                references: Some(finding.references.join("\n")).filter(|r| !r.is_empty()),
+                mitigation: package.and_then(|p| package_hint(p, finding.platform.as_deref())),
+                vuln_id_from_tool: finding.name.clone(),
+                unique_id_from_tool: format!(
+                    "{}/{}/{}",
+                    image.image_digest,
+                    finding.name,
+                    package.map_or("", |p| p.name.as_str())
+                ),
+                static_finding: true,
+                dynamic_finding: false,
+            });
+        }
+    }
+    findings
+}
+
+// DefectDojo severity of an AWS severity, findings without one are informational
+fn severity(finding: &Finding) -> &'static str {
+    match finding.severity.to_uppercase().as_str() {
+        "CRITICAL" => "Critical",
+        "HIGH" => "High",
+        "MEDIUM" => "Medium",
+        "LOW" => "Low",
+        _ => "Info",
+    }
+}
@@ -0,0 +1,314 @@
+use crate::errors::{
+    error_chain, ErrorKind, RunError, EXIT_API_FAILURE, EXIT_AUTH_CONFIG, EXIT_OK,
+};
+use crate::partition::Partition;
+use crate::telemetry::{ecr_client, sts_client};
+use aws_config::SdkConfig;
+use aws_sdk_ecr::config::ProvideCredentials;
+use aws_sdk_ecr::error::ProvideErrorMetadata;
+use aws_sdk_ecr::types::builders::ImageIdentifierBuilder;
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+// Number of repositories probed for an image to check the image permissions on
+const PROBED_REPOSITORIES: i32 = 20;
+
+// Outcome of a permission check
+enum Check {
+    Allowed,
+    Denied(String),  // Rejected by IAM, with the AWS message
+    Failed(String),  // Could not tell, e.g. network error
+    Skipped(String), // Nothing to call the action on
+}
+
+// Credential options of the run, telling where its credentials come from
+pub struct CredentialOptions<'a> {
+    pub sso: bool,
+    pub role_arn: Option<&'a str>,
+    pub web_identity_token_file: Option<&'a str>,
+}
+
+// Function to diagnose credentials, identity and the IAM permissions needed by a report run
+pub async fn run_doctor(
+    sdk_config: &SdkConfig,
+    repository_name: Option<&String>,
+    options: &CredentialOptions<'_>,
+) -> u8 {
+    let mut exit_code = EXIT_OK;
+
+    // Region used for every call
+    match sdk_config.region() {
+        Some(region) => println!(
+            "Region:      {} (partition {})",
+            region,
+            Partition::from_region(region.as_ref()).name()
+        ),
+        None => {
+            println!("Region:      not set (use --region, AWS_REGION or a profile region)");
+            exit_code = EXIT_AUTH_CONFIG;
+        }
+    }
+
+    // Source the credentials come from, before resolving them
+    let (source, warnings) = credential_source(options);
+    println!("Source:      {}", source);
+    for warning in warnings {
+        println!("Warning:     {}", warning);
+    }
+
+    // Resolve credentials from the default provider chain
+    let Some(provider) = sdk_config.credentials_provider() else {
+        println!("Credentials: no credentials provider configured");
+        return EXIT_AUTH_CONFIG;
+    };
+    match provider.provide_credentials().await {
+        Ok(credentials) => println!(
+            "Credentials: resolved (access key {})",
+            mask_key(credentials.access_key_id())
+        ),
+        Err(e) => {
+            println!("Credentials: not resolved: {}", error_chain(&e));
+            return EXIT_AUTH_CONFIG;
+        }
+    }
+
+    // Identity the credentials belong to
+    let sts = sts_client(sdk_config);
+    match sts.get_caller_identity().send().await {
+        Ok(identity) => {
+            println!("Account:     {}", identity.account().unwrap_or_default());
+            println!("Identity:    {}", identity.arn().unwrap_or_default());
+        }
+        Err(e) => {
+            let e = aws_sdk_sts::Error::from(e);
+            println!(
+                "Identity:    sts:GetCallerIdentity failed: {}",
+                error_chain(&e)
+            );
+            // Errors with a code come from STS rejecting the credentials
+            return match e.code() {
+                Some(_) => EXIT_AUTH_CONFIG,
+                None => EXIT_API_FAILURE,
+            };
+        }
+    }
+
+    // Call every ECR action of a report run with the smallest possible request
+    println!("Permissions:");
+    let client = ecr_client(sdk_config);
+    let mut checks = Vec::new();
+
+    let result = client.get_registry_scanning_configuration().send().await;
+    checks.push(("ecr:GetRegistryScanningConfiguration", check(result)));
+
+    let mut request = client
+        .describe_repositories()
+        .max_results(PROBED_REPOSITORIES);
+    if let Some(name) = repository_name {
+        request = request.repository_names(name);
+    }
+    let result = request.send().await;
+    let repositories = result
+        .as_ref()
+        .map(|r| r.repositories().to_vec())
+        .unwrap_or_default();
+    checks.push(("ecr:DescribeRepositories", check(result)));
+
+    // Resource tags are only read with --repo-tag and --tag-columns
+    let tags_check = match repositories.first().and_then(|r| r.repository_arn()) {
+        Some(arn) => check(
+            client
+                .list_tags_for_resource()
+                .resource_arn(arn)
+                .send()
+                .await,
+        ),
+        None => Check::Skipped("no repository found".to_string()),
+    };
+    checks.push(("ecr:ListTagsForResource", tags_check));
+
+    // Look for an image in the first repositories to check the image actions on
+    let mut images_check = Check::Skipped("no repository found".to_string());
+    let mut image = None;
+    for repository in &repositories {
+        let repository_name = repository.repository_name().unwrap_or_default();
+        let result = client
+            .describe_images()
+            .repository_name(repository_name)
+            .max_results(1)
+            .send()
+            .await;
+        image = result
+            .as_ref()
+            .ok()
+            .and_then(|r| r.image_details().first().cloned());
+        images_check = check(result);
+        if image.is_some() || !matches!(images_check, Check::Allowed) {
+            break;
+        }
+    }
+    checks.push(("ecr:DescribeImages", images_check));
+
+    let findings_check = match &image {
+        Some(detail) => {
+            let image_identifier = ImageIdentifierBuilder::default()
+                .image_digest(detail.image_digest().unwrap_or_default())
+                .build();
+            check(
+                client
+                    .describe_image_scan_findings()
+                    .repository_name(detail.repository_name().unwrap_or_default())
+                    .image_id(image_identifier)
+                    .max_results(1)
+                    .send()
+                    .await,
+            )
+        }
+        None => Check::Skipped("no image found".to_string()),
+    };
+    checks.push(("ecr:DescribeImageScanFindings", findings_check));
+
+    // Print the outcome of each check, missing permissions fail the diagnosis
+    let mut missing = Vec::new();
+    for (action, outcome) in &checks {
+        match outcome {
+            Check::Allowed => println!("  ok       {}", action),
+            Check::Denied(message) => {
+                println!("  MISSING  {} ({})", action, message);
+                missing.push(*action);
+            }
+            Check::Failed(message) => {
+                println!("  ERROR    {} ({})", action, message);
+                if exit_code == EXIT_OK {
+                    exit_code = EXIT_API_FAILURE;
+                }
+            }
+            Check::Skipped(reason) => println!("  skipped  {} ({})", action, reason),
+        }
+    }
+    if !missing.is_empty() {
+        println!("Missing permissions: {}", missing.join(", "));
+        exit_code = EXIT_AUTH_CONFIG;
+    }
+    exit_code
+}
+
+// Classify the result of a call: any answer other than an authorization error
+// (e.g. ScanNotFoundException) means IAM allowed the action
+fn check<T, E>(result: Result<T, E>) -> Check
+where
+    aws_sdk_ecr::Error: From<E>,
+{
+    let Err(e) = result else {
+        return Check::Allowed;
+    };
+    let error = RunError::from_error(None, &aws_sdk_ecr::Error::from(e));
+    match (error.kind, &error.code) {
+        (ErrorKind::Auth, _) => Check::Denied(error.message),
+        (_, Some(_)) => Check::Allowed,
+        (_, None) => Check::Failed(error.message),
+    }
+}
+
+// Only show the last characters of an access key
+fn mask_key(key: &str) -> String {
+    let visible = key.len().saturating_sub(4);
+    format!("****{}", &key[visible..])
+}
+
+// Source of the credentials of the run, and warnings about the credential
+// environment variables that are set but not used. The default provider chain
+// is told from the environment in its own order: access keys, profile, web
+// identity token (EKS IRSA), container endpoint (ECS task role, EKS Pod
+// Identity) and EC2 instance metadata
+fn credential_source(options: &CredentialOptions) -> (String, Vec<String>) {
+    let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+    let mut warnings = Vec::new();
+    let role_arn = options.role_arn.map(|r| r.to_string());
+    if let Some(token_file) = options.web_identity_token_file {
+        let role_arn = role_arn.or_else(|| var("AWS_ROLE_ARN")).unwrap_or_default();
+        return (
+            format!(
+                "web identity token {} as role {} (--web-identity-token-file)",
+                token_file, role_arn
+            ),
+            warnings,
+        );
+    }
+    let mut chain = Vec::new();
+    if var("AWS_ACCESS_KEY_ID").is_some() && var("AWS_SECRET_ACCESS_KEY").is_some() {
+        chain.push("access keys of AWS_ACCESS_KEY_ID".to_string());
+    }
+    let credentials_file = var("AWS_SHARED_CREDENTIALS_FILE")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| Path::new(&home).join(".aws/credentials")));
+    if let Some(profile) = var("AWS_PROFILE") {
+        chain.push(format!("profile {} (AWS_PROFILE)", profile));
+    } else if credentials_file.is_some_and(|file| file.exists()) {
+        chain.push("default profile of the shared credentials file".to_string());
+    }
+    match (var("AWS_WEB_IDENTITY_TOKEN_FILE"), var("AWS_ROLE_ARN")) {
+        (Some(token_file), Some(role_arn)) => {
+            if let Err(e) = File::open(&token_file) {
+                warnings.push(format!(
+                    "the web identity token {} cannot be read: {}",
+                    token_file, e
+                ));
+            }
+            chain.push(format!(
+                "web identity token {} as role {} (EKS IRSA)",
+                token_file, role_arn
+            ));
+        }
+        (Some(_), None) => warnings.push(
+            "AWS_WEB_IDENTITY_TOKEN_FILE is set without AWS_ROLE_ARN, its token is not used"
+                .to_string(),
+        ),
+        (None, Some(_)) => warnings.push(
+            "AWS_ROLE_ARN is set without AWS_WEB_IDENTITY_TOKEN_FILE, the role is not assumed"
+                .to_string(),
+        ),
+        (None, None) => (),
+    }
+    if let Some(uri) = var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+        chain.push(format!("ECS task role (endpoint {})", uri));
+    } else if let Some(uri) = var("AWS_CONTAINER_CREDENTIALS_FULL_URI") {
+        if let Some(token_file) = var("AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE") {
+            if let Err(e) = File::open(&token_file) {
+                warnings.push(format!(
+                    "the container authorization token {} cannot be read: {}",
+                    token_file, e
+                ));
+            }
+        }
+        chain.push(format!("container endpoint {} (EKS Pod Identity)", uri));
+    }
+    if var("AWS_EC2_METADATA_DISABLED").is_some_and(|v| v.eq_ignore_ascii_case("true")) {
+        if chain.is_empty() {
+            chain.push("none (AWS_EC2_METADATA_DISABLED is set)".to_string());
+        }
+    } else {
+        chain.push("EC2 instance metadata".to_string());
+    }
+    // Sources later in the chain are only used when the first ones fail
+    let mut chain = chain.into_iter();
+    let mut source = chain.next().unwrap_or_default();
+    for shadowed in chain.filter(|s| !s.starts_with("EC2 ")) {
+        warnings.push(format!(
+            "{} is set but not used, the default provider chain takes the {} first",
+            shadowed, source
+        ));
+    }
+    if options.sso {
+        source = "IAM Identity Center role (--sso-account-id and --sso-role-name)".to_string();
+        warnings.clear();
+    }
+    if let Some(role_arn) = role_arn {
+        source = format!(
+            "role {} (--role-arn), assumed with the {}",
+            role_arn, source
+        );
+    }
+    (source, warnings)
+}
@@ -0,0 +1,139 @@
+use crate::errors::{error_chain, errors_exit_code, RunError, EXIT_API_FAILURE, EXIT_OK};
+use crate::report::ReportSettings;
+use crate::repos::{describe_repositories, MAX_REPOSITORY_NAMES};
+use crate::suggest::repository_not_found;
+use crate::tags::fetch_repository_tags;
+use crate::telemetry::sts_client;
+use aws_config::SdkConfig;
+use std::collections::HashMap;
+use std::error::Error;
+
+// Function to print what a report run would process and the AWS calls it would
+// make, listing the repositories without describing any image
+pub async fn dry_run(
+    sdk_config: &SdkConfig,
+    client: &aws_sdk_ecr::Client,
+    repository_name: Option<&String>,
+    repository_names: Option<&[String]>,
+    settings: &ReportSettings,
+) -> u8 {
+    match plan(
+        sdk_config,
+        client,
+        repository_name,
+        repository_names,
+        settings,
+    )
+    .await
+    {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            eprintln!("Error planning the run: {}", error_chain(e.as_ref()));
+            let error = RunError::from_error(repository_name.map(|r| r.as_str()), e.as_ref());
+            errors_exit_code(&[error]).unwrap_or(EXIT_API_FAILURE)
+        }
+    }
+}
+
+async fn plan(
+    sdk_config: &SdkConfig,
+    client: &aws_sdk_ecr::Client,
+    repository_name: Option<&String>,
+    repository_names: Option<&[String]>,
+    settings: &ReportSettings,
+) -> Result<(), Box<dyn Error>> {
+    let region = sdk_config
+        .region()
+        .map(|r| r.to_string())
+        .unwrap_or_default();
+    let identity = sts_client(sdk_config)
+        .get_caller_identity()
+        .send()
+        .await
+        .map_err(aws_sdk_sts::Error::from)?;
+    let scanning = client
+        .get_registry_scanning_configuration()
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    let scan_type = scanning
+        .scanning_configuration()
+        .and_then(|c| c.scan_type())
+        .map_or("unknown", |t| t.as_str());
+
+    // Same repositories as the run, filtered by their resource tags
+    let names = repository_name
+        .map(std::slice::from_ref)
+        .or(repository_names);
+    let described = match describe_repositories(client, names).await {
+        Ok(described) => described,
+        Err(e) => {
+            // Suggest close names instead of the SDK error for a mistyped name
+            if let Some(name) = repository_name {
+                if let Some(not_found) = repository_not_found(client, name, &e).await {
+                    return Err(not_found.into());
+                }
+            }
+            return Err(e.into());
+        }
+    };
+    let mut repositories = Vec::new();
+    for repository in &described {
+        let tags = if settings.needs_repository_tags() {
+            fetch_repository_tags(client, repository.repository_arn().unwrap_or_default()).await?
+        } else {
+            HashMap::new()
+        };
+        if settings.matches_repository_tags(&tags) {
+            repositories.push(repository.repository_name().unwrap_or_default());
+        }
+    }
+
+    println!("Account:      {}", identity.account().unwrap_or_default());
+    println!("Region:       {}", region);
+    println!("Scan type:    {}", scan_type);
+    println!("Repositories: {}", repositories.len());
+    for repository in &repositories {
+        println!("  {}", repository);
+    }
+
+    // Images are unknown until described, so are the per-image calls
+    let count = repositories.len() as u64;
+    let max_images = match (settings.max_images_per_repo, settings.sample) {
+        (Some(max), Some(sample)) => Some((max * count).min(sample)),
+        (max, sample) => max.map(|max| max * count).or(sample),
+    };
+    let findings_calls = match max_images {
+        Some(max) => format!("one per image, {} image(s) at most", max),
+        None => "one per image".to_string(),
+    };
+    let findings_pages = if settings.needs_findings() {
+        ", plus one per 1000 findings of an image"
+    } else {
+        ""
+    };
+    println!("Approximate API calls:");
+    println!("  ecr:GetRegistryScanningConfiguration   1");
+    // A single repository is only described to read its resource tags
+    if repository_name.is_none() || settings.needs_repository_tags() {
+        println!(
+            "  ecr:DescribeRepositories               {}",
+            repository_names.map_or(1, |names| names.len().div_ceil(MAX_REPOSITORY_NAMES))
+        );
+    }
+    if settings.needs_repository_tags() {
+        println!(
+            "  ecr:ListTagsForResource                {}",
+            described.len()
+        );
+    }
+    println!(
+        "  ecr:DescribeImages                     {} at least, one per 1000 images of a repository",
+        count
+    );
+    println!(
+        "  ecr:DescribeImageScanFindings          {}{}",
+        findings_calls, findings_pages
+    );
+    Ok(())
+}
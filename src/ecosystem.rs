@@ -0,0 +1,58 @@
+use crate::findings::{Finding, Package};
+
+// Ecosystem of the findings without affected package
+const UNKNOWN: &str = "unknown";
+
+// Ecosystems of the Inspector package managers, the others keep their name
+const ECOSYSTEMS: [(&str, &str); 20] = [
+    ("OS", "os"),
+    ("NPM", "npm"),
+    ("NODEPKG", "npm"),
+    ("YARN", "npm"),
+    ("PNPM", "npm"),
+    ("PIP", "pip"),
+    ("PYTHONPKG", "pip"),
+    ("PIPENV", "pip"),
+    ("POETRY", "pip"),
+    ("JAR", "maven"),
+    ("POM", "maven"),
+    ("GRADLE", "maven"),
+    ("GOBINARY", "go"),
+    ("GOMOD", "go"),
+    ("GEM", "gem"),
+    ("GEMSPEC", "gem"),
+    ("BUNDLER", "gem"),
+    ("NUGET", "nuget"),
+    ("DOTNET_CORE", "nuget"),
+    ("CARGO", "cargo"),
+];
+
+// Ecosystem of an affected package, e.g. npm for NPM or NODEPKG packages.
+// Basic scanning only scans the packages of the image distribution, so its
+// packages, which have no package manager, are OS packages
+fn package_ecosystem(package: &Package) -> String {
+    let Some(manager) = package.package_manager.as_deref() else {
+        return "os".to_string();
+    };
+    ECOSYSTEMS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(manager))
+        .map_or_else(
+            || manager.to_lowercase(),
+            |(_, ecosystem)| ecosystem.to_string(),
+        )
+}
+
+// Ecosystems of the packages affected by a finding, each listed once
+pub fn finding_ecosystems(finding: &Finding) -> Vec<String> {
+    let mut ecosystems: Vec<String> = Vec::new();
+    for ecosystem in finding.packages.iter().map(package_ecosystem) {
+        if !ecosystems.contains(&ecosystem) {
+            ecosystems.push(ecosystem);
+        }
+    }
+    if ecosystems.is_empty() {
+        ecosystems.push(UNKNOWN.to_string());
+    }
+    ecosystems
+}
@@ -0,0 +1,117 @@
+use std::io::{self, Write};
+
+// Character encodings available with --encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+}
+
+// Line endings available with --line-endings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl Encoding {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "utf-8" => Ok(Encoding::Utf8),
+            "utf-8-bom" => Ok(Encoding::Utf8Bom),
+            "utf-16le" => Ok(Encoding::Utf16Le),
+            _ => Err(format!(
+                "unknown encoding '{}', expected 'utf-8', 'utf-8-bom' or 'utf-16le'",
+                value
+            )),
+        }
+    }
+
+    // Byte order mark written before the text, Excel relies on it to detect the encoding
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => b"",
+            Encoding::Utf8Bom => b"\xEF\xBB\xBF",
+            Encoding::Utf16Le => b"\xFF\xFE",
+        }
+    }
+}
+
+impl LineEnding {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => Err(format!(
+                "unknown line endings '{}', expected 'lf' or 'crlf'",
+                value
+            )),
+        }
+    }
+}
+
+// Writer re-encoding the UTF-8 text written to it
+pub struct EncodedWriter<W: Write> {
+    inner: W,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    started: bool,
+    // Start of a character split across two writes
+    pending: Vec<u8>,
+}
+
+impl<W: Write> EncodedWriter<W> {
+    pub fn new(inner: W, encoding: Encoding, line_ending: LineEnding) -> Self {
+        EncodedWriter {
+            inner,
+            encoding,
+            line_ending,
+            started: false,
+            pending: Vec::new(),
+        }
+    }
+
+    // Get the inner writer back, once flushed
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for EncodedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing to convert, text is written as is
+        if self.encoding == Encoding::Utf8 && self.line_ending == LineEnding::Lf {
+            return self.inner.write(buf);
+        }
+        if !self.started {
+            self.inner.write_all(self.encoding.bom())?;
+            self.started = true;
+        }
+        self.pending.extend_from_slice(buf);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            // Keep an incomplete character for the next write
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let text = std::str::from_utf8(&self.pending[..valid]).unwrap_or_default();
+        let text = match self.line_ending {
+            LineEnding::Lf => text.to_string(),
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+        };
+        match self.encoding {
+            Encoding::Utf16Le => {
+                let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+                self.inner.write_all(&bytes)?;
+            }
+            Encoding::Utf8 | Encoding::Utf8Bom => self.inner.write_all(text.as_bytes())?,
+        }
+        self.pending.drain(..valid);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,66 @@
+use indexmap::IndexMap;
+
+// Environments of the images, from the image tag patterns of the configuration
+// file, e.g. "production": ["prod-*", "v*"]
+#[derive(Debug, Clone, Default)]
+pub struct EnvironmentMapping {
+    environments: IndexMap<String, Vec<String>>,
+}
+
+impl EnvironmentMapping {
+    pub fn new(environments: IndexMap<String, Vec<String>>) -> Result<Self, String> {
+        for (name, patterns) in &environments {
+            if name.trim().is_empty() {
+                return Err("environment names cannot be empty".to_string());
+            }
+            if patterns.iter().any(|p| p.is_empty()) {
+                return Err(format!("environment '{}' has an empty tag pattern", name));
+            }
+        }
+        Ok(EnvironmentMapping { environments })
+    }
+
+    // True when the configuration file maps tags to environments
+    pub fn is_empty(&self) -> bool {
+        self.environments.is_empty()
+    }
+
+    // Check an environment is defined by the configuration file
+    pub fn contains(&self, name: &str) -> bool {
+        self.environments.contains_key(name)
+    }
+
+    // Environment of an image, the first one in configuration order with a
+    // pattern matching one of its tags
+    pub fn environment(&self, image_tags: &[String]) -> Option<&str> {
+        self.environments
+            .iter()
+            .find(|(_, patterns)| {
+                patterns
+                    .iter()
+                    .any(|pattern| image_tags.iter().any(|tag| matches_pattern(pattern, tag)))
+            })
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+// Match a tag against a pattern where '*' stands for any characters
+pub fn matches_pattern(pattern: &str, tag: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = tag.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No '*' in the pattern
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
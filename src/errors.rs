@@ -0,0 +1,191 @@
+use crate::interrupt::Interrupted;
+use crate::runid::RunIds;
+use crate::telemetry::failed_call_attempts;
+use aws_sdk_ecr::error::ProvideErrorMetadata;
+use aws_sdk_ecr::operation::RequestId;
+use serde::Serialize;
+use std::error::Error;
+
+// Exit codes of the tool, documented in the README
+pub const EXIT_OK: u8 = 0;
+pub const EXIT_FINDINGS: u8 = 1; // Findings over --fail-on, repository or tag thresholds, or --sla
+pub const EXIT_API_FAILURE: u8 = 2; // Some AWS API calls failed
+pub const EXIT_AUTH_CONFIG: u8 = 3; // Credentials, permissions, arguments or configuration file
+pub const EXIT_DEGRADED: u8 = 4; // Rows with degraded data under --strict
+pub const EXIT_EMPTY: u8 = 5; // No image to report under --fail-on-empty
+pub const EXIT_INTERRUPTED: u8 = 130; // Stopped by Ctrl-C or SIGTERM, as shells report SIGINT
+
+// AWS error codes caused by credentials or permissions
+const AUTH_ERROR_CODES: [&str; 9] = [
+    "AccessDeniedException",
+    "AccessDenied",
+    "UnrecognizedClientException",
+    "InvalidSignatureException",
+    "SignatureDoesNotMatch",
+    "ExpiredTokenException",
+    "ExpiredToken",
+    "InvalidClientTokenId",
+    "MissingAuthenticationTokenException",
+];
+
+// Category of an error, driving the exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Auth,        // Credentials or permissions
+    Config,      // Invalid arguments or configuration file
+    Api,         // Other AWS API failure
+    Output,      // Report could not be written
+    Interrupted, // Run stopped by Ctrl-C or SIGTERM
+}
+
+// Structured description of an error, for the run manifest and --errors-json
+#[derive(Debug, Clone, Serialize)]
+pub struct RunError {
+    pub kind: ErrorKind,
+    pub repository: Option<String>,
+    pub image: Option<String>, // Digest of the image whose findings could not be read
+    pub code: Option<String>,
+    pub request_id: Option<String>,
+    pub attempts: Option<u32>, // Attempts of the failed AWS call, retries included
+    pub message: String,
+}
+
+impl RunError {
+    // Describe an error, classifying AWS errors from their code
+    pub fn from_error(repository: Option<&str>, error: &(dyn Error + 'static)) -> Self {
+        // Errors of the AWS services called by the tool
+        let aws_error: Option<&dyn ProvideErrorMetadata> =
+            if let Some(e) = error.downcast_ref::<aws_sdk_ecr::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_s3::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_cloudwatch::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_cloudwatchlogs::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_firehose::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_ecs::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_lambda::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_sts::Error>() {
+                Some(e)
+            } else if let Some(e) = error.downcast_ref::<aws_sdk_kms::Error>() {
+                Some(e)
+            } else {
+                None
+            };
+        if let Some(aws_error) = aws_error {
+            let code = aws_error.code().map(|c| c.to_string());
+            // Errors without a code (network, credentials) are only readable with their sources
+            let message = match aws_error.message() {
+                Some(message) => message.to_string(),
+                None => error_chain(error),
+            };
+            let auth = code
+                .as_deref()
+                .is_some_and(|c| AUTH_ERROR_CODES.contains(&c))
+                || (code.is_none() && message.contains("credentials"));
+            let request_id = aws_error.meta().request_id();
+            return RunError {
+                kind: if auth {
+                    ErrorKind::Auth
+                } else {
+                    ErrorKind::Api
+                },
+                repository: repository.map(|r| r.to_string()),
+                image: None,
+                code,
+                request_id: request_id.map(|r| r.to_string()),
+                attempts: failed_call_attempts(request_id),
+                message,
+            };
+        }
+        let kind = if error.is::<std::io::Error>() {
+            ErrorKind::Output
+        } else if error.is::<Interrupted>() {
+            ErrorKind::Interrupted
+        } else {
+            ErrorKind::Api
+        };
+        RunError {
+            kind,
+            repository: repository.map(|r| r.to_string()),
+            image: None,
+            code: None,
+            request_id: None,
+            attempts: None,
+            message: error.to_string(),
+        }
+    }
+
+    // Describe an invalid argument or configuration error
+    pub fn config(message: impl ToString) -> Self {
+        RunError {
+            kind: ErrorKind::Config,
+            repository: None,
+            image: None,
+            code: None,
+            request_id: None,
+            attempts: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+// Describe an error with all its sources, as SDK errors without a code
+// (network, credentials) only say "unhandled error" on their own
+pub fn error_chain(error: &(dyn Error + 'static)) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+// Exit code for a list of errors: interruptions first, then auth/config errors,
+// then API failures
+pub fn errors_exit_code(errors: &[RunError]) -> Option<u8> {
+    if errors.iter().any(|e| e.kind == ErrorKind::Interrupted) {
+        Some(EXIT_INTERRUPTED)
+    } else if errors
+        .iter()
+        .any(|e| matches!(e.kind, ErrorKind::Auth | ErrorKind::Config))
+    {
+        Some(EXIT_AUTH_CONFIG)
+    } else if errors.is_empty() {
+        None
+    } else {
+        Some(EXIT_API_FAILURE)
+    }
+}
+
+// Document written with --errors-json
+#[derive(Serialize)]
+struct ErrorsFile<'a> {
+    #[serde(flatten)]
+    ids: &'a RunIds,
+    exit_code: u8,
+    errors: &'a [RunError],
+}
+
+// Write the structured errors as pretty-printed JSON
+pub fn write_errors(
+    path: &str,
+    exit_code: u8,
+    ids: &RunIds,
+    errors: &[RunError],
+) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(&ErrorsFile {
+        ids,
+        exit_code,
+        errors,
+    })?;
+    std::fs::write(path, json + "\n")?;
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use crate::labels::Labels;
+use crate::manifest::RunManifest;
+use crate::output::temporary_file;
+use crate::render::write_document;
+use crate::report::{JsonReport, OutputFormat};
+use crate::runid::RunIds;
+use crate::schedule::civil_from_days;
+use crate::signature::sign_digest;
+use crate::spill::{temporary_path, ImageSource};
+use crate::telemetry::s3_client;
+use aws_config::SdkConfig;
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::primitives::ByteStream;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime as ZipDateTime, ZipWriter};
+
+// File of the pack listing the SHA-256 digest of every other file, in the
+// format of sha256sum, checked with 'sha256sum -c SHA256SUMS'
+pub const CHECKSUMS: &str = "SHA256SUMS";
+// Detached ASCII armored signature of the checksum file, with --gpg-key
+const GPG_SIGNATURE: &str = "SHA256SUMS.asc";
+// KMS signature of the checksum file, with --sign-kms-key-id
+const KMS_SIGNATURE: &str = "SHA256SUMS.sig";
+
+// Options of the evidence subcommand
+#[derive(Debug, Clone)]
+pub struct EvidenceOptions {
+    pub path: String,               // File or S3 location of the zip archive
+    pub gpg_key: Option<String>,    // GPG key signing the checksum file
+    pub kms_key_id: Option<String>, // KMS asymmetric key signing the checksum file
+}
+
+// Function to bundle the HTML and JSON reports of a run with its manifest
+// and their checksums into a zip archive, returning the files of the pack
+pub async fn write_evidence(
+    sdk_config: &SdkConfig,
+    options: &EvidenceOptions,
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    labels: &Labels,
+    manifest: &RunManifest,
+    ids: &RunIds,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    // The archive is written to a temporary file uploaded to S3, or renamed to
+    // its file once complete so that a failed signing leaves no truncated pack
+    let s3_location = options
+        .path
+        .strip_prefix("s3://")
+        .map(|location| location.split_once('/').unwrap_or((location, "")));
+    let temporary = match s3_location {
+        Some(_) => temporary_path("zip"),
+        None => Path::new(&options.path).with_extension(format!("{}.tmp", std::process::id())),
+    };
+    let file = match s3_location {
+        Some(_) => temporary_file(&temporary)?,
+        None => File::create(&temporary)?,
+    };
+    // The files of the pack and their signed checksums
+    let packed = async {
+        // Every file is dated with the start of the run
+        let started_at = DateTime::from_str(&manifest.started_at, DateTimeFormat::DateTime)
+            .map(|at| at.secs())
+            .unwrap_or_default();
+        let mut archive = EvidenceArchive::new(file, started_at);
+        for (name, format) in [
+            ("report.html", OutputFormat::Html),
+            ("report.json", OutputFormat::Json),
+        ] {
+            archive.add(name, true, |out| {
+                write_document(report, images, format, labels, out)
+            })?;
+        }
+        archive.add("run-manifest.json", false, |out| {
+            serde_json::to_writer_pretty(&mut *out, manifest)?;
+            writeln!(out)
+        })?;
+        let checksums = archive.checksums.clone();
+        let signature = match &options.gpg_key {
+            Some(key) => Some(gpg_sign(key, checksums.as_bytes()).map_err(|e| {
+                format!("error signing {} with GPG key '{}': {}", CHECKSUMS, key, e)
+            })?),
+            None => None,
+        };
+        let kms_signature = match &options.kms_key_id {
+            Some(key_id) => {
+                let digest = Sha256::digest(checksums.as_bytes());
+                let signature = sign_digest(sdk_config, key_id, &digest, ids)
+                    .await
+                    .map_err(|e| {
+                        format!(
+                            "error signing {} with KMS key '{}': {}",
+                            CHECKSUMS, key_id, e
+                        )
+                    })?;
+                Some(serde_json::to_string_pretty(&signature)? + "\n")
+            }
+            None => None,
+        };
+        // The checksum file and its signatures are not listed in the checksums
+        archive.add_unlisted(CHECKSUMS, checksums.as_bytes())?;
+        if let Some(signature) = signature {
+            archive.add_unlisted(GPG_SIGNATURE, &signature)?;
+        }
+        if let Some(signature) = kms_signature {
+            archive.add_unlisted(KMS_SIGNATURE, signature.as_bytes())?;
+        }
+        Ok::<_, Box<dyn Error>>(archive.finish()?)
+    }
+    .await;
+    let (mut file, files) = match packed {
+        Ok(packed) => packed,
+        Err(e) => {
+            if s3_location.is_none() || cfg!(not(unix)) {
+                let _ = std::fs::remove_file(&temporary);
+            }
+            return Err(e);
+        }
+    };
+
+    match s3_location {
+        None => std::fs::rename(&temporary, &options.path)?,
+        Some((bucket, key)) => {
+            file.seek(SeekFrom::Start(0))?;
+            let body = ByteStream::read_from()
+                .file(tokio::fs::File::from_std(file))
+                .build()
+                .await?;
+            let uploaded = s3_client(sdk_config)
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(body)
+                .content_type("application/zip")
+                .set_metadata(Some(ids.object_metadata()))
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from);
+            if cfg!(not(unix)) {
+                let _ = std::fs::remove_file(&temporary);
+            }
+            uploaded?;
+        }
+    }
+    Ok(files)
+}
+
+// Lowercase hexadecimal digest
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Function to sign a document with gpg, the key and its passphrase coming
+// from the keyring and the agent of the user
+fn gpg_sign(key: &str, document: &[u8]) -> io::Result<Vec<u8>> {
+    let mut child = Command::new("gpg")
+        .args([
+            "--batch",
+            "--yes",
+            "--armor",
+            "--detach-sign",
+            "--local-user",
+            key,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(document)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+// Zip archive of the pack, its files deflated and streamed to disk, with
+// Zip64 records for the reports, which may be larger than 4 GiB
+struct EvidenceArchive {
+    zip: ZipWriter<BufWriter<File>>,
+    options: SimpleFileOptions,
+    files: Vec<String>,
+    checksums: String, // Content of the checksum file, in the format of sha256sum
+}
+
+impl EvidenceArchive {
+    fn new(file: File, time: i64) -> Self {
+        // MS-DOS date and time of the files, in UTC, 1980 at the earliest
+        let (year, month, day) = civil_from_days(time.div_euclid(86400));
+        let seconds = time.rem_euclid(86400);
+        let modified = ZipDateTime::from_date_and_time(
+            u16::try_from(year).unwrap_or_default(),
+            month as u8,
+            day as u8,
+            (seconds / 3600) as u8,
+            (seconds % 3600 / 60) as u8,
+            (seconds % 60) as u8,
+        )
+        .unwrap_or_default();
+        EvidenceArchive {
+            zip: ZipWriter::new(BufWriter::new(file)),
+            options: SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .last_modified_time(modified)
+                .unix_permissions(0o644),
+            files: Vec::new(),
+            checksums: String::new(),
+        }
+    }
+
+    // Add a file written by the given function, listed in the checksums
+    fn add(
+        &mut self,
+        name: &str,
+        large: bool,
+        write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.zip
+            .start_file(name, self.options.large_file(large))
+            .map_err(io::Error::other)?;
+        let mut out = DigestWriter {
+            inner: &mut self.zip,
+            digest: Sha256::new(),
+        };
+        write(&mut out)?;
+        let digest = out.digest.finalize();
+        self.checksums
+            .push_str(&format!("{}  {}\n", hex(&digest), name));
+        self.files.push(name.to_string());
+        Ok(())
+    }
+
+    // Add a file left out of the checksums
+    fn add_unlisted(&mut self, name: &str, content: &[u8]) -> io::Result<()> {
+        self.zip
+            .start_file(name, self.options)
+            .map_err(io::Error::other)?;
+        self.zip.write_all(content)?;
+        self.files.push(name.to_string());
+        Ok(())
+    }
+
+    // Write the central directory, returning the file of the archive and the
+    // names of its files
+    fn finish(self) -> io::Result<(File, Vec<String>)> {
+        let file = self
+            .zip
+            .finish()
+            .map_err(io::Error::other)?
+            .into_inner()
+            .map_err(|e| e.into_error())?;
+        Ok((file, self.files))
+    }
+}
+
+// Writer computing the SHA-256 digest of what goes through it
+struct DigestWriter<W: Write> {
+    inner: W,
+    digest: Sha256,
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.digest.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,97 @@
+use crate::dates::{format_day, parse_date};
+use crate::environments::matches_pattern;
+use aws_sdk_ecr::primitives::DateTime;
+
+// Images exempted from the --fail-on, repository and tag thresholds until a
+// date, given with --exempt-images for time-boxed risk acceptances. Exempted
+// images are still reported with their findings
+#[derive(Debug, Clone, Default)]
+pub struct ImageExemptions {
+    entries: Vec<Exemption>,
+}
+
+// Exemption of an image digest, e.g. sha256:3f2a..., or of the tags of a
+// repository, e.g. payments-api:v2.* where '*' stands for any characters
+#[derive(Debug, Clone)]
+pub struct Exemption {
+    pub pattern: String,
+    pub expires_at: DateTime,
+}
+
+impl ImageExemptions {
+    // Function to read the exemptions file, one "<digest|repository:tag> <expiry>"
+    // entry per line, '#' starting a comment. Every entry needs an expiry date
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut entries = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next().unwrap_or_default();
+            let (Some(expiry), None) = (fields.next(), fields.next()) else {
+                return Err(format!(
+                    "line {}: expected '<digest|repository:tag> <expiry date>', got '{}'",
+                    number + 1,
+                    line
+                ));
+            };
+            if !pattern.starts_with("sha256:") && !pattern.contains(':') {
+                return Err(format!(
+                    "line {}: '{}' is neither an image digest nor a repository:tag pattern",
+                    number + 1,
+                    pattern
+                ));
+            }
+            let expires_at =
+                parse_date(expiry).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            entries.push(Exemption {
+                pattern: pattern.to_string(),
+                expires_at,
+            });
+        }
+        Ok(ImageExemptions { entries })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Entries whose expiry date is past, no longer exempting their images
+    pub fn expired(&self, now: &DateTime) -> Vec<&Exemption> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.expires_at.secs() <= now.secs())
+            .collect()
+    }
+
+    // First entry still in force exempting an image, by digest or by one of its tags
+    pub fn find(
+        &self,
+        repository_name: &str,
+        image_digest: &str,
+        image_tags: &[String],
+        now: &DateTime,
+    ) -> Option<&Exemption> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.expires_at.secs() > now.secs())
+            .find(|entry| match entry.pattern.starts_with("sha256:") {
+                true => entry.pattern == image_digest,
+                false => {
+                    let (repository, tag) = entry.pattern.rsplit_once(':').unwrap_or_default();
+                    matches_pattern(repository, repository_name)
+                        && image_tags.iter().any(|t| matches_pattern(tag, t))
+                }
+            })
+    }
+}
+
+impl Exemption {
+    // Expiry date of the entry as YYYY-MM-DD
+    pub fn expiry_day(&self) -> String {
+        format_day(self.expires_at).unwrap_or_default()
+    }
+}
@@ -0,0 +1,338 @@
+use aws_sdk_ecr::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_ecr::operation::describe_image_scan_findings::DescribeImageScanFindingsOutput;
+use aws_sdk_ecr::primitives::DateTime;
+use aws_sdk_ecr::types::builders::ImageIdentifierBuilder;
+use aws_sdk_ecr::types::{
+    EnhancedImageScanFinding, FindingSeverity, ImageIdentifier, ImageScanFinding,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+// Findings requested per DescribeImageScanFindings page, the API maximum
+const PAGE_SIZE: i32 = 1000;
+
+// Attempts of a page of findings, on top of the retries of the SDK
+const PAGE_ATTEMPTS: u32 = 4;
+
+// Delay before the second attempt of a page, doubled on each attempt
+const PAGE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// ECR error codes of failures worth retrying
+const TRANSIENT_ERROR_CODES: [&str; 3] = [
+    "ThrottlingException",
+    "ServerException",
+    "ServiceUnavailableException",
+];
+
+// A single vulnerability finding, from either basic or enhanced scanning
+#[derive(Debug, Clone)]
+pub struct Finding {
+    // Vulnerability identifier, e.g. CVE-2024-1234
+    pub name: String,
+    // Inspector finding ARN, enhanced scanning only
+    pub finding_arn: Option<String>,
+    pub description: Option<String>,
+    pub severity: String,
+    pub vendor_severity: Option<String>,
+    // Dates the vulnerability was published and last updated by its vendor,
+    // enhanced scanning only
+    pub vendor_created_at: Option<DateTime>,
+    pub vendor_updated_at: Option<DateTime>,
+    pub cvss_score: Option<f64>,
+    // CVSS vector of the score, e.g. CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H
+    pub cvss_vector: Option<String>,
+    // Date the finding was first seen on the image, enhanced scanning only
+    pub first_observed_at: Option<DateTime>,
+    // Packages affected by the finding
+    pub packages: Vec<Package>,
+    // Operating system of the image, e.g. DEBIAN_11, enhanced scanning only
+    pub platform: Option<String>,
+    // Reference URLs of the vulnerability, e.g. NVD or vendor advisories
+    pub references: Vec<String>,
+}
+
+// Installed package affected by a finding
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    // Package manager, e.g. OS for distribution packages or NPM, enhanced scanning only
+    pub package_manager: Option<String>,
+    // First version fixing the finding, enhanced scanning only
+    pub fixed_in_version: Option<String>,
+}
+
+// Scan findings of one image
+#[derive(Debug, Clone, Default)]
+pub struct ScanFindings {
+    pub scan_completed_at: Option<DateTime>,
+    pub vulnerability_source_updated_at: Option<DateTime>,
+    pub severity_counts: HashMap<FindingSeverity, i32>,
+    pub findings: Vec<Finding>,
+    // True when findings were left out by --max-findings-per-image
+    pub truncated: bool,
+}
+
+impl ScanFindings {
+    // Severities whose summary count differs from the number of detailed
+    // findings, e.g. "HIGH: 3 in summary, 2 in findings"
+    pub fn count_mismatches(&self) -> Vec<String> {
+        let mut counts: BTreeMap<String, (i64, i64)> = BTreeMap::new();
+        for (severity, count) in &self.severity_counts {
+            counts
+                .entry(severity.as_str().to_uppercase())
+                .or_default()
+                .0 += *count as i64;
+        }
+        for finding in &self.findings {
+            counts.entry(finding.severity.to_uppercase()).or_default().1 += 1;
+        }
+        counts
+            .into_iter()
+            .filter(|(_, (summary, findings))| summary != findings)
+            .map(|(severity, (summary, findings))| {
+                format!(
+                    "{}: {} in summary, {} in findings",
+                    severity, summary, findings
+                )
+            })
+            .collect()
+    }
+
+    // Keep the first findings, flagging the scan when some are left out
+    pub fn truncate(&mut self, max_findings: usize) {
+        if self.findings.len() > max_findings {
+            self.findings.truncate(max_findings);
+            self.truncated = true;
+        }
+    }
+}
+
+impl From<&ImageScanFinding> for Finding {
+    // Basic scanning findings carry their CVSS score as an attribute
+    fn from(finding: &ImageScanFinding) -> Self {
+        let attribute = |key: &str| {
+            finding
+                .attributes()
+                .iter()
+                .find(|a| a.key() == key)
+                .and_then(|a| a.value())
+        };
+        let score = |key: &str| attribute(key).and_then(|v| v.parse::<f64>().ok());
+        let packages = match attribute("package_name") {
+            Some(name) => vec![Package {
+                name: name.to_string(),
+                version: attribute("package_version").unwrap_or_default().to_string(),
+                package_manager: None,
+                fixed_in_version: None,
+            }],
+            None => Vec::new(),
+        };
+        Finding {
+            name: finding.name().unwrap_or_default().to_string(),
+            description: finding.description().map(|d| d.to_string()),
+            severity: finding
+                .severity()
+                .map(|s| s.as_str().to_string())
+                .unwrap_or_default(),
+            vendor_severity: None,
+            vendor_created_at: None,
+            vendor_updated_at: None,
+            cvss_score: score("CVSS3_SCORE").or_else(|| score("CVSS2_SCORE")),
+            finding_arn: None,
+            cvss_vector: attribute("CVSS3_VECTOR")
+                .or_else(|| attribute("CVSS2_VECTOR"))
+                .map(|v| v.to_string()),
+            first_observed_at: None,
+            packages,
+            platform: None,
+            references: finding
+                .uri()
+                .map(|uri| uri.to_string())
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl From<&EnhancedImageScanFinding> for Finding {
+    fn from(finding: &EnhancedImageScanFinding) -> Self {
+        let details = finding.package_vulnerability_details();
+        Finding {
+            name: details
+                .and_then(|d| d.vulnerability_id())
+                .or(finding.title())
+                .unwrap_or_default()
+                .to_string(),
+            finding_arn: finding.finding_arn().map(|arn| arn.to_string()),
+            description: finding.description().map(|d| d.to_string()),
+            severity: finding.severity().unwrap_or_default().to_string(),
+            vendor_severity: details
+                .and_then(|d| d.vendor_severity())
+                .map(|s| s.to_string()),
+            vendor_created_at: details.and_then(|d| d.vendor_created_at),
+            vendor_updated_at: details.and_then(|d| d.vendor_updated_at),
+            cvss_score: finding
+                .score_details()
+                .and_then(|s| s.cvss())
+                .map(|c| c.score)
+                .or(Some(finding.score).filter(|s| *s > 0.0)),
+            cvss_vector: finding
+                .score_details()
+                .and_then(|s| s.cvss())
+                .and_then(|c| c.scoring_vector())
+                .or_else(|| {
+                    details
+                        .and_then(|d| d.cvss().first())
+                        .and_then(|c| c.scoring_vector())
+                })
+                .map(|v| v.to_string()),
+            first_observed_at: finding.first_observed_at,
+            packages: details
+                .map(|d| d.vulnerable_packages())
+                .unwrap_or_default()
+                .iter()
+                .map(|p| Package {
+                    name: p.name().unwrap_or_default().to_string(),
+                    version: p.version().unwrap_or_default().to_string(),
+                    package_manager: p.package_manager().map(|m| m.to_string()),
+                    fixed_in_version: p.fixed_in_version().map(|v| v.to_string()),
+                })
+                .collect(),
+            platform: finding
+                .resources()
+                .iter()
+                .filter_map(|r| r.details().and_then(|d| d.aws_ecr_container_image()))
+                .find_map(|image| image.platform())
+                .map(|platform| platform.to_string()),
+            references: enhanced_references(finding),
+        }
+    }
+}
+
+// Reference URLs of an enhanced finding, the vulnerability source first and
+// then its advisories, without duplicates
+fn enhanced_references(finding: &EnhancedImageScanFinding) -> Vec<String> {
+    let details = finding.package_vulnerability_details();
+    let mut references: Vec<String> = Vec::new();
+    let urls = details.and_then(|d| d.source_url()).into_iter().chain(
+        details
+            .map(|d| d.reference_urls())
+            .unwrap_or_default()
+            .iter()
+            .map(|u| u.as_str()),
+    );
+    for url in urls {
+        if !url.is_empty() && !references.iter().any(|r| r == url) {
+            references.push(url.to_string());
+        }
+    }
+    references
+}
+
+// Function to describe the scan findings of an image, optionally following
+// every page to get the full finding list, up to max_findings findings
+pub async fn describe_findings(
+    client: &aws_sdk_ecr::Client,
+    registry_id: &str,
+    repository_name: &str,
+    image_digest: &str,
+    all_pages: bool,
+    max_findings: Option<usize>,
+) -> Result<Option<ScanFindings>, aws_sdk_ecr::Error> {
+    // Create image identifier based on image digest
+    let image_identifier = ImageIdentifierBuilder::default()
+        .image_digest(image_digest)
+        .build();
+
+    let mut result: Option<ScanFindings> = None;
+    let mut next_token: Option<String> = None;
+    loop {
+        // Request one page of image findings for image digest
+        let page = describe_page(
+            client,
+            registry_id,
+            repository_name,
+            &image_identifier,
+            next_token.take(),
+        )
+        .await?;
+        next_token = page.next_token;
+        let Some(findings) = page.image_scan_findings else {
+            break;
+        };
+        let scan = result.get_or_insert_with(|| ScanFindings {
+            scan_completed_at: findings.image_scan_completed_at,
+            vulnerability_source_updated_at: findings.vulnerability_source_updated_at,
+            severity_counts: findings.finding_severity_counts.clone().unwrap_or_default(),
+            findings: Vec::new(),
+            truncated: false,
+        });
+        scan.findings
+            .extend(findings.findings().iter().map(Finding::from));
+        scan.findings
+            .extend(findings.enhanced_findings().iter().map(Finding::from));
+        // Stop fetching pages once the finding list reached its maximum size
+        if let Some(max) = max_findings.filter(|_| all_pages) {
+            if scan.findings.len() >= max {
+                scan.truncated = next_token.is_some();
+                scan.truncate(max);
+                break;
+            }
+        }
+        // Stop at the first page when only the summary is needed
+        if !all_pages || next_token.is_none() {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+// Function to request a page of findings, retried with an exponential backoff
+// when throttled or on transient failures so that one page does not fail an
+// image with thousands of findings
+async fn describe_page(
+    client: &aws_sdk_ecr::Client,
+    registry_id: &str,
+    repository_name: &str,
+    image_identifier: &ImageIdentifier,
+    next_token: Option<String>,
+) -> Result<DescribeImageScanFindingsOutput, aws_sdk_ecr::Error> {
+    let mut delay = PAGE_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        let response = client
+            .describe_image_scan_findings()
+            .registry_id(registry_id)
+            .repository_name(repository_name)
+            .image_id(image_identifier.clone())
+            .max_results(PAGE_SIZE)
+            .set_next_token(next_token.clone())
+            .send()
+            .await;
+        match response {
+            Ok(page) => return Ok(page),
+            Err(e) if attempt < PAGE_ATTEMPTS && is_transient(&e) => {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// Check if a failed call may succeed when retried later
+fn is_transient<E, R>(error: &SdkError<E, R>) -> bool
+where
+    E: ProvideErrorMetadata,
+{
+    match error {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) => true,
+        SdkError::ServiceError(e) => e
+            .err()
+            .code()
+            .is_some_and(|code| TRANSIENT_ERROR_CODES.contains(&code)),
+        _ => false,
+    }
+}
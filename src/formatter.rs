@@ -0,0 +1,77 @@
+use wasmi::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+// Instructions a formatter may run for one report, so that a looping module
+// fails instead of hanging the run
+const FORMATTER_FUEL: u64 = 1_000_000_000;
+// Linear memory a formatter may grow to, 256 MiB
+const FORMATTER_MEMORY: usize = 256 * 1024 * 1024;
+
+// Report format of a WebAssembly module given with --formatter. The module
+// gets no imports, so it can neither reach the filesystem, the network nor the
+// environment: it only turns the JSON report into the bytes to write. It exports
+//   memory: its linear memory
+//   alloc(len: i32) -> i32: an input buffer of len bytes
+//   format(ptr: i32, len: i32) -> i64: the output of the JSON report written in
+//   the input buffer, as its address in the high 32 bits and its length in the
+//   low 32 bits
+pub struct WasmFormatter {
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmFormatter {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let wasm = std::fs::read(path).map_err(|e| e.to_string())?;
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &wasm).map_err(|e| e.to_string())?;
+        if let Some(import) = module.imports().next() {
+            return Err(format!(
+                "the module imports {}::{}, formatters cannot import anything",
+                import.module(),
+                import.name()
+            ));
+        }
+        Ok(WasmFormatter { engine, module })
+    }
+
+    // Format a JSON report, each call running in a fresh instance
+    pub fn format(&self, report: &[u8]) -> Result<Vec<u8>, String> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(FORMATTER_MEMORY)
+            .instances(1)
+            .build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FORMATTER_FUEL).map_err(|e| e.to_string())?;
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|e| e.to_string())?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("the module exports no memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|e| format!("alloc: {}", e))?;
+        let format = instance
+            .get_typed_func::<(i32, i32), i64>(&store, "format")
+            .map_err(|e| format!("format: {}", e))?;
+
+        let len = i32::try_from(report.len()).map_err(|_| "the report is too large")?;
+        let ptr = alloc.call(&mut store, len).map_err(|e| e.to_string())?;
+        memory
+            .write(&mut store, ptr as u32 as usize, report)
+            .map_err(|e| format!("alloc returned an invalid buffer: {}", e))?;
+        let output = format
+            .call(&mut store, (ptr, len))
+            .map_err(|e| e.to_string())? as u64;
+        let (ptr, len) = ((output >> 32) as usize, (output & 0xffff_ffff) as usize);
+        let mut formatted = vec![0; len];
+        memory
+            .read(&store, ptr, &mut formatted)
+            .map_err(|e| format!("format returned an invalid buffer: {}", e))?;
+        Ok(formatted)
+    }
+}
@@ -0,0 +1,118 @@
+use crate::annotations::{ordered, parse_commit, summary, title, ImageAnnotation};
+use crate::errors::EXIT_OK;
+use crate::manifest::RunManifest;
+use crate::update::{github_api, http_client};
+use serde_json::{json, Value};
+use std::error::Error;
+
+// Name of the check run created on the commit
+const CHECK_NAME: &str = "ECR image scan";
+
+// File the image annotations are attached to, when --github-check-path is not given
+pub const DEFAULT_ANNOTATION_PATH: &str = "Dockerfile";
+
+// Annotations sent per request, the maximum of the Checks API
+const ANNOTATIONS_PER_REQUEST: usize = 50;
+
+// Commit of a GitHub repository given with --github-check owner/repo@sha,
+// e.g. "$GITHUB_REPOSITORY@$GITHUB_SHA" in GitHub Actions
+#[derive(Debug, Clone)]
+pub struct CheckTarget {
+    pub repository: String,
+    pub commit: String,
+    pub path: String, // File of the repository the annotations are attached to
+}
+
+impl CheckTarget {
+    pub fn parse(value: &str, path: Option<&str>) -> Result<Self, String> {
+        let (repository, commit) = parse_commit(value)?;
+        Ok(CheckTarget {
+            repository,
+            commit,
+            path: path.unwrap_or(DEFAULT_ANNOTATION_PATH).to_string(),
+        })
+    }
+}
+
+// Function to create a completed check run on the commit, with the finding
+// counts of the run as summary and one annotation per image with findings,
+// failing when the run fails. Returns the URL of the check run
+pub async fn create_check_run(
+    target: &CheckTarget,
+    levels: &[String],
+    annotations: &[ImageAnnotation],
+    manifest: &RunManifest,
+    exit_code: u8,
+) -> Result<String, Box<dyn Error>> {
+    let token = std::env::var("GITHUB_TOKEN").map_err(|_| "GITHUB_TOKEN is not set")?;
+    let (images, omitted) = ordered(annotations);
+    let annotations: Vec<Value> = images
+        .iter()
+        .map(|image| annotation(image, levels, &target.path))
+        .collect();
+    let output = |annotations: &[Value]| {
+        json!({
+            "title": title(manifest),
+            "summary": summary(manifest, omitted),
+            "annotations": annotations,
+        })
+    };
+
+    let client = http_client();
+    let url = format!("{}/repos/{}/check-runs", github_api(), target.repository);
+    let mut batches = annotations.chunks(ANNOTATIONS_PER_REQUEST);
+    let body = json!({
+        "name": CHECK_NAME,
+        "head_sha": target.commit,
+        "status": "completed",
+        "conclusion": if exit_code == EXIT_OK { "success" } else { "failure" },
+        "external_id": manifest.ids.run_id,
+        "output": output(batches.next().unwrap_or_default()),
+    });
+    let response = client
+        .post(&url)
+        .bearer_auth(&token)
+        .header("Accept", "application/vnd.github+json")
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    let check_run: Value = serde_json::from_slice(&response.bytes().await?)?;
+    let id = check_run["id"]
+        .as_u64()
+        .ok_or("GitHub answered without a check run id")?;
+    // Further annotations are added by updating the check run
+    for batch in batches {
+        client
+            .patch(format!("{}/{}", url, id))
+            .bearer_auth(&token)
+            .header("Accept", "application/vnd.github+json")
+            .header("Content-Type", "application/json")
+            .body(json!({ "output": output(batch) }).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+    Ok(check_run["html_url"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+// Annotation of an image, e.g. "Critical: 2, High: 5" on payments-api:v2.3.1
+fn annotation(image: &ImageAnnotation, levels: &[String], path: &str) -> Value {
+    let mut message = format!("{}\n{}", image.counts_text(levels), image.image_digest);
+    if image.failing {
+        message.push_str("\nOver the thresholds of the run");
+    }
+    json!({
+        "path": path,
+        "start_line": 1,
+        "end_line": 1,
+        "annotation_level": if image.failing { "failure" } else { "warning" },
+        "title": image.image_name(),
+        "message": message,
+        "raw_details": image.image_tags.join(", "),
+    })
+}
@@ -0,0 +1,181 @@
+use crate::labels::Labels;
+use crate::render::escape_html;
+use crate::report::JsonReport;
+use crate::spill::ImageSource;
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+// Background colors of the ANSI heatmap, from the lowest to the highest
+// intensity, in the 256 colors palette
+const ANSI_SHADES: [u8; 4] = [224, 217, 203, 160];
+// Background colors of the HTML heatmap, same scale
+const HTML_SHADES: [&str; 4] = ["#fde0dd", "#fa9fb5", "#f768a1", "#c51b8a"];
+
+// Finding counts of each repository by severity level, the repositories with
+// the most severe findings first
+struct Heatmap {
+    levels: Vec<String>,
+    rows: Vec<(String, Vec<i64>)>,
+    // Largest count of each level, the cells are shaded relative to it
+    max_counts: Vec<i64>,
+}
+
+impl Heatmap {
+    fn new(report: &JsonReport, images: &dyn ImageSource) -> io::Result<Self> {
+        let levels = report.severity_levels.clone();
+        let mut repositories: IndexMap<String, Vec<i64>> = IndexMap::new();
+        // Images listed once per tag with --per-tag are counted once
+        let mut counted = HashSet::new();
+        images.try_for_each(&mut |image| {
+            let key = (
+                image.account.clone(),
+                image.region.clone(),
+                image.repository_name.clone(),
+                image.image_digest.clone(),
+            );
+            if !counted.insert(key) {
+                return Ok(());
+            }
+            let counts = repositories
+                .entry(image.repository_name.clone())
+                .or_insert_with(|| vec![0; levels.len()]);
+            for (count, level) in counts.iter_mut().zip(&levels) {
+                *count += image
+                    .severity_counts
+                    .get(level)
+                    .copied()
+                    .unwrap_or_default();
+            }
+            Ok(())
+        })?;
+        let mut rows: Vec<(String, Vec<i64>)> = repositories.into_iter().collect();
+        rows.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+        let max_counts = (0..levels.len())
+            .map(|index| rows.iter().map(|(_, c)| c[index]).max().unwrap_or_default())
+            .collect();
+        Ok(Heatmap {
+            levels,
+            rows,
+            max_counts,
+        })
+    }
+
+    // Shade of a cell, None for a cell without findings
+    fn shade(&self, index: usize, count: i64) -> Option<usize> {
+        let max = self.max_counts[index];
+        if count <= 0 || max <= 0 {
+            return None;
+        }
+        let steps = ANSI_SHADES.len() as i64;
+        Some(((count * steps - 1) / max).clamp(0, steps - 1) as usize)
+    }
+}
+
+// Compact heatmap for terminals, with ANSI background colors unless NO_COLOR
+// is set
+pub fn write_ansi_heatmap(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    labels: &Labels,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let heatmap = Heatmap::new(report, images)?;
+    let colors = std::env::var_os("NO_COLOR").is_none();
+    let name_width = heatmap
+        .rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .chain([labels.column("repository_name").chars().count()])
+        .max()
+        .unwrap_or_default();
+    let headers: Vec<String> = heatmap
+        .levels
+        .iter()
+        .map(|level| labels.get(level).to_string())
+        .collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(index, header)| {
+            let digits = heatmap.max_counts[index].to_string().len();
+            header.chars().count().max(digits)
+        })
+        .collect();
+    write!(
+        out,
+        "{:<width$}",
+        labels.column("repository_name"),
+        width = name_width
+    )?;
+    for (header, width) in headers.iter().zip(&widths) {
+        write!(out, " {:>width$}", header, width = width)?;
+    }
+    writeln!(out)?;
+    for (name, counts) in &heatmap.rows {
+        write!(out, "{:<width$}", name, width = name_width)?;
+        for (index, (count, width)) in counts.iter().zip(&widths).enumerate() {
+            let cell = format!("{:>width$}", count, width = width);
+            match heatmap.shade(index, *count) {
+                Some(shade) if colors => {
+                    // White text on the two darkest shades
+                    let text = if shade >= 2 { 231 } else { 16 };
+                    write!(
+                        out,
+                        " \x1b[48;5;{};38;5;{}m{}\x1b[0m",
+                        ANSI_SHADES[shade], text, cell
+                    )?
+                }
+                _ => write!(out, " {}", cell)?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+// Standalone HTML page of the heatmap, for mail and static hosting
+pub fn write_html_heatmap(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    labels: &Labels,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let heatmap = Heatmap::new(report, images)?;
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(
+        out,
+        "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>",
+        escape_html(labels.get("ECR scan report"))
+    )?;
+    writeln!(
+        out,
+        "<style>table {{ border-collapse: collapse; }} th, td {{ border: 1px solid #ccc; padding: 2px 6px; }} td.count {{ text-align: right; }}</style>"
+    )?;
+    writeln!(out, "</head>\n<body>")?;
+    writeln!(out, "<h2>{}</h2>", escape_html(labels.get("Heatmap")))?;
+    writeln!(out, "<table>")?;
+    let headers: String = std::iter::once(labels.column("repository_name"))
+        .chain(heatmap.levels.iter().map(|l| labels.get(l).to_string()))
+        .map(|h| format!("<th>{}</th>", escape_html(&h)))
+        .collect();
+    writeln!(out, "<tr>{}</tr>", headers)?;
+    for (name, counts) in &heatmap.rows {
+        let cells: String = counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| match heatmap.shade(index, *count) {
+                Some(shade) => format!(
+                    "<td class=\"count\" style=\"background: {}{}\">{}</td>",
+                    HTML_SHADES[shade],
+                    if shade >= 2 { "; color: #fff" } else { "" },
+                    count
+                ),
+                None => format!("<td class=\"count\">{}</td>", count),
+            })
+            .collect();
+        writeln!(out, "<tr><td>{}</td>{}</tr>", escape_html(name), cells)?;
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "</body>\n</html>")
+}
@@ -0,0 +1,200 @@
+use crate::cli::Options;
+use crate::partition::Partition;
+use crate::signature::signature_location;
+use crate::sink::SinkTarget;
+use crate::workloads::UsageSource;
+use serde_json::{json, Value};
+
+// Function to build the least-privilege IAM policy of a report run with the
+// given options, for gen-iam-policy. Resources are scoped to the repositories,
+// buckets, log groups and streams named by the options, in any account since
+// the policy is generated without calling AWS
+pub fn iam_policy(options: &Options, region: &str, enhanced: bool) -> Value {
+    let partition = Partition::from_region(region).name();
+    let region = if region.is_empty() { "*" } else { region };
+    let settings = &options.settings;
+    let mut statements = vec![statement(
+        "ReadRegistryScanningConfiguration",
+        &["ecr:GetRegistryScanningConfiguration"],
+        vec!["*".to_string()],
+    )];
+
+    // Repositories of the run, every repository of the registry by default
+    let repositories = match (&options.repository_name, &options.repository_names) {
+        (Some(name), _) => vec![name.clone()],
+        (None, Some(names)) => names.clone(),
+        (None, None) => vec!["*".to_string()],
+    };
+    let mut actions = vec![
+        "ecr:DescribeRepositories",
+        "ecr:DescribeImages",
+        "ecr:DescribeImageScanFindings",
+    ];
+    if settings.needs_repository_tags() {
+        actions.push("ecr:ListTagsForResource");
+    }
+    statements.push(statement(
+        "ReadImageScanFindings",
+        &actions,
+        repositories
+            .iter()
+            .map(|name| format!("arn:{}:ecr:{}:*:repository/{}", partition, region, name))
+            .collect(),
+    ));
+    // Findings of enhanced scanning come from Amazon Inspector
+    if enhanced {
+        statements.push(statement(
+            "ReadInspectorFindings",
+            &["inspector2:ListFindings"],
+            vec!["*".to_string()],
+        ));
+    }
+
+    // Signature written next to the report with --sign-kms-key-id
+    let signature = options
+        .sign_kms_key_id
+        .as_ref()
+        .and(options.output_file.as_deref())
+        .map(signature_location);
+    // Objects written to S3 by the report, badges, Parquet files, manifest and signature
+    let objects: Vec<String> = [
+        options
+            .output_file
+            .as_deref()
+            .map(|location| (location, false)),
+        options.out_dir.as_deref().map(|location| (location, true)),
+        options
+            .quicksight_manifest
+            .as_deref()
+            .map(|location| (location, false)),
+        signature.as_deref().map(|location| (location, false)),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|(location, prefix)| {
+        let path = location.strip_prefix("s3://")?.trim_end_matches('/');
+        Some(match prefix {
+            true => format!("arn:{}:s3:::{}/*", partition, path),
+            false => format!("arn:{}:s3:::{}", partition, path),
+        })
+    })
+    .collect();
+    if !objects.is_empty() {
+        statements.push(statement("WriteReports", &["s3:PutObject"], objects));
+    }
+
+    // KMS key signing the report, or the checksum file of the evidence pack
+    let sign_key = options.sign_kms_key_id.as_deref().or(options
+        .evidence
+        .as_ref()
+        .and_then(|evidence| evidence.kms_key_id.as_deref()));
+    if let Some(key_id) = sign_key {
+        let mut signing = statement(
+            "SignReports",
+            &["kms:DescribeKey", "kms:Sign"],
+            vec![key_arn(key_id, partition, region)],
+        );
+        // Keys given by alias are only known by their alias
+        if key_id.starts_with("alias/") {
+            signing["Condition"] = json!({ "StringEquals": { "kms:RequestAlias": key_id } });
+        }
+        statements.push(signing);
+    }
+
+    // Report pushed as an artifact of the image with --attach-report
+    if let (true, Some(name)) = (options.attach_report, &options.repository_name) {
+        statements.push(statement(
+            "AttachReports",
+            &[
+                "ecr:BatchGetImage",
+                "ecr:BatchCheckLayerAvailability",
+                "ecr:InitiateLayerUpload",
+                "ecr:UploadLayerPart",
+                "ecr:CompleteLayerUpload",
+                "ecr:PutImage",
+            ],
+            vec![format!(
+                "arn:{}:ecr:{}:*:repository/{}",
+                partition, region, name
+            )],
+        ));
+    }
+
+    // Finding metrics, restricted to their namespace
+    if let Some(namespace) = &options.cloudwatch_namespace {
+        let mut metrics = statement(
+            "PublishFindingMetrics",
+            &["cloudwatch:PutMetricData"],
+            vec!["*".to_string()],
+        );
+        metrics["Condition"] = json!({ "StringEquals": { "cloudwatch:namespace": namespace } });
+        statements.push(metrics);
+    }
+    match &options.findings_sink {
+        Some(SinkTarget::Logs { group, .. }) => statements.push(statement(
+            "SendFindingsToLogs",
+            &["logs:CreateLogStream", "logs:PutLogEvents"],
+            vec![format!(
+                "arn:{}:logs:{}:*:log-group:{}:*",
+                partition, region, group
+            )],
+        )),
+        Some(SinkTarget::Firehose { stream }) => statements.push(statement(
+            "SendFindingsToFirehose",
+            &["firehose:PutRecordBatch"],
+            vec![format!(
+                "arn:{}:firehose:{}:*:deliverystream/{}",
+                partition, region, stream
+            )],
+        )),
+        Some(SinkTarget::Plugin { .. }) | None => {}
+    }
+
+    // Workloads running the images, looked up with --usage
+    for source in &settings.usage {
+        match source {
+            UsageSource::Ecs => statements.push(statement(
+                "ListEcsTasks",
+                &["ecs:ListClusters", "ecs:ListTasks", "ecs:DescribeTasks"],
+                vec!["*".to_string()],
+            )),
+            UsageSource::Lambda => {
+                statements.push(statement(
+                    "ListLambdaFunctions",
+                    &["lambda:ListFunctions"],
+                    vec!["*".to_string()],
+                ));
+                statements.push(statement(
+                    "GetLambdaFunctionImages",
+                    &["lambda:GetFunction"],
+                    vec![format!("arn:{}:lambda:{}:*:function:*", partition, region)],
+                ));
+            }
+        }
+    }
+
+    json!({
+        "Version": "2012-10-17",
+        "Statement": statements,
+    })
+}
+
+// ARN of a KMS key given by ARN, key ID or alias, any key for an alias
+fn key_arn(key_id: &str, partition: &str, region: &str) -> String {
+    match key_id {
+        arn if arn.starts_with("arn:") => arn.to_string(),
+        alias if alias.starts_with("alias/") => {
+            format!("arn:{}:kms:{}:*:key/*", partition, region)
+        }
+        id => format!("arn:{}:kms:{}:*:key/{}", partition, region, id),
+    }
+}
+
+fn statement(sid: &str, actions: &[&str], resources: Vec<String>) -> Value {
+    json!({
+        "Sid": sid,
+        "Effect": "Allow",
+        "Action": actions,
+        "Resource": resources,
+    })
+}
@@ -0,0 +1,71 @@
+use crate::errors::EXIT_INTERRUPTED;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+use tokio::sync::Notify;
+
+// True while a run listens for a signal to stop and write what it collected,
+// signals received at any other time stop the process at once
+static GRACEFUL: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: Notify = Notify::const_new();
+static SIGNAL: Mutex<&str> = Mutex::new("SIGINT");
+static LISTEN: Once = Once::new();
+
+// Error of a run stopped by Ctrl-C (SIGINT) or SIGTERM
+#[derive(Debug)]
+pub struct Interrupted(pub &'static str);
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "interrupted by {}", self.0)
+    }
+}
+
+impl Error for Interrupted {}
+
+// Function to run a future until it completes or the process receives SIGINT
+// or SIGTERM, in which case it is dropped at its current await point. A
+// second signal, e.g. while the partial report is written, stops the process
+pub async fn until_interrupted<F: Future>(future: F) -> Result<F::Output, Interrupted> {
+    LISTEN.call_once(|| {
+        tokio::spawn(listen());
+    });
+    GRACEFUL.store(true, Ordering::SeqCst);
+    let result = tokio::select! {
+        output = future => Ok(output),
+        _ = INTERRUPTED.notified() => Err(Interrupted(*SIGNAL.lock().unwrap_or_else(|e| e.into_inner()))),
+    };
+    GRACEFUL.store(false, Ordering::SeqCst);
+    result
+}
+
+// Once listened for, signals no longer stop the process by themselves
+async fn listen() {
+    while let Some(signal) = next_signal().await {
+        if GRACEFUL.swap(false, Ordering::SeqCst) {
+            *SIGNAL.lock().unwrap_or_else(|e| e.into_inner()) = signal;
+            INTERRUPTED.notify_one();
+        } else {
+            eprintln!("Interrupted by {}", signal);
+            std::process::exit(EXIT_INTERRUPTED.into());
+        }
+    }
+}
+
+// Next signal received, None when signals cannot be listened for
+#[cfg(unix)]
+async fn next_signal() -> Option<&'static str> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut terminate = signal(SignalKind::terminate()).ok()?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result.ok().map(|_| "SIGINT"),
+        _ = terminate.recv() => Some("SIGTERM"),
+    }
+}
+
+#[cfg(not(unix))]
+async fn next_signal() -> Option<&'static str> {
+    tokio::signal::ctrl_c().await.ok().map(|_| "SIGINT")
+}
@@ -0,0 +1,202 @@
+use crate::findings::Finding;
+use crate::pull::registry_host;
+use crate::report::{ImageReport, ReportMetadata};
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use indexmap::IndexMap;
+use serde::Serialize;
+
+// in-toto Statement v1 attesting the scan of one image, written with --output
+// in-toto. Its predicate is a cosign vulnerability attestation, for
+// `cosign attest --type vuln --predicate` once extracted with `jq .predicate`
+#[derive(Serialize)]
+pub struct Statement {
+    #[serde(rename = "_type")]
+    pub statement_type: &'static str,
+    pub subject: Vec<Subject>,
+    #[serde(rename = "predicateType")]
+    pub predicate_type: &'static str,
+    pub predicate: VulnPredicate,
+}
+
+// Image attested, by repository and digest
+#[derive(Serialize)]
+pub struct Subject {
+    pub name: String,
+    pub digest: IndexMap<String, String>,
+}
+
+// Predicate of https://cosign.sigstore.dev/attestation/vuln/v1
+#[derive(Serialize)]
+pub struct VulnPredicate {
+    pub invocation: Invocation,
+    pub scanner: Scanner,
+    pub metadata: ScanMetadata,
+}
+
+// Run of the report that attested the image
+#[derive(Serialize, Default)]
+pub struct Invocation {
+    pub parameters: Vec<String>, // Filters of the run, e.g. --severities critical,high
+    pub uri: String,
+    pub event_id: String, // Run ID
+    #[serde(rename = "builder.id")]
+    pub builder_id: String,
+}
+
+#[derive(Serialize)]
+pub struct Scanner {
+    pub uri: String,
+    pub version: String,
+    pub db: ScannerDb,
+    pub result: ScanResult,
+}
+
+// Vulnerability database of ECR basic scanning or of Amazon Inspector
+#[derive(Serialize, Default)]
+pub struct ScannerDb {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+// Findings of the image, as counted by the report
+#[derive(Serialize)]
+pub struct ScanResult {
+    pub repository_name: String,
+    pub image_tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_status: Option<String>,
+    pub severity_counts: IndexMap<String, i64>,
+    pub findings: Vec<AttestedFinding>,
+    // True when findings were left out by --max-findings-per-image
+    pub truncated: bool,
+}
+
+#[derive(Serialize)]
+pub struct AttestedFinding {
+    pub id: String,
+    pub severity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cvss_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cvss_vector: Option<String>,
+    pub packages: Vec<AttestedPackage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct AttestedPackage {
+    pub name: String,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixed_in_version: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ScanMetadata {
+    #[serde(rename = "scanStartedOn", skip_serializing_if = "Option::is_none")]
+    pub scan_started_on: Option<String>,
+    #[serde(rename = "scanFinishedOn", skip_serializing_if = "Option::is_none")]
+    pub scan_finished_on: Option<String>,
+}
+
+// Statement of an image and its findings, the invocation and database being
+// set from the report metadata once the run is done
+pub fn image_statement(image: &ImageReport, levels: &[String], region: &str) -> Statement {
+    // Redacted images have no account, so no registry
+    let name = match image.registry_id.as_str() {
+        "" => image.repository_name.clone(),
+        registry_id => format!(
+            "{}/{}",
+            registry_host(registry_id, region),
+            image.repository_name
+        ),
+    };
+    let (algorithm, digest) = image
+        .image_digest
+        .split_once(':')
+        .unwrap_or(("sha256", &image.image_digest));
+    let format_date =
+        |date: Option<DateTime>| date.and_then(|d| d.fmt(DateTimeFormat::DateTime).ok());
+    let scan = image.scan.clone().unwrap_or_default();
+    Statement {
+        statement_type: "https://in-toto.io/Statement/v1",
+        subject: vec![Subject {
+            name,
+            digest: IndexMap::from([(algorithm.to_string(), digest.to_string())]),
+        }],
+        predicate_type: "https://cosign.sigstore.dev/attestation/vuln/v1",
+        predicate: VulnPredicate {
+            invocation: Invocation::default(),
+            scanner: Scanner {
+                uri: env!("CARGO_PKG_REPOSITORY").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                db: ScannerDb {
+                    uri: String::new(),
+                    version: format_date(scan.vulnerability_source_updated_at),
+                },
+                result: ScanResult {
+                    repository_name: image.repository_name.clone(),
+                    image_tags: image.image_tags.clone(),
+                    scan_status: image.scan_status.clone(),
+                    severity_counts: levels
+                        .iter()
+                        .cloned()
+                        .zip(image.counts.iter().copied())
+                        .collect(),
+                    findings: scan.findings.iter().map(attested_finding).collect(),
+                    truncated: scan.truncated,
+                },
+            },
+            // ECR only tells when the scan completed, not when it started
+            metadata: ScanMetadata {
+                scan_started_on: None,
+                scan_finished_on: format_date(scan.scan_completed_at),
+            },
+        },
+    }
+}
+
+impl Statement {
+    // Set the run of the report and the scanner database, from the report metadata
+    pub fn set_invocation(&mut self, metadata: &ReportMetadata) {
+        let run_id = metadata.run_id.clone().unwrap_or_default();
+        self.predicate.invocation = Invocation {
+            parameters: metadata.filters.clone(),
+            uri: format!("{}/{}", env!("CARGO_PKG_NAME"), run_id),
+            event_id: run_id,
+            builder_id: env!("CARGO_PKG_NAME").to_string(),
+        };
+        let enhanced = metadata
+            .scan_type
+            .as_deref()
+            .is_some_and(|scan_type| scan_type.eq_ignore_ascii_case("enhanced"));
+        self.predicate.scanner.db.uri = match enhanced {
+            true => "https://aws.amazon.com/inspector/".to_string(),
+            false => {
+                "https://docs.aws.amazon.com/AmazonECR/latest/userguide/image-scanning-basic.html"
+                    .to_string()
+            }
+        };
+    }
+}
+
+fn attested_finding(finding: &Finding) -> AttestedFinding {
+    AttestedFinding {
+        id: finding.name.clone(),
+        severity: finding.severity.clone(),
+        cvss_score: finding.cvss_score,
+        cvss_vector: finding.cvss_vector.clone(),
+        packages: finding
+            .packages
+            .iter()
+            .map(|package| AttestedPackage {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                fixed_in_version: package.fixed_in_version.clone(),
+            })
+            .collect(),
+        references: finding.references.clone(),
+    }
+}
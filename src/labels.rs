@@ -0,0 +1,226 @@
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+// Languages of the built-in labels selected with --lang
+const LANGUAGES: [&str; 4] = ["en", "fr", "de", "es"];
+
+// Translations of the column headers, table titles and default severity
+// levels, keyed by their English name
+const FRENCH: [(&str, &str); 51] = [
+    ("repository_name", "Dépôt"),
+    ("image_tags", "Tags"),
+    ("image_digest", "Empreinte"),
+    ("image_scan_completed_date", "Date du scan"),
+    (
+        "vulnerability_source_updated_date",
+        "Mise à jour des vulnérabilités",
+    ),
+    ("scan_status", "Statut du scan"),
+    ("sla_breaches", "Dépassements SLA"),
+    ("scan_age_days", "Âge du scan (jours)"),
+    ("image_age_days", "Âge de l'image (jours)"),
+    ("tag_count", "Nombre de tags"),
+    ("vulnerable_since_push", "Vulnérable dès le push"),
+    ("vulnerable_after_days", "Vulnérable après (jours)"),
+    ("environment", "Environnement"),
+    ("upstream", "Amont"),
+    ("workloads", "Charges de travail"),
+    ("package_name", "Paquet"),
+    ("package_version", "Version"),
+    ("images", "Images"),
+    ("ecosystem", "Écosystème"),
+    ("level", "Niveau"),
+    ("total", "Total"),
+    ("mean", "Moyenne"),
+    ("median", "Médiane"),
+    ("max", "Max"),
+    ("images_%", "% images"),
+    ("findings", "Vulnérabilités"),
+    ("findings_%", "% vulnérabilités"),
+    ("Images", "Images"),
+    ("Packages", "Paquets"),
+    ("Ecosystems", "Écosystèmes"),
+    ("Statistics", "Statistiques"),
+    ("Findings per image", "Vulnérabilités par image"),
+    ("ECR scan report", "Rapport de scan ECR"),
+    ("Heatmap", "Carte de chaleur"),
+    ("Run", "Exécution"),
+    ("Generated at", "Généré le"),
+    ("Tool version", "Version de l'outil"),
+    ("Scan type", "Type de scan"),
+    ("Accounts", "Comptes"),
+    ("Regions", "Régions"),
+    ("Filters", "Filtres"),
+    ("none", "aucun"),
+    ("Run ID", "Identifiant du run"),
+    ("Correlation ID", "Identifiant de corrélation"),
+    ("tag:", "tag:"),
+    ("Critical", "Critique"),
+    ("High", "Élevée"),
+    ("Medium", "Moyenne"),
+    ("Low", "Faible"),
+    ("Informational", "Information"),
+    ("Undefined", "Non définie"),
+];
+
+const GERMAN: [(&str, &str); 51] = [
+    ("repository_name", "Repository"),
+    ("image_tags", "Tags"),
+    ("image_digest", "Digest"),
+    ("image_scan_completed_date", "Scan abgeschlossen"),
+    (
+        "vulnerability_source_updated_date",
+        "Schwachstellendaten aktualisiert",
+    ),
+    ("scan_status", "Scanstatus"),
+    ("sla_breaches", "SLA-Verstöße"),
+    ("scan_age_days", "Scanalter (Tage)"),
+    ("image_age_days", "Imagealter (Tage)"),
+    ("tag_count", "Anzahl Tags"),
+    ("vulnerable_since_push", "Verwundbar seit Push"),
+    ("vulnerable_after_days", "Verwundbar nach (Tagen)"),
+    ("environment", "Umgebung"),
+    ("upstream", "Upstream"),
+    ("workloads", "Workloads"),
+    ("package_name", "Paket"),
+    ("package_version", "Version"),
+    ("images", "Images"),
+    ("ecosystem", "Ökosystem"),
+    ("level", "Stufe"),
+    ("total", "Gesamt"),
+    ("mean", "Mittelwert"),
+    ("median", "Median"),
+    ("max", "Max"),
+    ("images_%", "% Images"),
+    ("findings", "Befunde"),
+    ("findings_%", "% Befunde"),
+    ("Images", "Images"),
+    ("Packages", "Pakete"),
+    ("Ecosystems", "Ökosysteme"),
+    ("Statistics", "Statistiken"),
+    ("Findings per image", "Befunde pro Image"),
+    ("ECR scan report", "ECR-Scanbericht"),
+    ("Heatmap", "Heatmap"),
+    ("Run", "Lauf"),
+    ("Generated at", "Erstellt am"),
+    ("Tool version", "Tool-Version"),
+    ("Scan type", "Scan-Typ"),
+    ("Accounts", "Konten"),
+    ("Regions", "Regionen"),
+    ("Filters", "Filter"),
+    ("none", "keine"),
+    ("Run ID", "Lauf-ID"),
+    ("Correlation ID", "Korrelations-ID"),
+    ("tag:", "tag:"),
+    ("Critical", "Kritisch"),
+    ("High", "Hoch"),
+    ("Medium", "Mittel"),
+    ("Low", "Niedrig"),
+    ("Informational", "Informativ"),
+    ("Undefined", "Undefiniert"),
+];
+
+const SPANISH: [(&str, &str); 51] = [
+    ("repository_name", "Repositorio"),
+    ("image_tags", "Etiquetas"),
+    ("image_digest", "Digest"),
+    ("image_scan_completed_date", "Fecha del escaneo"),
+    (
+        "vulnerability_source_updated_date",
+        "Actualización de vulnerabilidades",
+    ),
+    ("scan_status", "Estado del escaneo"),
+    ("sla_breaches", "Incumplimientos de SLA"),
+    ("scan_age_days", "Antigüedad del escaneo (días)"),
+    ("image_age_days", "Antigüedad de la imagen (días)"),
+    ("tag_count", "Número de etiquetas"),
+    ("vulnerable_since_push", "Vulnerable desde el push"),
+    ("vulnerable_after_days", "Vulnerable tras (días)"),
+    ("environment", "Entorno"),
+    ("upstream", "Origen"),
+    ("workloads", "Cargas de trabajo"),
+    ("package_name", "Paquete"),
+    ("package_version", "Versión"),
+    ("images", "Imágenes"),
+    ("ecosystem", "Ecosistema"),
+    ("level", "Nivel"),
+    ("total", "Total"),
+    ("mean", "Media"),
+    ("median", "Mediana"),
+    ("max", "Máx"),
+    ("images_%", "% imágenes"),
+    ("findings", "Hallazgos"),
+    ("findings_%", "% hallazgos"),
+    ("Images", "Imágenes"),
+    ("Packages", "Paquetes"),
+    ("Ecosystems", "Ecosistemas"),
+    ("Statistics", "Estadísticas"),
+    ("Findings per image", "Hallazgos por imagen"),
+    ("ECR scan report", "Informe de escaneo de ECR"),
+    ("Heatmap", "Mapa de calor"),
+    ("Run", "Ejecución"),
+    ("Generated at", "Generado el"),
+    ("Tool version", "Versión de la herramienta"),
+    ("Scan type", "Tipo de escaneo"),
+    ("Accounts", "Cuentas"),
+    ("Regions", "Regiones"),
+    ("Filters", "Filtros"),
+    ("none", "ninguno"),
+    ("Run ID", "ID de ejecución"),
+    ("Correlation ID", "ID de correlación"),
+    ("tag:", "etiqueta:"),
+    ("Critical", "Crítica"),
+    ("High", "Alta"),
+    ("Medium", "Media"),
+    ("Low", "Baja"),
+    ("Informational", "Informativa"),
+    ("Undefined", "Indefinida"),
+];
+
+// Labels of the column headers, table titles and severity levels of the CSV,
+// table, markdown and HTML reports, English names kept when not translated
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    labels: HashMap<String, String>,
+}
+
+impl Labels {
+    // Built-in labels of a --lang language, then the labels of the config file
+    pub fn new(lang: Option<&str>, overrides: &IndexMap<String, String>) -> Result<Self, String> {
+        let translations: &[(&str, &str)] = match lang {
+            None | Some("en") => &[],
+            Some("fr") => &FRENCH,
+            Some("de") => &GERMAN,
+            Some("es") => &SPANISH,
+            Some(lang) => {
+                return Err(format!(
+                    "unknown language '{}', expected one of {}",
+                    lang,
+                    LANGUAGES.join(", ")
+                ))
+            }
+        };
+        let mut labels: HashMap<String, String> = translations
+            .iter()
+            .map(|(name, label)| (name.to_string(), label.to_string()))
+            .collect();
+        labels.extend(overrides.iter().map(|(n, l)| (n.clone(), l.clone())));
+        Ok(Labels { labels })
+    }
+
+    // Label of a header, title or severity level
+    pub fn get<'a>(&'a self, name: &'a str) -> &'a str {
+        self.labels.get(name).map_or(name, |label| label.as_str())
+    }
+
+    // Label of a column, tag columns keep the tag key and translate the prefix
+    pub fn column(&self, name: &str) -> String {
+        if let Some(label) = self.labels.get(name) {
+            return label.clone();
+        }
+        match name.strip_prefix("tag:") {
+            Some(key) => format!("{}{}", self.get("tag:"), key),
+            None => name.to_string(),
+        }
+    }
+}
@@ -1,25 +1,206 @@
-use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
-use aws_sdk_ecr::types::builders::ImageIdentifierBuilder;
-use aws_sdk_ecr::types::{FindingSeverity, ScanType};
-use aws_sdk_ecr::Client;
+use aws_config::SdkConfig;
+use aws_sdk_ecr::types::{DescribeImagesFilter, ScanType};
+use azure::write_logging_commands;
+use badge::write_badges;
+use bitbucket::create_report;
+use cli::{
+    has_flag, option_value, output_encoding, output_labels, parse_options, sso_portal, subcommand,
+    usage, Options,
+};
+use continuation::ContinueToken;
+use credentials::{assume_role, assume_role_with_web_identity, Mfa};
+use crosscheck::{cross_check, write_cross_check, ScannerReport};
+use dataset::write_dataset;
+use doctor::{run_doctor, CredentialOptions};
+use dryrun::dry_run;
+use encoding::EncodedWriter;
+use errors::{
+    error_chain, errors_exit_code, write_errors, RunError, EXIT_API_FAILURE, EXIT_AUTH_CONFIG,
+    EXIT_DEGRADED, EXIT_EMPTY, EXIT_FINDINGS, EXIT_INTERRUPTED, EXIT_OK,
+};
+use evidence::write_evidence;
+use findings::{describe_findings, ScanFindings};
+use formatter::WasmFormatter;
+use github::create_check_run;
+use iam::iam_policy;
+use interrupt::until_interrupted;
+use manifest::{ImageCounts, RunManifest, RunStatus};
+use merge::merge_reports;
+use metrics::publish_metrics;
+use opentelemetry::context::FutureExt;
+use opentelemetry::KeyValue;
+use output::ReportOutput;
+use partition::check_region;
+use pins::TagPins;
+use pull::PullScript;
+use query::Query;
+use quicksight::write_quicksight_manifest;
+use referrer::{attach_report, ReportArtifact};
+use render::{load_report, write_document, write_queried};
+use replication::{audit_replication, write_replication};
+use report::{
+    is_output_closed, ImageReport, JsonReport, OutputFormat, ReportMetadata, ReportSettings,
+    ReportWriter,
+};
+use repos::describe_repositories;
+use scanrules::{simulate_scan_rules, write_scan_rules};
+use schedule::format_run;
+use schema::{json_schema, SchemaKind};
+use serve::{serve, serve_health, HealthStatus, LastRun};
+use sha2::{Digest, Sha256};
+use signature::{
+    read_location, sign_digest, signature_location, verify_file, write_signature, FileSignature,
+};
+use sink::FindingsSink;
+use split::write_split_reports;
+use sso::{login, sso_credentials};
+use std::collections::HashMap;
 use std::env;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use suggest::repository_not_found;
+use summary::{print_summary, write_summary};
+use tags::fetch_repository_tags;
+use targets::{print_scorecard, TargetResult};
+use telemetry::{
+    api_calls, ecr_client, init_tracing, load_sdk_config, print_api_calls, record_error,
+    reset_api_calls, start_span,
+};
+use update::{check_update, self_update};
+use workloads::WorkloadUsage;
+
+mod annotations;
+mod azure;
+mod badge;
+mod bitbucket;
+mod cache;
+mod cli;
+mod config;
+mod continuation;
+mod credentials;
+mod crosscheck;
+mod cvss;
+mod dataset;
+mod dates;
+mod defectdojo;
+mod doctor;
+mod dryrun;
+mod ecosystem;
+mod encoding;
+mod environments;
+mod errors;
+mod evidence;
+mod exemptions;
+mod findings;
+mod formatter;
+mod github;
+mod heatmap;
+mod iam;
+mod interrupt;
+mod intoto;
+mod labels;
+mod manifest;
+mod merge;
+mod metrics;
+mod output;
+mod partition;
+mod pins;
+mod pull;
+mod query;
+mod quicksight;
+mod redact;
+mod referrer;
+mod remediation;
+mod render;
+mod replication;
+mod report;
+mod repos;
+mod retry;
+mod runid;
+mod scanrules;
+mod schedule;
+mod schema;
+mod serve;
+mod severity;
+mod signature;
+mod sink;
+mod sla;
+mod spill;
+mod split;
+mod sso;
+mod stats;
+mod suggest;
+mod summary;
+mod tags;
+mod targets;
+mod telemetry;
+mod thresholds;
+mod update;
+mod upstream;
+mod workloads;
+
+// Maximum number of images returned by one DescribeImages call
+const MAX_PAGE_SIZE: u64 = 1000;
+
+// Scan status of images whose scan findings are no longer available
+const SCAN_ELIGIBILITY_EXPIRED: &str = "SCAN_ELIGIBILITY_EXPIRED";
+
+// Media type of the container images ECR can scan
+const SCANNABLE_MEDIA_TYPE: &str = "application/vnd.docker.container.image.v1+json";
+
+// Scan status of the images of other media types, with --include-unsupported
+const NOT_SCANNABLE: &str = "not_scannable";
 
 async fn list_all_repositories(
     client: &aws_sdk_ecr::Client, // Client for interacting with AWS ECR
+    repository_names: Option<&[String]>, // Repositories of --repos-file, all of them if not set
     scan_type: &ScanType,
+    settings: &ReportSettings,
+    writer: &mut ReportWriter,
+    manifest: &mut RunManifest,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Result indicating success or failure with an error type
     // List all repositories
-    let response = match client.describe_repositories().send().await {
-        Ok(response) => response, // If successful, store the response
+    let repositories = match describe_repositories(client, repository_names).await {
+        Ok(repositories) => repositories, // If successful, store the repositories
         Err(e) => {
             eprintln!("Error describing repositories: {}", e);
+            manifest.add_error(None, &e);
             return Err(e.into()); // Convert the error to a boxed trait object and return it
         }
     };
 
+    // Repository where the run of the --continue-token stopped
+    let mut resume_at = settings.resume.as_ref().map(|t| t.repository.as_str());
     // Iterate through each repository
-    for repo in response.repositories.unwrap_or_default() {
+    for repo in repositories {
+        // Skip the repositories reported before the continuation token
+        if let Some(name) = resume_at {
+            if repo.repository_name() != Some(name) {
+                continue;
+            }
+            resume_at = None;
+        }
+        // Skip the remaining repositories once --sample images are reported
+        if settings
+            .sample
+            .is_some_and(|sample| manifest.images >= sample)
+        {
+            manifest.sampled = true;
+            // Resume at this repository unless the previous one was left mid-way
+            if manifest.next_token.is_none() {
+                manifest.next_token = repo.repository_name.map(|repository| {
+                    ContinueToken {
+                        repository,
+                        skip: 0,
+                    }
+                    .encode()
+                });
+            }
+            break;
+        }
         // Extract the repository name
         let repository_name = match repo.repository_name {
             Some(name) => name.to_string(), // If found, convert it to a String
@@ -29,18 +210,75 @@ async fn list_all_repositories(
             }
         };
 
+        // Fetch repository resource tags when needed by filters or columns
+        let repository_tags = if settings.needs_repository_tags() {
+            let repository_arn = repo.repository_arn.unwrap_or_default();
+            match fetch_repository_tags(client, &repository_arn).await {
+                Ok(tags) => tags,
+                Err(e) => {
+                    eprintln!(
+                        "Error listing tags for repository '{}': {}",
+                        repository_name, e
+                    );
+                    manifest.repositories.failed += 1;
+                    manifest.add_error(Some(&repository_name), &e);
+                    if settings.keep_going {
+                        continue;
+                    }
+                    return Err(e.into());
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+        // Skip repositories not matching the tag filters
+        if !settings.matches_repository_tags(&repository_tags) {
+            continue;
+        }
+        manifest.repositories.requested += 1;
+
         // List images in the current repository
-        match list_images_in_repository(client, &repository_name, scan_type).await {
-            Ok(_) => {} // No need to do anything if successful
+        let repository_cx = start_span(
+            "repository",
+            vec![KeyValue::new(
+                "aws.ecr.repository_name",
+                repository_name.clone(),
+            )],
+        );
+        match list_images_in_repository(
+            client,
+            &repository_name,
+            &repository_tags,
+            scan_type,
+            settings,
+            writer,
+            manifest,
+        )
+        .with_context(repository_cx.clone())
+        .await
+        {
+            Ok(_) => manifest.repositories.processed += 1,
+            // A closed output ends the run, it is no failure of the repository
+            Err(e) if is_output_closed(e.as_ref()) => return Err(e),
             Err(e) => {
+                record_error(&repository_cx, e.as_ref());
                 eprintln!(
                     "Error listing images for repository '{}': {}",
                     repository_name, e
                 );
-                return Err(e.into()); // Convert the error to a boxed trait object and return it
+                manifest.repositories.failed += 1;
+                manifest.add_error(Some(&repository_name), e.as_ref());
+                // Go on with the next repositories, the run still exits with an error
+                if settings.keep_going {
+                    continue;
+                }
+                return Err(e); // Return the error as is
             }
         }
     }
+    if let Some(name) = resume_at {
+        return Err(format!("Repository '{}' of the continuation token not found", name).into());
+    }
 
     Ok(())
 }
@@ -49,167 +287,2086 @@ async fn list_all_repositories(
 async fn list_images_in_repository(
     client: &aws_sdk_ecr::Client, // Client for interacting with AWS ECR
     repository_name: &str,        // Name of the repository to list images from
+    repository_tags: &HashMap<String, String>, // Resource tags of the repository
     scan_type: &ScanType,
-) -> Result<(), aws_sdk_ecr::Error> {
-    // Result indicating success or failure with the AWS ECR error type
-    // Create a request to describe images in the repository
-    let request = client.describe_images().repository_name(repository_name);
-    // Send the request and await the response, handling any potential errors
-    let response = request.send().await?;
-    // Default date to use if specific dates are not available
-    let default_date = DateTime::from_secs(0);
-
-    // Iterate through each image detail in the response
-    for image_detail in response.image_details.unwrap_or_default() {
-        if let Some(media_type) = image_detail.artifact_media_type {
-            // Check if the image is in the expected format
-            if media_type == "application/vnd.docker.container.image.v1+json" {
+    settings: &ReportSettings,
+    writer: &mut ReportWriter,
+    manifest: &mut RunManifest,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Result indicating success or failure with an error type
+    // Images of the repository reported by the run of the --continue-token
+    let skip = settings
+        .resume
+        .as_ref()
+        .filter(|token| token.repository == repository_name)
+        .map_or(0, |token| token.skip);
+    // Images left to report with --max-images-per-repo and --sample
+    let max_images_per_repo = settings
+        .max_images_per_repo
+        .map(|max| max.saturating_sub(skip));
+    let limit = match (max_images_per_repo, settings.sample) {
+        (Some(max), Some(sample)) => Some(max.min(sample.saturating_sub(manifest.images))),
+        (max, sample) => max.or(sample.map(|n| n.saturating_sub(manifest.images))),
+    };
+    // Create a request to describe images in the repository, asking the
+    // server for no more images than needed
+    let mut request = client
+        .describe_images()
+        .repository_name(repository_name)
+        .set_filter(settings.tag_status.clone().map(|tag_status| {
+            // Let the server filter tagged or untagged images
+            DescribeImagesFilter::builder()
+                .tag_status(tag_status)
+                .build()
+        }))
+        .into_paginator();
+    if let Some(limit) = limit {
+        request = request.page_size((limit + skip).clamp(1, MAX_PAGE_SIZE) as i32);
+    }
+    let mut pages = request.send();
+    // Tags of --pin-tags resolved to their digests before listing the images
+    let pins = match settings.pin_tags.is_empty() {
+        true => None,
+        false => Some(TagPins::resolve(client, repository_name, &settings.pin_tags).await?),
+    };
+    // Reference date for finding ages
+    let now = dates::now();
+    let mut reported = 0;
+    // Images left out with --keep-going, still skipped by a continuation token
+    let mut failed = 0;
+    let mut skipped = 0;
+    let mut listed = 0;
+
+    // Iterate through each image detail of each page
+    'pages: while let Some(page) = pages.next().await {
+        // Handle any potential errors of the page request
+        let page = page.map_err(aws_sdk_ecr::Error::from)?;
+        for mut image_detail in page.image_details.unwrap_or_default() {
+            listed += 1;
+            if let (Some(pins), Some(digest)) = (&pins, image_detail.image_digest.as_deref()) {
+                pins.apply(digest, image_detail.image_tags.get_or_insert_with(Vec::new));
+            }
+            // Check if the image is in the expected format, other artifacts are
+            // only listed with --include-unsupported
+            let scannable =
+                image_detail.artifact_media_type.as_deref() == Some(SCANNABLE_MEDIA_TYPE);
+            if !scannable {
+                manifest.unsupported_images += 1;
+            }
+            if scannable || settings.include_unsupported {
+                // Stop once the limits are reached, the report is then truncated
+                if limit.is_some_and(|limit| reported >= limit) {
+                    if max_images_per_repo.is_some_and(|max| reported >= max) {
+                        manifest.truncated_repositories += 1;
+                    } else {
+                        manifest.sampled = true;
+                        manifest.next_token = Some(
+                            ContinueToken {
+                                repository: repository_name.to_string(),
+                                skip: skip + reported + failed,
+                            }
+                            .encode(),
+                        );
+                    }
+                    break 'pages;
+                }
+                // Skip the images no pod of the cluster runs, with k8s-correlate
+                if settings.running_images.as_ref().is_some_and(|running| {
+                    !running.runs(
+                        image_detail.registry_id().unwrap_or_default(),
+                        repository_name,
+                        image_detail.image_tags(),
+                        image_detail.image_digest().unwrap_or_default(),
+                    )
+                }) {
+                    continue;
+                }
+                // Skip the images that did not fail in the run of --retry-from
+                if settings.retry.as_ref().is_some_and(|retry| {
+                    !retry.retries(
+                        repository_name,
+                        image_detail.image_digest().unwrap_or_default(),
+                    )
+                }) {
+                    continue;
+                }
+                // Skip the images other than the one of --image-digest
+                if settings
+                    .image_digest
+                    .as_deref()
+                    .is_some_and(|digest| image_detail.image_digest() != Some(digest))
+                {
+                    continue;
+                }
+                // Skip the images scanned outside the --scanned-after and --scanned-before window
+                if settings.scanned_after.is_some() || settings.scanned_before.is_some() {
+                    let scanned_at = image_detail
+                        .image_scan_findings_summary()
+                        .and_then(|s| s.image_scan_completed_at())
+                        .map(|at| at.secs());
+                    let in_window = scanned_at.is_some_and(|at| {
+                        settings
+                            .scanned_after
+                            .is_none_or(|after| at >= after.secs())
+                            && settings
+                                .scanned_before
+                                .is_none_or(|before| at < before.secs())
+                    });
+                    if !in_window {
+                        continue;
+                    }
+                }
+                // Findings of images scanned too long ago are no longer available
+                let scan_status = match scannable {
+                    true => image_detail
+                        .image_scan_status
+                        .as_ref()
+                        .and_then(|s| s.status())
+                        .map(|s| s.as_str().to_string()),
+                    false => Some(NOT_SCANNABLE.to_string()),
+                };
+                let expired = scan_status.as_deref() == Some(SCAN_ELIGIBILITY_EXPIRED);
+                if expired && settings.skip_expired {
+                    manifest.expired_images += 1;
+                    continue;
+                }
+                // Skip the images of other environments before fetching their findings
+                let environment = settings
+                    .environments
+                    .environment(image_detail.image_tags.as_deref().unwrap_or_default())
+                    .map(|name| name.to_string());
+                if !settings.environment_filter.is_empty()
+                    && !environment
+                        .as_ref()
+                        .is_some_and(|e| settings.environment_filter.contains(e))
+                {
+                    continue;
+                }
+                // Images already reported by the run of the --continue-token
+                if skipped < skip {
+                    skipped += 1;
+                    continue;
+                }
+                // An interrupted run resumes at the image being reported
+                manifest.resume_at = Some(ContinueToken {
+                    repository: repository_name.to_string(),
+                    skip: skip + reported + failed,
+                });
                 // Extract necessary information about the image
                 let repository_name = image_detail.repository_name.unwrap_or_default();
                 let image_tags = image_detail.image_tags.unwrap_or_default();
                 let image_digest = image_detail.image_digest.unwrap_or_default();
-                // Print basic image information
-                print!(
-                    "{};{};{}",
-                    repository_name,
-                    image_tags.first().map_or("", |t| t.as_str()), // Use the first tag if available
-                    image_digest
+                let pushed_at = image_detail.image_pushed_at;
+                let registry_id = image_detail.registry_id.unwrap_or_default();
+                manifest.add_account(&registry_id);
+                // Span of the image, ended when dropped with the image
+                let image_cx = start_span(
+                    "image",
+                    vec![
+                        KeyValue::new("aws.ecr.repository_name", repository_name.clone()),
+                        KeyValue::new("aws.ecr.image_digest", image_digest.clone()),
+                    ],
                 );
                 // If registry use Basic Scan
-                if scan_type.as_str() == ScanType::Basic.as_str() {
-                    // Check if image scan findings are available
-                    if let Some(findings) = image_detail.image_scan_findings_summary {
-                        // Extract and format relevant scan and vulnerability update dates
-                        let scan_complete_date =
-                            findings.image_scan_completed_at.unwrap_or(default_date);
-                        let update_scan_date = findings
-                            .vulnerability_source_updated_at
-                            .unwrap_or(default_date);
-                        // Extract and print severity counts for different vulnerability levels
-                        let severity_map = findings.finding_severity_counts.unwrap_or_default();
-                        print_results(scan_complete_date, update_scan_date, severity_map);
-                    } else {
-                        // If no scan findings are available, print placeholders for severity counts
-                        println!(";;;0;0;0;0;0;0");
-                    }
-                } else {
-                    // Enhanced scan
-                    // Create image identifier based on image digest
-                    let image_identifier = ImageIdentifierBuilder::default()
-                        .image_digest(image_digest)
-                        .build();
-                    // Request image findings for image digest
-                    let request_scan = client
-                        .describe_image_scan_findings()
-                        .registry_id(image_detail.registry_id.unwrap())
-                        .repository_name(repository_name)
-                        .image_id(image_identifier);
-                    // Send the request and await the response, handling any potential errors
-                    let response_scan = request_scan.send().await?;
-
-                    if let Some(findings) = response_scan.image_scan_findings {
-                        // Extract and print severity counts for different vulnerability levels
-                        let severity_map = findings.finding_severity_counts.unwrap_or_default();
-                        let scan_complete_date =
-                            findings.image_scan_completed_at.unwrap_or(default_date);
-                        let update_scan_date = findings
-                            .vulnerability_source_updated_at
-                            .unwrap_or(default_date);
-                        // Print results
-                        print_results(scan_complete_date, update_scan_date, severity_map)
-                    } else {
-                        // If no scan findings are available, print placeholders for severity counts
-                        println!(";;;0;0;0;0;0;0");
+                let basic_scan = scan_type.as_str() == ScanType::Basic.as_str();
+                let max_findings = settings.max_findings_per_image.map(|max| max as usize);
+                // The first images of --verify-counts need every finding page
+                let verify = !expired
+                    && settings
+                        .verify_counts
+                        .is_some_and(|sample| manifest.verified_images < sample);
+                let all_pages = settings.needs_findings() || verify;
+                // Detailed findings of the same scan read by a previous run
+                let cached = match &settings.findings_cache {
+                    Some(cache) if !expired && (!basic_scan || all_pages) => image_detail
+                        .image_scan_findings_summary
+                        .as_ref()
+                        .and_then(|s| s.image_scan_completed_at)
+                        .and_then(|at| cache.load(&image_digest, &at, all_pages)),
+                    _ => None,
+                };
+                let mut scan = match image_detail.image_scan_findings_summary {
+                    // Stale findings would look like the image has none
+                    _ if expired => None,
+                    // Only listed for the inventory, ECR never scans these images
+                    _ if !scannable => None,
+                    // No scan findings are available for this image
+                    None if basic_scan => None,
+                    // The summary is enough unless findings must be mapped one by one
+                    Some(findings) if basic_scan && !all_pages => Some(ScanFindings {
+                        scan_completed_at: findings.image_scan_completed_at,
+                        vulnerability_source_updated_at: findings.vulnerability_source_updated_at,
+                        severity_counts: findings.finding_severity_counts.unwrap_or_default(),
+                        findings: Vec::new(),
+                        truncated: false,
+                    }),
+                    _ if cached.is_some() => {
+                        manifest.cached_images += 1;
+                        cached.map(|mut scan| {
+                            if let Some(max) = max_findings {
+                                scan.truncate(max);
+                            }
+                            scan
+                        })
+                    }
+                    // Enhanced scan, or detailed findings needed by the report settings
+                    _ => {
+                        let scan = match describe_findings(
+                            client,
+                            &registry_id,
+                            &repository_name,
+                            &image_digest,
+                            all_pages,
+                            max_findings,
+                        )
+                        .with_context(image_cx.clone())
+                        .await
+                        {
+                            Ok(scan) => scan,
+                            Err(e) => {
+                                record_error(&image_cx, &e);
+                                // Leave the image out and go on with the next ones
+                                if !settings.keep_going {
+                                    return Err(e.into());
+                                }
+                                eprintln!(
+                                    "Error describing findings of {} in repository '{}': {}",
+                                    image_digest,
+                                    repository_name,
+                                    error_chain(&e)
+                                );
+                                manifest.add_image_error(&repository_name, &image_digest, &e);
+                                failed += 1;
+                                continue;
+                            }
+                        };
+                        if let (Some(cache), Some(scan)) = (&settings.findings_cache, &scan) {
+                            // A cache failure only costs the next run the API calls
+                            if let Err(e) = cache.store(&image_digest, scan, all_pages) {
+                                eprintln!(
+                                    "Error writing findings cache of {}: {}",
+                                    image_digest, e
+                                );
+                            }
+                        }
+                        scan
+                    }
+                };
+                // Counters of the image, added to the manifest once it is written
+                let mut image_counts = ImageCounts::default();
+
+                // Cross-check the summary counts with the findings, with --verify-counts
+                let mut count_mismatches = Vec::new();
+                if let Some(scan) = scan.as_ref().filter(|s| verify && !s.truncated) {
+                    image_counts.verified = true;
+                    count_mismatches = scan.count_mismatches();
+                    image_counts.count_mismatch = !count_mismatches.is_empty();
+                }
+
+                // Leave out the findings not matching --attack-vector, --cvss-min and --fixable-only
+                let filtered = !settings.finding_filter.is_empty();
+                if let Some(scan) = scan.as_mut().filter(|_| filtered) {
+                    scan.findings
+                        .retain(|finding| settings.finding_filter.matches(finding));
+                }
+
+                // Count findings for each level of the severity scheme
+                let scheme = &settings.scheme;
+                let counts = match &scan {
+                    Some(findings) if scheme.needs_findings() || filtered => {
+                        scheme.count_findings(&findings.findings)
+                    }
+                    Some(findings) => scheme.count_summary(&findings.severity_counts),
+                    None => vec![0; scheme.levels.len()],
+                };
+                // Count findings open for longer than their remediation SLA
+                let sla_breaches = settings.sla.as_ref().map(|sla| {
+                    let findings = scan.as_ref().map_or(&[][..], |f| &f.findings);
+                    sla.count_breaches(scheme, findings, &now)
+                });
+                image_counts.sla_breaches = sla_breaches.unwrap_or_default();
+                image_counts.findings = counts.clone();
+                // Count images over the --fail-on, repository or tag thresholds,
+                // those exempted by --exempt-images apart
+                if settings
+                    .fail_on
+                    .thresholds(&repository_name, &image_tags)
+                    .is_some_and(|thresholds| thresholds.exceeded(&counts))
+                {
+                    match settings.fail_on.exemptions.find(
+                        &repository_name,
+                        &image_digest,
+                        &image_tags,
+                        &now,
+                    ) {
+                        Some(_) => image_counts.exempted = true,
+                        None => image_counts.threshold_violation = true,
+                    }
+                }
+
+                // Keep track of data that could not be retrieved for this image
+                let mut warnings = Vec::new();
+                match &scan {
+                    None if expired => {
+                        image_counts.expired = true;
+                        warnings.push("scan findings expired".to_string());
+                    }
+                    None if !scannable => {}
+                    None => warnings.push("no scan findings available".to_string()),
+                    Some(findings) => {
+                        if findings.truncated {
+                            image_counts.truncated = true;
+                            warnings.push(format!(
+                                "findings truncated to {} by --max-findings-per-image",
+                                findings.findings.len()
+                            ));
+                        }
+                        if findings.scan_completed_at.is_none() {
+                            warnings.push("scan completion date missing".to_string());
+                        }
+                        match (
+                            findings.vulnerability_source_updated_at,
+                            settings.max_db_age,
+                        ) {
+                            (None, _) => warnings
+                                .push("vulnerability source update date missing".to_string()),
+                            // Findings against a stale database give false confidence
+                            (Some(updated_at), Some(max_age))
+                                if now.secs() - updated_at.secs() > max_age =>
+                            {
+                                image_counts.stale = true;
+                                warnings.push(format!(
+                                    "vulnerability source updated {} day(s) ago",
+                                    (now.secs() - updated_at.secs()) / 86400
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                if !count_mismatches.is_empty() {
+                    warnings.push(format!(
+                        "severity counts disagree with the findings: {}",
+                        count_mismatches.join(", ")
+                    ));
+                }
+                // Compare the mirrored image with the image of the same tag upstream
+                let upstream = match settings
+                    .upstream
+                    .compare(&repository_name, &image_tags, &image_digest)
+                    .await
+                {
+                    Ok(status) => status.map(|s| s.to_string()),
+                    Err(e) => {
+                        warnings.push(format!("upstream lookup failed: {}", e));
+                        None
+                    }
+                };
+                if !warnings.is_empty() {
+                    image_counts.degraded = true;
+                    if settings.strict {
+                        eprintln!(
+                            "Warning: {}@{}: {}",
+                            repository_name,
+                            image_digest,
+                            warnings.join(", ")
+                        );
+                    }
+                }
+
+                // Ages in days at the time of the run, with --age-columns
+                let (scan_age_days, image_age_days) = match settings.age_columns {
+                    true => (
+                        dates::age_days(scan.as_ref().and_then(|s| s.scan_completed_at), &now),
+                        dates::age_days(pushed_at, &now),
+                    ),
+                    false => (None, None),
+                };
+                let workloads = settings.workloads.workloads(&image_digest);
+                let tag_count = settings.tag_count.then_some(image_tags.len());
+                // Days from the push to the first observation of a counted
+                // finding, with --vulnerable-since-push
+                let vulnerable_after_days = match (settings.vulnerable_since_push, &scan) {
+                    (true, Some(scan)) => scheme
+                        .first_observed(&scan.findings)
+                        .and_then(|first_observed| dates::age_days(pushed_at, &first_observed)),
+                    _ => None,
+                };
+                let image = ImageReport {
+                    registry_id,
+                    repository_name,
+                    image_tags,
+                    image_digest,
+                    pushed_at,
+                    scan,
+                    counts,
+                    sla_breaches,
+                    scan_age_days,
+                    image_age_days,
+                    tag_count,
+                    vulnerable_after_days,
+                    repository_tags: repository_tags.clone(),
+                    environment,
+                    upstream,
+                    workloads,
+                    scan_status,
+                    warnings,
+                };
+                writer.add_image(&image, scheme)?;
+                // Only the images written count as reported, an interrupted
+                // run or a closed output leaves the image out of every count
+                reported += 1;
+                manifest.add_image(&scheme.levels, &image_counts);
+                // Evaluate the compliance targets applying to the image
+                for (target, result) in settings.targets.iter().zip(manifest.targets.iter_mut()) {
+                    if target.selects(&image, &now) {
+                        result.record(target.complies(&image, &now));
                     }
                 }
+                manifest.resume_at = Some(ContinueToken {
+                    repository: image.repository_name.clone(),
+                    skip: skip + reported + failed,
+                });
             }
         }
     }
+    // Repositories resumed by --continue-token had images before the token
+    if listed == 0 && skip == 0 {
+        manifest.empty_repositories += 1;
+    }
+    // Pinned tags pushed again while the images were listed
+    if let Some(pins) = &pins {
+        let digest = |digest: &Option<String>| digest.clone().unwrap_or("no image".to_string());
+        for moved in pins.moved_tags(client).await? {
+            eprintln!(
+                "Warning: tag '{}' of repository '{}' moved from {} to {} during the run, reported on {}",
+                moved.tag,
+                repository_name,
+                digest(&moved.pinned),
+                digest(&moved.current),
+                digest(&moved.pinned)
+            );
+            manifest.moved_tags += 1;
+        }
+    }
     Ok(())
 }
 
-// Print severity and dates in csv line
-fn print_results(
-    scan_complete_date: DateTime,
-    update_scan_date: DateTime,
-    severity_map: std::collections::HashMap<FindingSeverity, i32>,
-) {
-    println!(
-        ";{};{};{};{};{};{};{};{}",
-        scan_complete_date
-            .fmt(DateTimeFormat::DateTime)
-            .unwrap_or_default(),
-        update_scan_date
-            .fmt(DateTimeFormat::DateTime)
-            .unwrap_or_default(),
-        severity_map.get(&FindingSeverity::Critical).unwrap_or(&0),
-        severity_map.get(&FindingSeverity::High).unwrap_or(&0),
-        severity_map.get(&FindingSeverity::Medium).unwrap_or(&0),
-        severity_map.get(&FindingSeverity::Low).unwrap_or(&0),
-        severity_map
-            .get(&FindingSeverity::Informational)
-            .unwrap_or(&0),
-        severity_map.get(&FindingSeverity::Undefined).unwrap_or(&0)
-    );
+// Function to get the resource tags of a repository from its name
+async fn describe_repository_tags(
+    client: &aws_sdk_ecr::Client,
+    repository_name: &str,
+) -> Result<HashMap<String, String>, aws_sdk_ecr::Error> {
+    let response = client
+        .describe_repositories()
+        .repository_names(repository_name)
+        .send()
+        .await?;
+    let repository_arn = response
+        .repositories()
+        .first()
+        .and_then(|r| r.repository_arn())
+        .unwrap_or_default();
+    fetch_repository_tags(client, repository_arn).await
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Set up AWS credentials and region
-    let config = aws_config::load_from_env().await;
-    let client = Client::new(&config);
-
+async fn main() -> ExitCode {
     // Retrieve command-line arguments
     let args: Vec<String> = env::args().collect();
 
+    // Set up AWS credentials and region, --region overriding the one of the
+    // environment or profile
+    let region_override = option_value(&args, "--region").map(|r| r.as_str());
+    if let Some(Err(e)) = region_override.map(check_region) {
+        eprintln!("Invalid --region value: {}", e);
+        return ExitCode::from(EXIT_AUTH_CONFIG);
+    }
+    let sdk_config = load_sdk_config(region_override, has_flag(&args, "--nice")).await;
+    // Sign in to IAM Identity Center, or make the calls as a role of one of its accounts
+    let sdk_region = sdk_config
+        .region()
+        .map(|r| r.to_string())
+        .unwrap_or_default();
+    let sso_portal = match sso_portal(&args, &sdk_region) {
+        Ok(portal) => portal,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+    };
+    if let Some(("login", _)) = subcommand(&args) {
+        let Some(portal) = &sso_portal else {
+            eprintln!("login needs --sso-start-url <url>");
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        };
+        return ExitCode::from(match login(&sdk_config, portal).await {
+            Ok(()) => EXIT_OK,
+            Err(e) => {
+                eprintln!(
+                    "Error signing in to {}: {}",
+                    portal.start_url,
+                    error_chain(e.as_ref())
+                );
+                EXIT_AUTH_CONFIG
+            }
+        });
+    }
+    let sso_role = (
+        option_value(&args, "--sso-account-id"),
+        option_value(&args, "--sso-role-name"),
+    );
+    let sdk_config = match (&sso_portal, sso_role) {
+        (Some(portal), (Some(account_id), Some(role_name))) => {
+            match sso_credentials(&sdk_config, portal, account_id, role_name) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(EXIT_AUTH_CONFIG);
+                }
+            }
+        }
+        (Some(_), _) => {
+            eprintln!("--sso-start-url needs --sso-account-id and --sso-role-name, or the login subcommand");
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+        (None, (None, None)) => sdk_config,
+        (None, _) => {
+            eprintln!("--sso-account-id and --sso-role-name need --sso-start-url");
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+    };
+    // Make the calls as the role of --role-arn, refreshing its session as needed,
+    // and asking for the code of its MFA device with --mfa-serial. With
+    // --web-identity-token-file, the role is assumed with the web identity token
+    // of the file instead, e.g. an EKS service account token
+    let session_length = option_value(&args, "--role-session").map(|s| s.as_str());
+    let mfa_token = option_value(&args, "--mfa-token").map(|t| t.as_str());
+    let mfa = option_value(&args, "--mfa-serial").map(|serial| Mfa {
+        serial,
+        token: mfa_token,
+    });
+    let web_identity_token_file = option_value(&args, "--web-identity-token-file");
+    let role_arn = option_value(&args, "--role-arn").cloned();
+    let sdk_config = match (web_identity_token_file, &role_arn) {
+        (Some(_), _) if sso_portal.is_some() => {
+            eprintln!("--web-identity-token-file cannot be used with --sso-start-url");
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+        (Some(_), _) if session_length.is_some() || mfa.is_some() => {
+            eprintln!(
+                "--role-session and --mfa-serial cannot be used with --web-identity-token-file"
+            );
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+        (Some(token_file), _) => {
+            let Some(role_arn) = role_arn
+                .clone()
+                .or_else(|| env::var("AWS_ROLE_ARN").ok().filter(|r| !r.is_empty()))
+            else {
+                eprintln!("--web-identity-token-file needs --role-arn or AWS_ROLE_ARN");
+                return ExitCode::from(EXIT_AUTH_CONFIG);
+            };
+            match assume_role_with_web_identity(&sdk_config, token_file, &role_arn) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(EXIT_AUTH_CONFIG);
+                }
+            }
+        }
+        (None, Some(role_arn)) => {
+            match assume_role(&sdk_config, role_arn, session_length, mfa).await {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(EXIT_AUTH_CONFIG);
+                }
+            }
+        }
+        (None, None) if session_length.is_some() || mfa.is_some() => {
+            eprintln!("--role-session and --mfa-serial need --role-arn");
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+        (None, None) => sdk_config,
+    };
+    if mfa_token.is_some() && option_value(&args, "--mfa-serial").is_none() {
+        eprintln!("--mfa-token needs --mfa-serial");
+        return ExitCode::from(EXIT_AUTH_CONFIG);
+    }
+    let client = ecr_client(&sdk_config);
+
+    // Export traces of the AWS calls, flushed when the guard is dropped on exit
+    let _telemetry = match option_value(&args, "--otlp-endpoint").map(|url| init_tracing(url)) {
+        Some(Ok(guard)) => Some(guard),
+        Some(Err(e)) => {
+            eprintln!("Invalid --otlp-endpoint value: {}", error_chain(e.as_ref()));
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+        None => None,
+    };
+
     // Check version
-    let process_version = args.contains(&String::from("--version"));
+    let process_version = has_flag(&args, "--version");
     if process_version {
         let version = option_env!("CARGO_PKG_VERSION").unwrap_or("unknown");
         eprintln!("{} version v{}", args[0], version);
-        return Ok(());
+        return ExitCode::from(EXIT_OK);
     }
 
-    // Check if the user wants to process all repositories
-    let process_all = args.contains(&String::from("--all"));
+    // Check for a newer release, and install it with self-update
+    if has_flag(&args, "--check-update") {
+        return ExitCode::from(check_update().await);
+    }
+    if let Some(("self-update", _)) = subcommand(&args) {
+        return ExitCode::from(self_update().await);
+    }
 
-    // Check if repository name argument is provided
-    let repository_name = if process_all {
-        None
-    } else if args.len() < 2 {
-        eprintln!("Usage: {} [--all | <repository_name> | --version]", args[0]);
-        return Ok(());
+    // Diagnose credentials and permissions instead of reporting
+    if let Some(("doctor" | "whoami", rest)) = subcommand(&args) {
+        let options = CredentialOptions {
+            sso: sso_portal.is_some(),
+            role_arn: role_arn.as_deref(),
+            web_identity_token_file: web_identity_token_file.map(|f| f.as_str()),
+        };
+        return ExitCode::from(run_doctor(&sdk_config, rest.first().copied(), &options).await);
+    }
+
+    // Compare the findings of an image with those of another scanner
+    if let Some(("cross-check", rest)) = subcommand(&args) {
+        return ExitCode::from(cross_check_image(&client, &args, &rest).await);
+    }
+
+    // Check a report against its KMS signature
+    if let Some(("verify", rest)) = subcommand(&args) {
+        return ExitCode::from(verify_signature(&sdk_config, &args, &rest).await);
+    }
+
+    // Re-render or merge previous JSON reports without calling AWS
+    match subcommand(&args) {
+        Some(("render", _)) => return ExitCode::from(render_report(&args)),
+        Some(("merge", paths)) => return ExitCode::from(merge_report_files(&args, &paths)),
+        Some(("schema", rest)) => return ExitCode::from(print_schema(&rest)),
+        _ => {}
+    }
+
+    let region = sdk_config
+        .region()
+        .map(|r| r.to_string())
+        .unwrap_or_default();
+
+    // Parse arguments and configuration, reporting errors as configuration errors
+    let options = match parse_options(&args, &region) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            let mut manifest = RunManifest::start();
+            manifest.add_region(&region);
+            manifest.errors.push(RunError::config(&e));
+            finish_run(
+                &mut manifest,
+                EXIT_AUTH_CONFIG,
+                option_value(&args, "--run-manifest").map(|p| p.as_str()),
+                option_value(&args, "--errors-json").map(|p| p.as_str()),
+                option_value(&args, "--summary-json").map(|p| p.as_str()),
+            );
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        }
+    };
+    // Print the IAM policy of the run instead of reporting
+    if let Some(("gen-iam-policy", _)) = subcommand(&args) {
+        return ExitCode::from(print_iam_policy(&args, &options, &region));
+    }
+    // Audit the replication of the images instead of writing a report
+    if let Some(("replication", _)) = subcommand(&args) {
+        return ExitCode::from(replication_report(&client, &args, &options).await);
+    }
+
+    // Match the repositories against the registry scanning rules instead of writing a report
+    if let Some(("simulate-scan-rules", _)) = subcommand(&args) {
+        return ExitCode::from(scan_rules_report(&client, &options).await);
+    }
+    // Serve the scan data over HTTP instead of writing a report
+    if let Some(serve_options) = options.serve {
+        return ExitCode::from(
+            serve(
+                client,
+                options.settings,
+                &region,
+                &serve_options.listen,
+                serve_options.cache_ttl,
+            )
+            .await,
+        );
+    }
+    let settings = &options.settings;
+    // References of k8s-correlate to images of other registries or regions
+    if let Some(running) = settings.running_images.as_ref().filter(|r| r.ignored > 0) {
+        eprintln!(
+            "Note: {} running image(s) outside of the ECR registries of region {} left out",
+            running.ignored, region
+        );
+    }
+    // Exemptions of --exempt-images past their expiry date gate their images again
+    for exemption in settings.fail_on.exemptions.expired(&dates::now()) {
+        eprintln!(
+            "Warning: exemption of '{}' expired on {}, its images are gated again",
+            exemption.pattern,
+            exemption.expiry_day()
+        );
+    }
+    // Errors of the run of --retry-from not attached to a repository
+    if let Some(retry) = settings.retry.as_ref().filter(|r| r.ignored > 0) {
+        eprintln!(
+            "Note: {} error(s) not attached to a repository or image are not retried",
+            retry.ignored
+        );
+    }
+    // List what would be processed without describing any image
+    if options.dry_run {
+        return ExitCode::from(
+            dry_run(
+                &sdk_config,
+                &client,
+                options.repository_name.as_ref(),
+                options.repository_names.as_deref(),
+                settings,
+            )
+            .await,
+        );
+    }
+    // Report once, or at each run of the schedule until the process is stopped
+    let Some(schedule) = &options.schedule else {
+        return ExitCode::from(report_registry(&sdk_config, &client, &options, &region).await);
+    };
+    let health = Arc::new(Mutex::new(HealthStatus::default()));
+    if let Some(listen) = options.health_listen.as_deref() {
+        match tokio::net::TcpListener::bind(listen).await {
+            Ok(listener) => {
+                eprintln!("Health endpoints listening on http://{}", listen);
+                tokio::spawn(serve_health(listener, health.clone()));
+            }
+            Err(e) => {
+                eprintln!("Error listening on '{}': {}", listen, e);
+                return ExitCode::from(EXIT_API_FAILURE);
+            }
+        }
+    }
+    let mut after = dates::now().secs();
+    loop {
+        let Some(next) = schedule.next_after(after) else {
+            eprintln!("Error: no run left in --schedule");
+            return ExitCode::from(EXIT_AUTH_CONFIG);
+        };
+        eprintln!("Next run scheduled at {}", format_run(next));
+        health.lock().unwrap_or_else(|e| e.into_inner()).next_run = Some(format_run(next));
+        let delay = Duration::from_secs((next - dates::now().secs()).max(0) as u64);
+        tokio::time::sleep(delay + schedule.jitter_delay()).await;
+        reset_api_calls();
+        health.lock().unwrap_or_else(|e| e.into_inner()).running = true;
+        let exit_code = report_registry(&sdk_config, &client, &options, &region).await;
+        // An interrupted run stops the schedule too
+        if exit_code == EXIT_INTERRUPTED {
+            return ExitCode::from(exit_code);
+        }
+        // Runs failing on AWS calls or the configuration make the process not ready
+        let status = match exit_code {
+            EXIT_API_FAILURE | EXIT_AUTH_CONFIG => RunStatus::Failed,
+            _ => RunStatus::Succeeded,
+        };
+        {
+            let mut state = health.lock().unwrap_or_else(|e| e.into_inner());
+            state.running = false;
+            state.last_run = Some(LastRun::new(status, Some(exit_code), None));
+        }
+        // Runs are never overlapping, those due while reporting are skipped
+        let finished = dates::now().secs();
+        eprintln!(
+            "Run scheduled at {} finished with exit code {}",
+            format_run(next),
+            exit_code
+        );
+        let skipped = schedule.runs_between(next, finished);
+        if skipped > 0 {
+            eprintln!(
+                "Warning: {} scheduled run(s) skipped while the previous run was reporting",
+                skipped
+            );
+        }
+        after = finished;
+    }
+}
+
+// Function to write the report of the registry and its other outputs, returning the exit code
+async fn report_registry(
+    sdk_config: &SdkConfig,
+    client: &aws_sdk_ecr::Client,
+    options: &Options,
+    region: &str,
+) -> u8 {
+    // Collect run metadata for the manifest
+    let mut manifest = RunManifest::start();
+    manifest.add_region(region);
+    manifest.ids.correlation_id = options.correlation_id.clone();
+    let ids = manifest.ids.clone();
+    // Workloads running the images, looked up once before describing them
+    let mut settings = options.settings.clone();
+    if !settings.usage.is_empty() {
+        match WorkloadUsage::collect(sdk_config, &settings.usage).await {
+            Ok(workloads) => settings.workloads = workloads,
+            Err(e) => {
+                eprintln!(
+                    "Error looking up the workloads running the images: {}",
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+            }
+        }
+    }
+    let settings = &settings;
+    manifest.targets = settings.targets.iter().map(TargetResult::new).collect();
+    manifest.add_findings(
+        &settings.scheme.levels,
+        &vec![0; settings.scheme.levels.len()],
+    );
+    // JSON report of the previous run, the images of --retry-from are merged into it
+    let snapshot = match (&settings.retry, options.output_file.as_deref()) {
+        (Some(_), Some(path))
+            if options.format == OutputFormat::Json
+                && options.compression.is_none()
+                && !path.starts_with("s3://")
+                && std::path::Path::new(path).exists() =>
+        {
+            match load_report(path) {
+                Ok(report) => Some(report),
+                Err(e) => {
+                    let message = format!(
+                        "Error loading report '{}' to merge the retried images into: {}",
+                        path, e
+                    );
+                    eprintln!("{}", message);
+                    manifest.errors.push(RunError::config(&message));
+                    finish_run(
+                        &mut manifest,
+                        EXIT_AUTH_CONFIG,
+                        options.manifest_path.as_deref(),
+                        options.errors_path.as_deref(),
+                        options.summary_path.as_deref(),
+                    );
+                    return EXIT_AUTH_CONFIG;
+                }
+            }
+        }
+        _ => None,
+    };
+    // Only the report goes to stdout or --output-file, every diagnostic goes to stderr
+    let mut output = match ReportOutput::create(options.output_file.as_deref(), options.compression)
+    {
+        Ok(output) => output,
+        Err(e) => {
+            let message = format!(
+                "Error creating report file '{}': {}",
+                options.output_file.as_deref().unwrap_or_default(),
+                e
+            );
+            eprintln!("{}", message);
+            manifest.errors.push(RunError::config(&message));
+            finish_run(
+                &mut manifest,
+                EXIT_AUTH_CONFIG,
+                options.manifest_path.as_deref(),
+                options.errors_path.as_deref(),
+                options.summary_path.as_deref(),
+            );
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    if options.sign_kms_key_id.is_some() {
+        output.keep_digest();
+    }
+    // Parquet files are added once written, nothing goes to the output, nor
+    // with --split-by unless the complete report goes to --output-file, nor
+    // with evidence whose reports go to the pack
+    let split_only = (settings.split_by.is_some() && options.output_file.is_none())
+        || options.evidence.is_some();
+    if options.format != OutputFormat::Parquet && !split_only {
+        manifest.add_output(
+            output.kind(),
+            options.format.as_str(),
+            options.output_file.as_deref(),
+        );
+    }
+    let out: Box<dyn Write> = match split_only {
+        true => Box::new(io::sink()),
+        false => Box::new(EncodedWriter::new(
+            output.writer(),
+            options.encoding,
+            options.line_ending,
+        )),
+    };
+    let mut writer = ReportWriter::new(settings, options.format, region, out);
+    if let Some(path) = options.pull_commands_path.as_deref() {
+        match PullScript::create(path, options.pull_tool, region) {
+            Ok(script) => writer.emit_pull_commands(script),
+            Err(e) => {
+                let message = format!("Error creating pull script '{}': {}", path, e);
+                eprintln!("{}", message);
+                manifest.errors.push(RunError::config(&message));
+                finish_run(
+                    &mut manifest,
+                    EXIT_AUTH_CONFIG,
+                    options.manifest_path.as_deref(),
+                    options.errors_path.as_deref(),
+                    options.summary_path.as_deref(),
+                );
+                return EXIT_AUTH_CONFIG;
+            }
+        }
+        manifest.add_output("pull-commands", "sh", Some(path));
+    }
+    if options.cloudwatch_namespace.is_some() {
+        writer.count_repositories();
+    }
+    if options.github_check.is_some() || options.bitbucket_report.is_some() || options.azure_devops
+    {
+        writer.annotate_images();
+    }
+    let sink = options.findings_sink.clone().map(|target| {
+        let sink = FindingsSink::start(sdk_config, target, ids.clone());
+        writer.send_findings(sink.sender());
+        sink
+    });
+    // Root span of the run, the repositories, images and AWS calls are its children
+    let mut run_attributes = vec![
+        KeyValue::new("cloud.region", region.to_string()),
+        KeyValue::new("run.id", ids.run_id.clone()),
+    ];
+    if let Some(correlation_id) = &ids.correlation_id {
+        run_attributes.push(KeyValue::new("run.correlation_id", correlation_id.clone()));
+    }
+    let run_cx = start_span("run", run_attributes);
+    let reporting = run(
+        client,
+        options.repository_name.as_ref(),
+        options.repository_names.as_deref(),
+        settings,
+        &mut writer,
+        &mut manifest,
+    )
+    .with_context(run_cx.clone());
+    // Ctrl-C or SIGTERM stop the run at its current call, the images reported
+    // until then are still written, with the token to resume after them
+    let result = match until_interrupted(reporting).await {
+        Ok(result) => result,
+        Err(interrupted) => {
+            eprintln!(
+                "Run {}, writing the {} image(s) reported so far",
+                interrupted, manifest.images
+            );
+            manifest.next_token = manifest.resume_at.as_ref().map(|t| t.encode());
+            if let Err(e) = finish_report(&mut writer, &manifest, settings) {
+                eprintln!("Error writing the interrupted report: {}", e);
+                manifest.add_error(None, &e);
+            }
+            manifest.add_error(None, &interrupted);
+            Err(interrupted.into())
+        }
+    };
+    // The reader of the report went away, e.g. piped to head: the run ends
+    // with the images written so far, its exit code given by their findings
+    let output_closed = matches!(&result, Err(e) if is_output_closed(e.as_ref()));
+    if output_closed {
+        eprintln!("Report output closed, {} image(s) written", manifest.images);
+        manifest.next_token = manifest.resume_at.as_ref().map(|t| t.encode());
+    }
+    // Deliver the findings collected so far, even when the run failed
+    writer.close_sink();
+    if let (Some(sink), Some(target)) = (sink, &options.findings_sink) {
+        let location = target.location();
+        match sink.finish().with_context(run_cx.clone()).await {
+            Ok(findings) => {
+                eprintln!("{} finding(s) sent to {}", findings, location);
+                manifest.add_output("findings-sink", "ndjson", Some(&location));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error sending findings to '{}': {}",
+                    location,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+            }
+        }
+    }
+    // Complete the report file, a report is only uploaded to S3 when complete
+    let destination = options.output_file.as_deref().unwrap_or("stdout");
+    let finished = output
+        .finish(
+            sdk_config,
+            options.format.content_type(),
+            result.is_ok(),
+            &ids,
+        )
+        .with_context(run_cx.clone())
+        .await;
+    match finished {
+        // The QuickSight manifest points at the uploaded report only
+        Ok(mut digest) if result.is_ok() => {
+            if let Some(previous) = snapshot {
+                match merge_retried_images(destination, previous, settings, options) {
+                    Ok(retried) => {
+                        eprintln!("{} retried image(s) merged into {}", retried, destination);
+                        // The signature covers the merged report
+                        digest = digest
+                            .and_then(|_| std::fs::read(destination).ok())
+                            .map(|content| Sha256::digest(content).to_vec());
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "Error merging the retried images into '{}': {}",
+                            destination, e
+                        );
+                        manifest.add_error(None, e.as_ref());
+                    }
+                }
+            }
+            if let Some(path) = options.quicksight_manifest.as_deref() {
+                match write_quicksight_manifest(sdk_config, path, destination, &ids)
+                    .with_context(run_cx.clone())
+                    .await
+                {
+                    Ok(()) => manifest.add_output("quicksight-manifest", "json", Some(path)),
+                    Err(e) => {
+                        eprintln!(
+                            "Error writing QuickSight manifest to '{}': {}",
+                            path,
+                            error_chain(e.as_ref())
+                        );
+                        manifest.add_error(None, e.as_ref());
+                    }
+                }
+            }
+            if let (Some(key_id), Some(digest)) = (options.sign_kms_key_id.as_deref(), digest) {
+                let location = signature_location(destination);
+                let signed = async {
+                    let signature = sign_digest(sdk_config, key_id, &digest, &ids).await?;
+                    write_signature(sdk_config, &location, &signature).await
+                }
+                .with_context(run_cx.clone())
+                .await;
+                match signed {
+                    Ok(()) => {
+                        eprintln!("Report signed with KMS key {} to {}", key_id, location);
+                        manifest.add_output("signature", "json", Some(&location));
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "Error signing report '{}' with KMS key '{}': {}",
+                            destination,
+                            key_id,
+                            error_chain(e.as_ref())
+                        );
+                        manifest.add_error(None, e.as_ref());
+                    }
+                }
+            }
+        }
+        Ok(_) => {}
+        Err(_) if output_closed => {}
+        Err(e) => {
+            eprintln!(
+                "Error writing report to '{}': {}",
+                destination,
+                error_chain(e.as_ref())
+            );
+            manifest.add_error(None, e.as_ref());
+        }
+    }
+    // Push the report as an artifact of the image, listed among its referrers
+    if let (Ok(()), true, Some(repository_name), Some(image_digest)) = (
+        &result,
+        options.attach_report,
+        options.repository_name.as_deref(),
+        settings.image_digest.as_deref(),
+    ) {
+        let attached = async {
+            let artifact = ReportArtifact::from_writer(&writer, &settings.labels)?;
+            attach_report(client, repository_name, image_digest, &artifact, &ids).await
+        }
+        .with_context(run_cx.clone())
+        .await;
+        match attached {
+            Ok(digest) => {
+                let location = format!("{}@{}", repository_name, digest);
+                eprintln!(
+                    "Report attached to {}@{} as {}",
+                    repository_name, image_digest, location
+                );
+                manifest.add_output("attached-report", "oci-artifact", Some(&location));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error attaching report to {}@{}: {}",
+                    repository_name,
+                    image_digest,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+            }
+        }
+    }
+    // Write the badges once every repository has been processed
+    if let (Ok(()), Some(level), Some(destination)) =
+        (&result, settings.badge_level, options.out_dir.as_deref())
+    {
+        let label = settings.scheme.levels[level].to_lowercase();
+        match write_badges(sdk_config, destination, &label, &writer.badges(), &ids)
+            .with_context(run_cx.clone())
+            .await
+        {
+            Ok(locations) => {
+                eprintln!("{} badge(s) written to {}", locations.len(), destination);
+                manifest.add_output("badges", "svg", Some(destination));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error writing badges to '{}': {}",
+                    destination,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+            }
+        }
+    }
+    // Write the report of each group once every repository has been processed
+    if let (Ok(()), Some(groups), Some(destination)) =
+        (&result, writer.split_groups(), options.out_dir.as_deref())
+    {
+        match write_split_reports(
+            sdk_config,
+            destination,
+            options,
+            writer.document(),
+            writer.images(),
+            groups,
+            &ids,
+        )
+        .with_context(run_cx.clone())
+        .await
+        {
+            Ok(locations) => {
+                eprintln!(
+                    "{} {} report(s) written to {}",
+                    locations.len(),
+                    options.format.as_str(),
+                    destination
+                );
+                manifest.add_output("split-reports", options.format.as_str(), Some(destination));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error writing the split reports to '{}': {}",
+                    destination,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+            }
+        }
+    }
+    // Write the Parquet files once every repository has been processed
+    if let (Ok(()), OutputFormat::Parquet, Some(destination)) =
+        (&result, options.format, options.out_dir.as_deref())
+    {
+        match write_dataset(
+            sdk_config,
+            destination,
+            &options.partition_by,
+            &settings.scheme.levels,
+            writer.images(),
+            &ids,
+        )
+        .with_context(run_cx.clone())
+        .await
+        {
+            Ok(files) => {
+                eprintln!("{} Parquet file(s) written to {}", files.len(), destination);
+                manifest.add_output("dataset", "parquet", Some(destination));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error writing Parquet files to '{}': {}",
+                    destination,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+            }
+        }
+    }
+    // Publish the metrics once every repository has been processed
+    if let (Ok(()), Some(namespace)) = (&result, options.cloudwatch_namespace.as_deref()) {
+        match publish_metrics(
+            sdk_config,
+            namespace,
+            &settings.scheme.levels,
+            &writer.repository_counts(),
+        )
+        .with_context(run_cx.clone())
+        .await
+        {
+            Ok(metrics) => {
+                eprintln!(
+                    "{} metric(s) published to CloudWatch {}",
+                    metrics, namespace
+                );
+                manifest.add_output("cloudwatch", "metrics", Some(namespace));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error publishing metrics to CloudWatch namespace '{}': {}",
+                    namespace,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+            }
+        }
+    }
+    if let (Err(e), false) = (&result, output_closed) {
+        record_error(&run_cx, e.as_ref());
+        eprintln!("Error: {}", error_chain(e.as_ref()));
+        if manifest.errors.is_empty() {
+            manifest.add_error(None, e.as_ref());
+        }
+    }
+
+    // Report rows emitted with placeholders instead of real data
+    if manifest.degraded_rows > 0 {
+        eprintln!(
+            "Warning: {} row(s) emitted with degraded data",
+            manifest.degraded_rows
+        );
+    }
+    // Images whose scan findings expired, reported without findings unless skipped
+    if manifest.expired_images > 0 {
+        eprintln!(
+            "Warning: {} image(s) with expired scan findings {}",
+            manifest.expired_images,
+            if settings.skip_expired {
+                "skipped by --skip-expired"
+            } else {
+                "reported without findings, rescan them or use --skip-expired"
+            }
+        );
+    }
+    if manifest.stale_images > 0 {
+        eprintln!(
+            "Warning: {} image(s) scanned against a vulnerability database older than --max-db-age",
+            manifest.stale_images
+        );
+    }
+    // Report limits that left images out of the report
+    if manifest.truncated_repositories > 0 {
+        eprintln!(
+            "Note: {} repository(ies) truncated to {} image(s) by --max-images-per-repo",
+            manifest.truncated_repositories,
+            settings.max_images_per_repo.unwrap_or_default()
+        );
+    }
+    if manifest.truncated_images > 0 {
+        eprintln!(
+            "Note: {} image(s) with findings truncated to {} by --max-findings-per-image",
+            manifest.truncated_images,
+            settings.max_findings_per_image.unwrap_or_default()
+        );
+    }
+    if manifest.sampled {
+        eprintln!(
+            "Note: report limited to the first {} image(s) by --sample",
+            settings.sample.unwrap_or_default()
+        );
+    }
+    if let Some(token) = &manifest.next_token {
+        eprintln!(
+            "Note: report the next images with --continue-token {}",
+            token
+        );
+    }
+    // Repositories and images left out of the report by --keep-going
+    if settings.keep_going && (manifest.repositories.failed > 0 || manifest.failed_images > 0) {
+        eprintln!(
+            "Warning: {} repository(ies) and {} image(s) failed and were left out of the report{}",
+            manifest.repositories.failed,
+            manifest.failed_images,
+            match &options.errors_path {
+                Some(path) => format!(", see {}", path),
+                None => ", list them with --errors-json".to_string(),
+            }
+        );
+    }
+    // Images of other media types are left out unless listed for the inventory
+    if manifest.unsupported_images > 0 {
+        eprintln!(
+            "Note: {} image(s) of a media type ECR cannot scan {}",
+            manifest.unsupported_images,
+            if settings.include_unsupported {
+                "listed as not_scannable"
+            } else {
+                "left out, list them with --include-unsupported"
+            }
+        );
+    }
+    // Report the images whose summary counts disagree with their findings
+    if settings.verify_counts.is_some() {
+        eprintln!(
+            "{}: severity counts of {} image(s) verified, {} disagree with their findings",
+            if manifest.count_mismatches > 0 {
+                "Warning"
+            } else {
+                "Note"
+            },
+            manifest.verified_images,
+            manifest.count_mismatches
+        );
+    }
+    // Tell an empty registry or repository apart from a clean one
+    if result.is_ok() && manifest.repositories.requested == 0 {
+        eprintln!("Note: no repository to report");
+    } else if manifest.empty_repositories > 0 {
+        eprintln!(
+            "Note: {} repository(ies) without images",
+            manifest.empty_repositories
+        );
+    }
+    // Report findings over thresholds and SLA
+    if manifest.threshold_violations > 0 {
+        eprintln!(
+            "{} image(s) with findings over the --fail-on, repository or tag thresholds",
+            manifest.threshold_violations
+        );
+    }
+    if manifest.exempted_images > 0 {
+        eprintln!(
+            "Note: {} image(s) over the thresholds exempted by --exempt-images",
+            manifest.exempted_images
+        );
+    }
+    if manifest.sla_breaches > 0 {
+        eprintln!(
+            "{} finding(s) breached their remediation SLA",
+            manifest.sla_breaches
+        );
+    }
+
+    // Report the images to pull for analysis
+    if let Some(path) = options.pull_commands_path.as_deref() {
+        eprintln!(
+            "{} pull command(s) written to {}",
+            writer.pull_commands(),
+            path
+        );
+    }
+
+    // Report image records that did not fit in memory
+    if writer.spilled_images() > 0 {
+        eprintln!(
+            "Note: {} image record(s) over --max-images-in-memory buffered on disk",
+            writer.spilled_images()
+        );
+    }
+
+    print_scorecard(&manifest.targets);
+    print_api_calls(&api_calls());
+
+    // Errors first, then untrustworthy data, then findings
+    let mut exit_code = if let Some(code) = errors_exit_code(&manifest.errors) {
+        code
+    } else if settings.strict && manifest.degraded_rows > 0 {
+        EXIT_DEGRADED
+    } else if settings.fail_on_empty && manifest.images == 0 {
+        EXIT_EMPTY
+    } else if manifest.threshold_violations > 0 || manifest.sla_breaches > 0 {
+        EXIT_FINDINGS
     } else {
-        Some(&args[1])
+        EXIT_OK
     };
+    // Bundle the reports and the manifest of the finished run into the evidence pack
+    if let (Ok(()), Some(evidence)) = (&result, &options.evidence) {
+        manifest.finish(exit_code);
+        match write_evidence(
+            sdk_config,
+            evidence,
+            writer.document(),
+            writer.images(),
+            &settings.labels,
+            &manifest,
+            &ids,
+        )
+        .with_context(run_cx.clone())
+        .await
+        {
+            Ok(files) => {
+                eprintln!(
+                    "Evidence pack of {} file(s) written to {}",
+                    files.len(),
+                    evidence.path
+                );
+                manifest.add_output("evidence", "zip", Some(&evidence.path));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error writing evidence pack to '{}': {}",
+                    evidence.path,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+                exit_code = errors_exit_code(&manifest.errors).unwrap_or(EXIT_API_FAILURE);
+            }
+        }
+    }
 
-    // Print headers
-    println!(
-        "repository_name;image_tags;image_digest;image_scan_completed_date;vulnerability_source_updated_date;Critical;High;Medium;Low;Informational;Undefined"
+    // Check run on the commit, failing with the run
+    if let Some(target) = &options.github_check {
+        match create_check_run(
+            target,
+            &settings.scheme.levels,
+            writer.annotations(),
+            &manifest,
+            exit_code,
+        )
+        .with_context(run_cx.clone())
+        .await
+        {
+            Ok(url) => {
+                eprintln!(
+                    "Check run created on {}@{}: {}",
+                    target.repository, target.commit, url
+                );
+                manifest.add_output("github-check", "check-run", Some(&url));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error creating check run on {}@{}: {}",
+                    target.repository,
+                    target.commit,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+                exit_code = errors_exit_code(&manifest.errors).unwrap_or(EXIT_API_FAILURE);
+            }
+        }
+    }
+
+    // Code Insights report on the Bitbucket commit, failed with the run
+    if let Some(target) = &options.bitbucket_report {
+        match create_report(
+            target,
+            &settings.scheme.levels,
+            writer.annotations(),
+            &manifest,
+            exit_code,
+        )
+        .with_context(run_cx.clone())
+        .await
+        {
+            Ok(annotations) => {
+                eprintln!(
+                    "Code Insights report created on {}@{} with {} annotation(s)",
+                    target.repository, target.commit, annotations
+                );
+                let location = format!("{}@{}", target.repository, target.commit);
+                manifest.add_output("bitbucket-report", "code-insights", Some(&location));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error creating Code Insights report on {}@{}: {}",
+                    target.repository,
+                    target.commit,
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+                exit_code = errors_exit_code(&manifest.errors).unwrap_or(EXIT_API_FAILURE);
+            }
+        }
+    }
+    // Issues, summary and report attachment of the Azure Pipelines job
+    if options.azure_devops {
+        match write_logging_commands(
+            &settings.scheme.levels,
+            writer.annotations(),
+            &manifest,
+            exit_code,
+            options.output_file.as_deref(),
+        ) {
+            Ok(summary) => {
+                let summary = summary.to_string_lossy();
+                manifest.add_output("azure-devops", "markdown", Some(&summary));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error writing Azure Pipelines logging commands: {}",
+                    error_chain(e.as_ref())
+                );
+                manifest.add_error(None, e.as_ref());
+                exit_code = errors_exit_code(&manifest.errors).unwrap_or(EXIT_API_FAILURE);
+            }
+        }
+    }
+
+    finish_run(
+        &mut manifest,
+        exit_code,
+        options.manifest_path.as_deref(),
+        options.errors_path.as_deref(),
+        options.summary_path.as_deref(),
+    );
+    exit_code
+}
+
+// Function to merge the images reported again with --retry-from into the JSON
+// report of the previous run, rewritten in place, returning the retried images
+fn merge_retried_images(
+    path: &str,
+    previous: JsonReport,
+    settings: &ReportSettings,
+    options: &Options,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let retried = load_report(path)?;
+    let count = retried.images.len();
+    // Records of the retry win, their scans are never older
+    let merged = merge_reports(vec![
+        (path.to_string(), previous),
+        (path.to_string(), retried),
+    ])?;
+    let file = std::fs::File::create(path)?;
+    let mut out = EncodedWriter::new(file, options.encoding, options.line_ending);
+    write_document(
+        &merged,
+        &merged.images,
+        OutputFormat::Json,
+        &settings.labels,
+        &mut out,
+    )?;
+    out.flush()?;
+    Ok(count)
+}
+
+// Function to render a JSON report given with --from in the --output format
+fn render_report(args: &[String]) -> u8 {
+    let Some(path) = option_value(args, "--from") else {
+        eprintln!("{}", usage(&args[0]));
+        return EXIT_AUTH_CONFIG;
+    };
+    match load_report(path) {
+        Ok(report) => write_rendered(&report, args),
+        Err(e) => {
+            eprintln!("Error loading report '{}': {}", path, e);
+            EXIT_AUTH_CONFIG
+        }
+    }
+}
+
+// Function to print the IAM policy of the AWS calls of a report run with the
+// given options, the registry scanning type given with --scan-type
+fn print_iam_policy(args: &[String], options: &Options, region: &str) -> u8 {
+    let enhanced = match option_value(args, "--scan-type").map(|t| t.to_lowercase()) {
+        None => false,
+        Some(scan_type) if scan_type == "basic" => false,
+        Some(scan_type) if scan_type == "enhanced" => true,
+        Some(scan_type) => {
+            eprintln!(
+                "Invalid --scan-type value: '{}', expected 'basic' or 'enhanced'",
+                scan_type
+            );
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    println!("{:#}", iam_policy(options, region, enhanced));
+    EXIT_OK
+}
+
+// Function to print the JSON Schema of the report (by default) or of the finding lines
+fn print_schema(rest: &[&String]) -> u8 {
+    let kind = rest
+        .first()
+        .map_or(Ok(SchemaKind::Report), |kind| SchemaKind::parse(kind));
+    match kind {
+        Ok(kind) => {
+            println!("{:#}", json_schema(kind));
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_AUTH_CONFIG
+        }
+    }
+}
+
+// Function to merge the JSON reports given as arguments into one deduplicated report
+fn merge_report_files(args: &[String], paths: &[&String]) -> u8 {
+    if paths.is_empty() {
+        eprintln!("{}", usage(&args[0]));
+        return EXIT_AUTH_CONFIG;
+    }
+    let mut reports = Vec::new();
+    for path in paths {
+        match load_report(path) {
+            Ok(report) => reports.push((path.to_string(), report)),
+            Err(e) => {
+                eprintln!("Error loading report '{}': {}", path, e);
+                return EXIT_AUTH_CONFIG;
+            }
+        }
+    }
+    match merge_reports(reports) {
+        Ok(report) => write_rendered(&report, args),
+        Err(e) => {
+            eprintln!("Error merging reports: {}", e);
+            EXIT_AUTH_CONFIG
+        }
+    }
+}
+
+// Function to cross-check the ECR findings of an image with the Trivy or Grype
+// report given with --from, in the --output format (csv or json)
+async fn cross_check_image(client: &aws_sdk_ecr::Client, args: &[String], rest: &[&String]) -> u8 {
+    let (Some(repository), Some(image), Some(path)) =
+        (rest.first(), rest.get(1), option_value(args, "--from"))
+    else {
+        eprintln!("{}", usage(&args[0]));
+        return EXIT_AUTH_CONFIG;
+    };
+    let format = match option_value(args, "--output").map(|value| OutputFormat::parse(value)) {
+        Some(Ok(format @ (OutputFormat::Csv | OutputFormat::Json))) => format,
+        Some(Ok(format)) => {
+            eprintln!(
+                "Invalid --output value: cross-check writes 'csv' or 'json', not '{}'",
+                format.as_str()
+            );
+            return EXIT_AUTH_CONFIG;
+        }
+        Some(Err(e)) => {
+            eprintln!("Invalid --output value: {}", e);
+            return EXIT_AUTH_CONFIG;
+        }
+        None => OutputFormat::Csv,
+    };
+    let (encoding, line_ending) = match output_encoding(args) {
+        Ok(encoding) => encoding,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    let report = match ScannerReport::load(path) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error loading scanner report '{}': {}", path, e);
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    let check = match cross_check(client, repository, image, report).await {
+        Ok(check) => check,
+        Err(e) => {
+            eprintln!(
+                "Error cross-checking {}:{}: {}",
+                repository,
+                image,
+                error_chain(e.as_ref())
+            );
+            let error = RunError::from_error(Some(repository), e.as_ref());
+            return errors_exit_code(&[error]).unwrap_or(EXIT_OK);
+        }
+    };
+    eprintln!(
+        "{} vulnerability(ies) found by both scanners, {} only by ECR, {} only by {}",
+        check.common.len(),
+        check.ecr_only.len(),
+        check.scanner_only.len(),
+        check.scanner
     );
+    let mut out = EncodedWriter::new(io::stdout(), encoding, line_ending);
+    match write_cross_check(&check, format, &mut out) {
+        Ok(()) => EXIT_OK,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => EXIT_OK,
+        Err(e) => {
+            eprintln!("Error writing cross-check: {}", e);
+            EXIT_API_FAILURE
+        }
+    }
+}
+
+// Function to check a file, e.g. a report, against the KMS signature written
+// next to it with --sign-kms-key-id, or given with --signature. The signature
+// must be made by the key of --verify-kms-key-id (or --sign-kms-key-id)
+async fn verify_signature(sdk_config: &SdkConfig, args: &[String], rest: &[&String]) -> u8 {
+    let Some(location) = rest.first() else {
+        eprintln!("{}", usage(&args[0]));
+        return EXIT_AUTH_CONFIG;
+    };
+    let Some(key_id) =
+        option_value(args, "--verify-kms-key-id").or(option_value(args, "--sign-kms-key-id"))
+    else {
+        eprintln!("verify needs the KMS key expected to have signed the file, with --verify-kms-key-id <key-id>");
+        return EXIT_AUTH_CONFIG;
+    };
+    let signature_path = option_value(args, "--signature")
+        .cloned()
+        .unwrap_or_else(|| signature_location(location));
+    let signature = match read_location(sdk_config, &signature_path).await {
+        Ok(document) => match serde_json::from_slice::<FileSignature>(&document) {
+            Ok(signature) => signature,
+            Err(e) => {
+                eprintln!("Invalid signature '{}': {}", signature_path, e);
+                return EXIT_AUTH_CONFIG;
+            }
+        },
+        Err(e) => {
+            eprintln!(
+                "Error reading signature '{}': {}",
+                signature_path,
+                error_chain(e.as_ref())
+            );
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    let content = match read_location(sdk_config, location).await {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Error reading '{}': {}", location, error_chain(e.as_ref()));
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    match verify_file(sdk_config, &content, &signature, key_id).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!(
+                "Error verifying '{}' with KMS key '{}': {}",
+                location,
+                key_id,
+                error_chain(e.as_ref())
+            );
+            let error = RunError::from_error(None, e.as_ref());
+            errors_exit_code(&[error]).unwrap_or(EXIT_API_FAILURE)
+        }
+    }
+}
+
+// Function to report the images that failed to replicate to the destinations
+// of the registry replication rules, with their finding counts
+async fn replication_report(
+    client: &aws_sdk_ecr::Client,
+    args: &[String],
+    options: &Options,
+) -> u8 {
+    let format = match options.format {
+        format @ (OutputFormat::Csv | OutputFormat::Json) => format,
+        format => {
+            eprintln!(
+                "Invalid --output value: replication writes 'csv' or 'json', not '{}'",
+                format.as_str()
+            );
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    let audit = match audit_replication(
+        client,
+        options.repository_name.as_deref(),
+        &options.settings.scheme,
+        has_flag(args, "--failed-only"),
+    )
+    .await
+    {
+        Ok(audit) => audit,
+        Err(e) => {
+            eprintln!("Error auditing replication: {}", error_chain(e.as_ref()));
+            let error = RunError::from_error(options.repository_name.as_deref(), e.as_ref());
+            return errors_exit_code(&[error]).unwrap_or(EXIT_OK);
+        }
+    };
+    if audit.destinations.is_empty() {
+        eprintln!("No replication rules configured for the registry");
+    }
+    let failed = audit.failed_images();
+    let mut out = EncodedWriter::new(io::stdout(), options.encoding, options.line_ending);
+    match write_replication(&audit, format, &mut out) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("Error writing replication report: {}", e);
+            return EXIT_API_FAILURE;
+        }
+    }
+    // Failed replications leave the DR regions without the image
+    if failed > 0 {
+        eprintln!("{} image(s) failed to replicate", failed);
+        return EXIT_FINDINGS;
+    }
+    EXIT_OK
+}
+
+// Function to report the scan frequency each repository gets from the
+// registry scanning configuration, catching the repositories left unscanned
+async fn scan_rules_report(client: &aws_sdk_ecr::Client, options: &Options) -> u8 {
+    let format = match options.format {
+        format @ (OutputFormat::Csv | OutputFormat::Json) => format,
+        format => {
+            eprintln!(
+                "Invalid --output value: simulate-scan-rules writes 'csv' or 'json', not '{}'",
+                format.as_str()
+            );
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    let simulation = match simulate_scan_rules(client, options.repository_name.as_deref()).await {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!(
+                "Error simulating the scanning rules: {}",
+                error_chain(e.as_ref())
+            );
+            let error = RunError::from_error(options.repository_name.as_deref(), e.as_ref());
+            return errors_exit_code(&[error]).unwrap_or(EXIT_OK);
+        }
+    };
+    let unscanned = simulation.unscanned();
+    let mut out = EncodedWriter::new(io::stdout(), options.encoding, options.line_ending);
+    match write_scan_rules(&simulation, format, &mut out) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("Error writing scanning rules simulation: {}", e);
+            return EXIT_API_FAILURE;
+        }
+    }
+    // Repositories outside every rule are never scanned on their own
+    if unscanned > 0 {
+        eprintln!(
+            "{} repository(ies) not scanned automatically by the {} scanning rules",
+            unscanned,
+            simulation.scan_type.to_lowercase()
+        );
+        return EXIT_FINDINGS;
+    }
+    EXIT_OK
+}
+
+// Write an already collected report to stdout in the --output format
+fn write_rendered(report: &JsonReport, args: &[String]) -> u8 {
+    let formatter = match option_value(args, "--formatter") {
+        Some(_) if option_value(args, "--output").is_some() => {
+            eprintln!("Invalid --formatter value: the module writes the report, it cannot be combined with --output");
+            return EXIT_AUTH_CONFIG;
+        }
+        Some(path) => match WasmFormatter::load(path) {
+            Ok(formatter) => Some(formatter),
+            Err(e) => {
+                eprintln!("Invalid --formatter value: '{}': {}", path, e);
+                return EXIT_AUTH_CONFIG;
+            }
+        },
+        None => None,
+    };
+    let format = match option_value(args, "--output").map(|value| OutputFormat::parse(value)) {
+        Some(Ok(OutputFormat::DefectDojo)) => {
+            eprintln!("Invalid --output value: defectdojo needs the findings of a live run, JSON reports only keep their counts");
+            return EXIT_AUTH_CONFIG;
+        }
+        Some(Ok(OutputFormat::Parquet)) => {
+            eprintln!("Invalid --output value: parquet is written to --out-dir during a run");
+            return EXIT_AUTH_CONFIG;
+        }
+        Some(Ok(format)) => format,
+        Some(Err(e)) => {
+            eprintln!("Invalid --output value: {}", e);
+            return EXIT_AUTH_CONFIG;
+        }
+        None => OutputFormat::Csv,
+    };
+    let query = match option_value(args, "--query").map(|value| Query::parse(value)) {
+        Some(Ok(_)) if format != OutputFormat::Json || formatter.is_some() => {
+            eprintln!("Invalid --query value: the expression selects from the JSON document, it needs --output json");
+            return EXIT_AUTH_CONFIG;
+        }
+        Some(Ok(query)) => Some(query),
+        Some(Err(e)) => {
+            eprintln!("Invalid --query value: {}", e);
+            return EXIT_AUTH_CONFIG;
+        }
+        None => None,
+    };
+    let labels = match output_labels(args) {
+        Ok(labels) => labels,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    let (encoding, line_ending) = match output_encoding(args) {
+        Ok(encoding) => encoding,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_AUTH_CONFIG;
+        }
+    };
+    let mut out = EncodedWriter::new(io::stdout(), encoding, line_ending);
+    let written = match &formatter {
+        Some(formatter) => {
+            let formatted = serde_json::to_vec(report)
+                .map_err(|e| e.to_string())
+                .and_then(|json| formatter.format(&json));
+            match formatted {
+                Ok(formatted) => out.write_all(&formatted).and_then(|()| out.flush()),
+                Err(e) => {
+                    eprintln!("Error running formatter: {}", e);
+                    return EXIT_AUTH_CONFIG;
+                }
+            }
+        }
+        None => match &query {
+            Some(query) => write_queried(report, &report.images, query, &mut out),
+            None => write_document(report, &report.images, format, &labels, &mut out),
+        },
+    };
+    match written {
+        Ok(()) => EXIT_OK,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => EXIT_OK,
+        Err(e) => {
+            eprintln!("Error writing report: {}", e);
+            errors_exit_code(&[RunError::from_error(None, &e)]).unwrap_or(EXIT_OK)
+        }
+    }
+}
+
+// Write the run manifest and the error file, whatever the outcome of the run
+fn finish_run(
+    manifest: &mut RunManifest,
+    exit_code: u8,
+    manifest_path: Option<&str>,
+    errors_path: Option<&str>,
+    summary_path: Option<&str>,
+) {
+    manifest.finish(exit_code);
+    if let Some(path) = summary_path {
+        if let Err(e) = write_summary(path, manifest) {
+            eprintln!("Error writing summary file '{}': {}", path, e);
+        }
+    }
+    if let Some(path) = manifest_path {
+        if let Err(e) = manifest.write(path) {
+            eprintln!("Error writing run manifest '{}': {}", path, e);
+        }
+    }
+    if let Some(path) = errors_path {
+        if let Err(e) = write_errors(path, exit_code, &manifest.ids, &manifest.errors) {
+            eprintln!("Error writing errors file '{}': {}", path, e);
+        }
+    }
+    // Last line of the run, for wrapper scripts
+    print_summary(manifest);
+}
+
+// Function to print the report for one repository or all of them
+async fn run(
+    client: &aws_sdk_ecr::Client,
+    repository_name: Option<&String>,
+    repository_names: Option<&[String]>,
+    settings: &ReportSettings,
+    writer: &mut ReportWriter,
+    manifest: &mut RunManifest,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Print headers
+    writer.start()?;
 
     // Get ECR Scan type
     // Create a request to describe images in the repository
     let request = client.get_registry_scanning_configuration();
     // Send the request and await the response, handling any potential errors
-    let response = request.send().await?;
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let e = aws_sdk_ecr::Error::from(e);
+            manifest.add_error(None, &e);
+            return Err(e.into());
+        }
+    };
     // Get Scan type from registry config
     let binding = response.scanning_configuration.unwrap();
     let scan_type = binding.scan_type().unwrap();
+    manifest.scan_type = Some(scan_type.as_str().to_string());
+    // Finding ages are only known with enhanced scanning
+    if settings.sla.is_some() && scan_type.as_str() == ScanType::Basic.as_str() {
+        eprintln!("Warning: --sla needs enhanced scanning, basic scan findings have no first-observed date");
+    }
 
     // Check if a repository name is provided and list images in that repository
     if let Some(repo) = repository_name {
-        match list_images_in_repository(&client, repo, scan_type).await {
-            Ok(_) => {} // No need to do anything if successful
+        // Fetch repository resource tags when needed by filters or columns
+        let repository_tags = if settings.needs_repository_tags() {
+            match describe_repository_tags(client, repo).await {
+                Ok(tags) => tags,
+                Err(e) => {
+                    manifest.add_error(Some(repo), &e);
+                    // Suggest close names instead of the SDK error for a mistyped name
+                    if let Some(not_found) = repository_not_found(client, repo, &e).await {
+                        return Err(not_found.into());
+                    }
+                    eprintln!("Error listing tags for repository '{}': {}", repo, e);
+                    return Err(e.into());
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+        if !settings.matches_repository_tags(&repository_tags) {
+            eprintln!(
+                "Repository '{}' does not match the --repo-tag filters",
+                repo
+            );
+            return Ok(());
+        }
+        manifest.repositories.requested = 1;
+        let repository_cx = start_span(
+            "repository",
+            vec![KeyValue::new("aws.ecr.repository_name", repo.clone())],
+        );
+        match list_images_in_repository(
+            client,
+            repo,
+            &repository_tags,
+            scan_type,
+            settings,
+            writer,
+            manifest,
+        )
+        .with_context(repository_cx.clone())
+        .await
+        {
+            Ok(_) => manifest.repositories.processed += 1,
+            Err(e) if is_output_closed(e.as_ref()) => return Err(e),
             Err(e) => {
+                record_error(&repository_cx, e.as_ref());
+                manifest.repositories.failed += 1;
+                manifest.add_error(Some(repo), e.as_ref());
+                if let Some(not_found) = repository_not_found(client, repo, e.as_ref()).await {
+                    return Err(not_found.into());
+                }
                 eprintln!("Error listing images for repository '{}': {}", repo, e);
-                return Err(e.into()); // Convert the error to a boxed trait object and return it
+                return Err(e); // Return the error as is
             }
         }
     } else {
-        // If no repository name is provided, list all repositories
-        match list_all_repositories(&client, scan_type).await {
+        // If no repository name is provided, list all repositories or those of --repos-file
+        match list_all_repositories(
+            client,
+            repository_names,
+            scan_type,
+            settings,
+            writer,
+            manifest,
+        )
+        .await
+        {
             Ok(_) => {} // No need to do anything if successful
+            Err(e) if is_output_closed(e.as_ref()) => return Err(e),
             Err(e) => {
                 eprintln!("Error listing all repositories: {}", e);
                 return Err(e); // Return the error as is
@@ -217,5 +2374,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    finish_report(writer, manifest, settings)?;
+
     Ok(())
 }
+
+// Function to write the aggregated groups and documents of the images
+// reported, at the end of a run or once it is interrupted
+fn finish_report(
+    writer: &mut ReportWriter,
+    manifest: &RunManifest,
+    settings: &ReportSettings,
+) -> io::Result<()> {
+    // Describe the run at the top of the human-readable reports
+    writer.set_metadata(ReportMetadata {
+        generated_at: manifest.started_at.clone(),
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        scan_type: manifest.scan_type.clone(),
+        accounts: manifest.accounts.clone(),
+        regions: manifest.regions.clone(),
+        filters: settings.filters.clone(),
+        run_id: Some(manifest.ids.run_id.clone()),
+        correlation_id: manifest.ids.correlation_id.clone(),
+    });
+    writer.finish()
+}
@@ -0,0 +1,257 @@
+use crate::continuation::ContinueToken;
+use crate::errors::RunError;
+use crate::runid::RunIds;
+use crate::targets::TargetResult;
+use crate::telemetry::{api_calls, ApiCallStats};
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::error::Error;
+use std::time::SystemTime;
+
+// Version of the manifest layout, only bumped on breaking changes so that
+// orchestration systems can rely on the field names below
+pub const MANIFEST_SCHEMA_VERSION: u32 = 1;
+
+// Machine-readable description of a run, written with --run-manifest
+#[derive(Debug, Serialize)]
+pub struct RunManifest {
+    pub schema_version: u32,
+    pub tool_version: String,
+    #[serde(flatten)]
+    pub ids: RunIds,
+    pub status: RunStatus,
+    pub exit_code: Option<u8>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_seconds: Option<f64>,
+    pub scan_type: Option<String>,
+    pub accounts: Vec<String>,
+    pub regions: Vec<String>,
+    pub repositories: RepositoryCounts,
+    pub images: u64,
+    // Findings of the reported images per severity level
+    pub findings: IndexMap<String, i64>,
+    pub sla_breaches: u64,
+    pub threshold_violations: u64,
+    // Images over the thresholds exempted by --exempt-images, not failing the run
+    pub exempted_images: u64,
+    pub degraded_rows: u64,
+    // Images whose scan findings expired, reported without findings or skipped
+    pub expired_images: u64,
+    // Images scanned against a vulnerability database older than --max-db-age
+    pub stale_images: u64,
+    // Images with findings left out by --max-findings-per-image
+    pub truncated_images: u64,
+    // Images whose findings were read from --findings-cache instead of ECR
+    pub cached_images: u64,
+    // Images whose summary counts were checked against their findings by
+    // --verify-counts, and the ones where they disagree
+    pub verified_images: u64,
+    pub count_mismatches: u64,
+    // Images of a media type ECR cannot scan, listed with --include-unsupported
+    pub unsupported_images: u64,
+    // Images left out by --keep-going after their findings could not be read
+    pub failed_images: u64,
+    // Tags of --pin-tags pushed again to another digest during the run
+    pub moved_tags: u64,
+    // Repositories with images left out by --max-images-per-repo
+    pub truncated_repositories: u64,
+    // Repositories without any image, told apart from repositories without findings
+    pub empty_repositories: u64,
+    // True when images were left out by --sample
+    pub sampled: bool,
+    // Token resuming the report after the last image of --sample, with --continue-token
+    pub next_token: Option<String>,
+    // Position after the last image written, for the token of an interrupted run
+    #[serde(skip)]
+    pub resume_at: Option<ContinueToken>,
+    // Outcome of the compliance targets of the configuration file
+    pub targets: Vec<TargetResult>,
+    pub errors: Vec<RunError>,
+    pub outputs: Vec<OutputLocation>,
+    // AWS calls made by the run, by operation
+    pub api_calls: Vec<ApiCallStats>,
+    #[serde(skip)]
+    start: SystemTime,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RepositoryCounts {
+    pub requested: u64,
+    pub processed: u64,
+    pub failed: u64,
+}
+
+// Counters of one image, added to the run once the image is written so that
+// an interrupted run only counts the images of its report
+#[derive(Debug, Default)]
+pub struct ImageCounts {
+    pub findings: Vec<i64>, // Findings per severity level
+    pub sla_breaches: u64,
+    pub threshold_violation: bool,
+    pub exempted: bool,
+    pub degraded: bool,
+    pub expired: bool,
+    pub stale: bool,
+    pub truncated: bool,
+    pub verified: bool,
+    pub count_mismatch: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutputLocation {
+    pub kind: String,
+    pub format: String,
+    pub location: Option<String>,
+}
+
+impl RunManifest {
+    // Start a new manifest, recording the current time as the run start
+    pub fn start() -> Self {
+        let start = SystemTime::now();
+        RunManifest {
+            schema_version: MANIFEST_SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            ids: RunIds::new(None),
+            status: RunStatus::Running,
+            exit_code: None,
+            started_at: format_time(start),
+            finished_at: None,
+            duration_seconds: None,
+            scan_type: None,
+            accounts: Vec::new(),
+            regions: Vec::new(),
+            repositories: RepositoryCounts::default(),
+            images: 0,
+            findings: IndexMap::new(),
+            sla_breaches: 0,
+            threshold_violations: 0,
+            exempted_images: 0,
+            degraded_rows: 0,
+            expired_images: 0,
+            truncated_images: 0,
+            cached_images: 0,
+            stale_images: 0,
+            unsupported_images: 0,
+            failed_images: 0,
+            moved_tags: 0,
+            verified_images: 0,
+            count_mismatches: 0,
+            truncated_repositories: 0,
+            empty_repositories: 0,
+            sampled: false,
+            next_token: None,
+            resume_at: None,
+            targets: Vec::new(),
+            errors: Vec::new(),
+            outputs: Vec::new(),
+            api_calls: Vec::new(),
+            start,
+        }
+    }
+
+    // Add the finding counts of an image to the totals of each level
+    pub fn add_findings(&mut self, levels: &[String], counts: &[i64]) {
+        for (level, count) in levels.iter().zip(counts) {
+            *self.findings.entry(level.clone()).or_default() += count;
+        }
+    }
+
+    // Count a written image with its findings
+    pub fn add_image(&mut self, levels: &[String], image: &ImageCounts) {
+        self.images += 1;
+        self.add_findings(levels, &image.findings);
+        self.sla_breaches += image.sla_breaches;
+        self.threshold_violations += u64::from(image.threshold_violation);
+        self.exempted_images += u64::from(image.exempted);
+        self.degraded_rows += u64::from(image.degraded);
+        self.expired_images += u64::from(image.expired);
+        self.stale_images += u64::from(image.stale);
+        self.truncated_images += u64::from(image.truncated);
+        self.verified_images += u64::from(image.verified);
+        self.count_mismatches += u64::from(image.count_mismatch);
+    }
+
+    // Remember an account id, ignoring duplicates
+    pub fn add_account(&mut self, account: &str) {
+        if !account.is_empty() && !self.accounts.iter().any(|a| a == account) {
+            self.accounts.push(account.to_string());
+        }
+    }
+
+    // Remember a region, ignoring duplicates
+    pub fn add_region(&mut self, region: &str) {
+        if !region.is_empty() && !self.regions.iter().any(|r| r == region) {
+            self.regions.push(region.to_string());
+        }
+    }
+
+    // Record an error, optionally attached to a repository
+    pub fn add_error(&mut self, repository: Option<&str>, error: &(dyn Error + 'static)) {
+        self.errors.push(RunError::from_error(repository, error));
+    }
+
+    // Record an error reading the findings of an image, skipped with --keep-going
+    pub fn add_image_error(
+        &mut self,
+        repository: &str,
+        image_digest: &str,
+        error: &(dyn Error + 'static),
+    ) {
+        self.failed_images += 1;
+        self.errors.push(RunError {
+            image: Some(image_digest.to_string()),
+            ..RunError::from_error(Some(repository), error)
+        });
+    }
+
+    // Record where the report has been written
+    pub fn add_output(&mut self, kind: &str, format: &str, location: Option<&str>) {
+        self.outputs.push(OutputLocation {
+            kind: kind.to_string(),
+            format: format.to_string(),
+            location: location.map(|l| l.to_string()),
+        });
+    }
+
+    // Mark the run as finished and compute its duration
+    pub fn finish(&mut self, exit_code: u8) {
+        let end = SystemTime::now();
+        self.finished_at = Some(format_time(end));
+        self.duration_seconds = Some(
+            end.duration_since(self.start)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or_default(),
+        );
+        self.exit_code = Some(exit_code);
+        self.api_calls = api_calls();
+        self.status = if self.errors.is_empty() {
+            RunStatus::Succeeded
+        } else {
+            RunStatus::Failed
+        };
+    }
+
+    // Write the manifest as pretty-printed JSON
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+}
+
+// Format a system time as an ISO-8601 date time
+fn format_time(time: SystemTime) -> String {
+    DateTime::from(time)
+        .fmt(DateTimeFormat::DateTime)
+        .unwrap_or_default()
+}
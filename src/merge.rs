@@ -0,0 +1,81 @@
+use crate::report::{ImageRecord, JsonReport, ReportMetadata};
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use indexmap::IndexMap;
+
+// Function to combine JSON reports into one, keeping a single record per
+// (account, region, repository, digest): the one with the newest scan
+pub fn merge_reports(reports: Vec<(String, JsonReport)>) -> Result<JsonReport, String> {
+    let mut merged = JsonReport::default();
+    let mut images: IndexMap<(String, String, String, String), ImageRecord> = IndexMap::new();
+    for (index, (path, report)) in reports.into_iter().enumerate() {
+        // Counts can only be merged when the columns are the same
+        if index == 0 {
+            merged.severity_levels = report.severity_levels.clone();
+        } else if report.severity_levels != merged.severity_levels {
+            return Err(format!(
+                "'{}' uses severity levels {:?}, expected {:?}",
+                path, report.severity_levels, merged.severity_levels
+            ));
+        }
+        // The merged report describes the latest run, with the accounts and
+        // regions of every run and the run identifiers they share
+        if let Some(metadata) = report.metadata {
+            merged.metadata = Some(match merged.metadata.take() {
+                Some(previous) => merge_metadata(previous, metadata),
+                None => metadata,
+            });
+        }
+        if !report.packages.is_empty() || !report.ecosystems.is_empty() || report.stats.is_some() {
+            eprintln!(
+                "Warning: '{}' has package or ecosystem groups or statistics, only image records are merged",
+                path
+            );
+        }
+        for image in report.images {
+            let key = (
+                image.account.clone(),
+                image.region.clone(),
+                image.repository_name.clone(),
+                image.image_digest.clone(),
+            );
+            match images.get(&key) {
+                // A later file wins on a tie, as it is usually the latest snapshot
+                Some(existing) if scan_date(existing) > scan_date(&image) => {}
+                _ => {
+                    images.insert(key, image);
+                }
+            }
+        }
+    }
+    merged.images = images.into_values().collect();
+    Ok(merged)
+}
+
+fn merge_metadata(previous: ReportMetadata, mut metadata: ReportMetadata) -> ReportMetadata {
+    for account in previous.accounts.into_iter().rev() {
+        if !metadata.accounts.contains(&account) {
+            metadata.accounts.insert(0, account);
+        }
+    }
+    for region in previous.regions.into_iter().rev() {
+        if !metadata.regions.contains(&region) {
+            metadata.regions.insert(0, region);
+        }
+    }
+    if previous.run_id != metadata.run_id {
+        metadata.run_id = None;
+    }
+    if previous.correlation_id != metadata.correlation_id {
+        metadata.correlation_id = None;
+    }
+    metadata
+}
+
+// Scan completion date of a record, images never scanned come first
+fn scan_date(image: &ImageRecord) -> Option<i128> {
+    image
+        .image_scan_completed_at
+        .as_deref()
+        .and_then(|date| DateTime::from_str(date, DateTimeFormat::DateTime).ok())
+        .map(|date| date.as_nanos())
+}
@@ -0,0 +1,54 @@
+use crate::telemetry::cloudwatch_client;
+use aws_config::SdkConfig;
+use aws_sdk_cloudwatch::primitives::DateTime;
+use aws_sdk_cloudwatch::types::{Dimension, MetricDatum, StandardUnit};
+use std::error::Error;
+use std::time::SystemTime;
+
+// Name of the published metric, one per repository and severity level
+const METRIC_NAME: &str = "Findings";
+
+// Metrics sent per PutMetricData request at most
+const MAX_METRICS_PER_REQUEST: usize = 1000;
+
+// Function to publish the finding count of each repository and severity level
+// as CloudWatch custom metrics, with Repository and Severity dimensions
+pub async fn publish_metrics(
+    sdk_config: &SdkConfig,
+    namespace: &str,
+    levels: &[String],
+    repository_counts: &[(String, Vec<i64>)],
+) -> Result<usize, Box<dyn Error>> {
+    let client = cloudwatch_client(sdk_config);
+    // Every data point of the run has the same timestamp
+    let timestamp = DateTime::from(SystemTime::now());
+    let mut metrics = Vec::new();
+    for (repository_name, counts) in repository_counts {
+        for (level, count) in levels.iter().zip(counts) {
+            metrics.push(
+                MetricDatum::builder()
+                    .metric_name(METRIC_NAME)
+                    .dimensions(dimension("Repository", repository_name))
+                    .dimensions(dimension("Severity", level))
+                    .timestamp(timestamp)
+                    .value(*count as f64)
+                    .unit(StandardUnit::Count)
+                    .build(),
+            );
+        }
+    }
+    for chunk in metrics.chunks(MAX_METRICS_PER_REQUEST) {
+        client
+            .put_metric_data()
+            .namespace(namespace)
+            .set_metric_data(Some(chunk.to_vec()))
+            .send()
+            .await
+            .map_err(aws_sdk_cloudwatch::Error::from)?;
+    }
+    Ok(metrics.len())
+}
+
+fn dimension(name: &str, value: &str) -> Dimension {
+    Dimension::builder().name(name).value(value).build()
+}
@@ -0,0 +1,229 @@
+use crate::runid::RunIds;
+use crate::spill::temporary_path;
+use crate::telemetry::s3_client;
+use aws_config::SdkConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Compressions available with --compress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!(
+                "unknown compression '{}', expected 'gzip' or 'zstd'",
+                value
+            )),
+        }
+    }
+
+    // Content-Encoding of the compressed S3 objects
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+// Destination of the report given with --output-file, stdout if not set
+pub struct ReportOutput {
+    s3_location: Option<(String, String)>, // Bucket and key of the uploaded report
+    compression: Option<Compression>,
+    temporary: Option<PathBuf>, // File buffering the report before its upload
+    digest: bool,               // Return the SHA-256 digest of the report file once complete
+    stream: Rc<RefCell<Option<Stream>>>,
+}
+
+// Handle writing to the report destination, given to the report writer
+pub struct OutputWriter(Rc<RefCell<Option<Stream>>>);
+
+// Report stream, compressed on the fly into a file when asked to
+enum Stream {
+    Stdout(io::Stdout),
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl ReportOutput {
+    // Open the destination: a local file, or a temporary file uploaded to an
+    // s3://bucket/key location once the report is complete
+    pub fn create(destination: Option<&str>, compression: Option<Compression>) -> io::Result<Self> {
+        let mut s3_location = None;
+        let mut temporary = None;
+        let stream = match destination {
+            None => Stream::Stdout(io::stdout()),
+            Some(destination) => {
+                let file = match destination.strip_prefix("s3://") {
+                    Some(location) => {
+                        let (bucket, key) = location.split_once('/').unwrap_or((location, ""));
+                        if bucket.is_empty() || key.is_empty() {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("'{}' is not an s3://bucket/key location", destination),
+                            ));
+                        }
+                        s3_location = Some((bucket.to_string(), key.to_string()));
+                        let path = temporary_path("report");
+                        let file = temporary_file(&path)?;
+                        temporary = Some(path);
+                        file
+                    }
+                    // Read back for its digest once complete
+                    None => OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(destination)?,
+                };
+                let file = BufWriter::new(file);
+                match compression {
+                    None => Stream::Plain(file),
+                    Some(Compression::Gzip) => {
+                        Stream::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+                    }
+                    Some(Compression::Zstd) => Stream::Zstd(zstd::Encoder::new(file, 0)?),
+                }
+            }
+        };
+        Ok(ReportOutput {
+            s3_location,
+            compression,
+            temporary,
+            digest: false,
+            stream: Rc::new(RefCell::new(Some(stream))),
+        })
+    }
+
+    // Get a handle to write the report
+    pub fn writer(&self) -> OutputWriter {
+        OutputWriter(Rc::clone(&self.stream))
+    }
+
+    // Compute the SHA-256 digest of the report file, as stored, when complete
+    pub fn keep_digest(&mut self) {
+        self.digest = true;
+    }
+
+    // Kind of destination, for the run manifest
+    pub fn kind(&self) -> &'static str {
+        match &*self.stream.borrow() {
+            Some(Stream::Stdout(_)) => "stdout",
+            _ if self.s3_location.is_some() => "s3",
+            _ => "file",
+        }
+    }
+
+    // Complete the compressed stream, then upload the report when its
+    // destination is S3 and upload is true. Returns the digest of the report
+    // file with keep_digest
+    pub async fn finish(
+        self,
+        sdk_config: &SdkConfig,
+        content_type: &str,
+        upload: bool,
+        ids: &RunIds,
+    ) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let Some(stream) = self.stream.borrow_mut().take() else {
+            return Ok(None);
+        };
+        let file = match stream {
+            Stream::Stdout(mut stdout) => {
+                stdout.flush()?;
+                return Ok(None);
+            }
+            Stream::Plain(file) => file,
+            Stream::Gzip(encoder) => encoder.finish()?,
+            Stream::Zstd(encoder) => encoder.finish()?,
+        };
+        let mut file = file.into_inner().map_err(|e| e.into_error())?;
+        let digest = match self.digest {
+            true => {
+                file.seek(SeekFrom::Start(0))?;
+                let mut hasher = Sha256::new();
+                io::copy(&mut file, &mut hasher)?;
+                Some(hasher.finalize().to_vec())
+            }
+            false => None,
+        };
+        let Some((bucket, key)) = self.s3_location.as_ref().filter(|_| upload) else {
+            return Ok(digest);
+        };
+        file.seek(SeekFrom::Start(0))?;
+        let body = ByteStream::read_from()
+            .file(tokio::fs::File::from_std(file))
+            .build()
+            .await?;
+        s3_client(sdk_config)
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(body)
+            .content_type(content_type)
+            .set_metadata(Some(ids.object_metadata()))
+            .set_content_encoding(self.compression.map(|c| c.as_str().to_string()))
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+        Ok(digest)
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *self.0.borrow_mut() {
+            Some(Stream::Stdout(out)) => out.write(buf),
+            Some(Stream::Plain(out)) => out.write(buf),
+            Some(Stream::Gzip(out)) => out.write(buf),
+            Some(Stream::Zstd(out)) => out.write(buf),
+            None => Err(io::Error::other("report output already closed")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.0.borrow_mut() {
+            Some(Stream::Stdout(out)) => out.flush(),
+            Some(Stream::Plain(out)) => out.flush(),
+            Some(Stream::Gzip(out)) => out.flush(),
+            Some(Stream::Zstd(out)) => out.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ReportOutput {
+    fn drop(&mut self) {
+        if let Some(path) = self.temporary.as_ref().filter(|_| cfg!(not(unix))) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// Create the file buffering a report before its upload, unlinked right away on
+// Unix so that nothing is left behind, removed on drop elsewhere
+pub fn temporary_file(path: &Path) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    #[cfg(unix)]
+    std::fs::remove_file(path)?;
+    Ok(file)
+}
@@ -0,0 +1,126 @@
+// AWS partition of a region, each with its own domain, credentials and
+// endpoints, e.g. us-gov-west-1 in aws-us-gov or cn-north-1 in aws-cn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    Aws,
+    AwsCn,
+    AwsUsGov,
+}
+
+impl Partition {
+    pub fn from_region(region: &str) -> Self {
+        if region.starts_with("cn-") {
+            Partition::AwsCn
+        } else if region.starts_with("us-gov-") {
+            Partition::AwsUsGov
+        } else {
+            Partition::Aws
+        }
+    }
+
+    // Name of the partition, as in the ARNs of its resources
+    pub fn name(&self) -> &'static str {
+        match self {
+            Partition::Aws => "aws",
+            Partition::AwsCn => "aws-cn",
+            Partition::AwsUsGov => "aws-us-gov",
+        }
+    }
+
+    // Domain of the service endpoints and registry hosts, GovCloud regions
+    // sharing the one of the commercial regions
+    pub fn dns_suffix(&self) -> &'static str {
+        match self {
+            Partition::AwsCn => "amazonaws.com.cn",
+            Partition::Aws | Partition::AwsUsGov => "amazonaws.com",
+        }
+    }
+}
+
+// Check the name of a region given with --region, e.g. us-gov-west-1
+pub fn check_region(region: &str) -> Result<(), String> {
+    let parts: Vec<&str> = region.split('-').collect();
+    let valid = parts.len() >= 3
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+        && parts
+            .last()
+            .is_some_and(|part| part.chars().all(|c| c.is_ascii_digit()));
+    match valid {
+        true => Ok(()),
+        false => Err(format!(
+            "'{}' is not a region name, e.g. eu-west-1, us-gov-west-1 or cn-north-1",
+            region
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pull::registry_host;
+
+    #[test]
+    fn partition_of_region() {
+        assert_eq!(Partition::from_region("us-gov-west-1"), Partition::AwsUsGov);
+        assert_eq!(Partition::from_region("cn-north-1"), Partition::AwsCn);
+        assert_eq!(Partition::from_region("eu-west-1"), Partition::Aws);
+        assert_eq!(Partition::from_region("us-gov-west-1").name(), "aws-us-gov");
+        assert_eq!(Partition::from_region("cn-north-1").name(), "aws-cn");
+        assert_eq!(Partition::from_region("eu-west-1").name(), "aws");
+    }
+
+    #[test]
+    fn dns_suffix_of_partition() {
+        assert_eq!(
+            Partition::from_region("us-gov-west-1").dns_suffix(),
+            "amazonaws.com"
+        );
+        assert_eq!(
+            Partition::from_region("cn-north-1").dns_suffix(),
+            "amazonaws.com.cn"
+        );
+        assert_eq!(
+            Partition::from_region("eu-west-1").dns_suffix(),
+            "amazonaws.com"
+        );
+    }
+
+    #[test]
+    fn accepted_regions() {
+        for region in ["eu-west-1", "us-gov-west-1", "cn-north-1", "ap-southeast-5"] {
+            assert!(check_region(region).is_ok(), "{} rejected", region);
+        }
+    }
+
+    #[test]
+    fn rejected_regions() {
+        for region in [
+            "",
+            "eu-west",
+            "EU-WEST-1",
+            "eu_west_1",
+            "eu--1",
+            "eu-west-1a",
+            "eu-west-1 ",
+        ] {
+            assert!(check_region(region).is_err(), "{} accepted", region);
+        }
+    }
+
+    #[test]
+    fn registry_host_in_china() {
+        assert_eq!(
+            registry_host("123456789012", "cn-north-1"),
+            "123456789012.dkr.ecr.cn-north-1.amazonaws.com.cn"
+        );
+        assert_eq!(
+            registry_host("123456789012", "cn-northwest-1"),
+            "123456789012.dkr.ecr.cn-northwest-1.amazonaws.com.cn"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use aws_sdk_ecr::types::ImageIdentifier;
+
+// Digests of the moving tags of a repository given with --pin-tags, e.g.
+// latest or stable, resolved before its images are listed. The report gives
+// these tags to the digests they pointed at then, even if they are pushed
+// again while the run lists the images
+#[derive(Debug, Clone, Default)]
+pub struct TagPins {
+    repository_name: String,
+    // Pinned digest of each tag, None when the tag does not exist
+    pins: Vec<(String, Option<String>)>,
+}
+
+// Tag pushed again to another digest during the run
+pub struct MovedTag {
+    pub tag: String,
+    pub pinned: Option<String>,
+    pub current: Option<String>,
+}
+
+impl TagPins {
+    // Function to resolve the given tags of a repository to their digests
+    pub async fn resolve(
+        client: &aws_sdk_ecr::Client,
+        repository_name: &str,
+        tags: &[String],
+    ) -> Result<Self, aws_sdk_ecr::Error> {
+        let mut pins = Vec::new();
+        for tag in tags {
+            pins.push((tag.clone(), tag_digest(client, repository_name, tag).await?));
+        }
+        Ok(TagPins {
+            repository_name: repository_name.to_string(),
+            pins,
+        })
+    }
+
+    // Give the pinned tags to the digests they were resolved to, and only to
+    // them, returning true when the listed tags of the image were changed
+    pub fn apply(&self, image_digest: &str, image_tags: &mut Vec<String>) -> bool {
+        let mut changed = false;
+        for (tag, pinned) in &self.pins {
+            let listed = image_tags.contains(tag);
+            match pinned.as_deref() == Some(image_digest) {
+                true if !listed => image_tags.push(tag.clone()),
+                false if listed => image_tags.retain(|t| t != tag),
+                _ => continue,
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    // Function to resolve the tags again once the images are listed, giving
+    // the tags that moved since they were pinned
+    pub async fn moved_tags(
+        &self,
+        client: &aws_sdk_ecr::Client,
+    ) -> Result<Vec<MovedTag>, aws_sdk_ecr::Error> {
+        let mut moved = Vec::new();
+        for (tag, pinned) in &self.pins {
+            let current = tag_digest(client, &self.repository_name, tag).await?;
+            if current != *pinned {
+                moved.push(MovedTag {
+                    tag: tag.clone(),
+                    pinned: pinned.clone(),
+                    current,
+                });
+            }
+        }
+        Ok(moved)
+    }
+}
+
+// Digest of a tag of a repository, None when no image has the tag
+async fn tag_digest(
+    client: &aws_sdk_ecr::Client,
+    repository_name: &str,
+    tag: &str,
+) -> Result<Option<String>, aws_sdk_ecr::Error> {
+    let response = client
+        .describe_images()
+        .repository_name(repository_name)
+        .image_ids(ImageIdentifier::builder().image_tag(tag).build())
+        .send()
+        .await;
+    match response {
+        Ok(response) => Ok(response
+            .image_details()
+            .first()
+            .and_then(|image| image.image_digest())
+            .map(|digest| digest.to_string())),
+        Err(e)
+            if e.as_service_error()
+                .is_some_and(|error| error.is_image_not_found_exception()) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,125 @@
+use crate::partition::Partition;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// Tools available with --pull-tool to fetch the flagged images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullTool {
+    Docker,
+    Crane,
+}
+
+impl PullTool {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "docker" => Ok(PullTool::Docker),
+            "crane" => Ok(PullTool::Crane),
+            _ => Err(format!(
+                "unknown pull tool '{}', expected 'docker' or 'crane'",
+                value
+            )),
+        }
+    }
+}
+
+// Shell script given with --emit-pull-commands, one command per flagged image
+pub struct PullScript {
+    out: BufWriter<File>,
+    tool: PullTool,
+    region: String,
+    // Registries already logged in to, logged in once before their first image
+    registries: HashSet<String>,
+    commands: usize,
+}
+
+impl PullScript {
+    // Create the script, replacing any previous one
+    pub fn create(path: &str, tool: PullTool, region: &str) -> io::Result<Self> {
+        let file = File::create(path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o755))?;
+        }
+        let mut out = BufWriter::new(file);
+        writeln!(out, "#!/bin/sh")?;
+        writeln!(
+            out,
+            "# Images flagged by {}, pulled by digest",
+            env!("CARGO_PKG_NAME")
+        )?;
+        if tool == PullTool::Crane {
+            writeln!(out, "# Images are copied to the DESTINATION registry")?;
+            writeln!(
+                out,
+                ": \"${{DESTINATION:?set DESTINATION to the registry to copy the images to}}\""
+            )?;
+        }
+        writeln!(out, "set -e")?;
+        Ok(PullScript {
+            out,
+            tool,
+            region: region.to_string(),
+            registries: HashSet::new(),
+            commands: 0,
+        })
+    }
+
+    // Append the command fetching an image, after logging in to its registry
+    pub fn add(
+        &mut self,
+        registry_id: &str,
+        repository_name: &str,
+        image_digest: &str,
+        image_tags: &[String],
+    ) -> io::Result<()> {
+        let registry = registry_host(registry_id, &self.region);
+        if self.registries.insert(registry.clone()) {
+            let login = match self.tool {
+                PullTool::Docker => format!("docker login {} --username AWS", registry),
+                PullTool::Crane => format!("crane auth login {} --username AWS", registry),
+            };
+            writeln!(
+                self.out,
+                "aws ecr get-login-password --region {} | {} --password-stdin",
+                self.region, login
+            )?;
+        }
+        let image = format!("{}/{}@{}", registry, repository_name, image_digest);
+        let command = match self.tool {
+            PullTool::Docker => format!("docker pull {}", image),
+            PullTool::Crane => format!(
+                "crane copy {} \"$DESTINATION/{}@{}\"",
+                image, repository_name, image_digest
+            ),
+        };
+        if image_tags.is_empty() {
+            writeln!(self.out, "{}", command)?;
+        } else {
+            writeln!(self.out, "{} # {}", command, image_tags.join(","))?;
+        }
+        self.commands += 1;
+        Ok(())
+    }
+
+    // Number of images in the script
+    pub fn commands(&self) -> usize {
+        self.commands
+    }
+
+    // Write the buffered commands to the script
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// Host name of the ECR registry of an account in a region
+pub fn registry_host(registry_id: &str, region: &str) -> String {
+    format!(
+        "{}.dkr.ecr.{}.{}",
+        registry_id,
+        region,
+        Partition::from_region(region).dns_suffix()
+    )
+}
@@ -0,0 +1,28 @@
+use jmespath::Expression;
+use serde_json::Value;
+
+// JMESPath expression given with --query, applied to the JSON document of a
+// report before it is written, as with the --query option of the AWS CLI.
+// Expressions and functions are those of the JMESPath specification
+#[derive(Debug, Clone)]
+pub struct Query {
+    expression: Expression<'static>,
+}
+
+impl Query {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        jmespath::compile(expression)
+            .map(|expression| Query { expression })
+            .map_err(|e| format!("invalid expression '{}': {}", expression, e))
+    }
+
+    // Result of the expression on a document, errors are the type and arity
+    // errors of the functions
+    pub fn search(&self, document: &Value) -> Result<Value, String> {
+        let result = self
+            .expression
+            .search(document)
+            .map_err(|e| format!("error evaluating '{}': {}", self.expression, e))?;
+        serde_json::to_value(&*result).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,71 @@
+use crate::runid::RunIds;
+use crate::telemetry::s3_client;
+use aws_config::SdkConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use serde::Serialize;
+use std::error::Error;
+
+// QuickSight manifest of an S3 data source, see "Supported formats for Amazon S3
+// manifest files" in the QuickSight documentation
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest<'a> {
+    file_locations: Vec<FileLocation<'a>>,
+    global_upload_settings: UploadSettings,
+}
+
+#[derive(Serialize)]
+struct FileLocation<'a> {
+    #[serde(rename = "URIs")]
+    uris: Vec<&'a str>,
+}
+
+// Settings of the CSV report, QuickSight expects strings for every value
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadSettings {
+    format: &'static str,
+    delimiter: &'static str,
+    contains_header: &'static str,
+}
+
+// Function to write the QuickSight manifest of an uploaded CSV report to a
+// file or an s3://bucket/key location
+pub async fn write_quicksight_manifest(
+    sdk_config: &SdkConfig,
+    destination: &str,
+    report_location: &str,
+    ids: &RunIds,
+) -> Result<(), Box<dyn Error>> {
+    let manifest = Manifest {
+        file_locations: vec![FileLocation {
+            uris: vec![report_location],
+        }],
+        global_upload_settings: UploadSettings {
+            format: "CSV",
+            delimiter: ";",
+            contains_header: "true",
+        },
+    };
+    let content = serde_json::to_vec_pretty(&manifest)?;
+    match destination.strip_prefix("s3://") {
+        Some(location) => {
+            let (bucket, key) = location.split_once('/').unwrap_or((location, ""));
+            if bucket.is_empty() || key.is_empty() {
+                return Err(format!("'{}' is not an s3://bucket/key location", destination).into());
+            }
+            s3_client(sdk_config)
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(ByteStream::from(content))
+                .content_type("application/json")
+                .set_metadata(Some(ids.object_metadata()))
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+        }
+        None => std::fs::write(destination, content)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use crate::report::ImageReport;
+use sha2::{Digest, Sha256};
+
+// Copy of an image report line safe to share outside the organization, with
+// the repository name hashed and the account removed
+pub fn redact_image(image: &ImageReport) -> ImageReport {
+    ImageReport {
+        registry_id: String::new(),
+        repository_name: redact_name(&image.repository_name),
+        ..image.clone()
+    }
+}
+
+// Stable pseudonym of a repository, the same name giving the same pseudonym
+// across runs so that shared reports can be compared
+fn redact_name(repository_name: &str) -> String {
+    let digest = Sha256::digest(repository_name.as_bytes());
+    let hex: String = digest
+        .iter()
+        .take(6)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("repo-{}", hex)
+}
@@ -0,0 +1,205 @@
+use crate::dates;
+use crate::labels::Labels;
+use crate::render::write_document;
+use crate::report::{OutputFormat, ReportWriter};
+use crate::runid::RunIds;
+use aws_sdk_ecr::primitives::{Blob, DateTimeFormat};
+use aws_sdk_ecr::types::{ImageIdentifier, LayerAvailability};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::io;
+
+// Media type of the manifests pushed, OCI 1.1 artifacts with a subject
+const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+
+// Empty config of the artifact manifests, whose type is given by artifactType
+const EMPTY_CONFIG: &str = "application/vnd.oci.empty.v1+json";
+
+// Artifact types of the attached reports
+const REPORT_ARTIFACT: &str = "application/vnd.aws-ecr-scan-detail.report.v1+json";
+const IN_TOTO_ARTIFACT: &str = "application/vnd.in-toto+json";
+
+// Media types of the image manifests accepted as subject
+const SUBJECT_MEDIA_TYPES: [&str; 4] = [
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+
+// Report attached to an image: the in-toto statement with --output in-toto,
+// the JSON report otherwise
+pub struct ReportArtifact {
+    pub artifact_type: &'static str,
+    pub file_name: &'static str,
+    pub content: Vec<u8>,
+}
+
+impl ReportArtifact {
+    pub fn from_writer(writer: &ReportWriter, labels: &Labels) -> io::Result<Self> {
+        if let Some(statement) = writer.statement() {
+            return Ok(ReportArtifact {
+                artifact_type: IN_TOTO_ARTIFACT,
+                file_name: "scan.intoto.json",
+                content: serde_json::to_vec_pretty(statement)?,
+            });
+        }
+        let mut content = Vec::new();
+        write_document(
+            writer.document(),
+            writer.images(),
+            OutputFormat::Json,
+            labels,
+            &mut content,
+        )?;
+        Ok(ReportArtifact {
+            artifact_type: REPORT_ARTIFACT,
+            file_name: "report.json",
+            content,
+        })
+    }
+}
+
+// Function to push the report as an OCI artifact whose subject is the image,
+// so that registries list it among the referrers of the image digest. The
+// report and the empty config are uploaded as layers, then the artifact
+// manifest is put untagged. Returns the digest of the artifact manifest
+pub async fn attach_report(
+    client: &aws_sdk_ecr::Client,
+    repository_name: &str,
+    image_digest: &str,
+    artifact: &ReportArtifact,
+    ids: &RunIds,
+) -> Result<String, Box<dyn Error>> {
+    // The subject names the image manifest with its media type and size
+    let images = client
+        .batch_get_image()
+        .repository_name(repository_name)
+        .image_ids(
+            ImageIdentifier::builder()
+                .image_digest(image_digest)
+                .build(),
+        )
+        .set_accepted_media_types(Some(
+            SUBJECT_MEDIA_TYPES.iter().map(|t| t.to_string()).collect(),
+        ))
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    let Some(image) = images.images().first() else {
+        return Err(format!("image {} not found in {}", image_digest, repository_name).into());
+    };
+    let subject_manifest = image.image_manifest().unwrap_or_default();
+    let subject_media_type = image
+        .image_manifest_media_type()
+        .unwrap_or(OCI_MANIFEST)
+        .to_string();
+
+    let config = upload_blob(client, repository_name, b"{}").await?;
+    let layer = upload_blob(client, repository_name, &artifact.content).await?;
+    // Annotations of the artifact, with the identifiers of the run
+    let mut annotations = ids.object_metadata();
+    annotations.insert(
+        "org.opencontainers.image.created".to_string(),
+        dates::now().fmt(DateTimeFormat::DateTime)?,
+    );
+    let manifest = json!({
+        "schemaVersion": 2,
+        "mediaType": OCI_MANIFEST,
+        "artifactType": artifact.artifact_type,
+        "config": {
+            "mediaType": EMPTY_CONFIG,
+            "digest": config,
+            "size": 2,
+        },
+        "layers": [{
+            "mediaType": artifact.artifact_type,
+            "digest": layer,
+            "size": artifact.content.len(),
+            "annotations": { "org.opencontainers.image.title": artifact.file_name },
+        }],
+        "subject": {
+            "mediaType": subject_media_type,
+            "digest": image_digest,
+            "size": subject_manifest.len(),
+        },
+        "annotations": annotations,
+    })
+    .to_string();
+    let digest = sha256_digest(manifest.as_bytes());
+    client
+        .put_image()
+        .repository_name(repository_name)
+        .image_manifest(manifest)
+        .image_manifest_media_type(OCI_MANIFEST)
+        .image_digest(&digest)
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    Ok(digest)
+}
+
+// Function to upload a blob to the repository in parts of the size ECR
+// asks for, unless the repository already has it. Returns its digest
+async fn upload_blob(
+    client: &aws_sdk_ecr::Client,
+    repository_name: &str,
+    content: &[u8],
+) -> Result<String, Box<dyn Error>> {
+    let digest = sha256_digest(content);
+    let availability = client
+        .batch_check_layer_availability()
+        .repository_name(repository_name)
+        .layer_digests(&digest)
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    let available = availability
+        .layers()
+        .iter()
+        .any(|layer| layer.layer_availability() == Some(&LayerAvailability::Available));
+    if available {
+        return Ok(digest);
+    }
+
+    let upload = client
+        .initiate_layer_upload()
+        .repository_name(repository_name)
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    let upload_id = upload.upload_id().unwrap_or_default();
+    let part_size = upload.part_size().unwrap_or(content.len() as i64).max(1) as usize;
+    let mut first_byte = 0;
+    for part in content.chunks(part_size) {
+        client
+            .upload_layer_part()
+            .repository_name(repository_name)
+            .upload_id(upload_id)
+            .part_first_byte(first_byte as i64)
+            .part_last_byte((first_byte + part.len() - 1) as i64)
+            .layer_part_blob(Blob::new(part))
+            .send()
+            .await
+            .map_err(aws_sdk_ecr::Error::from)?;
+        first_byte += part.len();
+    }
+    client
+        .complete_layer_upload()
+        .repository_name(repository_name)
+        .upload_id(upload_id)
+        .layer_digests(&digest)
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    Ok(digest)
+}
+
+fn sha256_digest(content: &[u8]) -> String {
+    let hex: String = Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256:{}", hex)
+}
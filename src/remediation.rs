@@ -0,0 +1,71 @@
+use crate::findings::{Finding, Package};
+
+// Package manager of the packages installed by the base image distribution
+const OS_PACKAGE_MANAGER: &str = "OS";
+
+// Fixed version given by Inspector when no fix is released yet
+const NO_FIX: &str = "NotAvailable";
+
+// Best-effort fix of a finding, e.g. "upgrade debian:11 base" for distribution
+// packages or "bump lodash to 4.17.21" for application dependencies, None when
+// no affected package has a fixed version
+pub fn remediation_hint(finding: &Finding) -> Option<String> {
+    let mut hints: Vec<String> = Vec::new();
+    for hint in finding
+        .packages
+        .iter()
+        .filter_map(|package| package_hint(package, finding.platform.as_deref()))
+    {
+        if !hints.contains(&hint) {
+            hints.push(hint);
+        }
+    }
+    Some(hints.join("; ")).filter(|h| !h.is_empty())
+}
+
+// Fix of one affected package, distribution packages being fixed by a newer
+// base image when the image platform is known
+pub fn package_hint(package: &Package, platform: Option<&str>) -> Option<String> {
+    let fixed_in_version = fixed_version(package)?;
+    match (
+        package.package_manager.as_deref(),
+        platform.and_then(base_image),
+    ) {
+        (Some(OS_PACKAGE_MANAGER), Some(base)) => Some(format!("upgrade {} base", base)),
+        _ => Some(format!("bump {} to {}", package.name, fixed_in_version)),
+    }
+}
+
+// Check a fixed version is released for one of the packages affected by a
+// finding, never the case of basic scan findings which have no fixed version
+pub fn is_fixable(finding: &Finding) -> bool {
+    finding.packages.iter().any(|p| fixed_version(p).is_some())
+}
+
+// First version fixing a package, None when no fix is released yet
+fn fixed_version(package: &Package) -> Option<&str> {
+    package
+        .fixed_in_version
+        .as_deref()
+        .filter(|v| !v.is_empty() && *v != NO_FIX)
+}
+
+// Base image of an Inspector platform, e.g. DEBIAN_11 as debian:11,
+// UBUNTU_22_04 as ubuntu:22.04 or ALPINE_LINUX_3_18 as alpine:3.18
+fn base_image(platform: &str) -> Option<String> {
+    let parts: Vec<&str> = platform.split('_').collect();
+    let split = parts
+        .iter()
+        .position(|part| part.starts_with(|c: char| c.is_ascii_digit()))?;
+    let (distribution, version) = parts.split_at(split);
+    let distribution = match distribution.join("").to_lowercase().as_str() {
+        "" => return None,
+        "alpinelinux" => "alpine".to_string(),
+        name => name.to_string(),
+    };
+    Some(format!(
+        "{}:{}",
+        distribution,
+        version.join(".").to_lowercase()
+    ))
+}
@@ -0,0 +1,781 @@
+use crate::heatmap::{write_ansi_heatmap, write_html_heatmap};
+use crate::labels::Labels;
+use crate::query::Query;
+use crate::report::{ImageRecord, JsonReport, OutputFormat, REPORT_SCHEMA_VERSION};
+use crate::spill::ImageSource;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::{self, Write};
+
+// Rows of a report section, rendered as a table in human-readable formats
+struct Table<'a> {
+    title: String,
+    headers: Vec<String>,
+    rows: Rows<'a>,
+}
+
+// Rows of a table, the image rows are built one at a time from their source
+enum Rows<'a> {
+    Images(ImageColumns, &'a dyn ImageSource),
+    Lines(Vec<Vec<String>>),
+}
+
+// Columns of the image rows
+struct ImageColumns {
+    levels: Vec<String>,
+    with_sla: bool,
+    with_ages: bool,
+    with_tag_count: bool,
+    with_vulnerable_since_push: bool,
+    with_environment: bool,
+    with_upstream: bool,
+    with_workloads: bool,
+    tag_keys: Vec<String>,
+}
+
+// Function to load a JSON report written with --output json
+pub fn load_report(path: &str) -> Result<JsonReport, Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)?;
+    let report: JsonReport = serde_json::from_str(&content)?;
+    // Later layouts may have changed the meaning of the fields
+    if report.schema_version > REPORT_SCHEMA_VERSION {
+        return Err(format!(
+            "report schema version {} is not supported, this version reads up to {}",
+            report.schema_version, REPORT_SCHEMA_VERSION
+        )
+        .into());
+    }
+    Ok(report)
+}
+
+// Write a report document in the given format, with the image records of
+// images (those of the report itself, or spilled to disk during a run) and the
+// headers of the text formats in labels
+pub fn write_document(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    format: OutputFormat,
+    labels: &Labels,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => write_json(report, images, out),
+        OutputFormat::Sarif => write_sarif(report, images, out),
+        OutputFormat::Csv => write_csv(report, images, labels, out),
+        OutputFormat::Table => write_table(report, images, labels, out),
+        OutputFormat::Markdown => write_markdown(report, images, labels, out),
+        OutputFormat::Html => write_html(report, images, labels, out),
+        OutputFormat::Heatmap => write_ansi_heatmap(report, images, labels, out),
+        OutputFormat::HeatmapHtml => write_html_heatmap(report, images, labels, out),
+        // Reports keep finding counts only, the findings come from a live run
+        OutputFormat::DefectDojo => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "defectdojo output needs the findings of a live run, JSON reports only keep their counts",
+        )),
+        OutputFormat::InToto => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "in-toto output needs the findings of a live run, JSON reports only keep their counts",
+        )),
+        // Parquet files are written to --out-dir, not to a stream
+        OutputFormat::Parquet => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "parquet output is written to --out-dir during a run",
+        )),
+    }
+}
+
+// Function to write the result of a --query expression on the JSON document
+// of a report, the document is built in memory to be searched
+pub fn write_queried(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    query: &Query,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut document = Vec::new();
+    write_json(report, images, &mut document)?;
+    let document = serde_json::from_slice(&document)?;
+    let result = query
+        .search(&document)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    serde_json::to_writer_pretty(&mut *out, &result)?;
+    writeln!(out)
+}
+
+// Pretty JSON with the image records written one at a time, byte for byte
+// what serde_json writes for the whole document
+fn write_json(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    write!(
+        out,
+        "{{\n  \"schema_version\": {},\n  \"severity_levels\": {}",
+        REPORT_SCHEMA_VERSION,
+        pretty(&report.severity_levels, 1)?
+    )?;
+    if let Some(metadata) = &report.metadata {
+        write!(out, ",\n  \"metadata\": {}", pretty(metadata, 1)?)?;
+    }
+    if !images.is_empty() {
+        write!(out, ",\n  \"images\": ")?;
+        write_pretty_array(out, images, 1, &|image| Ok(vec![pretty(image, 2)?]))?;
+    }
+    if !report.packages.is_empty() {
+        write!(out, ",\n  \"packages\": {}", pretty(&report.packages, 1)?)?;
+    }
+    if !report.ecosystems.is_empty() {
+        write!(
+            out,
+            ",\n  \"ecosystems\": {}",
+            pretty(&report.ecosystems, 1)?
+        )?;
+    }
+    if let Some(stats) = &report.stats {
+        write!(out, ",\n  \"stats\": {}", pretty(stats, 1)?)?;
+    }
+    writeln!(out, "\n}}")
+}
+
+// Same layout as the CSV written during a run
+fn write_csv(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    labels: &Labels,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    if let Some(stats) = &report.stats {
+        return stats.write_text(out);
+    }
+    for table in tables(report, images, labels)? {
+        writeln!(out, "{}", table.headers.join(";"))?;
+        table
+            .rows
+            .try_for_each(&mut |row| writeln!(out, "{}", row.join(";")))?;
+    }
+    Ok(())
+}
+
+// Aligned text columns, for terminals
+fn write_table(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    labels: &Labels,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let metadata = metadata_lines(report, labels);
+    if !metadata.is_empty() {
+        let width = metadata
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max();
+        for (label, value) in &metadata {
+            let label = format!("{}:", label);
+            writeln!(
+                out,
+                "{:<width$} {}",
+                label,
+                value,
+                width = width.unwrap_or(0) + 1
+            )?;
+        }
+        writeln!(out)?;
+    }
+    if let Some(stats) = &report.stats {
+        return stats.write_text(out);
+    }
+    for table in tables(report, images, labels)? {
+        // A first pass over the rows gets the column widths
+        let mut widths: Vec<usize> = table.headers.iter().map(|h| h.chars().count()).collect();
+        table.rows.try_for_each(&mut |row| {
+            for (width, cell) in widths.iter_mut().zip(&row) {
+                *width = (*width).max(cell.chars().count());
+            }
+            Ok(())
+        })?;
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+        writeln!(out, "{}", line(&table.headers))?;
+        let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        writeln!(out, "{}", line(&separator))?;
+        table
+            .rows
+            .try_for_each(&mut |row| writeln!(out, "{}", line(&row)))?;
+    }
+    Ok(())
+}
+
+// GitHub flavored markdown tables, for pull requests and wikis
+fn write_markdown(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    labels: &Labels,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let escape = |cell: &String| cell.replace('|', "\\|");
+    let metadata = metadata_lines(report, labels);
+    if !metadata.is_empty() {
+        writeln!(out, "## {}", labels.get("Run"))?;
+        writeln!(out)?;
+        for (label, value) in &metadata {
+            writeln!(out, "- **{}:** {}", label, value)?;
+        }
+        writeln!(out)?;
+    }
+    for table in tables(report, images, labels)? {
+        writeln!(out, "## {}", table.title)?;
+        writeln!(out)?;
+        let headers: Vec<String> = table.headers.iter().map(escape).collect();
+        writeln!(out, "| {} |", headers.join(" | "))?;
+        writeln!(out, "|{}", "---|".repeat(headers.len()))?;
+        table.rows.try_for_each(&mut |row| {
+            let cells: Vec<String> = row.iter().map(escape).collect();
+            writeln!(out, "| {} |", cells.join(" | "))
+        })?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+// Standalone HTML page, for mail and static hosting
+fn write_html(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    labels: &Labels,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(
+        out,
+        "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>",
+        escape_html(labels.get("ECR scan report"))
+    )?;
+    writeln!(
+        out,
+        "<style>table {{ border-collapse: collapse; }} th, td {{ border: 1px solid #ccc; padding: 2px 6px; }}</style>"
+    )?;
+    writeln!(out, "</head>\n<body>")?;
+    let metadata = metadata_lines(report, labels);
+    if !metadata.is_empty() {
+        writeln!(out, "<h2>{}</h2>", escape_html(labels.get("Run")))?;
+        writeln!(out, "<dl>")?;
+        for (label, value) in &metadata {
+            writeln!(
+                out,
+                "<dt>{}</dt><dd>{}</dd>",
+                escape_html(label),
+                escape_html(value)
+            )?;
+        }
+        writeln!(out, "</dl>")?;
+    }
+    for table in tables(report, images, labels)? {
+        writeln!(out, "<h2>{}</h2>", escape_html(&table.title))?;
+        writeln!(out, "<table>")?;
+        let headers: String = table
+            .headers
+            .iter()
+            .map(|h| format!("<th>{}</th>", escape_html(h)))
+            .collect();
+        writeln!(out, "<tr>{}</tr>", headers)?;
+        table.rows.try_for_each(&mut |row| {
+            let cells: String = row
+                .iter()
+                .map(|c| format!("<td>{}</td>", escape_html(c)))
+                .collect();
+            writeln!(out, "<tr>{}</tr>", cells)
+        })?;
+        writeln!(out, "</table>")?;
+    }
+    writeln!(out, "</body>\n</html>")
+}
+
+// Labels and values of the metadata block of the text formats, none for
+// reports written before the run was described
+fn metadata_lines(report: &JsonReport, labels: &Labels) -> Vec<(String, String)> {
+    let Some(metadata) = &report.metadata else {
+        return Vec::new();
+    };
+    let list = |values: &[String]| match values.is_empty() {
+        true => labels.get("none").to_string(),
+        false => values.join(", "),
+    };
+    let mut lines = vec![
+        ("Generated at", metadata.generated_at.clone()),
+        (
+            "Tool version",
+            format!("{} {}", env!("CARGO_PKG_NAME"), metadata.tool_version),
+        ),
+        ("Scan type", metadata.scan_type.clone().unwrap_or_default()),
+        ("Accounts", list(&metadata.accounts)),
+        ("Regions", list(&metadata.regions)),
+        // As written on the command line
+        (
+            "Filters",
+            match metadata.filters.is_empty() {
+                true => labels.get("none").to_string(),
+                false => metadata.filters.join(" "),
+            },
+        ),
+    ];
+    // Reports of older versions and merged reports have no run
+    if let Some(run_id) = &metadata.run_id {
+        lines.push(("Run ID", run_id.clone()));
+    }
+    if let Some(correlation_id) = &metadata.correlation_id {
+        lines.push(("Correlation ID", correlation_id.clone()));
+    }
+    lines
+        .into_iter()
+        .map(|(label, value)| (labels.get(label).to_string(), value))
+        .collect()
+}
+
+// Static Analysis Results Interchange Format 2.1.0, one result per image and
+// severity level with findings, for code scanning dashboards. The results are
+// written one image at a time, the keys sorted as serde_json does
+fn write_sarif(
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let rule_id = |level: &str| format!("ecr-findings-{}", level.to_lowercase());
+    // The two most severe levels are errors, the next one a warning
+    let sarif_level = |index: usize| match index {
+        0 | 1 => "error",
+        2 => "warning",
+        _ => "note",
+    };
+    let rules: Vec<_> = report
+        .severity_levels
+        .iter()
+        .map(|level| {
+            json!({
+                "id": rule_id(level),
+                "name": format!("{}Findings", level.replace(' ', "")),
+                "shortDescription": { "text": format!("{} vulnerability findings", level) },
+            })
+        })
+        .collect();
+    let image_results = |image: &ImageRecord| {
+        // The other tags of the digest are named too, e.g. latest is also v2.3.1
+        let name = match image.image_tags.split_first() {
+            Some((tag, [])) => format!("{}:{}", image.repository_name, tag),
+            Some((tag, others)) => format!(
+                "{}:{} (also tagged {})",
+                image.repository_name,
+                tag,
+                others.join(", ")
+            ),
+            None => format!("{}@{}", image.repository_name, image.image_digest),
+        };
+        // Redacted images have no account
+        let qualified_name = match image.account.as_str() {
+            "" => format!("{}@{}", image.repository_name, image.image_digest),
+            account => format!(
+                "{}/{}@{}",
+                account, image.repository_name, image.image_digest
+            ),
+        };
+        let mut results = Vec::new();
+        for (index, level) in report.severity_levels.iter().enumerate() {
+            let count = image
+                .severity_counts
+                .get(level)
+                .copied()
+                .unwrap_or_default();
+            if count == 0 {
+                continue;
+            }
+            let result = json!({
+                "ruleId": rule_id(level),
+                "ruleIndex": index,
+                "level": sarif_level(index),
+                "message": { "text": format!("{} {} finding(s) in image {}", count, level, name) },
+                "locations": [{
+                    "logicalLocations": [{
+                        "name": image.image_digest,
+                        "fullyQualifiedName": qualified_name,
+                        "kind": "image",
+                    }],
+                }],
+                "properties": {
+                    "account": image.account,
+                    "region": image.region,
+                    "repositoryName": image.repository_name,
+                    "imageTags": image.image_tags,
+                    "imageDigest": image.image_digest,
+                    "count": count,
+                },
+            });
+            results.push(pretty(&result, 4)?);
+        }
+        Ok(results)
+    };
+    let tool = json!({
+        "driver": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "rules": rules,
+        },
+    });
+    // Run of the report, for dashboards tying results to the run that found them
+    let automation = report
+        .metadata
+        .as_ref()
+        .and_then(|metadata| {
+            let run_id = metadata.run_id.as_ref()?;
+            let mut details = json!({
+                "guid": run_id,
+                "id": format!("{}/{}", env!("CARGO_PKG_NAME"), run_id),
+            });
+            if let Some(correlation_id) = &metadata.correlation_id {
+                details["properties"] = json!({ "correlationId": correlation_id });
+            }
+            Some(details)
+        })
+        .map(|details| pretty(&details, 3))
+        .transpose()?;
+    write!(
+        out,
+        "{{\n  \"$schema\": \"https://json.schemastore.org/sarif-2.1.0.json\",\n  \"runs\": [\n    {{\n"
+    )?;
+    if let Some(automation) = automation {
+        writeln!(out, "      \"automationDetails\": {},", automation)?;
+    }
+    write!(out, "      \"results\": ")?;
+    write_pretty_array(out, images, 3, &image_results)?;
+    writeln!(
+        out,
+        ",\n      \"tool\": {}\n    }}\n  ],\n  \"version\": \"2.1.0\"\n}}",
+        pretty(&tool, 3)?
+    )
+}
+
+// Write the items made from each image record as a pretty JSON array nested
+// at depth in the document
+fn write_pretty_array(
+    out: &mut dyn Write,
+    images: &dyn ImageSource,
+    depth: usize,
+    items: &dyn Fn(&ImageRecord) -> io::Result<Vec<String>>,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth + 1);
+    let mut empty = true;
+    images.try_for_each(&mut |image| {
+        for item in items(image)? {
+            write!(out, "{}\n{}{}", if empty { "[" } else { "," }, indent, item)?;
+            empty = false;
+        }
+        Ok(())
+    })?;
+    if empty {
+        write!(out, "[]")
+    } else {
+        write!(out, "\n{}]", "  ".repeat(depth))
+    }
+}
+
+// Pretty JSON of a value nested at depth in the document
+fn pretty<T: Serialize + ?Sized>(value: &T, depth: usize) -> io::Result<String> {
+    let json = serde_json::to_string_pretty(value)?;
+    Ok(json.replace('\n', &format!("\n{}", "  ".repeat(depth))))
+}
+
+// Get the sections of a report: images, package groups and statistics
+fn tables<'a>(
+    report: &JsonReport,
+    images: &'a dyn ImageSource,
+    labels: &Labels,
+) -> io::Result<Vec<Table<'a>>> {
+    let mut tables = Vec::new();
+    let levels = &report.severity_levels;
+
+    if !images.is_empty() {
+        // Optional columns are known once every image has been seen
+        let mut with_sla = false;
+        let mut with_ages = false;
+        let mut with_tag_count = false;
+        let mut with_vulnerable_since_push = false;
+        let mut with_environment = false;
+        let mut with_upstream = false;
+        let mut with_workloads = false;
+        let mut tag_keys = BTreeSet::new();
+        images.try_for_each(&mut |image| {
+            with_sla |= image.sla_breaches.is_some();
+            with_ages |= image.scan_age_days.is_some() || image.image_age_days.is_some();
+            with_tag_count |= image.tag_count.is_some();
+            with_vulnerable_since_push |= image.vulnerable_after_days.is_some();
+            with_environment |= image.environment.is_some();
+            with_upstream |= image.upstream.is_some();
+            with_workloads |= !image.workloads.is_empty();
+            tag_keys.extend(image.repository_tags.keys().cloned());
+            Ok(())
+        })?;
+        let columns = ImageColumns {
+            levels: levels.clone(),
+            with_sla,
+            with_ages,
+            with_tag_count,
+            with_vulnerable_since_push,
+            with_environment,
+            with_upstream,
+            with_workloads,
+            tag_keys: tag_keys.into_iter().collect(),
+        };
+        let mut headers: Vec<String> = [
+            "repository_name",
+            "image_tags",
+            "image_digest",
+            "image_scan_completed_date",
+            "vulnerability_source_updated_date",
+        ]
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+        headers.extend(levels.iter().cloned());
+        if with_sla {
+            headers.push("sla_breaches".to_string());
+        }
+        if with_ages {
+            headers.push("scan_age_days".to_string());
+            headers.push("image_age_days".to_string());
+        }
+        if with_tag_count {
+            headers.push("tag_count".to_string());
+        }
+        if with_vulnerable_since_push {
+            headers.push("vulnerable_since_push".to_string());
+            headers.push("vulnerable_after_days".to_string());
+        }
+        if with_environment {
+            headers.push("environment".to_string());
+        }
+        if with_upstream {
+            headers.push("upstream".to_string());
+        }
+        if with_workloads {
+            headers.push("workloads".to_string());
+        }
+        headers.extend(columns.tag_keys.iter().map(|key| format!("tag:{}", key)));
+        headers.push("scan_status".to_string());
+        tables.push(Table {
+            title: labels.get("Images").to_string(),
+            headers: headers.iter().map(|h| labels.column(h)).collect(),
+            rows: Rows::Images(columns, images),
+        });
+    }
+
+    if !report.packages.is_empty() {
+        let mut headers: Vec<String> = ["package_name", "package_version", "images"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        headers.extend(levels.iter().cloned());
+        let rows = report
+            .packages
+            .iter()
+            .map(|package| {
+                let mut row = vec![
+                    package.package_name.clone(),
+                    package.package_version.clone(),
+                    package.images.to_string(),
+                ];
+                row.extend(levels.iter().map(|level| {
+                    package
+                        .severity_counts
+                        .get(level)
+                        .copied()
+                        .unwrap_or_default()
+                        .to_string()
+                }));
+                row
+            })
+            .collect();
+        tables.push(Table {
+            title: labels.get("Packages").to_string(),
+            headers: headers.iter().map(|h| labels.column(h)).collect(),
+            rows: Rows::Lines(rows),
+        });
+    }
+
+    if !report.ecosystems.is_empty() {
+        let mut headers: Vec<String> = ["ecosystem", "images", "findings", "findings_%"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        headers.extend(levels.iter().cloned());
+        let rows = report
+            .ecosystems
+            .iter()
+            .map(|ecosystem| {
+                let mut row = vec![
+                    ecosystem.ecosystem.clone(),
+                    ecosystem.images.to_string(),
+                    ecosystem.findings.to_string(),
+                    format!("{:.1}", ecosystem.findings_percent),
+                ];
+                row.extend(levels.iter().map(|level| {
+                    ecosystem
+                        .severity_counts
+                        .get(level)
+                        .copied()
+                        .unwrap_or_default()
+                        .to_string()
+                }));
+                row
+            })
+            .collect();
+        tables.push(Table {
+            title: labels.get("Ecosystems").to_string(),
+            headers: headers.iter().map(|h| labels.column(h)).collect(),
+            rows: Rows::Lines(rows),
+        });
+    }
+
+    if let Some(stats) = &report.stats {
+        let headers = [
+            "level", "total", "mean", "median", "p90", "p95", "max", "images_%",
+        ]
+        .iter()
+        .map(|h| labels.get(h).to_string())
+        .collect();
+        let rows = stats
+            .levels
+            .iter()
+            .map(|(level, s)| {
+                vec![
+                    labels.get(level).to_string(),
+                    s.total.to_string(),
+                    format!("{:.2}", s.mean),
+                    s.median.to_string(),
+                    s.p90.to_string(),
+                    s.p95.to_string(),
+                    s.max.to_string(),
+                    format!("{:.1}", s.images_with_findings_percent),
+                ]
+            })
+            .collect();
+        tables.push(Table {
+            title: labels.get("Statistics").to_string(),
+            headers,
+            rows: Rows::Lines(rows),
+        });
+        tables.push(Table {
+            title: labels.get("Findings per image").to_string(),
+            headers: vec![
+                labels.get("findings").to_string(),
+                labels.get("images").to_string(),
+            ],
+            rows: Rows::Lines(
+                stats
+                    .histogram
+                    .iter()
+                    .map(|(range, images)| vec![range.clone(), images.to_string()])
+                    .collect(),
+            ),
+        });
+    }
+    Ok(tables)
+}
+
+impl Rows<'_> {
+    // Call f with each row in order
+    fn try_for_each(&self, f: &mut dyn FnMut(Vec<String>) -> io::Result<()>) -> io::Result<()> {
+        match self {
+            Rows::Images(columns, images) => {
+                images.try_for_each(&mut |image| f(columns.row(image)))
+            }
+            Rows::Lines(rows) => rows.iter().try_for_each(|row| f(row.clone())),
+        }
+    }
+}
+
+impl ImageColumns {
+    // Get the cells of the row of an image
+    fn row(&self, image: &ImageRecord) -> Vec<String> {
+        let mut row = vec![
+            image.repository_name.clone(),
+            image.image_tags.join(","),
+            image.image_digest.clone(),
+            image.image_scan_completed_at.clone().unwrap_or_default(),
+            image
+                .vulnerability_source_updated_at
+                .clone()
+                .unwrap_or_default(),
+        ];
+        row.extend(self.levels.iter().map(|level| {
+            image
+                .severity_counts
+                .get(level)
+                .copied()
+                .unwrap_or_default()
+                .to_string()
+        }));
+        if self.with_sla {
+            row.push(image.sla_breaches.unwrap_or_default().to_string());
+        }
+        if self.with_ages {
+            for age in [image.scan_age_days, image.image_age_days] {
+                row.push(age.map(|a| a.to_string()).unwrap_or_default());
+            }
+        }
+        if self.with_tag_count {
+            row.push(
+                image
+                    .tag_count
+                    .unwrap_or(image.image_tags.len())
+                    .to_string(),
+            );
+        }
+        if self.with_vulnerable_since_push {
+            row.push(
+                image
+                    .vulnerable_since_push
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            );
+            row.push(
+                image
+                    .vulnerable_after_days
+                    .map(|d| d.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        if self.with_environment {
+            row.push(image.environment.clone().unwrap_or_default());
+        }
+        if self.with_upstream {
+            row.push(image.upstream.clone().unwrap_or_default());
+        }
+        if self.with_workloads {
+            row.push(image.workloads.join(","));
+        }
+        row.extend(
+            self.tag_keys
+                .iter()
+                .map(|key| image.repository_tags.get(key).cloned().unwrap_or_default()),
+        );
+        row.push(image.scan_status.clone().unwrap_or_default());
+        row
+    }
+}
+
+// Escape the characters with a meaning in HTML
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
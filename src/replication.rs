@@ -0,0 +1,220 @@
+use crate::report::OutputFormat;
+use crate::severity::SeverityScheme;
+use aws_sdk_ecr::types::{ImageIdentifier, ReplicationRule, RepositoryFilterType};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::error::Error;
+use std::io::{self, Write};
+
+// Replication status of images that did not reach a destination
+const REPLICATION_FAILED: &str = "FAILED";
+
+// Replication of the images of the registry to the destinations of its
+// replication rules, written by the replication subcommand
+#[derive(Debug, Serialize)]
+pub struct ReplicationAudit {
+    pub severity_levels: Vec<String>,
+    pub destinations: Vec<Destination>,
+    pub images: Vec<ReplicationRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Destination {
+    pub region: String,
+    pub registry_id: String,
+}
+
+// Replication of one image to one destination
+#[derive(Debug, Serialize)]
+pub struct ReplicationRecord {
+    pub repository_name: String,
+    pub image_tags: Vec<String>,
+    pub image_digest: String,
+    pub destination_region: String,
+    pub destination_registry_id: String,
+    pub status: String,
+    pub failure_code: Option<String>,
+    // Findings of the source image, from its scan summary
+    pub severity_counts: IndexMap<String, i64>,
+}
+
+impl ReplicationAudit {
+    // Images that failed to replicate to at least one destination
+    pub fn failed_images(&self) -> usize {
+        let mut digests: Vec<&str> = self
+            .images
+            .iter()
+            .filter(|r| r.status == REPLICATION_FAILED)
+            .map(|r| r.image_digest.as_str())
+            .collect();
+        digests.sort_unstable();
+        digests.dedup();
+        digests.len()
+    }
+}
+
+// Function to get the replication status of every image of the repositories
+// replicated by the registry rules, or of one repository, optionally keeping
+// only the failed replications
+pub async fn audit_replication(
+    client: &aws_sdk_ecr::Client,
+    repository_name: Option<&str>,
+    scheme: &SeverityScheme,
+    failed_only: bool,
+) -> Result<ReplicationAudit, Box<dyn Error>> {
+    let registry = client
+        .describe_registry()
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    let rules = registry
+        .replication_configuration()
+        .map(|c| c.rules())
+        .unwrap_or_default();
+    let mut audit = ReplicationAudit {
+        severity_levels: scheme.levels.clone(),
+        destinations: Vec::new(),
+        images: Vec::new(),
+    };
+    for destination in rules.iter().flat_map(|rule| rule.destinations()) {
+        let destination = Destination {
+            region: destination.region().to_string(),
+            registry_id: destination.registry_id().to_string(),
+        };
+        if !audit.destinations.contains(&destination) {
+            audit.destinations.push(destination);
+        }
+    }
+    if audit.destinations.is_empty() {
+        return Ok(audit);
+    }
+
+    let repositories = match repository_name {
+        Some(name) => vec![name.to_string()],
+        None => {
+            let mut names = Vec::new();
+            let mut pages = client.describe_repositories().into_paginator().send();
+            while let Some(page) = pages.next().await {
+                let page = page.map_err(aws_sdk_ecr::Error::from)?;
+                names.extend(
+                    page.repositories()
+                        .iter()
+                        .filter_map(|r| r.repository_name())
+                        .map(|n| n.to_string()),
+                );
+            }
+            names
+        }
+    };
+
+    for repository in repositories {
+        // Only the repositories matched by a rule are replicated
+        if !rules.iter().any(|rule| replicates(rule, &repository)) {
+            continue;
+        }
+        let mut pages = client
+            .describe_images()
+            .repository_name(&repository)
+            .into_paginator()
+            .send();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(aws_sdk_ecr::Error::from)?;
+            for image in page.image_details() {
+                let image_digest = image.image_digest().unwrap_or_default();
+                let response = client
+                    .describe_image_replication_status()
+                    .repository_name(&repository)
+                    .image_id(
+                        ImageIdentifier::builder()
+                            .image_digest(image_digest)
+                            .build(),
+                    )
+                    .send()
+                    .await
+                    .map_err(aws_sdk_ecr::Error::from)?;
+                let counts = image
+                    .image_scan_findings_summary()
+                    .and_then(|s| s.finding_severity_counts())
+                    .map(|severity_counts| scheme.count_summary(severity_counts))
+                    .unwrap_or_else(|| vec![0; scheme.levels.len()]);
+                for status in response.replication_statuses() {
+                    let status_name = status
+                        .status()
+                        .map(|s| s.as_str().to_string())
+                        .unwrap_or_default();
+                    if failed_only && status_name != REPLICATION_FAILED {
+                        continue;
+                    }
+                    audit.images.push(ReplicationRecord {
+                        repository_name: repository.clone(),
+                        image_tags: image.image_tags().to_vec(),
+                        image_digest: image_digest.to_string(),
+                        destination_region: status.region().unwrap_or_default().to_string(),
+                        destination_registry_id: status
+                            .registry_id()
+                            .unwrap_or_default()
+                            .to_string(),
+                        status: status_name,
+                        failure_code: status.failure_code().map(|c| c.to_string()),
+                        severity_counts: scheme
+                            .levels
+                            .iter()
+                            .cloned()
+                            .zip(counts.clone())
+                            .collect(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(audit)
+}
+
+// Check if a replication rule applies to a repository, rules without filters
+// replicating every repository
+fn replicates(rule: &ReplicationRule, repository_name: &str) -> bool {
+    let filters = rule.repository_filters();
+    filters.is_empty()
+        || filters.iter().any(|filter| match filter.filter_type() {
+            RepositoryFilterType::PrefixMatch => repository_name.starts_with(filter.filter()),
+            _ => false,
+        })
+}
+
+// Function to write the replication audit as CSV lines or a JSON document
+pub fn write_replication(
+    audit: &ReplicationAudit,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    if format == OutputFormat::Json {
+        serde_json::to_writer_pretty(&mut *out, audit)?;
+        writeln!(out)?;
+        return out.flush();
+    }
+    writeln!(
+        out,
+        "repository_name;image_tags;image_digest;destination_region;destination_registry_id;replication_status;failure_code;{}",
+        audit.severity_levels.join(";")
+    )?;
+    for record in &audit.images {
+        let counts: Vec<String> = record
+            .severity_counts
+            .values()
+            .map(|count| count.to_string())
+            .collect();
+        writeln!(
+            out,
+            "{};{};{};{};{};{};{};{}",
+            record.repository_name,
+            record.image_tags.join(","),
+            record.image_digest,
+            record.destination_region,
+            record.destination_registry_id,
+            record.status,
+            record.failure_code.as_deref().unwrap_or_default(),
+            counts.join(";")
+        )?;
+    }
+    out.flush()
+}
@@ -0,0 +1,1141 @@
+use crate::annotations::ImageAnnotation;
+use crate::badge::RepositoryBadge;
+use crate::cache::FindingsCache;
+use crate::continuation::ContinueToken;
+use crate::cvss::FindingFilter;
+use crate::dates;
+use crate::defectdojo::image_findings;
+use crate::ecosystem::finding_ecosystems;
+use crate::environments::EnvironmentMapping;
+use crate::findings::ScanFindings;
+use crate::intoto::{image_statement, Statement};
+use crate::labels::Labels;
+use crate::pull::PullScript;
+use crate::query::Query;
+use crate::redact::redact_image;
+use crate::render::{write_document, write_queried};
+use crate::retry::RetryTargets;
+use crate::severity::SeverityScheme;
+use crate::sink::SinkSender;
+use crate::sla::SlaPolicy;
+use crate::spill::{ImageSource, ImageStore};
+use crate::split::{SplitBy, SplitGroups};
+use crate::stats::{StatsAccumulator, StatsReport};
+use crate::tags::TagFilter;
+use crate::targets::Target;
+use crate::thresholds::FailurePolicy;
+use crate::upstream::UpstreamImages;
+use crate::workloads::{RunningImages, UsageSource, WorkloadUsage};
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_ecr::types::TagStatus;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+// Settings shaping the report content
+#[derive(Clone)]
+pub struct ReportSettings {
+    pub scheme: SeverityScheme,  // Severity levels used for the report columns
+    pub sla: Option<SlaPolicy>,  // Remediation SLA checked against finding ages
+    pub max_db_age: Option<i64>, // Vulnerability database age in seconds flagging a scan as stale
+    pub fail_on: FailurePolicy,  // Finding counts failing the run
+    pub group_by: Option<GroupBy>, // Aggregation replacing the per-image rows
+    pub tag_filters: Vec<TagFilter>, // Repository resource tags required to report a repository
+    pub tag_columns: Vec<String>, // Repository resource tags added as columns
+    pub strict: bool,            // Fail when a row has degraded data
+    pub fail_on_empty: bool,     // Fail when no image is reported
+    pub keep_going: bool,        // Skip the failed repositories and images instead of stopping
+    pub max_images_per_repo: Option<u64>, // Images reported per repository at most
+    pub sample: Option<u64>,     // Images reported in the whole run at most
+    pub resume: Option<ContinueToken>, // Position where a run stopped by --sample resumes
+    pub tag_status: Option<TagStatus>, // Only report tagged or untagged images
+    pub stats: bool,             // Print distribution statistics instead of the images
+    pub targets: Vec<Target>,    // Compliance targets evaluated in the summary
+    pub badge_level: Option<usize>, // Write a badge of this level per repository instead of a report
+    pub max_images_in_memory: usize, // Image records buffered in memory before spilling to disk
+    pub per_tag: bool,              // One line per image tag instead of one per image digest
+    pub pin_tags: Vec<String>, // Moving tags reported on the digest they had when the run started
+    pub list_findings: bool, // Findings listed one by one, with --output defectdojo or --findings-sink
+    pub labels: Labels,      // Column headers and severity labels of the text reports
+    pub redact: bool,        // Hash the repository names and remove the accounts of the report
+    pub environments: EnvironmentMapping, // Environments of the images, from their tags
+    pub environment_filter: Vec<String>, // Only report images of these environments
+    pub skip_expired: bool,  // Leave out the images whose scan findings expired
+    pub scanned_after: Option<DateTime>, // Only report images scanned at or after this date
+    pub scanned_before: Option<DateTime>, // Only report images scanned before this date
+    pub include_unsupported: bool, // List the images of media types ECR cannot scan
+    pub findings_cache: Option<FindingsCache>, // Detailed findings kept on disk across runs
+    pub max_findings_per_image: Option<u64>, // Detailed findings kept per image at most
+    pub verify_counts: Option<u64>, // Images whose summary counts are checked against their findings
+    pub finding_filter: FindingFilter, // Findings counted, by CVSS attack vector, score and fix
+    pub upstream: UpstreamImages,   // Upstream images compared with the mirrored repositories
+    pub age_columns: bool,          // Add the scan and image ages in days
+    pub tag_count: bool,            // Add the number of tags of each image digest
+    pub vulnerable_since_push: bool, // Tell the images shipped vulnerable from those found vulnerable later
+    pub running_images: Option<RunningImages>, // Only report the images running in a cluster
+    pub retry: Option<RetryTargets>, // Only report the repositories and images failed in a previous run
+    pub filters: Vec<String>,        // Options narrowing the report, for the report metadata
+    pub usage: Vec<UsageSource>,     // Services looked up for the workloads running the images
+    pub workloads: WorkloadUsage, // Workloads running the images, looked up at the start of a run
+    pub split_by: Option<SplitBy>, // Groups of repositories written to a report file each
+    pub query: Option<Query>,     // JMESPath expression selecting from the JSON document
+    pub image_digest: Option<String>, // Only report the image of this digest
+}
+
+impl ReportSettings {
+    // True when the detailed finding list of each image is needed
+    pub fn needs_findings(&self) -> bool {
+        self.scheme.needs_findings()
+            || self.sla.is_some()
+            || self.group_by.is_some()
+            || self.list_findings
+            || !self.finding_filter.is_empty()
+            || self.vulnerable_since_push
+    }
+
+    // True when the resource tags of each repository are needed
+    pub fn needs_repository_tags(&self) -> bool {
+        !self.tag_filters.is_empty()
+            || !self.tag_columns.is_empty()
+            || self.targets.iter().any(|t| t.needs_repository_tags())
+            || self
+                .split_by
+                .as_ref()
+                .is_some_and(|split| split.needs_repository_tags())
+    }
+
+    // Check the repository resource tags match every --repo-tag filter
+    pub fn matches_repository_tags(&self, tags: &HashMap<String, String>) -> bool {
+        self.tag_filters.iter().all(|filter| filter.matches(tags))
+    }
+}
+
+// Aggregations available with --group-by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Package,
+    Ecosystem,
+}
+
+impl GroupBy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "package" => Ok(GroupBy::Package),
+            "ecosystem" => Ok(GroupBy::Ecosystem),
+            _ => Err(format!(
+                "unknown grouping '{}', expected 'package' or 'ecosystem'",
+                value
+            )),
+        }
+    }
+}
+
+// Report formats available with --output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+    Table,
+    Markdown,
+    Html,
+    Sarif,
+    DefectDojo,
+    InToto,
+    Parquet,
+    Heatmap,
+    HeatmapHtml,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            "sarif" => Ok(OutputFormat::Sarif),
+            "defectdojo" => Ok(OutputFormat::DefectDojo),
+            "in-toto" => Ok(OutputFormat::InToto),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "heatmap" => Ok(OutputFormat::Heatmap),
+            "heatmap-html" => Ok(OutputFormat::HeatmapHtml),
+            _ => Err(format!(
+                "unknown output '{}', expected 'csv', 'json', 'table', 'markdown', 'html', 'sarif', 'defectdojo', 'in-toto', 'parquet', 'heatmap' or 'heatmap-html'",
+                value
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Table => "table",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Sarif => "sarif",
+            OutputFormat::DefectDojo => "defectdojo",
+            OutputFormat::InToto => "in-toto",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Heatmap => "heatmap",
+            OutputFormat::HeatmapHtml => "heatmap-html",
+        }
+    }
+
+    // Extension of the report files written to a directory, e.g. with --split-by
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json | OutputFormat::DefectDojo | OutputFormat::InToto => "json",
+            OutputFormat::Table | OutputFormat::Heatmap => "txt",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html | OutputFormat::HeatmapHtml => "html",
+            OutputFormat::Sarif => "sarif",
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+
+    // Media type of the uploaded reports
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "text/csv",
+            OutputFormat::Json => "application/json",
+            OutputFormat::Table => "text/plain",
+            OutputFormat::Markdown => "text/markdown",
+            OutputFormat::Html => "text/html",
+            OutputFormat::Sarif => "application/sarif+json",
+            OutputFormat::DefectDojo => "application/json",
+            OutputFormat::InToto => "application/vnd.in-toto+json",
+            OutputFormat::Parquet => "application/vnd.apache.parquet",
+            OutputFormat::Heatmap => "text/plain",
+            OutputFormat::HeatmapHtml => "text/html",
+        }
+    }
+}
+
+// Report line of one image
+#[derive(Clone)]
+pub struct ImageReport {
+    pub registry_id: String,
+    pub repository_name: String,
+    pub image_tags: Vec<String>,
+    pub image_digest: String,
+    pub pushed_at: Option<DateTime>,
+    pub scan: Option<ScanFindings>, // Scan findings, if the image has been scanned
+    pub counts: Vec<i64>,           // Finding count per level of the severity scheme
+    pub sla_breaches: Option<u64>,  // Findings over their SLA, when --sla is used
+    pub scan_age_days: Option<i64>, // Days since the scan completed, with --age-columns
+    pub image_age_days: Option<i64>, // Days since the image was pushed, with --age-columns
+    pub tag_count: Option<usize>,   // Tags of the image digest, with --tag-count
+    pub vulnerable_after_days: Option<i64>, // Days from the push to the first counted finding, with --vulnerable-since-push
+    pub repository_tags: HashMap<String, String>, // Resource tags of the repository
+    pub environment: Option<String>,        // Environment matching the image tags, if any
+    pub upstream: Option<String>, // Same, diverged or missing tag in the upstream registry
+    pub workloads: Vec<String>,   // Workloads running the image, with --usage
+    pub scan_status: Option<String>, // ECR scan status, e.g. COMPLETE or SCAN_ELIGIBILITY_EXPIRED
+    pub warnings: Vec<String>,    // Reasons why the line has degraded data
+}
+
+// Version of the JSON report layout, only bumped on breaking changes, printed
+// as a JSON Schema by the schema subcommand
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+// JSON document written with --output json
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonReport {
+    // Reports written before the version was recorded have the first layout
+    #[serde(default = "report_schema_version")]
+    pub schema_version: u32,
+    pub severity_levels: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ReportMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<PackageRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ecosystems: Vec<EcosystemRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<StatsReport>,
+}
+
+impl Default for JsonReport {
+    fn default() -> Self {
+        JsonReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            severity_levels: Vec::new(),
+            metadata: None,
+            images: Vec::new(),
+            packages: Vec::new(),
+            ecosystems: Vec::new(),
+            stats: None,
+        }
+    }
+}
+
+fn report_schema_version() -> u32 {
+    REPORT_SCHEMA_VERSION
+}
+
+// Description of the run of a report, printed at the top of the table,
+// markdown and HTML reports so that a forwarded file tells how it was made
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReportMetadata {
+    pub generated_at: String,
+    pub tool_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<String>,
+    // Command-line options narrowing the report, e.g. --severities critical,high
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<String>,
+    // Identifiers of the run, see --correlation-id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+// JSON record of one image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageRecord {
+    pub account: String,
+    pub region: String,
+    pub repository_name: String,
+    pub image_tags: Vec<String>,
+    pub image_digest: String,
+    pub image_scan_completed_at: Option<String>,
+    pub vulnerability_source_updated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_status: Option<String>,
+    pub severity_counts: IndexMap<String, i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sla_breaches: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_age_days: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_age_days: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vulnerable_since_push: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vulnerable_after_days: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workloads: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub repository_tags: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+// JSON record of one package version, with --group-by package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageRecord {
+    pub package_name: String,
+    pub package_version: String,
+    pub images: usize,
+    pub severity_counts: IndexMap<String, i64>,
+}
+
+// JSON record of one package ecosystem, with --group-by ecosystem
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EcosystemRecord {
+    pub ecosystem: String,
+    pub images: usize,
+    pub findings: i64,
+    pub findings_percent: f64, // Share of the findings of every ecosystem
+    pub severity_counts: IndexMap<String, i64>,
+}
+
+// Findings of one package version, or of one ecosystem, across the registry
+#[derive(Default)]
+struct PackageGroup {
+    images: usize,
+    counts: Vec<i64>,
+}
+
+// Writes the report, one line per image or one line per group
+pub struct ReportWriter {
+    out: Box<dyn Write>,
+    format: OutputFormat,
+    region: String,
+    levels: Vec<String>,
+    columns: Vec<String>,
+    with_ages: bool,
+    with_tag_count: bool,
+    with_vulnerable_since_push: bool,
+    with_environment: bool,
+    with_upstream: bool,
+    with_workloads: bool,
+    tag_columns: Vec<String>,
+    group_by: Option<GroupBy>,
+    per_tag: bool,
+    // DefectDojo findings written so far
+    dojo_findings: usize,
+    // Statement of the attested image, with --output in-toto
+    statement: Option<Statement>,
+    packages: BTreeMap<(String, String), PackageGroup>,
+    ecosystems: BTreeMap<String, PackageGroup>,
+    // Digests of the images already counted in the package groups, an image
+    // pushed to several repositories is counted once
+    grouped_images: HashSet<String>,
+    stats: bool,
+    distribution: StatsAccumulator,
+    // Badge level and badge of the most recent image of each repository
+    badge_level: Option<usize>,
+    badges: BTreeMap<String, RepositoryBadge>,
+    // Script fetching the flagged images, with --emit-pull-commands
+    fail_on: FailurePolicy,
+    pull_script: Option<PullScript>,
+    // Images with findings, with --github-check, --bitbucket-report or --azure-devops
+    annotations: Option<Vec<ImageAnnotation>>,
+    // Finding counts of each repository, with --cloudwatch-namespace
+    repository_counts: Option<BTreeMap<String, Vec<i64>>>,
+    // Findings sink fed image by image, with --findings-sink
+    sink: Option<SinkSender>,
+    // Groups of the repositories, with --split-by
+    split_groups: Option<SplitGroups>,
+    // Image records of the formats rendered once complete, and of --split-by
+    images: ImageStore,
+    json: JsonReport,
+    labels: Labels,
+    query: Option<Query>,
+    redact: bool,
+}
+
+impl ReportWriter {
+    pub fn new(
+        settings: &ReportSettings,
+        format: OutputFormat,
+        region: &str,
+        out: Box<dyn Write>,
+    ) -> Self {
+        let mut columns = settings.scheme.levels.clone();
+        if settings.sla.is_some() && settings.group_by.is_none() {
+            columns.push("sla_breaches".to_string());
+        }
+        let with_ages = settings.age_columns && settings.group_by.is_none();
+        if with_ages {
+            columns.push("scan_age_days".to_string());
+            columns.push("image_age_days".to_string());
+        }
+        let with_tag_count = settings.tag_count && settings.group_by.is_none();
+        if with_tag_count {
+            columns.push("tag_count".to_string());
+        }
+        let with_vulnerable_since_push =
+            settings.vulnerable_since_push && settings.group_by.is_none();
+        if with_vulnerable_since_push {
+            columns.push("vulnerable_since_push".to_string());
+            columns.push("vulnerable_after_days".to_string());
+        }
+        let with_environment = !settings.environments.is_empty() && settings.group_by.is_none();
+        if with_environment {
+            columns.push("environment".to_string());
+        }
+        let with_upstream = !settings.upstream.is_empty() && settings.group_by.is_none();
+        if with_upstream {
+            columns.push("upstream".to_string());
+        }
+        let with_workloads = !settings.usage.is_empty() && settings.group_by.is_none();
+        if with_workloads {
+            columns.push("workloads".to_string());
+        }
+        // Resource tag columns only make sense on image lines
+        if settings.group_by.is_none() {
+            columns.extend(
+                settings
+                    .tag_columns
+                    .iter()
+                    .map(|key| format!("tag:{}", key)),
+            );
+            // Last, so that the columns of earlier reports keep their position
+            columns.push("scan_status".to_string());
+        }
+        ReportWriter {
+            out,
+            format,
+            region: region.to_string(),
+            levels: settings.scheme.levels.clone(),
+            columns,
+            with_ages,
+            with_tag_count,
+            with_vulnerable_since_push,
+            with_environment,
+            with_upstream,
+            with_workloads,
+            tag_columns: settings.tag_columns.clone(),
+            group_by: settings.group_by,
+            per_tag: settings.per_tag,
+            dojo_findings: 0,
+            statement: None,
+            packages: BTreeMap::new(),
+            ecosystems: BTreeMap::new(),
+            grouped_images: HashSet::new(),
+            stats: settings.stats,
+            distribution: StatsAccumulator::default(),
+            badge_level: settings.badge_level,
+            badges: BTreeMap::new(),
+            fail_on: settings.fail_on.clone(),
+            pull_script: None,
+            annotations: None,
+            repository_counts: None,
+            sink: None,
+            split_groups: settings.split_by.clone().map(SplitGroups::new),
+            images: ImageStore::new(settings.max_images_in_memory),
+            json: JsonReport {
+                severity_levels: settings.scheme.levels.clone(),
+                ..JsonReport::default()
+            },
+            labels: settings.labels.clone(),
+            query: settings.query.clone(),
+            redact: settings.redact,
+        }
+    }
+
+    // Print headers, with one column per level of the severity scheme
+    pub fn start(&mut self) -> io::Result<()> {
+        if self.stats || self.badge_level.is_some() {
+            return Ok(());
+        }
+        let headers: &[&str] = match self.group_by {
+            Some(GroupBy::Package) => &["package_name", "package_version", "images"],
+            Some(GroupBy::Ecosystem) => &["ecosystem", "images", "findings", "findings_%"],
+            None => &[
+                "repository_name",
+                "image_tags",
+                "image_digest",
+                "image_scan_completed_date",
+                "vulnerability_source_updated_date",
+            ],
+        };
+        let header: Vec<String> = headers
+            .iter()
+            .map(|h| h.to_string())
+            .chain(self.columns.iter().cloned())
+            .map(|column| self.labels.column(&column))
+            .collect();
+        let result = match self.format {
+            // Findings are written as images are processed
+            OutputFormat::DefectDojo => write!(self.out, "{{\n  \"findings\": "),
+            OutputFormat::Csv => writeln!(self.out, "{}", header.join(";")),
+            // Other formats are rendered from the JSON document once complete
+            _ => Ok(()),
+        };
+        closed_output(result)
+    }
+
+    // Append a pull command to the script for every flagged image
+    pub fn emit_pull_commands(&mut self, script: PullScript) {
+        self.pull_script = Some(script);
+    }
+
+    // Number of images in the pull script
+    pub fn pull_commands(&self) -> usize {
+        self.pull_script
+            .as_ref()
+            .map_or(0, |script| script.commands())
+    }
+
+    // Keep the images with findings, annotated by the CI integrations
+    pub fn annotate_images(&mut self) {
+        self.annotations = Some(Vec::new());
+    }
+
+    // Get the images with findings
+    pub fn annotations(&self) -> &[ImageAnnotation] {
+        self.annotations.as_deref().unwrap_or_default()
+    }
+
+    // Send the findings of every image to a findings sink
+    pub fn send_findings(&mut self, sink: SinkSender) {
+        self.sink = Some(sink);
+    }
+
+    // Stop sending findings, so that the sink sends its last batch
+    pub fn close_sink(&mut self) {
+        self.sink = None;
+    }
+
+    // Sum the finding counts of the images of each repository
+    pub fn count_repositories(&mut self) {
+        self.repository_counts = Some(BTreeMap::new());
+    }
+
+    // Get the finding counts of each repository, in repository name order
+    pub fn repository_counts(&self) -> Vec<(String, Vec<i64>)> {
+        self.repository_counts
+            .iter()
+            .flatten()
+            .map(|(name, counts)| (name.clone(), counts.clone()))
+            .collect()
+    }
+
+    // Print the image line, or add its findings to the groups
+    pub fn add_image(&mut self, image: &ImageReport, scheme: &SeverityScheme) -> io::Result<()> {
+        if let Some(sink) = &self.sink {
+            sink.send_image(image, &self.region)?;
+        }
+        if let Some(repositories) = &mut self.repository_counts {
+            let counts = repositories
+                .entry(image.repository_name.clone())
+                .or_insert_with(|| vec![0; image.counts.len()]);
+            for (total, count) in counts.iter_mut().zip(&image.counts) {
+                *total += count;
+            }
+        }
+        // Images are flagged over the --fail-on thresholds, or with any finding without them
+        if let Some(script) = &mut self.pull_script {
+            let flagged = match self
+                .fail_on
+                .thresholds(&image.repository_name, &image.image_tags)
+            {
+                Some(thresholds) => thresholds.exceeded(&image.counts),
+                None => image.counts.iter().any(|count| *count > 0),
+            };
+            if flagged {
+                script.add(
+                    &image.registry_id,
+                    &image.repository_name,
+                    &image.image_digest,
+                    &image.image_tags,
+                )?;
+            }
+        }
+        // Images over their thresholds fail their annotation, unless exempted
+        if let Some(annotations) = &mut self.annotations {
+            if image.counts.iter().any(|count| *count > 0) {
+                let failing = self
+                    .fail_on
+                    .thresholds(&image.repository_name, &image.image_tags)
+                    .is_some_and(|thresholds| thresholds.exceeded(&image.counts))
+                    && self
+                        .fail_on
+                        .exemptions
+                        .find(
+                            &image.repository_name,
+                            &image.image_digest,
+                            &image.image_tags,
+                            &dates::now(),
+                        )
+                        .is_none();
+                annotations.push(ImageAnnotation {
+                    repository_name: image.repository_name.clone(),
+                    image_digest: image.image_digest.clone(),
+                    image_tags: image.image_tags.clone(),
+                    counts: image.counts.clone(),
+                    failing,
+                });
+            }
+        }
+        // Badges only show the most recently pushed image of each repository
+        if let Some(level) = self.badge_level {
+            let badge = RepositoryBadge {
+                repository_name: image.repository_name.clone(),
+                pushed_at: image.pushed_at,
+                count: image.scan.as_ref().map(|_| image.counts[level]),
+            };
+            match self.badges.get(&image.repository_name) {
+                Some(latest) if latest.pushed_at >= badge.pushed_at => {}
+                _ => {
+                    self.badges.insert(image.repository_name.clone(), badge);
+                }
+            }
+            return Ok(());
+        }
+        let group = self.split_groups.as_ref().map(|groups| groups.group(image));
+        // Only the report is redacted, the sink, metrics, pull script and badges stay internal
+        let redacted;
+        let image = if self.redact {
+            redacted = redact_image(image);
+            &redacted
+        } else {
+            image
+        };
+        if let (Some(groups), Some(group)) = (&mut self.split_groups, group) {
+            groups.add(&image.repository_name, group);
+        }
+        // Statistics are computed once every image is known
+        if self.stats {
+            match image.scan {
+                Some(_) => self.distribution.add(&image.counts),
+                None => self.distribution.add_unscanned(),
+            }
+            return Ok(());
+        }
+        let result = match (self.group_by, self.format) {
+            (Some(GroupBy::Package), _) => {
+                self.add_packages(image, scheme);
+                Ok(())
+            }
+            (Some(GroupBy::Ecosystem), _) => {
+                self.add_ecosystems(image, scheme);
+                Ok(())
+            }
+            (None, OutputFormat::DefectDojo) => self.print_findings(image),
+            (None, OutputFormat::InToto) => {
+                self.statement = Some(image_statement(image, &self.levels, &self.region));
+                Ok(())
+            }
+            // Each digest is listed once with all its tags, unless --per-tag
+            (None, OutputFormat::Csv) if self.per_tag && image.image_tags.len() > 1 => image
+                .image_tags
+                .iter()
+                .try_for_each(|tag| self.print_image(image, tag)),
+            (None, OutputFormat::Csv) => self.print_image(image, &image.image_tags.join(",")),
+            (None, _) => self.push_records(image),
+        };
+        // The split reports are rendered from the records, CSV ones included
+        let result = match (result, self.format) {
+            (Ok(()), OutputFormat::Csv) if self.split_groups.is_some() => self.push_records(image),
+            (result, _) => result,
+        };
+        closed_output(result)
+    }
+
+    // Keep the records of an image, one per tag with --per-tag
+    fn push_records(&mut self, image: &ImageReport) -> io::Result<()> {
+        if self.per_tag && image.image_tags.len() > 1 {
+            return image.image_tags.iter().try_for_each(|tag| {
+                let mut record = self.image_record(image);
+                record.image_tags = vec![tag.clone()];
+                self.images.push(record)
+            });
+        }
+        let record = self.image_record(image);
+        self.images.push(record)
+    }
+
+    // Get the JSON document collected so far, spilled image records included
+    pub fn into_document(self) -> io::Result<JsonReport> {
+        let mut json = self.json;
+        json.images = self.images.into_records()?;
+        Ok(json)
+    }
+
+    // Image records of the run, once finished
+    pub fn images(&self) -> &dyn ImageSource {
+        &self.images
+    }
+
+    // Statement of the attested image, with --output in-toto
+    pub fn statement(&self) -> Option<&Statement> {
+        self.statement.as_ref()
+    }
+
+    // JSON document of the run without its image records, once finished
+    pub fn document(&self) -> &JsonReport {
+        &self.json
+    }
+
+    // Groups of the repositories of the run, with --split-by
+    pub fn split_groups(&self) -> Option<&SplitGroups> {
+        self.split_groups.as_ref()
+    }
+
+    // Number of image records written to disk by lack of memory
+    pub fn spilled_images(&self) -> usize {
+        self.images.spilled()
+    }
+
+    // Print the groups, the statistics and the JSON document once every image has been processed
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(script) = &mut self.pull_script {
+            script.flush()?;
+        }
+        let result = self.write_trailer();
+        closed_output(result)
+    }
+
+    fn write_trailer(&mut self) -> io::Result<()> {
+        // Badges are written by the caller, nothing goes to the output
+        if self.badge_level.is_some() {
+            return Ok(());
+        }
+        if self.stats {
+            let stats = StatsReport::compute(&self.levels, &self.distribution);
+            match self.format {
+                OutputFormat::Csv => stats.write_text(&mut self.out)?,
+                _ => self.json.stats = Some(stats),
+            }
+        }
+        if self.group_by == Some(GroupBy::Package) {
+            // Most severe packages first, then the most widespread
+            let mut packages: Vec<_> = self.packages.iter().collect();
+            packages.sort_by(|a, b| {
+                b.1.counts
+                    .cmp(&a.1.counts)
+                    .then(b.1.images.cmp(&a.1.images))
+            });
+            for ((name, version), group) in packages {
+                match self.format {
+                    OutputFormat::Csv => writeln!(
+                        self.out,
+                        "{};{};{};{}",
+                        name,
+                        version,
+                        group.images,
+                        join_counts(&group.counts)
+                    )?,
+                    _ => self.json.packages.push(PackageRecord {
+                        package_name: name.clone(),
+                        package_version: version.clone(),
+                        images: group.images,
+                        severity_counts: level_counts(&self.levels, &group.counts),
+                    }),
+                }
+            }
+        }
+        if self.group_by == Some(GroupBy::Ecosystem) {
+            let total: i64 = self.ecosystems.values().flat_map(|g| &g.counts).sum();
+            // Ecosystems with the most findings first
+            let mut ecosystems: Vec<_> = self
+                .ecosystems
+                .iter()
+                .map(|(name, group)| (name, group, group.counts.iter().sum::<i64>()))
+                .collect();
+            ecosystems.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.counts.cmp(&a.1.counts)));
+            for (name, group, findings) in ecosystems {
+                let percent = match total {
+                    0 => 0.0,
+                    total => (findings as f64 * 1000.0 / total as f64).round() / 10.0,
+                };
+                match self.format {
+                    OutputFormat::Csv => writeln!(
+                        self.out,
+                        "{};{};{};{:.1};{}",
+                        name,
+                        group.images,
+                        findings,
+                        percent,
+                        join_counts(&group.counts)
+                    )?,
+                    _ => self.json.ecosystems.push(EcosystemRecord {
+                        ecosystem: name.clone(),
+                        images: group.images,
+                        findings,
+                        findings_percent: percent,
+                        severity_counts: level_counts(&self.levels, &group.counts),
+                    }),
+                }
+            }
+        }
+        match self.format {
+            // Records of the split reports, read back by the caller
+            OutputFormat::Csv => self.images.flush()?,
+            OutputFormat::DefectDojo if self.dojo_findings == 0 => write!(self.out, "[]\n}}\n")?,
+            OutputFormat::DefectDojo => write!(self.out, "\n  ]\n}}\n")?,
+            // The statement is written once the run is described
+            OutputFormat::InToto => {
+                let Some(statement) = &mut self.statement else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "no image of --image-digest was reported, nothing to attest",
+                    ));
+                };
+                if let Some(metadata) = &self.json.metadata {
+                    statement.set_invocation(metadata);
+                }
+                serde_json::to_writer_pretty(&mut self.out, statement)?;
+                writeln!(self.out)?;
+            }
+            // Parquet files are written by the caller from the image records
+            OutputFormat::Parquet => self.images.flush()?,
+            _ => {
+                self.images.flush()?;
+                match &self.query {
+                    Some(query) => write_queried(&self.json, &self.images, query, &mut self.out)?,
+                    None => write_document(
+                        &self.json,
+                        &self.images,
+                        self.format,
+                        &self.labels,
+                        &mut self.out,
+                    )?,
+                }
+            }
+        }
+        self.out.flush()
+    }
+
+    // Describe the run in the report, without its accounts with --redact
+    pub fn set_metadata(&mut self, mut metadata: ReportMetadata) {
+        if self.redact {
+            metadata.accounts.clear();
+        }
+        self.json.metadata = Some(metadata);
+    }
+
+    // Get the badge of each repository, in repository name order
+    pub fn badges(&self) -> Vec<RepositoryBadge> {
+        self.badges.values().cloned().collect()
+    }
+
+    // Count each finding once for every package version it affects
+    fn add_packages(&mut self, image: &ImageReport, scheme: &SeverityScheme) {
+        let Some(scan) = &image.scan else {
+            return;
+        };
+        // Package versions of the image, counted once per image
+        let first_time = self.grouped_images.insert(image.image_digest.clone());
+        let mut image_packages = HashSet::new();
+        for finding in &scan.findings {
+            let Some(index) = scheme.level_index(
+                &finding.severity,
+                finding.vendor_severity.as_deref(),
+                finding.cvss_score,
+            ) else {
+                continue;
+            };
+            for package in &finding.packages {
+                let group = self
+                    .packages
+                    .entry((package.name.clone(), package.version.clone()))
+                    .or_default();
+                if group.counts.is_empty() {
+                    group.counts = vec![0; scheme.levels.len()];
+                }
+                group.counts[index] += 1;
+                if first_time && image_packages.insert((&package.name, &package.version)) {
+                    group.images += 1;
+                }
+            }
+        }
+    }
+
+    // Count each finding once for every ecosystem of the packages it affects
+    fn add_ecosystems(&mut self, image: &ImageReport, scheme: &SeverityScheme) {
+        let Some(scan) = &image.scan else {
+            return;
+        };
+        // Ecosystems of the image, counted once per image
+        let first_time = self.grouped_images.insert(image.image_digest.clone());
+        let mut image_ecosystems = HashSet::new();
+        for finding in &scan.findings {
+            let Some(index) = scheme.level_index(
+                &finding.severity,
+                finding.vendor_severity.as_deref(),
+                finding.cvss_score,
+            ) else {
+                continue;
+            };
+            for ecosystem in finding_ecosystems(finding) {
+                let group = self.ecosystems.entry(ecosystem.clone()).or_default();
+                if group.counts.is_empty() {
+                    group.counts = vec![0; scheme.levels.len()];
+                }
+                group.counts[index] += 1;
+                if first_time && image_ecosystems.insert(ecosystem) {
+                    group.images += 1;
+                }
+            }
+        }
+    }
+
+    // Write the DefectDojo findings of an image in the findings array
+    fn print_findings(&mut self, image: &ImageReport) -> io::Result<()> {
+        for finding in image_findings(image, &self.region) {
+            let json = serde_json::to_string_pretty(&finding)?.replace('\n', "\n    ");
+            let separator = if self.dojo_findings == 0 { "[" } else { "," };
+            write!(self.out, "{}\n    {}", separator, json)?;
+            self.dojo_findings += 1;
+        }
+        Ok(())
+    }
+
+    // Print severity and dates in csv line
+    fn print_image(&mut self, image: &ImageReport, image_tags: &str) -> io::Result<()> {
+        // Dates not given by the API are left empty
+        let format_date = |date: Option<DateTime>| {
+            date.and_then(|d| d.fmt(DateTimeFormat::DateTime).ok())
+                .unwrap_or_default()
+        };
+        // Leave dates empty if no scan findings are available
+        let (scan_complete_date, update_scan_date) = match &image.scan {
+            Some(scan) => (
+                format_date(scan.scan_completed_at),
+                format_date(scan.vulnerability_source_updated_at),
+            ),
+            None => (String::new(), String::new()),
+        };
+        let mut counts = image.counts.clone();
+        if let Some(breaches) = image.sla_breaches {
+            counts.push(breaches as i64);
+        }
+        let ages = match self.with_ages {
+            true => [image.scan_age_days, image.image_age_days]
+                .iter()
+                .map(|age| format!(";{}", age.map(|a| a.to_string()).unwrap_or_default()))
+                .collect(),
+            false => String::new(),
+        };
+        // Tags of the digest, also with one line per tag
+        let tag_count = match self.with_tag_count {
+            true => format!(";{}", image.tag_count.unwrap_or(image.image_tags.len())),
+            false => String::new(),
+        };
+        let vulnerable_since_push = match self.with_vulnerable_since_push {
+            true => format!(
+                ";{};{}",
+                vulnerable_since_push(image.vulnerable_after_days)
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+                image
+                    .vulnerable_after_days
+                    .map(|d| d.to_string())
+                    .unwrap_or_default()
+            ),
+            false => String::new(),
+        };
+        let environment = match self.with_environment {
+            true => format!(";{}", image.environment.as_deref().unwrap_or_default()),
+            false => String::new(),
+        };
+        let upstream = match self.with_upstream {
+            true => format!(";{}", image.upstream.as_deref().unwrap_or_default()),
+            false => String::new(),
+        };
+        let workloads = match self.with_workloads {
+            true => format!(";{}", image.workloads.join(",")),
+            false => String::new(),
+        };
+        // Append the requested repository resource tags, empty when not set
+        let tags: String = self
+            .tag_columns
+            .iter()
+            .map(|key| {
+                format!(
+                    ";{}",
+                    image.repository_tags.get(key).map_or("", |v| v.as_str())
+                )
+            })
+            .collect();
+        writeln!(
+            self.out,
+            "{};{};{};{};{};{}{}{}{}{}{}{}{};{}",
+            image.repository_name,
+            image_tags,
+            image.image_digest,
+            scan_complete_date,
+            update_scan_date,
+            join_counts(&counts),
+            ages,
+            tag_count,
+            vulnerable_since_push,
+            environment,
+            upstream,
+            workloads,
+            tags,
+            image.scan_status.as_deref().unwrap_or_default()
+        )
+    }
+
+    // Build the JSON record of an image
+    fn image_record(&self, image: &ImageReport) -> ImageRecord {
+        let format_date =
+            |date: Option<DateTime>| date.and_then(|d| d.fmt(DateTimeFormat::DateTime).ok());
+        ImageRecord {
+            account: image.registry_id.clone(),
+            region: self.region.clone(),
+            repository_name: image.repository_name.clone(),
+            image_tags: image.image_tags.clone(),
+            image_digest: image.image_digest.clone(),
+            image_scan_completed_at: image
+                .scan
+                .as_ref()
+                .and_then(|s| format_date(s.scan_completed_at)),
+            vulnerability_source_updated_at: image
+                .scan
+                .as_ref()
+                .and_then(|s| format_date(s.vulnerability_source_updated_at)),
+            scan_status: image.scan_status.clone(),
+            severity_counts: level_counts(&self.levels, &image.counts),
+            sla_breaches: image.sla_breaches,
+            scan_age_days: image.scan_age_days,
+            image_age_days: image.image_age_days,
+            tag_count: image.tag_count,
+            vulnerable_since_push: vulnerable_since_push(image.vulnerable_after_days),
+            vulnerable_after_days: image.vulnerable_after_days,
+            environment: image.environment.clone(),
+            upstream: image.upstream.clone(),
+            workloads: image.workloads.clone(),
+            repository_tags: image
+                .repository_tags
+                .iter()
+                .filter(|(key, _)| self.tag_columns.contains(key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            warnings: image.warnings.clone(),
+        }
+    }
+}
+
+// Error of a report whose reader went away (e.g. piped to head), which ends
+// the run with the images written so far as other command-line tools do
+#[derive(Debug)]
+pub struct OutputClosed;
+
+impl fmt::Display for OutputClosed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "report output closed by its reader")
+    }
+}
+
+impl Error for OutputClosed {}
+
+// Whether an error, or one of its sources, is a closed report output
+pub fn is_output_closed(error: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(e) = source {
+        let closed = match e.downcast_ref::<io::Error>() {
+            Some(e) => e.get_ref().is_some_and(|inner| inner.is::<OutputClosed>()),
+            None => e.is::<OutputClosed>(),
+        };
+        if closed {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+fn closed_output(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, OutputClosed))
+        }
+        result => result,
+    }
+}
+
+// Images whose first counted finding was observed within a day of their push
+// shipped vulnerable, the others became vulnerable once in the registry
+fn vulnerable_since_push(vulnerable_after_days: Option<i64>) -> Option<bool> {
+    vulnerable_after_days.map(|days| days == 0)
+}
+
+// Pair each severity level with its count
+fn level_counts(levels: &[String], counts: &[i64]) -> IndexMap<String, i64> {
+    levels.iter().cloned().zip(counts.iter().copied()).collect()
+}
+
+// Join counts with the column separator
+fn join_counts(counts: &[i64]) -> String {
+    counts
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(";")
+}
@@ -0,0 +1,105 @@
+use aws_sdk_ecr::error::ErrorMetadata;
+use aws_sdk_ecr::types::error::RepositoryNotFoundException;
+use aws_sdk_ecr::types::Repository;
+use std::io::{self, Read};
+
+// Repositories described by one DescribeRepositories call at most
+pub const MAX_REPOSITORY_NAMES: usize = 100;
+
+// Function to read the repositories given with --repos-file, one name per
+// line from a file or from stdin with '-', ignoring blank lines and # comments
+pub fn read_repository_list(path: &str) -> io::Result<Vec<String>> {
+    let mut content = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut content)?;
+    } else {
+        content = std::fs::read_to_string(path)?;
+    }
+    let mut names: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let name = line.split('#').next().unwrap_or_default().trim();
+        if !name.is_empty() && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+// Function to describe every repository of the registry, or only the given ones
+// in batches of MAX_REPOSITORY_NAMES, which fails when one of them does not
+// exist. A batch with missing names is described again without them, so that
+// the error lists every missing repository instead of the first one
+pub async fn describe_repositories(
+    client: &aws_sdk_ecr::Client,
+    names: Option<&[String]>,
+) -> Result<Vec<Repository>, aws_sdk_ecr::Error> {
+    let Some(names) = names else {
+        let response = client.describe_repositories().send().await?;
+        return Ok(response.repositories.unwrap_or_default());
+    };
+    let mut repositories = Vec::new();
+    let mut missing = Vec::new();
+    let mut not_found = None;
+    for chunk in names.chunks(MAX_REPOSITORY_NAMES) {
+        let mut chunk = chunk.to_vec();
+        while !chunk.is_empty() {
+            let error = match client
+                .describe_repositories()
+                .set_repository_names(Some(chunk.clone()))
+                .send()
+                .await
+            {
+                Ok(response) => {
+                    repositories.extend(response.repositories.unwrap_or_default());
+                    break;
+                }
+                Err(e) => aws_sdk_ecr::Error::from(e),
+            };
+            let name = match &error {
+                aws_sdk_ecr::Error::RepositoryNotFoundException(e) => {
+                    e.message().and_then(missing_repository_name)
+                }
+                _ => None,
+            };
+            // Errors other than a missing name of the batch fail the run as is
+            let Some(index) = name.and_then(|name| chunk.iter().position(|n| n == name)) else {
+                return Err(error);
+            };
+            missing.push(chunk.remove(index));
+            not_found.get_or_insert(error);
+        }
+    }
+    match (not_found, missing.len()) {
+        (None, _) => Ok(repositories),
+        // The error of a single missing name keeps its request id
+        (Some(error), 1) => Err(error),
+        (Some(_), _) => {
+            let message = format!(
+                "The repositories {} do not exist in the registry",
+                missing
+                    .iter()
+                    .map(|name| format!("'{}'", name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            Err(aws_sdk_ecr::Error::RepositoryNotFoundException(
+                RepositoryNotFoundException::builder()
+                    .message(&message)
+                    .meta(
+                        ErrorMetadata::builder()
+                            .code("RepositoryNotFoundException")
+                            .message(message)
+                            .build(),
+                    )
+                    .build(),
+            ))
+        }
+    }
+}
+
+// Name of the missing repository of a RepositoryNotFoundException message,
+// e.g. "The repository with name 'x' does not exist in the registry with id '...'"
+fn missing_repository_name(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once("name '")?;
+    rest.split_once('\'').map(|(name, _)| name)
+}
@@ -0,0 +1,77 @@
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::error::Error;
+
+// Errors of the file written with --errors-json, only their repository and
+// image are needed to retry them
+#[derive(Deserialize)]
+struct ErrorsFile {
+    errors: Vec<FailedItem>,
+}
+
+#[derive(Deserialize)]
+struct FailedItem {
+    repository: Option<String>,
+    // Files written before images were recorded only name repositories
+    #[serde(default)]
+    image: Option<String>,
+}
+
+// Repositories and images that failed in a previous run, reported again with
+// --retry-from
+#[derive(Debug, Clone, Default)]
+pub struct RetryTargets {
+    // Images to report again by repository, None when the whole repository
+    // failed, in the order of the errors
+    repositories: IndexMap<String, Option<Vec<String>>>,
+    // Errors of the run itself (metrics, sink, workloads), not retried
+    pub ignored: usize,
+}
+
+impl RetryTargets {
+    // Function to read the failed repositories and images of an errors file
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let file: ErrorsFile = serde_json::from_str(&content)?;
+        let mut targets = RetryTargets::default();
+        for error in file.errors {
+            let Some(repository) = error.repository else {
+                targets.ignored += 1;
+                continue;
+            };
+            match (targets.repositories.get_mut(&repository), error.image) {
+                // The whole repository is reported again
+                (Some(None), _) => {}
+                (Some(Some(images)), Some(image)) => {
+                    if !images.contains(&image) {
+                        images.push(image);
+                    }
+                }
+                (Some(images), None) => *images = None,
+                (None, image) => {
+                    targets
+                        .repositories
+                        .insert(repository, image.map(|image| vec![image]));
+                }
+            }
+        }
+        if targets.repositories.is_empty() {
+            return Err("no failed repository or image to retry".into());
+        }
+        Ok(targets)
+    }
+
+    // Repositories to report again
+    pub fn repositories(&self) -> Vec<String> {
+        self.repositories.keys().cloned().collect()
+    }
+
+    // Check if an image failed, or belongs to a repository that failed
+    pub fn retries(&self, repository_name: &str, image_digest: &str) -> bool {
+        match self.repositories.get(repository_name) {
+            Some(Some(images)) => images.iter().any(|image| image == image_digest),
+            Some(None) => true,
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Longest --correlation-id, within the 2 KB of the S3 user metadata
+const MAX_CORRELATION_ID: usize = 128;
+
+// Identifiers of a run, given to its outputs so that downstream systems can
+// tie alerts, tickets and artifacts back to the exact run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunIds {
+    // Random UUID, new at each run
+    pub run_id: String,
+    // Identifier of the caller given with --correlation-id, e.g. a pipeline id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+impl RunIds {
+    pub fn new(correlation_id: Option<String>) -> Self {
+        RunIds {
+            run_id: random_uuid(),
+            correlation_id,
+        }
+    }
+
+    // User metadata of the S3 objects written by the run, returned by S3 as
+    // the x-amz-meta-run-id and x-amz-meta-correlation-id headers
+    pub fn object_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::from([("run-id".to_string(), self.run_id.clone())]);
+        if let Some(correlation_id) = &self.correlation_id {
+            metadata.insert("correlation-id".to_string(), correlation_id.clone());
+        }
+        metadata
+    }
+}
+
+// Function to check a --correlation-id value, sent as an HTTP header to S3
+pub fn check_correlation_id(value: &str) -> Result<(), String> {
+    if value.is_empty()
+        || value.len() > MAX_CORRELATION_ID
+        || !value.chars().all(|c| c.is_ascii_graphic())
+    {
+        return Err(format!(
+            "'{}' is not 1 to {} printable ASCII characters without spaces",
+            value, MAX_CORRELATION_ID
+        ));
+    }
+    Ok(())
+}
+
+// Random version 4 UUID, e.g. 3f2b8c1e-9a4d-4e7f-b210-5c6d7e8f9a0b, from the
+// random keys of the standard library hashers
+fn random_uuid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let random = |seed: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(seed);
+        hasher.write_u32(std::process::id());
+        hasher.finish() as u128
+    };
+    let mut bits = (random(nanos) << 64) | random(!nanos);
+    // Version 4 and RFC 4122 variant bits
+    bits = (bits & !(0xf << 76)) | (0x4 << 76);
+    bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        bits >> 96,
+        (bits >> 80) & 0xffff,
+        (bits >> 64) & 0xffff,
+        (bits >> 48) & 0xffff,
+        bits & 0xffff_ffff_ffff
+    )
+}
@@ -0,0 +1,171 @@
+use crate::environments::matches_pattern;
+use crate::report::OutputFormat;
+use aws_sdk_ecr::types::{
+    RegistryScanningRule, ScanFrequency, ScanType, ScanningRepositoryFilter,
+    ScanningRepositoryFilterType,
+};
+use serde::Serialize;
+use std::error::Error;
+use std::io::{self, Write};
+
+// Frequency of the repositories no rule of an enhanced scanning registry
+// matches, Inspector never scans them
+const NOT_SCANNED: &str = "NOT_SCANNED";
+// Frequency of the repositories a basic scanning registry only scans on demand
+const MANUAL: &str = "MANUAL";
+
+// Scan frequency of every repository under the registry scanning
+// configuration, written by the simulate-scan-rules subcommand
+#[derive(Debug, Serialize)]
+pub struct ScanRuleSimulation {
+    pub scan_type: String,
+    pub rules: Vec<SimulatedRule>,
+    pub repositories: Vec<RepositoryScan>,
+}
+
+// Scanning rule of the registry, its filters as <type>:<filter>
+#[derive(Debug, Serialize)]
+pub struct SimulatedRule {
+    pub scan_frequency: String,
+    pub filters: Vec<String>,
+}
+
+// Scan frequency of one repository, and the filter or setting deciding it
+#[derive(Debug, Serialize)]
+pub struct RepositoryScan {
+    pub repository_name: String,
+    pub scan_frequency: String,
+    pub matched_by: Option<String>,
+}
+
+impl ScanRuleSimulation {
+    // Repositories never scanned automatically
+    pub fn unscanned(&self) -> usize {
+        self.repositories
+            .iter()
+            .filter(|r| matches!(r.scan_frequency.as_str(), NOT_SCANNED | MANUAL))
+            .count()
+    }
+}
+
+// Function to match every repository of the registry, or one repository,
+// against the filters of the registry scanning rules
+pub async fn simulate_scan_rules(
+    client: &aws_sdk_ecr::Client,
+    repository_name: Option<&str>,
+) -> Result<ScanRuleSimulation, Box<dyn Error>> {
+    let response = client
+        .get_registry_scanning_configuration()
+        .send()
+        .await
+        .map_err(aws_sdk_ecr::Error::from)?;
+    let configuration = response.scanning_configuration();
+    let scan_type = configuration
+        .and_then(|c| c.scan_type())
+        .cloned()
+        .unwrap_or(ScanType::Basic);
+    let rules = configuration.map(|c| c.rules()).unwrap_or_default();
+    let mut simulation = ScanRuleSimulation {
+        scan_type: scan_type.as_str().to_string(),
+        rules: rules
+            .iter()
+            .map(|rule| SimulatedRule {
+                scan_frequency: rule.scan_frequency().as_str().to_string(),
+                filters: rule.repository_filters().iter().map(filter_name).collect(),
+            })
+            .collect(),
+        repositories: Vec::new(),
+    };
+
+    let mut request = client.describe_repositories();
+    if let Some(name) = repository_name {
+        request = request.repository_names(name);
+    }
+    let mut pages = request.into_paginator().send();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(aws_sdk_ecr::Error::from)?;
+        for repository in page.repositories() {
+            let name = repository.repository_name().unwrap_or_default();
+            // Scan on push set on the repository itself, only read by basic scanning
+            let scan_on_push = repository
+                .image_scanning_configuration()
+                .is_some_and(|c| c.scan_on_push());
+            let (scan_frequency, matched_by) = match matching_rule(rules, name) {
+                Some((frequency, filter)) => (frequency.as_str().to_string(), Some(filter)),
+                None if scan_type == ScanType::Enhanced => (NOT_SCANNED.to_string(), None),
+                None if scan_on_push => (
+                    ScanFrequency::ScanOnPush.as_str().to_string(),
+                    Some("repository scanOnPush".to_string()),
+                ),
+                None => (MANUAL.to_string(), None),
+            };
+            simulation.repositories.push(RepositoryScan {
+                repository_name: name.to_string(),
+                scan_frequency,
+                matched_by,
+            });
+        }
+    }
+    Ok(simulation)
+}
+
+// Most frequent scanning of the rules matching a repository, continuous
+// scanning winning over scan on push as in ECR, with the matching filter
+fn matching_rule(
+    rules: &[RegistryScanningRule],
+    repository_name: &str,
+) -> Option<(ScanFrequency, String)> {
+    let rank = |frequency: &ScanFrequency| match frequency {
+        ScanFrequency::ContinuousScan => 2,
+        ScanFrequency::ScanOnPush => 1,
+        _ => 0,
+    };
+    rules
+        .iter()
+        .filter_map(|rule| {
+            rule.repository_filters()
+                .iter()
+                .find(|filter| match filter.filter_type() {
+                    ScanningRepositoryFilterType::Wildcard => {
+                        matches_pattern(filter.filter(), repository_name)
+                    }
+                    _ => false,
+                })
+                .map(|filter| (rule.scan_frequency().clone(), filter_name(filter)))
+        })
+        .filter(|(frequency, _)| *frequency != ScanFrequency::Manual)
+        .max_by_key(|(frequency, _)| rank(frequency))
+}
+
+fn filter_name(filter: &ScanningRepositoryFilter) -> String {
+    format!(
+        "{}:{}",
+        filter.filter_type().as_str().to_lowercase(),
+        filter.filter()
+    )
+}
+
+// Function to write the simulation as CSV lines or a JSON document
+pub fn write_scan_rules(
+    simulation: &ScanRuleSimulation,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    if format == OutputFormat::Json {
+        serde_json::to_writer_pretty(&mut *out, simulation)?;
+        writeln!(out)?;
+        return out.flush();
+    }
+    writeln!(out, "repository_name;scan_type;scan_frequency;matched_by")?;
+    for repository in &simulation.repositories {
+        writeln!(
+            out,
+            "{};{};{};{}",
+            repository.repository_name,
+            simulation.scan_type,
+            repository.scan_frequency,
+            repository.matched_by.as_deref().unwrap_or_default()
+        )?;
+    }
+    out.flush()
+}
@@ -0,0 +1,169 @@
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+// Years searched for the next run, expressions such as "0 0 30 2 *" never firing
+const MAX_YEARS: i64 = 5;
+
+// Runs of a report given with --schedule, as a cron expression of minute,
+// hour, day of month, month and day of week fields evaluated in UTC, e.g.
+// "0 6 * * 1" for every Monday at 06:00
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Cron runs on either the day of month or the day of week when both are given
+    any_day: bool,
+    any_weekday: bool,
+    // Random delay added to each run with --schedule-jitter, so that replicas
+    // started from the same schedule do not call AWS at the same time
+    pub jitter: Duration,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str, jitter: Duration) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "'{}' is not a cron expression of 5 fields (minute hour day month weekday)",
+                expression
+            ));
+        };
+        let weekday_mask = parse_field(weekdays, 0, 7, "weekday")?;
+        let schedule = Schedule {
+            minutes: parse_field(minutes, 0, 59, "minute")?,
+            hours: parse_field(hours, 0, 23, "hour")?,
+            days: parse_field(days, 1, 31, "day")?,
+            months: parse_field(months, 1, 12, "month")?,
+            // Sunday is both 0 and 7
+            weekdays: (weekday_mask | (weekday_mask >> 7)) & 0x7f,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+            jitter,
+        };
+        if schedule.next_after(0).is_none() {
+            return Err(format!("'{}' never runs", expression));
+        }
+        Ok(schedule)
+    }
+
+    // Start of the first minute of the schedule after a time, in seconds since
+    // the epoch
+    pub fn next_after(&self, secs: i64) -> Option<i64> {
+        let mut minute = secs.div_euclid(60) + 1;
+        let end = minute + MAX_YEARS * 366 * 1440;
+        while minute < end {
+            let days = minute.div_euclid(1440);
+            let (_, month, day) = civil_from_days(days);
+            // Weekday of the day, 1970-01-01 being a Thursday
+            let weekday = (days + 4).rem_euclid(7);
+            let day_matches = match (self.any_day, self.any_weekday) {
+                (true, true) => true,
+                (false, true) => bit(self.days, day),
+                (true, false) => bit(self.weekdays, weekday),
+                (false, false) => bit(self.days, day) || bit(self.weekdays, weekday),
+            };
+            if !bit(self.months, month) || !day_matches {
+                minute = (days + 1) * 1440;
+                continue;
+            }
+            let hour = minute.div_euclid(60).rem_euclid(24);
+            if !bit(self.hours, hour) {
+                minute = (minute.div_euclid(60) + 1) * 60;
+                continue;
+            }
+            if bit(self.minutes, minute.rem_euclid(60)) {
+                return Some(minute * 60);
+            }
+            minute += 1;
+        }
+        None
+    }
+
+    // Runs of the schedule after a time up to another, both excluded
+    pub fn runs_between(&self, after: i64, before: i64) -> usize {
+        let mut runs = 0;
+        let mut secs = after;
+        while let Some(next) = self.next_after(secs).filter(|next| *next < before) {
+            runs += 1;
+            secs = next;
+        }
+        runs
+    }
+
+    // Random delay of a run, up to --schedule-jitter
+    pub fn jitter_delay(&self) -> Duration {
+        let millis = self.jitter.as_millis() as u64;
+        if millis == 0 {
+            return Duration::ZERO;
+        }
+        let random = RandomState::new().build_hasher().finish();
+        Duration::from_millis(random % (millis + 1))
+    }
+}
+
+// Date time of a run, e.g. 2024-06-10T06:00:00Z
+pub fn format_run(secs: i64) -> String {
+    DateTime::from_secs(secs)
+        .fmt(DateTimeFormat::DateTime)
+        .unwrap_or_default()
+}
+
+// Values of a cron field as a bit mask, e.g. "*", "5", "1-5", "*/15" or "0,30"
+fn parse_field(field: &str, min: i64, max: i64, name: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid {} field '{}'", name, field);
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<i64>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (
+                start.parse().map_err(|_| invalid())?,
+                end.parse().map_err(|_| invalid())?,
+            ),
+            // "5/10" runs from 5 to the last value
+            None if part.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+            None => {
+                let value = range.parse().map_err(|_| invalid())?;
+                (value, value)
+            }
+        };
+        if step < 1 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn bit(mask: u64, value: i64) -> bool {
+    mask & (1 << value) != 0
+}
+
+// Year, month and day of a day counted from the epoch
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
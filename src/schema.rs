@@ -0,0 +1,212 @@
+use crate::report::REPORT_SCHEMA_VERSION;
+use crate::sink::FINDINGS_SCHEMA_VERSION;
+use serde_json::{json, Value};
+
+// Documents described by the schema subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaKind {
+    Report,
+    Findings,
+}
+
+impl SchemaKind {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "report" => Ok(SchemaKind::Report),
+            "findings" => Ok(SchemaKind::Findings),
+            _ => Err(format!(
+                "unknown schema '{}', expected 'report' or 'findings'",
+                value
+            )),
+        }
+    }
+}
+
+// JSON Schema of the --output json report, or of the finding lines sent with
+// --findings-sink
+pub fn json_schema(kind: SchemaKind) -> Value {
+    match kind {
+        SchemaKind::Report => report_schema(),
+        SchemaKind::Findings => findings_schema(),
+    }
+}
+
+fn report_schema() -> Value {
+    // Counts are keyed by the severity levels of the scheme used for the report
+    let severity_counts = json!({
+        "type": "object",
+        "additionalProperties": { "type": "integer" }
+    });
+    let date = json!({ "type": ["string", "null"], "format": "date-time" });
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("{} JSON report", env!("CARGO_PKG_NAME")),
+        "type": "object",
+        "required": ["schema_version", "severity_levels"],
+        "properties": {
+            "schema_version": { "const": REPORT_SCHEMA_VERSION },
+            "severity_levels": { "type": "array", "items": { "type": "string" } },
+            "metadata": {
+                "type": "object",
+                "required": ["generated_at", "tool_version"],
+                "properties": {
+                    "generated_at": { "type": "string", "format": "date-time" },
+                    "tool_version": { "type": "string" },
+                    "scan_type": { "type": "string" },
+                    "accounts": { "type": "array", "items": { "type": "string" } },
+                    "regions": { "type": "array", "items": { "type": "string" } },
+                    "filters": { "type": "array", "items": { "type": "string" } },
+                    "run_id": { "type": "string", "format": "uuid" },
+                    "correlation_id": { "type": "string" }
+                }
+            },
+            "images": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": [
+                        "account", "region", "repository_name", "image_tags", "image_digest",
+                        "image_scan_completed_at", "vulnerability_source_updated_at", "severity_counts"
+                    ],
+                    "properties": {
+                        "account": { "type": "string" },
+                        "region": { "type": "string" },
+                        "repository_name": { "type": "string" },
+                        "image_tags": { "type": "array", "items": { "type": "string" } },
+                        "image_digest": { "type": "string" },
+                        "image_scan_completed_at": date,
+                        "vulnerability_source_updated_at": date,
+                        "scan_status": { "type": "string" },
+                        "severity_counts": severity_counts,
+                        "sla_breaches": { "type": "integer", "minimum": 0 },
+                        "scan_age_days": { "type": "integer", "minimum": 0 },
+                        "image_age_days": { "type": "integer", "minimum": 0 },
+                        "tag_count": { "type": "integer", "minimum": 0 },
+                        "vulnerable_since_push": { "type": "boolean" },
+                        "vulnerable_after_days": { "type": "integer", "minimum": 0 },
+                        "environment": { "type": "string" },
+                        "upstream": { "enum": ["same", "diverged", "missing"] },
+                        "workloads": { "type": "array", "items": { "type": "string" } },
+                        "repository_tags": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" }
+                        },
+                        "warnings": { "type": "array", "items": { "type": "string" } }
+                    }
+                }
+            },
+            "packages": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["package_name", "package_version", "images", "severity_counts"],
+                    "properties": {
+                        "package_name": { "type": "string" },
+                        "package_version": { "type": "string" },
+                        "images": { "type": "integer", "minimum": 0 },
+                        "severity_counts": severity_counts
+                    }
+                }
+            },
+            "ecosystems": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": [
+                        "ecosystem", "images", "findings", "findings_percent", "severity_counts"
+                    ],
+                    "properties": {
+                        "ecosystem": { "type": "string" },
+                        "images": { "type": "integer", "minimum": 0 },
+                        "findings": { "type": "integer", "minimum": 0 },
+                        "findings_percent": { "type": "number" },
+                        "severity_counts": severity_counts
+                    }
+                }
+            },
+            "stats": {
+                "type": "object",
+                "required": [
+                    "images", "unscanned_images", "images_without_findings",
+                    "images_without_findings_percent", "levels", "histogram"
+                ],
+                "properties": {
+                    "images": { "type": "integer", "minimum": 0 },
+                    "unscanned_images": { "type": "integer", "minimum": 0 },
+                    "images_without_findings": { "type": "integer", "minimum": 0 },
+                    "images_without_findings_percent": { "type": "number" },
+                    "levels": {
+                        "type": "object",
+                        "additionalProperties": {
+                            "type": "object",
+                            "required": [
+                                "total", "mean", "median", "p90", "p95", "max",
+                                "images_with_findings_percent"
+                            ],
+                            "properties": {
+                                "total": { "type": "integer" },
+                                "mean": { "type": "number" },
+                                "median": { "type": "integer" },
+                                "p90": { "type": "integer" },
+                                "p95": { "type": "integer" },
+                                "max": { "type": "integer" },
+                                "images_with_findings_percent": { "type": "number" }
+                            }
+                        }
+                    },
+                    "histogram": {
+                        "type": "object",
+                        "additionalProperties": { "type": "integer", "minimum": 0 }
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn findings_schema() -> Value {
+    let date = json!({ "type": ["string", "null"], "format": "date-time" });
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("{} finding line", env!("CARGO_PKG_NAME")),
+        "type": "object",
+        "required": [
+            "schema_version", "account", "region", "repository_name", "image_digest", "image_tags",
+            "vulnerability_id", "severity", "cvss_score", "packages", "first_observed_at",
+            "image_scan_completed_at"
+        ],
+        "properties": {
+            "schema_version": { "const": FINDINGS_SCHEMA_VERSION },
+            "account": { "type": "string" },
+            "region": { "type": "string" },
+            "repository_name": { "type": "string" },
+            "image_digest": { "type": "string" },
+            "image_tags": { "type": "array", "items": { "type": "string" } },
+            "vulnerability_id": { "type": "string" },
+            "finding_arn": { "type": ["string", "null"] },
+            "severity": { "type": "string" },
+            "vendor_severity": { "type": ["string", "null"] },
+            "cvss_score": { "type": ["number", "null"] },
+            "cvss_vector": { "type": ["string", "null"] },
+            "packages": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["name", "version"],
+                    "properties": {
+                        "name": { "type": "string" },
+                        "version": { "type": "string" }
+                    }
+                }
+            },
+            "references": { "type": "array", "items": { "type": "string", "format": "uri" } },
+            "remediation_hint": { "type": ["string", "null"] },
+            "first_observed_at": date,
+            "vendor_created_at": date,
+            "vendor_updated_at": date,
+            "image_scan_completed_at": date,
+            "run_id": { "type": "string", "format": "uuid" },
+            "correlation_id": { "type": "string" }
+        }
+    })
+}
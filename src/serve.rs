@@ -0,0 +1,684 @@
+use crate::continuation::ContinueToken;
+use crate::dates;
+use crate::errors::{error_chain, ErrorKind, RunError, EXIT_API_FAILURE, EXIT_OK};
+use crate::findings::describe_findings;
+use crate::list_images_in_repository;
+use crate::manifest::{RunManifest, RunStatus};
+use crate::remediation::remediation_hint;
+use crate::report::{JsonReport, OutputFormat, ReportSettings, ReportWriter};
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_ecr::types::{ImageIdentifier, ScanType};
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// Largest request head accepted, the API only has GET requests
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+
+// Repositories returned by one DescribeRepositories call at most
+const MAX_REPOSITORIES_PER_PAGE: u64 = 1000;
+
+// Answers kept in the cache at most, the oldest one dropped for a new one
+const MAX_CACHE_ENTRIES: usize = 1000;
+
+// Time given to a client to send its request head, before it is answered 408
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// State shared by every connection
+struct Server {
+    client: aws_sdk_ecr::Client,
+    settings: ReportSettings,
+    scan_type: ScanType,
+    region: String,
+    cache: RefCell<HashMap<String, (Instant, String)>>,
+    cache_ttl: Duration,
+    health: Arc<Mutex<HealthStatus>>,
+}
+
+// State of a long-lived process for GET /healthz, /readyz and /status, updated
+// by serve after each answer read from AWS and by --schedule after each run
+#[derive(Debug, Default, Serialize)]
+pub struct HealthStatus {
+    // True while a scheduled run is reporting
+    pub running: bool,
+    pub next_run: Option<String>,
+    pub last_run: Option<LastRun>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LastRun {
+    pub finished_at: String,
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LastRun {
+    pub fn new(status: RunStatus, exit_code: Option<u8>, error: Option<String>) -> Self {
+        LastRun {
+            finished_at: format_date(Some(&dates::now())).unwrap_or_default(),
+            status,
+            exit_code,
+            error,
+        }
+    }
+}
+
+// HTTP response, always JSON
+struct Response {
+    status: u16,
+    body: String,
+}
+
+// JSON record of one finding, for GET /images/{digest}/findings
+#[derive(Serialize)]
+struct FindingRecord {
+    name: String,
+    finding_arn: Option<String>,
+    severity: String,
+    level: String,
+    vendor_severity: Option<String>,
+    cvss_score: Option<f64>,
+    cvss_vector: Option<String>,
+    first_observed_at: Option<String>,
+    vendor_created_at: Option<String>,
+    vendor_updated_at: Option<String>,
+    packages: Vec<PackageVersion>,
+    references: Vec<String>,
+    remediation_hint: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PackageVersion {
+    name: String,
+    version: String,
+}
+
+// Report document of GET /repos/{name}/images, with the token of the next page
+#[derive(Serialize)]
+struct ImagesPage {
+    #[serde(flatten)]
+    report: JsonReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<String>,
+}
+
+// Function to serve the scan data over HTTP until the process is stopped
+pub async fn serve(
+    client: aws_sdk_ecr::Client,
+    settings: ReportSettings,
+    region: &str,
+    listen: &str,
+    cache_ttl: Duration,
+) -> u8 {
+    // The scanning type of the registry drives how findings are read
+    let scan_type = match client.get_registry_scanning_configuration().send().await {
+        Ok(response) => response
+            .scanning_configuration
+            .and_then(|c| c.scan_type)
+            .unwrap_or(ScanType::Basic),
+        Err(e) => {
+            let e = aws_sdk_ecr::Error::from(e);
+            eprintln!("Error: {}", error_chain(&e));
+            return crate::errors::errors_exit_code(&[RunError::from_error(None, &e)])
+                .unwrap_or(EXIT_API_FAILURE);
+        }
+    };
+    let listener = match TcpListener::bind(listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Error listening on '{}': {}", listen, e);
+            return EXIT_API_FAILURE;
+        }
+    };
+    eprintln!("Listening on http://{}", listen);
+
+    let server = Rc::new(Server {
+        client,
+        settings,
+        scan_type,
+        region: region.to_string(),
+        cache: RefCell::new(HashMap::new()),
+        cache_ttl,
+        health: Arc::default(),
+    });
+    // The report writer is not Send, connections are served concurrently on this thread
+    let connections = tokio::task::LocalSet::new();
+    connections
+        .run_until(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let server = server.clone();
+                        tokio::task::spawn_local(async move {
+                            if let Err(e) = handle_connection(&server, stream).await {
+                                eprintln!("Error handling request: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        eprintln!("Error accepting connection: {}", e);
+                        return EXIT_OK;
+                    }
+                }
+            }
+        })
+        .await
+}
+
+// Function to answer the health endpoints of --health-listen while --schedule
+// runs the reports, until the process is stopped
+pub async fn serve_health(listener: TcpListener, health: Arc<Mutex<HealthStatus>>) {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("Error accepting connection: {}", e);
+                return;
+            }
+        };
+        let health = health.clone();
+        tokio::spawn(async move {
+            let result = match read_request(&mut stream).await {
+                Ok(Some((method, target))) => {
+                    let path = target.split('?').next().unwrap_or_default();
+                    let response = match health_route(&health, path) {
+                        _ if method != "GET" => {
+                            error_response(405, "only GET requests are supported")
+                        }
+                        Some(response) => response,
+                        None => error_response(404, "unknown path"),
+                    };
+                    write_response(&mut stream, &response).await
+                }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                eprintln!("Error handling request: {}", e);
+            }
+        });
+    }
+}
+
+// Read one request, answer it and close the connection
+async fn handle_connection(server: &Server, mut stream: TcpStream) -> std::io::Result<()> {
+    let Some((method, target)) = read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let response = if method != "GET" {
+        error_response(405, "only GET requests are supported")
+    } else {
+        route(server, &target).await
+    };
+    eprintln!("{} {} {}", method, target, response.status);
+    write_response(&mut stream, &response).await
+}
+
+// Read the method and target of a request, None when the connection is closed,
+// or the request too large or too slow to come, which is answered with an error
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<(String, String)>> {
+    match tokio::time::timeout(REQUEST_TIMEOUT, read_request_head(stream)).await {
+        Ok(result) => result,
+        Err(_) => {
+            write_response(stream, &error_response(408, "request not received in time")).await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn read_request_head(stream: &mut TcpStream) -> std::io::Result<Option<(String, String)>> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 4096];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buffer[..read]);
+        if request.len() > MAX_REQUEST_SIZE {
+            write_response(stream, &error_response(431, "request too large")).await?;
+            return Ok(None);
+        }
+    }
+    let head = String::from_utf8_lossy(&request);
+    let mut parts = head.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    Ok(Some((method.to_string(), target.to_string())))
+}
+
+// GET /healthz: the process answers. GET /readyz: the last run, or answer read
+// from AWS, did not fail. GET /status: the last run and the next one
+fn health_route(health: &Mutex<HealthStatus>, path: &str) -> Option<Response> {
+    let health = health.lock().unwrap_or_else(|e| e.into_inner());
+    match path {
+        "/healthz" => Some(Response {
+            status: 200,
+            body: json!({ "status": "ok" }).to_string(),
+        }),
+        "/readyz" => {
+            let failed = health
+                .last_run
+                .as_ref()
+                .is_some_and(|run| run.status == RunStatus::Failed);
+            Some(Response {
+                status: if failed { 503 } else { 200 },
+                body: json!({
+                    "status": if failed { "not_ready" } else { "ready" },
+                    "last_run": health.last_run,
+                })
+                .to_string(),
+            })
+        }
+        "/status" => Some(Response {
+            status: 200,
+            body: serde_json::to_string(&*health).unwrap_or_default(),
+        }),
+        _ => None,
+    }
+}
+
+// Answer a request, from the cache when a fresh answer is available
+async fn route(server: &Server, target: &str) -> Response {
+    if let Some(response) =
+        health_route(&server.health, target.split('?').next().unwrap_or_default())
+    {
+        return response;
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query: HashMap<String, String> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (percent_decode(key), percent_decode(value)))
+        .collect();
+    let key = cache_key(path, &query);
+    if let Some((issued, body)) = server.cache.borrow().get(&key) {
+        if issued.elapsed() < server.cache_ttl {
+            return Response {
+                status: 200,
+                body: body.clone(),
+            };
+        }
+    }
+    // Listings are paged when given a limit, the next page asked with the
+    // next_token of the previous one
+    let limit = match query.get("limit").map(|limit| limit.parse::<u64>()) {
+        None => None,
+        Some(Ok(limit)) if limit > 0 => Some(limit),
+        Some(_) => return error_response(400, "limit must be a positive number"),
+    };
+    let token = query.get("token");
+    let result = if path == "/repos" {
+        list_repositories(server, limit, token).await
+    } else if let Some(name) = path
+        .strip_prefix("/repos/")
+        .and_then(|p| p.strip_suffix("/images"))
+    {
+        let name = percent_decode(name);
+        let resume = match token.map(|token| ContinueToken::parse(token)) {
+            None => None,
+            Some(Ok(token)) if token.repository == name => Some(token),
+            Some(_) => return error_response(400, "invalid token for this repository"),
+        };
+        list_images(server, &name, limit, resume).await
+    } else if let Some(digest) = path
+        .strip_prefix("/images/")
+        .and_then(|p| p.strip_suffix("/findings"))
+    {
+        image_findings(server, &percent_decode(digest), query.get("repository")).await
+    } else {
+        return error_response(404, "unknown path");
+    };
+
+    // Answers read from AWS are the runs of serve for /readyz and /status
+    let status = match &result {
+        Err(e) => match RunError::from_error(None, e.as_ref()).code.as_deref() {
+            Some("RepositoryNotFoundException" | "ImageNotFoundException") => RunStatus::Succeeded,
+            _ => RunStatus::Failed,
+        },
+        Ok(_) => RunStatus::Succeeded,
+    };
+    let error = match (&result, &status) {
+        (Err(e), RunStatus::Failed) => Some(error_chain(e.as_ref())),
+        _ => None,
+    };
+    server
+        .health
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .last_run = Some(LastRun::new(status, None, error));
+
+    match result {
+        Ok(Some(body)) => {
+            cache_answer(server, key, &body);
+            Response { status: 200, body }
+        }
+        Ok(None) => error_response(404, "image not found"),
+        Err(e) => {
+            let error = RunError::from_error(None, e.as_ref());
+            let status = match (error.kind, error.code.as_deref()) {
+                (_, Some("RepositoryNotFoundException" | "ImageNotFoundException")) => 404,
+                (ErrorKind::Auth, _) => 403,
+                _ => 502,
+            };
+            Response {
+                status,
+                body: json!({ "error": error }).to_string(),
+            }
+        }
+    }
+}
+
+// Key of a cached answer: the decoded path and the parameters the answer
+// depends on, so that other parameters do not add entries
+fn cache_key(path: &str, query: &HashMap<String, String>) -> String {
+    let mut key = percent_decode(path);
+    for name in ["limit", "token", "repository"] {
+        if let Some(value) = query.get(name) {
+            key.push_str(&format!("\n{}={}", name, value));
+        }
+    }
+    key
+}
+
+// Keep an answer in the cache, dropping the expired ones and, when the cache
+// is full, the oldest one
+fn cache_answer(server: &Server, key: String, body: &str) {
+    let mut cache = server.cache.borrow_mut();
+    cache.retain(|_, (issued, _)| issued.elapsed() < server.cache_ttl);
+    if cache.len() >= MAX_CACHE_ENTRIES && !cache.contains_key(&key) {
+        let oldest = cache
+            .iter()
+            .min_by_key(|(_, (issued, _))| *issued)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            cache.remove(&oldest);
+        }
+    }
+    cache.insert(key, (Instant::now(), body.to_string()));
+}
+
+// GET /repos[?limit=n&token=t]: every repository of the registry, or one page
+// of at most limit repositories
+async fn list_repositories(
+    server: &Server,
+    limit: Option<u64>,
+    token: Option<&String>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let mut repositories = Vec::new();
+    let mut next_token = None;
+    let mut pages = server
+        .client
+        .describe_repositories()
+        .set_next_token(token.cloned())
+        .set_max_results(limit.map(|limit| limit.min(MAX_REPOSITORIES_PER_PAGE) as i32))
+        .into_paginator()
+        .send();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(aws_sdk_ecr::Error::from)?;
+        for repository in page.repositories() {
+            repositories.push(json!({
+                "repository_name": repository.repository_name(),
+                "repository_uri": repository.repository_uri(),
+                "registry_id": repository.registry_id(),
+                "created_at": format_date(repository.created_at()),
+            }));
+        }
+        // Only the first page is listed when given a limit
+        if limit.is_some() {
+            next_token = page.next_token;
+            break;
+        }
+    }
+    let mut body = json!({ "repositories": repositories });
+    if let Some(next_token) = next_token {
+        body["next_token"] = next_token.into();
+    }
+    Ok(Some(body.to_string()))
+}
+
+// GET /repos/{name}/images[?limit=n&token=t]: the report records of a
+// repository, or at most limit of them
+async fn list_images(
+    server: &Server,
+    repository_name: &str,
+    limit: Option<u64>,
+    resume: Option<ContinueToken>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let settings = ReportSettings {
+        sample: limit.or(server.settings.sample),
+        resume,
+        ..server.settings.clone()
+    };
+    let mut writer = ReportWriter::new(
+        &settings,
+        OutputFormat::Json,
+        &server.region,
+        Box::new(std::io::sink()),
+    );
+    let mut manifest = RunManifest::start();
+    list_images_in_repository(
+        &server.client,
+        repository_name,
+        &HashMap::new(),
+        &server.scan_type,
+        &settings,
+        &mut writer,
+        &mut manifest,
+    )
+    .await?;
+    writer.finish()?;
+    let page = ImagesPage {
+        report: writer.into_document()?,
+        next_token: manifest.next_token,
+    };
+    Ok(Some(serde_json::to_string(&page)?))
+}
+
+// GET /images/{digest}/findings[?repository=name]: the findings of an image,
+// looked up in every repository when the repository is not given
+async fn image_findings(
+    server: &Server,
+    image_digest: &str,
+    repository_name: Option<&String>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let repositories = match repository_name {
+        Some(name) => vec![name.clone()],
+        None => {
+            let mut names = Vec::new();
+            let mut pages = server
+                .client
+                .describe_repositories()
+                .into_paginator()
+                .send();
+            while let Some(page) = pages.next().await {
+                let page = page.map_err(aws_sdk_ecr::Error::from)?;
+                names.extend(
+                    page.repositories()
+                        .iter()
+                        .filter_map(|r| r.repository_name())
+                        .map(|n| n.to_string()),
+                );
+            }
+            names
+        }
+    };
+
+    for repository in &repositories {
+        let response = server
+            .client
+            .describe_images()
+            .repository_name(repository)
+            .image_ids(
+                ImageIdentifier::builder()
+                    .image_digest(image_digest)
+                    .build(),
+            )
+            .send()
+            .await;
+        let detail = match response {
+            Ok(response) => response.image_details().first().cloned(),
+            // Keep looking in the other repositories
+            Err(e) if repository_name.is_none() && is_not_found(&e) => None,
+            Err(e) => return Err(aws_sdk_ecr::Error::from(e).into()),
+        };
+        let Some(detail) = detail else {
+            continue;
+        };
+
+        let scheme = &server.settings.scheme;
+        let mut scan = describe_findings(
+            &server.client,
+            detail.registry_id().unwrap_or_default(),
+            repository,
+            image_digest,
+            true,
+            server
+                .settings
+                .max_findings_per_image
+                .map(|max| max as usize),
+        )
+        .await?;
+        // Findings left out by --attack-vector, --cvss-min and --fixable-only are neither listed nor counted
+        if let Some(scan) = scan.as_mut() {
+            let filter = &server.settings.finding_filter;
+            scan.findings.retain(|finding| filter.matches(finding));
+        }
+        let findings: Vec<FindingRecord> = scan
+            .iter()
+            .flat_map(|s| &s.findings)
+            // Findings of the levels left out by --severities are not reported
+            .filter_map(|finding| {
+                let index = scheme.level_index(
+                    &finding.severity,
+                    finding.vendor_severity.as_deref(),
+                    finding.cvss_score,
+                )?;
+                Some((finding, index))
+            })
+            .map(|(finding, index)| FindingRecord {
+                name: finding.name.clone(),
+                finding_arn: finding.finding_arn.clone(),
+                severity: finding.severity.clone(),
+                level: scheme.levels[index].clone(),
+                vendor_severity: finding.vendor_severity.clone(),
+                cvss_score: finding.cvss_score,
+                cvss_vector: finding.cvss_vector.clone(),
+                first_observed_at: format_date(finding.first_observed_at.as_ref()),
+                vendor_created_at: format_date(finding.vendor_created_at.as_ref()),
+                vendor_updated_at: format_date(finding.vendor_updated_at.as_ref()),
+                packages: finding
+                    .packages
+                    .iter()
+                    .map(|p| PackageVersion {
+                        name: p.name.clone(),
+                        version: p.version.clone(),
+                    })
+                    .collect(),
+                references: finding.references.clone(),
+                remediation_hint: remediation_hint(finding),
+            })
+            .collect();
+        let counts = scan
+            .as_ref()
+            .map(|s| scheme.count_findings(&s.findings))
+            .unwrap_or_else(|| vec![0; scheme.levels.len()]);
+        let body = json!({
+            "repository_name": repository,
+            "image_digest": image_digest,
+            "image_tags": detail.image_tags(),
+            "image_scan_completed_at": scan.as_ref().and_then(|s| format_date(s.scan_completed_at.as_ref())),
+            "vulnerability_source_updated_at": scan.as_ref().and_then(|s| format_date(s.vulnerability_source_updated_at.as_ref())),
+            "severity_counts": scheme.levels.iter().cloned().zip(counts).collect::<IndexMap<_, _>>(),
+            "findings": findings,
+        });
+        return Ok(Some(body.to_string()));
+    }
+    Ok(None)
+}
+
+// Check if an image lookup failed because the image is not in the repository
+fn is_not_found<E, R>(error: &aws_sdk_ecr::error::SdkError<E, R>) -> bool
+where
+    E: aws_sdk_ecr::error::ProvideErrorMetadata,
+{
+    error
+        .as_service_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "ImageNotFoundException")
+}
+
+// Format an AWS date time as ISO-8601
+fn format_date(date: Option<&DateTime>) -> Option<String> {
+    date.and_then(|d| d.fmt(DateTimeFormat::DateTime).ok())
+}
+
+// Build an error response with a message
+fn error_response(status: u16, message: &str) -> Response {
+    Response {
+        status,
+        body: json!({ "error": { "message": message } }).to_string(),
+    }
+}
+
+// Write an HTTP/1.1 response and close the connection
+async fn write_response(stream: &mut TcpStream, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        431 => "Request Header Fields Too Large",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        // Other statuses get the generic phrase of their class
+        status if status >= 500 => "Server Error",
+        status if status >= 400 => "Client Error",
+        _ => "",
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// Decode %XX escapes of a URL component, e.g. repository namespaces
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,226 @@
+use crate::findings::Finding;
+use aws_sdk_ecr::primitives::DateTime;
+use aws_sdk_ecr::types::FindingSeverity;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// AWS severity labels, in the order of the report columns
+pub const AWS_SEVERITIES: [FindingSeverity; 6] = [
+    FindingSeverity::Critical,
+    FindingSeverity::High,
+    FindingSeverity::Medium,
+    FindingSeverity::Low,
+    FindingSeverity::Informational,
+    FindingSeverity::Undefined,
+];
+
+// Severity scheme used for the report columns: either the AWS labels or an
+// org-specific set of levels (e.g. P1-P4) computed with ordered rules
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeverityScheme {
+    // Levels, from the most to the least severe
+    pub levels: Vec<String>,
+    // Rules evaluated in order, the first matching rule gives the level
+    #[serde(default)]
+    pub rules: Vec<SeverityRule>,
+    // Level used when no rule matches, the least severe level if not set
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+// A mapping rule, every condition set on the rule must match
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeverityRule {
+    pub level: String,
+    // AWS severities (CRITICAL, HIGH, ...) matched by the rule
+    #[serde(default)]
+    pub severities: Vec<String>,
+    // Vendor severities (enhanced scanning) matched by the rule
+    #[serde(default)]
+    pub vendor_severities: Vec<String>,
+    // CVSS score range matched by the rule, bounds included
+    pub cvss_min: Option<f64>,
+    pub cvss_max: Option<f64>,
+}
+
+impl Default for SeverityScheme {
+    // Identity scheme, one level per AWS severity
+    fn default() -> Self {
+        SeverityScheme {
+            levels: AWS_SEVERITIES
+                .iter()
+                .map(|s| severity_label(s.as_str()))
+                .collect(),
+            rules: AWS_SEVERITIES
+                .iter()
+                .map(|s| SeverityRule {
+                    level: severity_label(s.as_str()),
+                    severities: vec![s.as_str().to_string()],
+                    vendor_severities: Vec::new(),
+                    cvss_min: None,
+                    cvss_max: None,
+                })
+                .collect(),
+            default: None,
+        }
+    }
+}
+
+impl SeverityScheme {
+    // Check the scheme only refers to declared levels
+    pub fn validate(&self) -> Result<(), String> {
+        if self.levels.is_empty() {
+            return Err("severity scheme must declare at least one level".to_string());
+        }
+        let unknown = self
+            .rules
+            .iter()
+            .map(|r| &r.level)
+            .chain(self.default.iter())
+            .find(|level| !self.levels.contains(level));
+        match unknown {
+            Some(level) => Err(format!("severity scheme uses undeclared level '{}'", level)),
+            None => Ok(()),
+        }
+    }
+
+    // True when rules need the detailed finding list rather than the summary counts
+    pub fn needs_findings(&self) -> bool {
+        self.rules.iter().any(|r| {
+            !r.vendor_severities.is_empty() || r.cvss_min.is_some() || r.cvss_max.is_some()
+        })
+    }
+
+    // Get the index of a level from its name, ignoring case
+    pub fn level_position(&self, name: &str) -> Option<usize> {
+        self.levels
+            .iter()
+            .position(|l| l.eq_ignore_ascii_case(name.trim()))
+    }
+
+    // Keep only some levels, in the given order, e.g. to hide the Informational
+    // and Undefined columns. Findings of the other levels are no longer counted
+    pub fn select(&self, names: &[String]) -> Result<Self, String> {
+        let mut levels: Vec<String> = Vec::new();
+        for name in names {
+            let Some(position) = self.level_position(name) else {
+                return Err(format!(
+                    "unknown severity level '{}', expected some of {}",
+                    name,
+                    self.levels.join(", ")
+                ));
+            };
+            let level = &self.levels[position];
+            if levels.contains(level) {
+                return Err(format!("severity level '{}' given twice", name));
+            }
+            levels.push(level.clone());
+        }
+        if levels.is_empty() {
+            return Err("at least one severity level is needed".to_string());
+        }
+        Ok(SeverityScheme {
+            levels,
+            rules: self.rules.clone(),
+            // Unmatched findings keep going to the least severe level of the full scheme
+            default: self.default.clone().or(self.levels.last().cloned()),
+        })
+    }
+
+    // Get the index of the level matching a finding, none when the level is
+    // not selected
+    pub fn level_index(
+        &self,
+        severity: &str,
+        vendor_severity: Option<&str>,
+        cvss: Option<f64>,
+    ) -> Option<usize> {
+        let level = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(severity, vendor_severity, cvss))
+            .map(|rule| &rule.level)
+            .or(self.default.as_ref());
+        match level {
+            Some(level) => self.levels.iter().position(|l| l == level),
+            None => Some(self.levels.len() - 1),
+        }
+    }
+
+    // Count findings per level from the AWS severity summary
+    pub fn count_summary(&self, severity_map: &HashMap<FindingSeverity, i32>) -> Vec<i64> {
+        let mut counts = vec![0; self.levels.len()];
+        for (severity, count) in severity_map {
+            if let Some(index) = self.level_index(severity.as_str(), None, None) {
+                counts[index] += *count as i64;
+            }
+        }
+        counts
+    }
+
+    // Count findings per level from the detailed finding list
+    pub fn count_findings(&self, findings: &[Finding]) -> Vec<i64> {
+        let mut counts = vec![0; self.levels.len()];
+        for finding in findings {
+            if let Some(index) = self.level_index(
+                &finding.severity,
+                finding.vendor_severity.as_deref(),
+                finding.cvss_score,
+            ) {
+                counts[index] += 1;
+            }
+        }
+        counts
+    }
+
+    // Date the earliest finding counted in a level was first observed on the
+    // image, enhanced scanning only
+    pub fn first_observed(&self, findings: &[Finding]) -> Option<DateTime> {
+        findings
+            .iter()
+            .filter(|finding| {
+                self.level_index(
+                    &finding.severity,
+                    finding.vendor_severity.as_deref(),
+                    finding.cvss_score,
+                )
+                .is_some()
+            })
+            .filter_map(|finding| finding.first_observed_at)
+            .filter(|date| date.secs() > 0)
+            .min_by_key(|date| date.secs())
+    }
+}
+
+impl SeverityRule {
+    // Check if all conditions of the rule match the finding
+    fn matches(&self, severity: &str, vendor_severity: Option<&str>, cvss: Option<f64>) -> bool {
+        let severity_ok = self.severities.is_empty()
+            || self
+                .severities
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(severity));
+        let vendor_ok = self.vendor_severities.is_empty()
+            || vendor_severity.is_some_and(|v| {
+                self.vendor_severities
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(v))
+            });
+        let cvss_ok = (self.cvss_min.is_none() && self.cvss_max.is_none())
+            || cvss.is_some_and(|score| {
+                self.cvss_min.is_none_or(|min| score >= min)
+                    && self.cvss_max.is_none_or(|max| score <= max)
+            });
+        severity_ok && vendor_ok && cvss_ok
+    }
+}
+
+// Turn an AWS severity (CRITICAL) into a column label (Critical)
+fn severity_label(severity: &str) -> String {
+    let lower = severity.to_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,236 @@
+use crate::errors::EXIT_FINDINGS;
+use crate::runid::RunIds;
+use crate::telemetry::{kms_client, s3_client};
+use aws_config::SdkConfig;
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::types::{KeyMetadata, KeyUsageType, MessageType, SigningAlgorithmSpec};
+use aws_smithy_types::base64;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::time::SystemTime;
+
+// Extension of the signature written next to a signed file
+pub const SIGNATURE_EXTENSION: &str = "sig";
+// Only digest the signatures are made of
+const DIGEST_ALGORITHM: &str = "SHA-256";
+// Signing algorithms of the KMS key specs on SHA-256 digests, in order of preference
+const SIGNING_ALGORITHMS: [SigningAlgorithmSpec; 3] = [
+    SigningAlgorithmSpec::EcdsaSha256,
+    SigningAlgorithmSpec::RsassaPssSha256,
+    SigningAlgorithmSpec::RsassaPkcs1V15Sha256,
+];
+
+// Signature of a report by a KMS asymmetric key, written to <report>.sig and
+// checked by the verify subcommand
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileSignature {
+    pub key_id: String, // ARN of the key
+    pub signing_algorithm: String,
+    pub digest_algorithm: String,
+    pub digest: String,    // Hexadecimal digest of the signed file
+    pub signature: String, // Base64 signature of the digest
+    pub signed_at: String,
+    #[serde(flatten)]
+    pub ids: RunIds,
+}
+
+// Function to sign the SHA-256 digest of a file with a KMS asymmetric key,
+// given by key ID, ARN or alias, with the first algorithm of the key able
+// to sign SHA-256 digests
+pub async fn sign_digest(
+    sdk_config: &SdkConfig,
+    key_id: &str,
+    digest: &[u8],
+    ids: &RunIds,
+) -> Result<FileSignature, Box<dyn Error>> {
+    let client = kms_client(sdk_config);
+    let key = describe_signing_key(&client, key_id).await?;
+    let Some(algorithm) = SIGNING_ALGORITHMS
+        .into_iter()
+        .find(|a| key.signing_algorithms().contains(a))
+    else {
+        return Err(format!("KMS key '{}' cannot sign SHA-256 digests", key_id).into());
+    };
+    let signed = client
+        .sign()
+        .key_id(key_id)
+        .message(Blob::new(digest))
+        .message_type(MessageType::Digest)
+        .signing_algorithm(algorithm.clone())
+        .send()
+        .await
+        .map_err(aws_sdk_kms::Error::from)?;
+    Ok(FileSignature {
+        key_id: signed.key_id().or(key.arn()).unwrap_or(key_id).to_string(),
+        signing_algorithm: algorithm.as_str().to_string(),
+        digest_algorithm: DIGEST_ALGORITHM.to_string(),
+        digest: hex(digest),
+        signature: base64::encode(
+            signed
+                .signature()
+                .ok_or("KMS answered without a signature")?
+                .as_ref(),
+        ),
+        signed_at: DateTime::from(SystemTime::now())
+            .fmt(DateTimeFormat::DateTime)
+            .unwrap_or_default(),
+        ids: ids.clone(),
+    })
+}
+
+// Function to describe a KMS key given by key ID, ARN or alias, which must be
+// an asymmetric key of usage SIGN_VERIFY
+async fn describe_signing_key(
+    client: &aws_sdk_kms::Client,
+    key_id: &str,
+) -> Result<KeyMetadata, Box<dyn Error>> {
+    let description = client
+        .describe_key()
+        .key_id(key_id)
+        .send()
+        .await
+        .map_err(aws_sdk_kms::Error::from)?;
+    let Some(key) = description.key_metadata else {
+        return Err(format!("KMS answered without the metadata of key '{}'", key_id).into());
+    };
+    if key.key_usage() != Some(&KeyUsageType::SignVerify) {
+        return Err(format!(
+            "KMS key '{}' is not an asymmetric key of usage SIGN_VERIFY",
+            key_id
+        )
+        .into());
+    }
+    Ok(key)
+}
+
+// Location of the signature of a file or S3 object, e.g. report.json.sig
+pub fn signature_location(location: &str) -> String {
+    format!("{}.{}", location, SIGNATURE_EXTENSION)
+}
+
+// Function to write a signature next to the signed file or S3 object
+pub async fn write_signature(
+    sdk_config: &SdkConfig,
+    location: &str,
+    signature: &FileSignature,
+) -> Result<(), Box<dyn Error>> {
+    let document = serde_json::to_string_pretty(signature)? + "\n";
+    match location.strip_prefix("s3://") {
+        Some(object) => {
+            let (bucket, key) = object.split_once('/').unwrap_or((object, ""));
+            s3_client(sdk_config)
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(document.into_bytes().into())
+                .content_type("application/json")
+                .set_metadata(Some(signature.ids.object_metadata()))
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+        }
+        None => std::fs::write(location, document)?,
+    }
+    Ok(())
+}
+
+// Function to read a local file or an S3 object
+pub async fn read_location(
+    sdk_config: &SdkConfig,
+    location: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let Some(object) = location.strip_prefix("s3://") else {
+        return Ok(std::fs::read(location)?);
+    };
+    let (bucket, key) = object.split_once('/').unwrap_or((object, ""));
+    let response = s3_client(sdk_config)
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    Ok(response.body.collect().await?.into_bytes().to_vec())
+}
+
+// Function to check a file against its signature: the signature must name
+// the expected key, given by key ID, ARN or alias, the digest of the file
+// must be the signed one, and KMS must find the signature valid for it.
+// Returns the exit code of the verify subcommand
+pub async fn verify_file(
+    sdk_config: &SdkConfig,
+    content: &[u8],
+    signature: &FileSignature,
+    expected_key_id: &str,
+) -> Result<u8, Box<dyn Error>> {
+    if signature.digest_algorithm != DIGEST_ALGORITHM {
+        return Err(format!(
+            "unsupported digest algorithm '{}'",
+            signature.digest_algorithm
+        )
+        .into());
+    }
+    // The key named by the signature is not trusted, anyone can sign with a key of their own
+    let client = kms_client(sdk_config);
+    let key = describe_signing_key(&client, expected_key_id).await?;
+    let key_arn = key.arn().unwrap_or_default();
+    if signature.key_id != key_arn {
+        println!(
+            "Invalid: signed by {}, not by the expected key {}",
+            signature.key_id, key_arn
+        );
+        return Ok(EXIT_FINDINGS);
+    }
+    let digest = Sha256::digest(content);
+    if hex(&digest) != signature.digest {
+        println!(
+            "Invalid: the SHA-256 digest of the file is {}, the signed digest is {}",
+            hex(&digest),
+            signature.digest
+        );
+        return Ok(EXIT_FINDINGS);
+    }
+    let verified = client
+        .verify()
+        .key_id(key_arn)
+        .message(Blob::new(digest.as_slice()))
+        .message_type(MessageType::Digest)
+        .signature(Blob::new(base64::decode(&signature.signature)?))
+        .signing_algorithm(SigningAlgorithmSpec::from(
+            signature.signing_algorithm.as_str(),
+        ))
+        .send()
+        .await
+        .map_err(aws_sdk_kms::Error::from);
+    match verified {
+        Ok(answer) if answer.signature_valid() => {
+            println!(
+                "Valid: signed by {} with {} at {} (run {})",
+                signature.key_id,
+                signature.signing_algorithm,
+                signature.signed_at,
+                signature.ids.run_id
+            );
+            Ok(0)
+        }
+        // KMS answers an error rather than false for signatures not matching
+        Ok(_) => {
+            println!("Invalid: KMS does not find the signature valid");
+            Ok(EXIT_FINDINGS)
+        }
+        Err(aws_sdk_kms::Error::KmsInvalidSignatureException(_)) => {
+            println!(
+                "Invalid: the signature was not made by {} for this digest",
+                signature.key_id
+            );
+            Ok(EXIT_FINDINGS)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
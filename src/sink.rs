@@ -0,0 +1,452 @@
+use crate::remediation::remediation_hint;
+use crate::report::ImageReport;
+use crate::runid::RunIds;
+use crate::telemetry::{firehose_client, logs_client};
+use aws_config::SdkConfig;
+use aws_sdk_cloudwatchlogs::types::InputLogEvent;
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_firehose::primitives::Blob;
+use aws_sdk_firehose::types::Record;
+use indexmap::IndexMap;
+use opentelemetry::context::FutureExt;
+use opentelemetry::Context;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+// Batch limits of PutLogEvents (events, bytes with 26 bytes of overhead per event)
+const MAX_LOG_EVENTS: usize = 10_000;
+const MAX_LOG_BATCH_BYTES: usize = 1_048_576;
+const LOG_EVENT_OVERHEAD: usize = 26;
+
+// Batch limits of PutRecordBatch (records, bytes)
+const MAX_FIREHOSE_RECORDS: usize = 500;
+const MAX_FIREHOSE_BATCH_BYTES: usize = 4 * 1024 * 1024;
+
+// Attempts to deliver the records rejected by Firehose in a batch
+const FIREHOSE_ATTEMPTS: usize = 3;
+
+// Lines written to a plugin before flushing its standard input
+const MAX_PLUGIN_LINES: usize = 1000;
+
+// Version of the layout of the finding lines, only bumped on breaking changes,
+// printed as a JSON Schema by the schema subcommand
+pub const FINDINGS_SCHEMA_VERSION: u32 = 1;
+
+// Error of the sink task, sent across threads
+type SinkError = Box<dyn Error + Send + Sync>;
+
+// Destination of the findings given with --findings-sink
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    // CloudWatch Logs group, and stream (one per run by default)
+    Logs {
+        group: String,
+        stream: Option<String>,
+    },
+    // Kinesis Data Firehose delivery stream
+    Firehose {
+        stream: String,
+    },
+    // Executable of the sink_plugins section of the configuration file
+    Plugin {
+        name: String,
+        plugin: SinkPlugin,
+    },
+}
+
+// Custom sink reading the finding lines on its standard input, e.g.
+// "ticketing": { "command": "/usr/local/bin/open-tickets", "args": ["--queue", "SEC"] }
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SinkPlugin {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl SinkTarget {
+    // Parse logs:<group>[:<stream>], firehose:<delivery stream> or plugin:<name>
+    pub fn parse(value: &str, plugins: &IndexMap<String, SinkPlugin>) -> Result<Self, String> {
+        if let Some(name) = value.strip_prefix("plugin:") {
+            let plugin = plugins.get(name).ok_or_else(|| {
+                format!(
+                    "'{}' is not a plugin of the sink_plugins section of the configuration file",
+                    name
+                )
+            })?;
+            return Ok(SinkTarget::Plugin {
+                name: name.to_string(),
+                plugin: plugin.clone(),
+            });
+        }
+        let target = match value.split_once(':') {
+            Some(("logs", destination)) => {
+                let (group, stream) = match destination.split_once(':') {
+                    Some((group, stream)) => (group, Some(stream.to_string())),
+                    None => (destination, None),
+                };
+                if group.is_empty() || stream.as_deref() == Some("") {
+                    None
+                } else {
+                    Some(SinkTarget::Logs {
+                        group: group.to_string(),
+                        stream,
+                    })
+                }
+            }
+            Some(("firehose", stream)) if !stream.is_empty() => Some(SinkTarget::Firehose {
+                stream: stream.to_string(),
+            }),
+            _ => None,
+        };
+        target.ok_or_else(|| {
+            format!(
+                "'{}' is not a logs:<group>[:<stream>], firehose:<stream> or plugin:<name> destination",
+                value
+            )
+        })
+    }
+
+    // Location of the sink, for the run manifest
+    pub fn location(&self) -> String {
+        match self {
+            SinkTarget::Logs { group, .. } => format!("logs:{}", group),
+            SinkTarget::Firehose { stream } => format!("firehose:{}", stream),
+            SinkTarget::Plugin { name, .. } => format!("plugin:{}", name),
+        }
+    }
+}
+
+// Finding line sent to the sink, with the image it was found on
+#[derive(Serialize)]
+struct FindingEvent<'a> {
+    schema_version: u32,
+    account: &'a str,
+    region: &'a str,
+    repository_name: &'a str,
+    image_digest: &'a str,
+    image_tags: &'a [String],
+    vulnerability_id: &'a str,
+    finding_arn: Option<&'a str>,
+    severity: &'a str,
+    vendor_severity: Option<&'a str>,
+    cvss_score: Option<f64>,
+    cvss_vector: Option<&'a str>,
+    packages: Vec<PackageEvent<'a>>,
+    references: &'a [String],
+    remediation_hint: Option<String>,
+    first_observed_at: Option<String>,
+    vendor_created_at: Option<String>,
+    vendor_updated_at: Option<String>,
+    image_scan_completed_at: Option<String>,
+    run_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct PackageEvent<'a> {
+    name: &'a str,
+    version: &'a str,
+}
+
+// Findings sink running next to the report, fed with NDJSON lines
+pub struct FindingsSink {
+    sender: UnboundedSender<String>,
+    ids: RunIds,
+    task: JoinHandle<Result<usize, SinkError>>,
+}
+
+// Handle sending finding lines to the sink, given to the report writer, with
+// the identifiers of the run the findings belong to
+#[derive(Clone)]
+pub struct SinkSender(UnboundedSender<String>, RunIds);
+
+impl FindingsSink {
+    // Start delivering findings to the target in the background
+    pub fn start(sdk_config: &SdkConfig, target: SinkTarget, ids: RunIds) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        let sdk_config = sdk_config.clone();
+        let task =
+            tokio::spawn(deliver(sdk_config, target, receiver).with_context(Context::current()));
+        FindingsSink { sender, ids, task }
+    }
+
+    pub fn sender(&self) -> SinkSender {
+        SinkSender(self.sender.clone(), self.ids.clone())
+    }
+
+    // Send the last batch once every sender is dropped, and get the number
+    // of findings delivered
+    pub async fn finish(self) -> Result<usize, Box<dyn Error>> {
+        drop(self.sender);
+        match self.task.await {
+            Ok(result) => result.map_err(|e| e as Box<dyn Error>),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl SinkSender {
+    // Queue the findings of an image, one JSON line each
+    pub fn send_image(&self, image: &ImageReport, region: &str) -> serde_json::Result<()> {
+        let Some(scan) = &image.scan else {
+            return Ok(());
+        };
+        let format_date =
+            |date: Option<DateTime>| date.and_then(|d| d.fmt(DateTimeFormat::DateTime).ok());
+        for finding in &scan.findings {
+            let event = FindingEvent {
+                schema_version: FINDINGS_SCHEMA_VERSION,
+                account: &image.registry_id,
+                region,
+                repository_name: &image.repository_name,
+                image_digest: &image.image_digest,
+                image_tags: &image.image_tags,
+                vulnerability_id: &finding.name,
+                finding_arn: finding.finding_arn.as_deref(),
+                severity: &finding.severity,
+                vendor_severity: finding.vendor_severity.as_deref(),
+                cvss_score: finding.cvss_score,
+                cvss_vector: finding.cvss_vector.as_deref(),
+                packages: finding
+                    .packages
+                    .iter()
+                    .map(|p| PackageEvent {
+                        name: &p.name,
+                        version: &p.version,
+                    })
+                    .collect(),
+                references: &finding.references,
+                remediation_hint: remediation_hint(finding),
+                first_observed_at: format_date(finding.first_observed_at),
+                vendor_created_at: format_date(finding.vendor_created_at),
+                vendor_updated_at: format_date(finding.vendor_updated_at),
+                image_scan_completed_at: format_date(scan.scan_completed_at),
+                run_id: &self.1.run_id,
+                correlation_id: self.1.correlation_id.as_deref(),
+            };
+            // The sink task reports its own errors once the run is over
+            let _ = self.0.send(serde_json::to_string(&event)?);
+        }
+        Ok(())
+    }
+}
+
+// Deliver the finding lines in batches as large as the service accepts
+async fn deliver(
+    sdk_config: SdkConfig,
+    target: SinkTarget,
+    mut receiver: UnboundedReceiver<String>,
+) -> Result<usize, SinkError> {
+    let (max_records, max_bytes, overhead) = match target {
+        SinkTarget::Logs { .. } => (MAX_LOG_EVENTS, MAX_LOG_BATCH_BYTES, LOG_EVENT_OVERHEAD),
+        // Each record gets its line feed, for NDJSON objects in S3
+        SinkTarget::Firehose { .. } => (MAX_FIREHOSE_RECORDS, MAX_FIREHOSE_BATCH_BYTES, 1),
+        SinkTarget::Plugin { .. } => (MAX_PLUGIN_LINES, usize::MAX, 1),
+    };
+    let mut destination = Destination::open(&sdk_config, target).await?;
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+    let mut delivered = 0;
+    while let Some(line) = receiver.recv().await {
+        if !batch.is_empty()
+            && (batch.len() == max_records || batch_bytes + line.len() + overhead > max_bytes)
+        {
+            delivered += destination.send(std::mem::take(&mut batch)).await?;
+            batch_bytes = 0;
+        }
+        batch_bytes += line.len() + overhead;
+        batch.push(line);
+    }
+    if !batch.is_empty() {
+        delivered += destination.send(batch).await?;
+    }
+    destination.close().await?;
+    Ok(delivered)
+}
+
+// Opened destination of the finding lines
+enum Destination {
+    Logs {
+        client: aws_sdk_cloudwatchlogs::Client,
+        group: String,
+        stream: String,
+    },
+    Firehose {
+        client: aws_sdk_firehose::Client,
+        stream: String,
+    },
+    Plugin {
+        name: String,
+        child: Child,
+        stdin: ChildStdin,
+    },
+}
+
+impl Destination {
+    // Create the log stream of the run, the log group must exist
+    async fn open(sdk_config: &SdkConfig, target: SinkTarget) -> Result<Self, SinkError> {
+        match target {
+            SinkTarget::Logs { group, stream } => {
+                let client = logs_client(sdk_config);
+                let stream = stream.unwrap_or_else(|| {
+                    let started = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    format!(
+                        "{}/{}-{}",
+                        env!("CARGO_PKG_NAME"),
+                        started.as_secs(),
+                        std::process::id()
+                    )
+                });
+                let created = client
+                    .create_log_stream()
+                    .log_group_name(&group)
+                    .log_stream_name(&stream)
+                    .send()
+                    .await;
+                // A stream given with --findings-sink may already exist
+                if let Err(e) = created {
+                    if !e
+                        .as_service_error()
+                        .is_some_and(|e| e.is_resource_already_exists_exception())
+                    {
+                        return Err(aws_sdk_cloudwatchlogs::Error::from(e).into());
+                    }
+                }
+                Ok(Destination::Logs {
+                    client,
+                    group,
+                    stream,
+                })
+            }
+            SinkTarget::Firehose { stream } => Ok(Destination::Firehose {
+                client: firehose_client(sdk_config),
+                stream,
+            }),
+            SinkTarget::Plugin { name, plugin } => {
+                // The standard output of the plugin goes to stderr, stdout is the report
+                let mut child = Command::new(&plugin.command)
+                    .args(&plugin.args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| format!("cannot start plugin '{}': {}", name, e))?;
+                if let Some(mut stdout) = child.stdout.take() {
+                    tokio::spawn(async move {
+                        let _ = tokio::io::copy(&mut stdout, &mut tokio::io::stderr()).await;
+                    });
+                }
+                let stdin = child.stdin.take().ok_or("plugin without standard input")?;
+                Ok(Destination::Plugin { name, child, stdin })
+            }
+        }
+    }
+
+    // Wait for a plugin to read every line, a plugin failing fails the sink
+    async fn close(self) -> Result<(), SinkError> {
+        if let Destination::Plugin {
+            name,
+            mut child,
+            stdin,
+        } = self
+        {
+            drop(stdin);
+            let status = child.wait().await?;
+            if !status.success() {
+                return Err(format!("plugin '{}' exited with {}", name, status).into());
+            }
+        }
+        Ok(())
+    }
+
+    // Send a batch of lines, and get the number of lines delivered
+    async fn send(&mut self, lines: Vec<String>) -> Result<usize, SinkError> {
+        let count = lines.len();
+        match self {
+            Destination::Logs {
+                client,
+                group,
+                stream,
+            } => {
+                // Every event of the batch has the time it is sent at
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as i64;
+                let events = lines
+                    .into_iter()
+                    .map(|message| {
+                        InputLogEvent::builder()
+                            .timestamp(timestamp)
+                            .message(message)
+                            .build()
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                client
+                    .put_log_events()
+                    .log_group_name(group.as_str())
+                    .log_stream_name(stream.as_str())
+                    .set_log_events(Some(events))
+                    .send()
+                    .await
+                    .map_err(aws_sdk_cloudwatchlogs::Error::from)?;
+            }
+            Destination::Plugin { name, stdin, .. } => {
+                write_lines(stdin, lines)
+                    .await
+                    .map_err(|e| format!("plugin '{}' stopped reading findings: {}", name, e))?;
+            }
+            Destination::Firehose { client, stream } => {
+                let mut records = lines
+                    .into_iter()
+                    .map(|line| Record::builder().data(Blob::new(line + "\n")).build())
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Retry the records rejected by Firehose, e.g. when throttled
+                for attempt in 1..=FIREHOSE_ATTEMPTS {
+                    let response = client
+                        .put_record_batch()
+                        .delivery_stream_name(stream.as_str())
+                        .set_records(Some(records.clone()))
+                        .send()
+                        .await
+                        .map_err(aws_sdk_firehose::Error::from)?;
+                    if response.failed_put_count() == 0 {
+                        break;
+                    }
+                    if attempt == FIREHOSE_ATTEMPTS {
+                        return Err(format!(
+                            "{} record(s) rejected by Firehose stream '{}'",
+                            response.failed_put_count(),
+                            stream
+                        )
+                        .into());
+                    }
+                    records = records
+                        .into_iter()
+                        .zip(response.request_responses())
+                        .filter(|(_, result)| result.error_code().is_some())
+                        .map(|(record, _)| record)
+                        .collect();
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+// Write a batch of lines to a plugin, one JSON object per line
+async fn write_lines(stdin: &mut ChildStdin, lines: Vec<String>) -> std::io::Result<()> {
+    let mut batch = lines.join("\n");
+    batch.push('\n');
+    stdin.write_all(batch.as_bytes()).await?;
+    stdin.flush().await
+}
@@ -0,0 +1,54 @@
+use crate::dates::parse_duration;
+use crate::findings::Finding;
+use crate::severity::SeverityScheme;
+use aws_sdk_ecr::primitives::DateTime;
+
+// Remediation SLA per severity level, given with --sla critical=7d,high=30d
+#[derive(Debug, Clone)]
+pub struct SlaPolicy {
+    // Index of the severity level and maximum age in seconds
+    limits: Vec<(usize, i64)>,
+}
+
+impl SlaPolicy {
+    // Parse the --sla value, levels are matched against the severity scheme
+    pub fn parse(value: &str, scheme: &SeverityScheme) -> Result<Self, String> {
+        let mut limits = Vec::new();
+        for entry in value.split(',').filter(|e| !e.trim().is_empty()) {
+            let (level, duration) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("invalid SLA '{}', expected <level>=<duration>", entry))?;
+            let index = scheme
+                .level_position(level)
+                .ok_or_else(|| format!("unknown severity level '{}' in SLA", level.trim()))?;
+            limits.push((index, parse_duration(duration)?));
+        }
+        Ok(SlaPolicy { limits })
+    }
+
+    // Count findings open for longer than the SLA of their severity level
+    pub fn count_breaches(
+        &self,
+        scheme: &SeverityScheme,
+        findings: &[Finding],
+        now: &DateTime,
+    ) -> u64 {
+        findings
+            .iter()
+            .filter(|finding| {
+                let Some(first_observed_at) = finding.first_observed_at else {
+                    return false;
+                };
+                let index = scheme.level_index(
+                    &finding.severity,
+                    finding.vendor_severity.as_deref(),
+                    finding.cvss_score,
+                );
+                self.limits
+                    .iter()
+                    .find(|(level, _)| Some(*level) == index)
+                    .is_some_and(|(_, max_age)| now.secs() - first_observed_at.secs() > *max_age)
+            })
+            .count() as u64
+    }
+}
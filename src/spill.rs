@@ -0,0 +1,154 @@
+use crate::report::ImageRecord;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Image records kept in memory by default before spilling to disk
+pub const DEFAULT_MAX_IMAGES_IN_MEMORY: usize = 10_000;
+
+// Number of temporary files named by the process, to name the next one
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+// Image records of a report document, visited in insertion order as many times
+// as a renderer needs
+pub trait ImageSource {
+    fn is_empty(&self) -> bool;
+    fn try_for_each(&self, f: &mut dyn FnMut(&ImageRecord) -> io::Result<()>) -> io::Result<()>;
+}
+
+impl ImageSource for Vec<ImageRecord> {
+    fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    fn try_for_each(&self, f: &mut dyn FnMut(&ImageRecord) -> io::Result<()>) -> io::Result<()> {
+        self.iter().try_for_each(f)
+    }
+}
+
+// Image records of a run, the first ones in memory and the others appended to a
+// temporary JSON lines file, so that memory does not grow with the registry
+pub struct ImageStore {
+    memory: Vec<ImageRecord>,
+    max_in_memory: usize,
+    spill: Option<SpillFile>,
+}
+
+// Temporary file holding the records over the memory bound
+struct SpillFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    records: usize,
+}
+
+impl ImageStore {
+    pub fn new(max_in_memory: usize) -> Self {
+        ImageStore {
+            memory: Vec::new(),
+            max_in_memory,
+            spill: None,
+        }
+    }
+
+    // Add a record, in memory while below the bound
+    pub fn push(&mut self, record: ImageRecord) -> io::Result<()> {
+        if self.memory.len() < self.max_in_memory {
+            self.memory.push(record);
+            return Ok(());
+        }
+        let spill = match &mut self.spill {
+            Some(spill) => spill,
+            None => self.spill.insert(SpillFile::create()?),
+        };
+        serde_json::to_writer(&mut spill.writer, &record)?;
+        spill.writer.write_all(b"\n")?;
+        spill.records += 1;
+        Ok(())
+    }
+
+    // Number of records spilled to disk
+    pub fn spilled(&self) -> usize {
+        self.spill.as_ref().map_or(0, |spill| spill.records)
+    }
+
+    // Write the buffered records to the spill file, before reading them back
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.spill {
+            Some(spill) => spill.writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // Get every record in memory, reading back the spilled ones
+    pub fn into_records(mut self) -> io::Result<Vec<ImageRecord>> {
+        self.flush()?;
+        let mut records = Vec::with_capacity(self.memory.len() + self.spilled());
+        self.try_for_each(&mut |record| {
+            records.push(record.clone());
+            Ok(())
+        })?;
+        Ok(records)
+    }
+}
+
+impl ImageSource for ImageStore {
+    fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.spilled() == 0
+    }
+
+    fn try_for_each(&self, f: &mut dyn FnMut(&ImageRecord) -> io::Result<()>) -> io::Result<()> {
+        self.memory.iter().try_for_each(&mut *f)?;
+        let Some(spill) = &self.spill else {
+            return Ok(());
+        };
+        let mut file = spill.writer.get_ref();
+        file.seek(SeekFrom::Start(0))?;
+        for line in BufReader::new(file).lines() {
+            f(&serde_json::from_str(&line?)?)?;
+        }
+        // Appends go on at the end of the file
+        file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+impl SpillFile {
+    // Create a new file in the temporary directory
+    fn create() -> io::Result<Self> {
+        let path = temporary_path("jsonl");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // Unlinked right away on Unix, so that nothing is left behind when the
+        // process exits early, the open file stays usable
+        #[cfg(unix)]
+        std::fs::remove_file(&path)?;
+        Ok(SpillFile {
+            path,
+            writer: BufWriter::new(file),
+            records: 0,
+        })
+    }
+}
+
+// Get a new file path in the temporary directory (TMPDIR on Unix)
+pub fn temporary_path(extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "{}-{}-{}.{}",
+        env!("CARGO_PKG_NAME"),
+        std::process::id(),
+        TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if cfg!(not(unix)) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::cli::Options;
+use crate::encoding::EncodedWriter;
+use crate::render::write_document;
+use crate::report::{ImageRecord, ImageReport, JsonReport};
+use crate::runid::RunIds;
+use crate::spill::ImageSource;
+use crate::telemetry::s3_client;
+use aws_config::SdkConfig;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+
+// Report of the repositories without the tag or the prefix of --split-by
+const UNASSIGNED: &str = "unassigned";
+
+// How --split-by groups the repositories, one report file per group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitBy {
+    // Value of a repository resource tag, e.g. repo-tag:team
+    RepositoryTag(String),
+    // Namespace of the repository name, e.g. team-a of team-a/payments-api
+    Prefix,
+}
+
+impl SplitBy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().split_once(':') {
+            Some(("repo-tag", key)) if !key.trim().is_empty() => {
+                Ok(SplitBy::RepositoryTag(key.trim().to_string()))
+            }
+            None if value.trim() == "prefix" => Ok(SplitBy::Prefix),
+            _ => Err(format!(
+                "unknown split '{}', expected 'repo-tag:<key>' or 'prefix'",
+                value
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> String {
+        match self {
+            SplitBy::RepositoryTag(key) => format!("repo-tag:{}", key),
+            SplitBy::Prefix => "prefix".to_string(),
+        }
+    }
+
+    pub fn needs_repository_tags(&self) -> bool {
+        matches!(self, SplitBy::RepositoryTag(_))
+    }
+
+    // Group of an image, also the name of its report file
+    fn group(&self, image: &ImageReport) -> String {
+        let group = match self {
+            SplitBy::RepositoryTag(key) => image.repository_tags.get(key).map(|v| v.trim()),
+            SplitBy::Prefix => image.repository_name.split_once('/').map(|(p, _)| p),
+        };
+        group
+            .filter(|g| !g.is_empty())
+            .map_or(UNASSIGNED.to_string(), file_name)
+    }
+}
+
+// Groups of the repositories of a run, filled as its images are reported
+#[derive(Debug, Clone)]
+pub struct SplitGroups {
+    split: SplitBy,
+    // Group of each repository, by its name in the report records
+    repositories: BTreeMap<String, String>,
+}
+
+impl SplitGroups {
+    pub fn new(split: SplitBy) -> Self {
+        SplitGroups {
+            split,
+            repositories: BTreeMap::new(),
+        }
+    }
+
+    // Group of an image, before its repository name is redacted
+    pub fn group(&self, image: &ImageReport) -> String {
+        self.split.group(image)
+    }
+
+    pub fn add(&mut self, repository_name: &str, group: String) {
+        self.repositories.insert(repository_name.to_string(), group);
+    }
+
+    fn groups(&self) -> BTreeSet<&str> {
+        self.repositories.values().map(|g| g.as_str()).collect()
+    }
+}
+
+// Image records of the repositories of one group
+struct GroupImages<'a> {
+    images: &'a dyn ImageSource,
+    groups: &'a SplitGroups,
+    group: &'a str,
+}
+
+impl ImageSource for GroupImages<'_> {
+    fn is_empty(&self) -> bool {
+        !self.groups.repositories.values().any(|g| g == self.group)
+    }
+
+    fn try_for_each(&self, f: &mut dyn FnMut(&ImageRecord) -> io::Result<()>) -> io::Result<()> {
+        self.images.try_for_each(&mut |image| match self
+            .groups
+            .repositories
+            .get(&image.repository_name)
+        {
+            Some(group) if group == self.group => f(image),
+            _ => Ok(()),
+        })
+    }
+}
+
+// Function to write the report of each group of --split-by, named by the
+// group, in a directory or an s3://bucket/prefix location, returns the
+// written locations
+pub async fn write_split_reports(
+    sdk_config: &SdkConfig,
+    destination: &str,
+    options: &Options,
+    report: &JsonReport,
+    images: &dyn ImageSource,
+    groups: &SplitGroups,
+    ids: &RunIds,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let s3 = destination.strip_prefix("s3://").map(|location| {
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        (bucket, prefix.trim_end_matches('/'), s3_client(sdk_config))
+    });
+    let mut locations = Vec::new();
+    for group in groups.groups() {
+        // Each report tells which group it is the report of
+        let mut group_report = JsonReport {
+            severity_levels: report.severity_levels.clone(),
+            metadata: report.metadata.clone(),
+            ..JsonReport::default()
+        };
+        if let Some(metadata) = &mut group_report.metadata {
+            metadata
+                .filters
+                .push(format!("--split-by {}={}", groups.split.as_str(), group));
+        }
+        let group_images = GroupImages {
+            images,
+            groups,
+            group,
+        };
+        let mut out = EncodedWriter::new(Vec::new(), options.encoding, options.line_ending);
+        write_document(
+            &group_report,
+            &group_images,
+            options.format,
+            &options.settings.labels,
+            &mut out,
+        )?;
+        out.flush()?;
+        let document = out.into_inner();
+        let file = format!("{}.{}", group, options.format.extension());
+        match &s3 {
+            Some((bucket, prefix, client)) => {
+                let key = match prefix.is_empty() {
+                    true => file,
+                    false => format!("{}/{}", prefix, file),
+                };
+                client
+                    .put_object()
+                    .bucket(*bucket)
+                    .key(&key)
+                    .body(document.into())
+                    .content_type(options.format.content_type())
+                    .set_metadata(Some(ids.object_metadata()))
+                    .send()
+                    .await
+                    .map_err(aws_sdk_s3::Error::from)?;
+                locations.push(format!("s3://{}/{}", bucket, key));
+            }
+            None => {
+                std::fs::create_dir_all(destination)?;
+                let path = Path::new(destination).join(file);
+                std::fs::write(&path, document)?;
+                locations.push(path.display().to_string());
+            }
+        }
+    }
+    Ok(locations)
+}
+
+// Tag values and prefixes as file names, other characters than letters,
+// digits, '-', '_' and '.' replaced with '_'
+fn file_name(group: &str) -> String {
+    let name: String = group
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // Leading dots would hide the file or name a parent directory
+    match name.trim_start_matches('.') {
+        "" => UNASSIGNED.to_string(),
+        name => name.to_string(),
+    }
+}
@@ -0,0 +1,193 @@
+use crate::telemetry::ssooidc_client;
+use aws_config::provider_config::ProviderConfig;
+use aws_config::sso::SsoCredentialsProvider;
+use aws_config::{Region, SdkConfig};
+use aws_sdk_ecr::config::SharedCredentialsProvider;
+use aws_sdk_ecr::primitives::{DateTime, DateTimeFormat};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Grant of the device authorization flow, and of the refresh of its tokens
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const REFRESH_TOKEN_GRANT: &str = "refresh_token";
+// Scope of the tokens, giving access to the accounts and roles of the user
+const ACCOUNT_ACCESS_SCOPE: &str = "sso:account:access";
+// Delay added to the polling interval when IAM Identity Center asks to slow down
+const SLOW_DOWN_DELAY: Duration = Duration::from_secs(5);
+
+// IAM Identity Center portal given with --sso-start-url, and the session of the
+// cached token given with --sso-session, the AWS CLI sso-session names
+pub struct SsoPortal {
+    pub start_url: String,
+    pub region: String,
+    pub session: Option<String>,
+}
+
+impl SsoPortal {
+    // Cache file of the token of the portal, where the AWS CLI and SDKs look
+    // for it: ~/.aws/sso/cache/<sha1 of the session name, or of the start URL>.json
+    fn cached_token_path(&self) -> Result<PathBuf, String> {
+        let home = std::env::var("HOME")
+            .or_else(|_| std::env::var("USERPROFILE"))
+            .map_err(|_| "no home directory to cache the SSO token in".to_string())?;
+        let identifier = self.session.as_deref().unwrap_or(&self.start_url);
+        let digest = Sha1::digest(identifier.as_bytes());
+        let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(PathBuf::from(home)
+            .join(".aws")
+            .join("sso")
+            .join("cache")
+            .join(format!("{}.json", name)))
+    }
+}
+
+// Function to sign in to the portal with the device authorization flow of the
+// login subcommand: the user confirms a code in a browser, and the token is
+// cached for the runs of the next hours, refreshed by the SDK with a session
+pub async fn login(sdk_config: &SdkConfig, portal: &SsoPortal) -> Result<(), Box<dyn Error>> {
+    let client = ssooidc_client(sdk_config, &portal.region);
+    let mut register = client
+        .register_client()
+        .client_name(env!("CARGO_PKG_NAME"))
+        .client_type("public")
+        .scopes(ACCOUNT_ACCESS_SCOPE);
+    // Tokens of a session come with a refresh token
+    if portal.session.is_some() {
+        register = register
+            .grant_types(DEVICE_CODE_GRANT)
+            .grant_types(REFRESH_TOKEN_GRANT)
+            .issuer_url(&portal.start_url);
+    }
+    let registration = register.send().await?;
+    let client_id = registration.client_id().unwrap_or_default();
+    let client_secret = registration.client_secret().unwrap_or_default();
+
+    let authorization = client
+        .start_device_authorization()
+        .client_id(client_id)
+        .client_secret(client_secret)
+        .start_url(&portal.start_url)
+        .send()
+        .await?;
+    let device_code = authorization.device_code().unwrap_or_default();
+    eprintln!(
+        "Open {} in a browser and confirm the code {}",
+        authorization
+            .verification_uri_complete()
+            .or(authorization.verification_uri())
+            .unwrap_or_default(),
+        authorization.user_code().unwrap_or_default()
+    );
+
+    // Poll until the user confirms the code, or the code expires
+    let mut interval = Duration::from_secs(authorization.interval().max(1) as u64);
+    let deadline =
+        tokio::time::Instant::now() + Duration::from_secs(authorization.expires_in().max(0) as u64);
+    let token = loop {
+        tokio::time::sleep(interval).await;
+        let response = client
+            .create_token()
+            .client_id(client_id)
+            .client_secret(client_secret)
+            .grant_type(DEVICE_CODE_GRANT)
+            .device_code(device_code)
+            .send()
+            .await;
+        match response {
+            Ok(token) => break token,
+            Err(e) if tokio::time::Instant::now() >= deadline => {
+                return Err(format!("the code expired before it was confirmed: {}", e).into())
+            }
+            Err(e) => match e.as_service_error() {
+                Some(error) if error.is_authorization_pending_exception() => {}
+                Some(error) if error.is_slow_down_exception() => interval += SLOW_DOWN_DELAY,
+                _ => return Err(e.into()),
+            },
+        }
+    };
+
+    let now = DateTime::from(std::time::SystemTime::now()).secs();
+    let expires_at = format_date(now + i64::from(token.expires_in()));
+    let mut cached = json!({
+        "startUrl": portal.start_url,
+        "region": portal.region,
+        "accessToken": token.access_token().unwrap_or_default(),
+        "expiresAt": expires_at,
+    });
+    if let Some(refresh_token) = token.refresh_token() {
+        cached["refreshToken"] = json!(refresh_token);
+        cached["clientId"] = json!(client_id);
+        cached["clientSecret"] = json!(client_secret);
+        cached["registrationExpiresAt"] =
+            json!(format_date(registration.client_secret_expires_at()));
+    }
+    let path = portal.cached_token_path()?;
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // The token gives access to every account of the user
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    writeln!(options.open(&path)?, "{:#}", cached)?;
+    eprintln!(
+        "Signed in to {}, token cached in {} until {}",
+        portal.start_url,
+        path.display(),
+        expires_at
+    );
+    Ok(())
+}
+
+// Function to make every call of the run with the credentials of a role of an
+// account of the portal, given with --sso-account-id and --sso-role-name, from
+// the token cached by login
+pub fn sso_credentials(
+    sdk_config: &SdkConfig,
+    portal: &SsoPortal,
+    account_id: &str,
+    role_name: &str,
+) -> Result<SdkConfig, String> {
+    let path = portal.cached_token_path()?;
+    if !path.exists() {
+        return Err(format!(
+            "no SSO token cached for {}, sign in with: {} login --sso-start-url {}{}",
+            portal.start_url,
+            env!("CARGO_PKG_NAME"),
+            portal.start_url,
+            portal
+                .session
+                .as_ref()
+                .map(|session| format!(" --sso-session {}", session))
+                .unwrap_or_default()
+        ));
+    }
+    let mut provider = SsoCredentialsProvider::builder()
+        .configure(&ProviderConfig::without_region().with_region(sdk_config.region().cloned()))
+        .start_url(&portal.start_url)
+        .region(Region::new(portal.region.clone()))
+        .account_id(account_id)
+        .role_name(role_name);
+    if let Some(session) = &portal.session {
+        provider = provider.session_name(session);
+    }
+    Ok(sdk_config
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(provider.build()))
+        .build())
+}
+
+fn format_date(secs: i64) -> String {
+    DateTime::from_secs(secs)
+        .fmt(DateTimeFormat::DateTime)
+        .unwrap_or_default()
+}
@@ -0,0 +1,202 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+// Upper bounds of the histogram buckets of findings per image
+const BUCKETS: [i64; 6] = [0, 1, 5, 10, 50, 100];
+// Width of the longest histogram bar
+const BAR_WIDTH: usize = 40;
+
+// Registry-wide distribution of findings, printed by the stats subcommand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsReport {
+    pub images: usize,
+    // Images without scan findings, left out of the distribution
+    pub unscanned_images: usize,
+    pub images_without_findings: usize,
+    pub images_without_findings_percent: f64,
+    pub levels: IndexMap<String, LevelStats>,
+    // Number of images per range of total findings, e.g. "2-5"
+    pub histogram: IndexMap<String, usize>,
+}
+
+// Distribution of the findings of one severity level over the scanned images
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelStats {
+    pub total: i64,
+    pub mean: f64,
+    pub median: i64,
+    pub p90: i64,
+    pub p95: i64,
+    pub max: i64,
+    pub images_with_findings_percent: f64,
+}
+
+// Distribution of the finding counts of the images seen so far, as the number
+// of images per count value: memory depends on the distinct counts, not on the
+// number of images
+#[derive(Debug, Default)]
+pub struct StatsAccumulator {
+    scanned_images: usize,
+    unscanned_images: usize,
+    levels: Vec<BTreeMap<i64, usize>>,
+    totals: BTreeMap<i64, usize>,
+}
+
+impl StatsAccumulator {
+    // Take the finding counts of a scanned image into account
+    pub fn add(&mut self, counts: &[i64]) {
+        if self.levels.len() < counts.len() {
+            self.levels.resize_with(counts.len(), BTreeMap::new);
+        }
+        for (values, count) in self.levels.iter_mut().zip(counts) {
+            *values.entry(*count).or_default() += 1;
+        }
+        *self.totals.entry(counts.iter().sum()).or_default() += 1;
+        self.scanned_images += 1;
+    }
+
+    // Count an image without scan findings
+    pub fn add_unscanned(&mut self) {
+        self.unscanned_images += 1;
+    }
+}
+
+impl StatsReport {
+    // Compute the statistics from the distribution of the scanned images
+    pub fn compute(levels: &[String], distribution: &StatsAccumulator) -> Self {
+        let scanned = distribution.scanned_images;
+        let unscanned_images = distribution.unscanned_images;
+        let totals = &distribution.totals;
+        let images_without_findings = totals.get(&0).copied().unwrap_or_default();
+
+        let no_values = BTreeMap::new();
+        let levels = levels
+            .iter()
+            .enumerate()
+            .map(|(index, level)| {
+                let values = distribution.levels.get(index).unwrap_or(&no_values);
+                let total = values.iter().map(|(v, n)| v * *n as i64).sum();
+                let with_findings: usize = values.range(1..).map(|(_, n)| n).sum();
+                let stats = LevelStats {
+                    total,
+                    mean: ratio(total as f64, scanned),
+                    median: percentile(values, scanned, 50.0),
+                    p90: percentile(values, scanned, 90.0),
+                    p95: percentile(values, scanned, 95.0),
+                    max: values.keys().next_back().copied().unwrap_or_default(),
+                    images_with_findings_percent: 100.0 * ratio(with_findings as f64, scanned),
+                };
+                (level.clone(), stats)
+            })
+            .collect();
+
+        // Count images per range of total findings, the last range being open
+        let mut histogram = IndexMap::new();
+        let mut lower = 0;
+        for upper in BUCKETS {
+            let label = if lower == upper {
+                upper.to_string()
+            } else {
+                format!("{}-{}", lower, upper)
+            };
+            let images = totals.range(lower..=upper).map(|(_, n)| n).sum();
+            histogram.insert(label, images);
+            lower = upper + 1;
+        }
+        let images = totals.range(lower..).map(|(_, n)| n).sum();
+        histogram.insert(format!("{}+", lower), images);
+
+        StatsReport {
+            images: scanned + unscanned_images,
+            unscanned_images,
+            images_without_findings,
+            images_without_findings_percent: 100.0 * ratio(images_without_findings as f64, scanned),
+            levels,
+            histogram,
+        }
+    }
+
+    // Print the statistics as text tables and a histogram
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "Images: {} ({} scanned, {} without scan findings)",
+            self.images,
+            self.images - self.unscanned_images,
+            self.unscanned_images
+        )?;
+        writeln!(
+            out,
+            "Images with zero findings: {} ({:.1}%)",
+            self.images_without_findings, self.images_without_findings_percent
+        )?;
+        writeln!(out)?;
+
+        let width = self
+            .levels
+            .keys()
+            .map(|l| l.len())
+            .max()
+            .unwrap_or(0)
+            .max(5);
+        writeln!(
+            out,
+            "{:<width$} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>9}",
+            "Level", "Total", "Mean", "Median", "P90", "P95", "Max", "Images %"
+        )?;
+        for (level, stats) in &self.levels {
+            writeln!(
+                out,
+                "{:<width$} {:>8} {:>8.2} {:>8} {:>8} {:>8} {:>8} {:>8.1}%",
+                level,
+                stats.total,
+                stats.mean,
+                stats.median,
+                stats.p90,
+                stats.p95,
+                stats.max,
+                stats.images_with_findings_percent
+            )?;
+        }
+        writeln!(out)?;
+
+        writeln!(out, "Findings per image:")?;
+        let largest = self.histogram.values().copied().max().unwrap_or(0);
+        for (range, images) in &self.histogram {
+            // Scale bars on the largest bucket, keeping non-empty buckets visible
+            let bar = match images {
+                0 => String::new(),
+                n => "#".repeat((n * BAR_WIDTH / largest).max(1)) + " ",
+            };
+            writeln!(out, "{:>8} | {}{}", range, bar, images)?;
+        }
+        Ok(())
+    }
+}
+
+// Nearest-rank percentile of the values counted in a distribution of count images
+fn percentile(values: &BTreeMap<i64, usize>, count: usize, percent: f64) -> i64 {
+    if count == 0 {
+        return 0;
+    }
+    let rank = ((percent / 100.0 * count as f64).ceil() as usize).clamp(1, count);
+    let mut seen = 0;
+    for (value, images) in values {
+        seen += images;
+        if seen >= rank {
+            return *value;
+        }
+    }
+    0
+}
+
+// Divide, zero when there is nothing to divide by
+fn ratio(value: f64, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        value / count as f64
+    }
+}
@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::fmt;
+
+// Number of repository names suggested for a mistyped name
+const MAX_SUGGESTIONS: usize = 3;
+
+// Error reported instead of the SDK error when a repository does not exist
+#[derive(Debug)]
+pub struct RepositoryNotFound {
+    repository_name: String,
+    suggestions: Vec<String>,
+}
+
+impl fmt::Display for RepositoryNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "repository '{}' does not exist", self.repository_name)?;
+        if !self.suggestions.is_empty() {
+            write!(f, ", did you mean: {}?", self.suggestions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RepositoryNotFound {}
+
+// Function to turn a RepositoryNotFoundException into an error suggesting
+// the closest existing repository names, None for any other error
+pub async fn repository_not_found(
+    client: &aws_sdk_ecr::Client,
+    repository_name: &str,
+    error: &(dyn Error + 'static),
+) -> Option<RepositoryNotFound> {
+    if !matches!(
+        error.downcast_ref::<aws_sdk_ecr::Error>(),
+        Some(aws_sdk_ecr::Error::RepositoryNotFoundException(_))
+    ) {
+        return None;
+    }
+
+    // List every repository name of the registry to match against
+    let mut names = Vec::new();
+    let mut pages = client.describe_repositories().into_paginator().send();
+    while let Some(page) = pages.next().await {
+        // Suggestions are best effort, stop at the first failure
+        let Ok(page) = page else {
+            break;
+        };
+        names.extend(
+            page.repositories()
+                .iter()
+                .filter_map(|r| r.repository_name())
+                .map(|n| n.to_string()),
+        );
+    }
+
+    Some(RepositoryNotFound {
+        repository_name: repository_name.to_string(),
+        suggestions: closest_names(repository_name, &names)
+            .into_iter()
+            .map(|n| n.to_string())
+            .collect(),
+    })
+}
+
+// Get the names close to the given one, closest first
+fn closest_names<'a>(name: &str, candidates: &'a [String]) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    // Allow about one typo every three characters
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let distance = edit_distance(&name, &lower);
+            // Partial names (e.g. without the namespace) are close too
+            let partial = lower.contains(&name) || name.contains(&lower);
+            (distance <= max_distance || partial).then_some((distance, candidate.as_str()))
+        })
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
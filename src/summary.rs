@@ -0,0 +1,73 @@
+use crate::manifest::RunManifest;
+use serde_json::{Map, Value};
+use std::error::Error;
+
+// Run health as key=value pairs, in the order of the summary line: repositories,
+// images, repositories without images, findings per severity level, errors,
+// duration, exit code, then the identifiers of the run
+fn summary_fields(manifest: &RunManifest) -> Vec<(String, Value)> {
+    let mut fields = vec![
+        ("repos".to_string(), manifest.repositories.processed.into()),
+        ("images".to_string(), manifest.images.into()),
+        (
+            "empty_repos".to_string(),
+            manifest.empty_repositories.into(),
+        ),
+    ];
+    fields.extend(
+        manifest
+            .findings
+            .iter()
+            .map(|(level, count)| (key_name(level), (*count).into())),
+    );
+    fields.push(("errors".to_string(), manifest.errors.len().into()));
+    fields.push((
+        "duration".to_string(),
+        manifest.duration_seconds.unwrap_or_default().into(),
+    ));
+    fields.push(("exit_code".to_string(), manifest.exit_code.into()));
+    fields.push(("run_id".to_string(), manifest.ids.run_id.clone().into()));
+    if let Some(correlation_id) = &manifest.ids.correlation_id {
+        fields.push(("correlation_id".to_string(), correlation_id.clone().into()));
+    }
+    fields
+}
+
+// Function to print the last stderr line of a run, e.g.
+// summary repos=42 images=310 empty_repos=2 critical=12 high=40 errors=1 duration=93s exit_code=1 run_id=3f2b8c1e-...
+pub fn print_summary(manifest: &RunManifest) {
+    let pairs: Vec<String> = summary_fields(manifest)
+        .into_iter()
+        .map(|(key, value)| match key.as_str() {
+            "duration" => format!("{}={:.0}s", key, value.as_f64().unwrap_or_default()),
+            _ => match value.as_str() {
+                Some(text) => format!("{}={}", key, text),
+                None => format!("{}={}", key, value),
+            },
+        })
+        .collect();
+    eprintln!("summary {}", pairs.join(" "));
+}
+
+// Function to write the fields of the summary line as a JSON object
+pub fn write_summary(path: &str, manifest: &RunManifest) -> Result<(), Box<dyn Error>> {
+    let object: Map<String, Value> = summary_fields(manifest)
+        .into_iter()
+        .map(|(key, value)| match key.as_str() {
+            "duration" => ("duration_seconds".to_string(), value),
+            _ => (key, value),
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&object)?;
+    std::fs::write(path, json + "\n")?;
+    Ok(())
+}
+
+// Key of a severity level, e.g. "critical" or "p1_urgent"
+fn key_name(level: &str) -> String {
+    level
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+// Repository resource tag condition given with --repo-tag key=value (or key)
+#[derive(Debug, Clone)]
+pub struct TagFilter {
+    key: String,
+    value: Option<String>,
+}
+
+impl TagFilter {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let (key, value) = match input.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim().to_string())),
+            None => (input.trim(), None),
+        };
+        if key.is_empty() {
+            return Err(format!(
+                "invalid tag filter '{}', expected <key>=<value>",
+                input
+            ));
+        }
+        Ok(TagFilter {
+            key: key.to_string(),
+            value,
+        })
+    }
+
+    // Check the tag is set, with the expected value if any
+    pub fn matches(&self, tags: &HashMap<String, String>) -> bool {
+        match (tags.get(&self.key), &self.value) {
+            (Some(actual), Some(expected)) => actual == expected,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+// Function to get the resource tags of a repository
+pub async fn fetch_repository_tags(
+    client: &aws_sdk_ecr::Client,
+    repository_arn: &str,
+) -> Result<HashMap<String, String>, aws_sdk_ecr::Error> {
+    let response = client
+        .list_tags_for_resource()
+        .resource_arn(repository_arn)
+        .send()
+        .await?;
+    Ok(response
+        .tags()
+        .iter()
+        .map(|tag| (tag.key().to_string(), tag.value().to_string()))
+        .collect())
+}
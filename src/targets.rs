@@ -0,0 +1,160 @@
+use crate::dates::parse_duration;
+use crate::report::ImageReport;
+use crate::severity::SeverityScheme;
+use crate::tags::TagFilter;
+use aws_sdk_ecr::primitives::DateTime;
+use serde::{Deserialize, Serialize};
+
+// Compliance target from the "targets" section of the configuration file, e.g.
+// { "name": "Prod scanned weekly", "repository_tag": "env=prod", "scanned_within": "7d", "min_percent": 95 }
+#[derive(Debug, Clone, Deserialize)]
+pub struct TargetConfig {
+    pub name: String,
+    // Images the target applies to: repository resource tag and minimum image age
+    #[serde(default)]
+    pub repository_tag: Option<String>,
+    #[serde(default)]
+    pub older_than: Option<String>,
+    // Conditions every compliant image meets
+    #[serde(default)]
+    pub scanned_within: Option<String>,
+    #[serde(default)]
+    pub no_findings: Option<String>,
+    // Share of the images that must be compliant, all of them if not set
+    #[serde(default)]
+    pub min_percent: Option<f64>,
+}
+
+// Compliance target resolved against the severity scheme
+#[derive(Debug, Clone)]
+pub struct Target {
+    name: String,
+    repository_tag: Option<TagFilter>,
+    older_than: Option<i64>,
+    scanned_within: Option<i64>,
+    // Index of the least severe level that must have no finding
+    no_findings: Option<usize>,
+    min_percent: f64,
+}
+
+// Outcome of a target over the run, reported in the run manifest
+#[derive(Debug, Serialize)]
+pub struct TargetResult {
+    pub name: String,
+    pub images: u64,
+    pub compliant_images: u64,
+    pub compliant_percent: f64,
+    pub min_percent: f64,
+    pub passed: bool,
+}
+
+impl Target {
+    // Check the target, levels are matched against the severity scheme
+    pub fn parse(config: &TargetConfig, scheme: &SeverityScheme) -> Result<Self, String> {
+        let duration = |value: &Option<String>| value.as_deref().map(parse_duration).transpose();
+        let no_findings = config
+            .no_findings
+            .as_deref()
+            .map(|level| {
+                scheme
+                    .level_position(level)
+                    .ok_or_else(|| format!("unknown severity level '{}'", level))
+            })
+            .transpose()?;
+        let target = Target {
+            name: config.name.clone(),
+            repository_tag: config
+                .repository_tag
+                .as_deref()
+                .map(TagFilter::parse)
+                .transpose()?,
+            older_than: duration(&config.older_than)?,
+            scanned_within: duration(&config.scanned_within)?,
+            no_findings,
+            min_percent: config.min_percent.unwrap_or(100.0),
+        };
+        if target.scanned_within.is_none() && target.no_findings.is_none() {
+            return Err("expected 'scanned_within' or 'no_findings' condition".to_string());
+        }
+        if !(0.0..=100.0).contains(&target.min_percent) {
+            return Err("'min_percent' must be between 0 and 100".to_string());
+        }
+        Ok(target)
+    }
+
+    // True when the target needs the resource tags of each repository
+    pub fn needs_repository_tags(&self) -> bool {
+        self.repository_tag.is_some()
+    }
+
+    // Check if the target applies to an image
+    pub fn selects(&self, image: &ImageReport, now: &DateTime) -> bool {
+        let tag_ok = self
+            .repository_tag
+            .as_ref()
+            .is_none_or(|filter| filter.matches(&image.repository_tags));
+        let age_ok = self.older_than.is_none_or(|min_age| {
+            image
+                .pushed_at
+                .is_some_and(|pushed_at| now.secs() - pushed_at.secs() > min_age)
+        });
+        tag_ok && age_ok
+    }
+
+    // Check if an image meets every condition of the target
+    pub fn complies(&self, image: &ImageReport, now: &DateTime) -> bool {
+        let scan_ok = self.scanned_within.is_none_or(|max_age| {
+            image
+                .scan
+                .as_ref()
+                .and_then(|scan| scan.scan_completed_at)
+                .is_some_and(|completed_at| now.secs() - completed_at.secs() <= max_age)
+        });
+        let findings_ok = self
+            .no_findings
+            .is_none_or(|index| image.counts.iter().take(index + 1).all(|count| *count == 0));
+        scan_ok && findings_ok
+    }
+}
+
+impl TargetResult {
+    // Start the evaluation of a target, passing while no image is selected
+    pub fn new(target: &Target) -> Self {
+        TargetResult {
+            name: target.name.clone(),
+            images: 0,
+            compliant_images: 0,
+            compliant_percent: 100.0,
+            min_percent: target.min_percent,
+            passed: true,
+        }
+    }
+
+    // Take an image selected by the target into account
+    pub fn record(&mut self, compliant: bool) {
+        self.images += 1;
+        if compliant {
+            self.compliant_images += 1;
+        }
+        self.compliant_percent = 100.0 * self.compliant_images as f64 / self.images as f64;
+        self.passed = self.compliant_percent >= self.min_percent;
+    }
+}
+
+// Print the pass/fail scorecard of the compliance targets
+pub fn print_scorecard(results: &[TargetResult]) {
+    if results.is_empty() {
+        return;
+    }
+    eprintln!("Compliance targets:");
+    for result in results {
+        eprintln!(
+            "  {}  {}: {:.1}% of {} image(s) compliant, target {}%",
+            if result.passed { "PASS" } else { "FAIL" },
+            result.name,
+            result.compliant_percent,
+            result.images,
+            result.min_percent
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use crate::errors::error_chain;
+use aws_config::retry::RetryConfig;
+use aws_config::Region;
+use aws_config::SdkConfig;
+use aws_sdk_ecr::config::interceptors::{
+    BeforeSerializationInterceptorContextRef, BeforeTransmitInterceptorContextRef,
+    FinalizerInterceptorContextRef,
+};
+use aws_sdk_ecr::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::orchestrator::Metadata;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Name of the instrumentation scope and of the reported service
+const SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+
+// Calls made to each AWS operation during the run, by service.operation name
+static API_CALLS: Mutex<BTreeMap<String, ApiCallStats>> = Mutex::new(BTreeMap::new());
+
+// Attempts of the failed AWS calls of the run, by request ID of their last
+// attempt (empty for the calls failing without a response)
+static FAILED_CALL_ATTEMPTS: Mutex<BTreeMap<String, u32>> = Mutex::new(BTreeMap::new());
+
+// Interval kept between the start of two AWS calls with --nice, and the
+// earliest start of the next call
+static CALL_PACING: Mutex<Option<(Duration, Instant)>> = Mutex::new(None);
+
+// Interval between two AWS calls with --nice
+const NICE_CALL_INTERVAL: Duration = Duration::from_millis(250);
+// Attempts of a throttled call with --nice, and the delay before the first retry
+const NICE_MAX_ATTEMPTS: u32 = 6;
+const NICE_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+// Calls to an AWS operation, with their attempts (retries included) and the
+// time spent waiting for them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApiCallStats {
+    pub operation: String,
+    pub calls: u64,
+    pub attempts: u64,
+    pub failed: u64,
+    pub duration_seconds: f64,
+}
+
+// Get the calls made so far, in service.operation order
+pub fn api_calls() -> Vec<ApiCallStats> {
+    let calls = API_CALLS.lock().unwrap_or_else(|e| e.into_inner());
+    calls.values().cloned().collect()
+}
+
+// Forget the calls of the previous run, before each run of --schedule
+pub fn reset_api_calls() {
+    API_CALLS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    FAILED_CALL_ATTEMPTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+// Get the attempts of a failed call, retries included, from its request ID
+pub fn failed_call_attempts(request_id: Option<&str>) -> Option<u32> {
+    let attempts = FAILED_CALL_ATTEMPTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    attempts.get(request_id.unwrap_or_default()).copied()
+}
+
+// Print the calls of the run on stderr with the share of the AWS time spent
+// in each operation, to tune filters and limits
+pub fn print_api_calls(calls: &[ApiCallStats]) {
+    let total_calls: u64 = calls.iter().map(|c| c.calls).sum();
+    let total_seconds: f64 = calls.iter().map(|c| c.duration_seconds).sum();
+    if total_calls == 0 {
+        return;
+    }
+    eprintln!("AWS API calls: {} in {:.2}s", total_calls, total_seconds);
+    for call in calls {
+        let share = if total_seconds > 0.0 {
+            call.duration_seconds / total_seconds * 100.0
+        } else {
+            0.0
+        };
+        let mut line = format!(
+            "  {:<40} {:>6} call(s) {:>8.2}s {:>3.0}%",
+            call.operation, call.calls, call.duration_seconds, share
+        );
+        if call.attempts > call.calls {
+            line += &format!(", {} retried", call.attempts - call.calls);
+        }
+        if call.failed > 0 {
+            line += &format!(", {} failed", call.failed);
+        }
+        eprintln!("{}", line);
+    }
+}
+
+// Update the calls of an operation
+pub fn record_call(operation: &str, update: impl FnOnce(&mut ApiCallStats)) {
+    let mut calls = API_CALLS.lock().unwrap_or_else(|e| e.into_inner());
+    let stats = calls
+        .entry(operation.to_string())
+        .or_insert_with(|| ApiCallStats {
+            operation: operation.to_string(),
+            ..ApiCallStats::default()
+        });
+    update(stats);
+}
+
+// Flushes the spans still buffered when dropped at the end of the process
+pub struct TelemetryGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Error exporting traces: {}", e);
+        }
+    }
+}
+
+// Function to export spans to an OTLP/HTTP collector, e.g. http://localhost:4318,
+// spans are discarded when this is not called
+pub fn init_tracing(endpoint: &str) -> Result<TelemetryGuard, Box<dyn Error>> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    };
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpBinary)
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(SERVICE_NAME)
+                .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+                .build(),
+        )
+        .build();
+    global::set_tracer_provider(provider.clone());
+    Ok(TelemetryGuard { provider })
+}
+
+// Start a span as a child of the current one, the returned context is attached
+// to the futures of the span with FutureExt::with_context
+pub fn start_span(name: &'static str, attributes: Vec<KeyValue>) -> Context {
+    let tracer = global::tracer(SERVICE_NAME);
+    let span = tracer
+        .span_builder(name)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &Context::current());
+    Context::current_with_span(span)
+}
+
+// Mark the span of a context as failed
+pub fn record_error(cx: &Context, e: &(dyn Error + 'static)) {
+    cx.span().set_status(Status::error(error_chain(e)));
+}
+
+// Function to load the AWS credentials and region, the region given with
+// --region selecting the endpoints and signing region of its partition. With
+// --nice, for shared accounts, the calls are made one at a time with a pause
+// between them and throttled calls are retried in the standard retry mode,
+// whatever the AWS_RETRY_MODE of the environment, with more attempts and a
+// longer backoff
+pub async fn load_sdk_config(region: Option<&str>, nice: bool) -> SdkConfig {
+    let mut loader = aws_config::from_env();
+    if let Some(region) = region {
+        loader = loader.region(Region::new(region.to_string()));
+    }
+    if nice {
+        *CALL_PACING.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((NICE_CALL_INTERVAL, Instant::now()));
+        loader = loader.retry_config(
+            RetryConfig::standard()
+                .with_max_attempts(NICE_MAX_ATTEMPTS)
+                .with_initial_backoff(NICE_INITIAL_BACKOFF),
+        );
+    }
+    loader.load().await
+}
+
+// Wait for the turn of a call with --nice. Interceptors are not async,
+// so the wait blocks the thread of the call, which the run awaits anyway
+fn wait_call_turn() {
+    let wait = {
+        let mut pacing = CALL_PACING.lock().unwrap_or_else(|e| e.into_inner());
+        let Some((interval, next)) = pacing.as_mut() else {
+            return;
+        };
+        let now = Instant::now();
+        let start = (*next).max(now);
+        *next = start + *interval;
+        start - now
+    };
+    std::thread::sleep(wait);
+}
+
+// ECR client recording a span for every AWS call
+pub fn ecr_client(sdk_config: &SdkConfig) -> aws_sdk_ecr::Client {
+    let config = aws_sdk_ecr::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_ecr::Client::from_conf(config)
+}
+
+// STS client recording a span for every AWS call
+pub fn sts_client(sdk_config: &SdkConfig) -> aws_sdk_sts::Client {
+    let config = aws_sdk_sts::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_sts::Client::from_conf(config)
+}
+
+// S3 client recording a span for every AWS call
+pub fn s3_client(sdk_config: &SdkConfig) -> aws_sdk_s3::Client {
+    let config = aws_sdk_s3::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_s3::Client::from_conf(config)
+}
+
+// CloudWatch client recording a span for every AWS call
+pub fn cloudwatch_client(sdk_config: &SdkConfig) -> aws_sdk_cloudwatch::Client {
+    let config = aws_sdk_cloudwatch::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_cloudwatch::Client::from_conf(config)
+}
+
+// CloudWatch Logs client recording a span for every AWS call
+pub fn logs_client(sdk_config: &SdkConfig) -> aws_sdk_cloudwatchlogs::Client {
+    let config = aws_sdk_cloudwatchlogs::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_cloudwatchlogs::Client::from_conf(config)
+}
+
+// ECS client recording a span for every AWS call
+pub fn ecs_client(sdk_config: &SdkConfig) -> aws_sdk_ecs::Client {
+    let config = aws_sdk_ecs::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_ecs::Client::from_conf(config)
+}
+
+// Lambda client recording a span for every AWS call
+pub fn lambda_client(sdk_config: &SdkConfig) -> aws_sdk_lambda::Client {
+    let config = aws_sdk_lambda::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_lambda::Client::from_conf(config)
+}
+
+// IAM Identity Center OIDC client of the region of the portal, recording a span
+// for every AWS call
+pub fn ssooidc_client(sdk_config: &SdkConfig, region: &str) -> aws_sdk_ssooidc::Client {
+    let config = aws_sdk_ssooidc::config::Builder::from(sdk_config)
+        .region(Region::new(region.to_string()))
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_ssooidc::Client::from_conf(config)
+}
+
+// Firehose client recording a span for every AWS call
+pub fn firehose_client(sdk_config: &SdkConfig) -> aws_sdk_firehose::Client {
+    let config = aws_sdk_firehose::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_firehose::Client::from_conf(config)
+}
+
+// KMS client recording a span for every AWS call
+pub fn kms_client(sdk_config: &SdkConfig) -> aws_sdk_kms::Client {
+    let config = aws_sdk_kms::config::Builder::from(sdk_config)
+        .interceptor(AwsCallSpans)
+        .build();
+    aws_sdk_kms::Client::from_conf(config)
+}
+
+// Span of an AWS call in progress, kept in the interceptor state of the call
+#[derive(Debug)]
+struct CallSpan {
+    cx: Context,
+    operation: String,
+    started: Instant,
+}
+
+impl Storable for CallSpan {
+    type Storer = StoreReplace<Self>;
+}
+
+// Attempts made so far by an AWS call
+#[derive(Debug)]
+struct CallAttempts(u32);
+
+impl Storable for CallAttempts {
+    type Storer = StoreReplace<Self>;
+}
+
+// SDK interceptor wrapping each AWS call, retries included, in a client span
+// and counting it in the API calls of the run
+#[derive(Debug)]
+struct AwsCallSpans;
+
+impl Intercept for AwsCallSpans {
+    fn name(&self) -> &'static str {
+        "AwsCallSpans"
+    }
+
+    // The operation metadata is not known yet before execution
+    fn read_before_serialization(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        // Retries are spaced by the backoff of the retry mode, and the wait for
+        // the turn of the call is left out of its span and duration
+        wait_call_turn();
+        let (service, operation) = cfg
+            .load::<Metadata>()
+            .map(|m| (m.service().to_string(), m.name().to_string()))
+            .unwrap_or_default();
+        let name = format!("{}.{}", service, operation);
+        let tracer = global::tracer(SERVICE_NAME);
+        let span = tracer
+            .span_builder(name.clone())
+            .with_kind(SpanKind::Client)
+            .with_attributes(vec![
+                KeyValue::new("rpc.system", "aws-api"),
+                KeyValue::new("rpc.service", service),
+                KeyValue::new("rpc.method", operation),
+            ])
+            .start_with_context(&tracer, &Context::current());
+        record_call(&name, |stats| stats.calls += 1);
+        cfg.interceptor_state().store_put(CallSpan {
+            cx: Context::current_with_span(span),
+            operation: name,
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+
+    fn read_before_attempt(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Some(call) = cfg.load::<CallSpan>() {
+            record_call(&call.operation, |stats| stats.attempts += 1);
+        }
+        let attempts = cfg.load::<CallAttempts>().map_or(0, |a| a.0);
+        cfg.interceptor_state()
+            .store_put(CallAttempts(attempts + 1));
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(call) = cfg.load::<CallSpan>() else {
+            return Ok(());
+        };
+        let failed = matches!(context.output_or_error(), Some(Err(_)));
+        record_call(&call.operation, |stats| {
+            stats.duration_seconds += call.started.elapsed().as_secs_f64();
+            stats.failed += u64::from(failed);
+        });
+        let span = call.cx.span();
+        let mut request_id = None;
+        if let Some(response) = context.response() {
+            span.set_attribute(KeyValue::new(
+                "http.response.status_code",
+                i64::from(response.status().as_u16()),
+            ));
+            let headers = response.headers();
+            request_id = headers
+                .get("x-amzn-requestid")
+                .or_else(|| headers.get("x-amz-request-id"));
+            if let Some(request_id) = request_id {
+                span.set_attribute(KeyValue::new("aws.request_id", request_id.to_string()));
+            }
+        }
+        // Retries of the failed calls are given in the structured errors
+        if failed {
+            let attempts = cfg.load::<CallAttempts>().map_or(1, |a| a.0);
+            FAILED_CALL_ATTEMPTS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(request_id.unwrap_or_default().to_string(), attempts);
+        }
+        if let Some(Err(e)) = context.output_or_error() {
+            let message = match e.as_operation_error() {
+                Some(operation_error) => error_chain(operation_error),
+                None => error_chain(e),
+            };
+            span.set_status(Status::error(message));
+        }
+        span.end();
+        Ok(())
+    }
+}
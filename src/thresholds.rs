@@ -0,0 +1,92 @@
+use crate::environments::matches_pattern;
+use crate::exemptions::ImageExemptions;
+use crate::severity::SeverityScheme;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+// Maximum number of findings allowed per image and severity level, given
+// with --fail-on critical,high=5 (a level alone allows no finding)
+#[derive(Debug, Clone)]
+pub struct Thresholds {
+    // Index of the severity level and maximum count
+    limits: Vec<(usize, i64)>,
+}
+
+impl Thresholds {
+    // Parse the --fail-on value, levels are matched against the severity scheme
+    pub fn parse(value: &str, scheme: &SeverityScheme) -> Result<Self, String> {
+        let mut limits = Vec::new();
+        for entry in value.split(',').filter(|e| !e.trim().is_empty()) {
+            let (level, max) = match entry.split_once('=') {
+                Some((level, max)) => {
+                    let max = max
+                        .trim()
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|max| *max >= 0)
+                        .ok_or_else(|| format!("invalid threshold '{}'", entry))?;
+                    (level, max)
+                }
+                None => (entry, 0),
+            };
+            let index = scheme
+                .level_position(level)
+                .ok_or_else(|| format!("unknown severity level '{}' in threshold", level.trim()))?;
+            limits.push((index, max));
+        }
+        Ok(Thresholds { limits })
+    }
+
+    // Build the thresholds of a repository_thresholds entry of the configuration
+    // file, a maximum count per severity level
+    pub fn from_levels(
+        levels: &IndexMap<String, i64>,
+        scheme: &SeverityScheme,
+    ) -> Result<Self, String> {
+        let limits = levels
+            .iter()
+            .map(|(level, max)| {
+                if *max < 0 {
+                    return Err(format!("invalid threshold '{}={}'", level, max));
+                }
+                scheme
+                    .level_position(level)
+                    .map(|index| (index, *max))
+                    .ok_or_else(|| format!("unknown severity level '{}' in threshold", level))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Thresholds { limits })
+    }
+
+    // Check if the finding counts of an image exceed any threshold
+    pub fn exceeded(&self, counts: &[i64]) -> bool {
+        self.limits
+            .iter()
+            .any(|(index, max)| counts.get(*index).is_some_and(|count| count > max))
+    }
+}
+
+// Thresholds failing the run, --fail-on for every image unless the
+// configuration file has thresholds of its own for its tags or repository
+#[derive(Debug, Clone, Default)]
+pub struct FailurePolicy {
+    pub global: Option<Thresholds>,
+    pub repositories: HashMap<String, Thresholds>,
+    // Image tag patterns and their thresholds, in configuration order
+    pub tags: Vec<(String, Thresholds)>,
+    // Images not failing the run until their exemption expires, with --exempt-images
+    pub exemptions: ImageExemptions,
+}
+
+impl FailurePolicy {
+    // Thresholds applying to an image, those of the first tag pattern matching
+    // one of its tags, then those of its repository, if any
+    pub fn thresholds(&self, repository_name: &str, image_tags: &[String]) -> Option<&Thresholds> {
+        self.tags
+            .iter()
+            .find(|(pattern, _)| image_tags.iter().any(|tag| matches_pattern(pattern, tag)))
+            .map(|(_, thresholds)| thresholds)
+            .or(self.repositories.get(repository_name))
+            .or(self.global.as_ref())
+    }
+}
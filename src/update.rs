@@ -0,0 +1,227 @@
+use crate::errors::{error_chain, EXIT_API_FAILURE, EXIT_AUTH_CONFIG, EXIT_OK};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+// GitHub repository publishing the releases, e.g. bartleboeuf/aws-ecr-scan-detail
+const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
+
+// Release of the GitHub releases API
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+// Function to print the latest release and whether it is newer than this binary
+pub async fn check_update() -> u8 {
+    let client = http_client();
+    match latest_release(&client).await {
+        Ok(release) => {
+            println!("Current version: v{}", env!("CARGO_PKG_VERSION"));
+            println!(
+                "Latest version:  {} ({})",
+                release.tag_name, release.html_url
+            );
+            if is_newer(&release.tag_name, env!("CARGO_PKG_VERSION")) {
+                println!(
+                    "An update is available, run '{} self-update' to install it",
+                    env!("CARGO_PKG_NAME")
+                );
+            } else {
+                println!("Up to date");
+            }
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("Error checking for updates: {}", error_chain(e.as_ref()));
+            EXIT_API_FAILURE
+        }
+    }
+}
+
+// Function to replace this binary with the one of the latest release for the
+// current platform, checked against its .sha256 asset
+pub async fn self_update() -> u8 {
+    let client = http_client();
+    let release = match latest_release(&client).await {
+        Ok(release) => release,
+        Err(e) => {
+            eprintln!("Error checking for updates: {}", error_chain(e.as_ref()));
+            return EXIT_API_FAILURE;
+        }
+    };
+    if !is_newer(&release.tag_name, env!("CARGO_PKG_VERSION")) {
+        println!(
+            "Already up to date (v{}, latest {})",
+            env!("CARGO_PKG_VERSION"),
+            release.tag_name
+        );
+        return EXIT_OK;
+    }
+    let name = asset_name();
+    let Some(asset) = release.assets.iter().find(|a| a.name == name) else {
+        eprintln!(
+            "Release {} has no '{}' binary for this platform, see {}",
+            release.tag_name, name, release.html_url
+        );
+        return EXIT_AUTH_CONFIG;
+    };
+    // An unverified binary never replaces the running one
+    let Some(checksum) = release
+        .assets
+        .iter()
+        .find(|a| a.name == format!("{}.sha256", name))
+    else {
+        eprintln!(
+            "Release {} has no '{}.sha256' checksum to verify the binary, not updating, see {}",
+            release.tag_name, name, release.html_url
+        );
+        return EXIT_API_FAILURE;
+    };
+    match install(&client, asset, checksum).await {
+        Ok(path) => {
+            println!("Updated {} to {}", path, release.tag_name);
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!(
+                "Error updating to {}: {}",
+                release.tag_name,
+                error_chain(e.as_ref())
+            );
+            // The binary location is not writable, e.g. installed by root
+            match e.downcast_ref::<io::Error>() {
+                Some(e) if e.kind() == io::ErrorKind::PermissionDenied => EXIT_AUTH_CONFIG,
+                _ => EXIT_API_FAILURE,
+            }
+        }
+    }
+}
+
+// HTTP client of the GitHub API, which needs a user agent
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()
+        .unwrap_or_default()
+}
+
+// URL of the GitHub API, GITHUB_API_URL for GitHub Enterprise
+pub fn github_api() -> String {
+    std::env::var("GITHUB_API_URL")
+        .unwrap_or("https://api.github.com".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+// Get the latest release, with GITHUB_TOKEN when set to avoid the rate limit
+// of anonymous calls
+async fn latest_release(client: &reqwest::Client) -> Result<Release, Box<dyn Error>> {
+    let api = github_api();
+    let repository = REPOSITORY.trim_start_matches("https://github.com/");
+    let url = format!("{}/repos/{}/releases/latest", api, repository);
+    let mut request = client
+        .get(url)
+        .header("Accept", "application/vnd.github+json");
+    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+    let body = request.send().await?.error_for_status()?.bytes().await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+// Name of the release binary of the current platform,
+// e.g. aws-ecr-scan-detail-x86_64-linux or aws-ecr-scan-detail-aarch64-macos
+fn asset_name() -> String {
+    format!(
+        "{}-{}-{}{}",
+        env!("CARGO_PKG_NAME"),
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+// Check if a release tag such as v1.2.0 is a later version than the current one
+fn is_newer(tag: &str, current: &str) -> bool {
+    let numbers = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|n| n.parse().unwrap_or(0))
+            .collect()
+    };
+    numbers(tag) > numbers(current)
+}
+
+// Download the binary next to the running one, then move it in its place so
+// that the running binary is never left half written, returns its path
+async fn install(
+    client: &reqwest::Client,
+    asset: &Asset,
+    checksum: &Asset,
+) -> Result<String, Box<dyn Error>> {
+    let binary = client
+        .get(&asset.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let expected = client
+        .get(&checksum.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    // sha256sum output, the digest followed by the file name
+    let expected = expected.split_whitespace().next().unwrap_or_default();
+    let actual: String = Sha256::digest(&binary)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(format!(
+            "checksum mismatch for '{}': expected {}, got {}",
+            asset.name, expected, actual
+        )
+        .into());
+    }
+
+    let current = std::env::current_exe()?;
+    let directory = current.parent().unwrap_or(Path::new("."));
+    let download = directory.join(format!(".{}.download", asset.name));
+    std::fs::write(&download, &binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&download, std::fs::Permissions::from_mode(0o755))?;
+    }
+    // A running binary cannot be replaced on Windows, only renamed
+    #[cfg(windows)]
+    std::fs::rename(&current, current.with_extension("old.exe"))?;
+    if let Err(e) = std::fs::rename(&download, &current) {
+        let _ = std::fs::remove_file(&download);
+        return Err(e.into());
+    }
+    Ok(current.display().to_string())
+}
@@ -0,0 +1,235 @@
+use crate::environments::matches_pattern;
+use indexmap::IndexMap;
+use reqwest::header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Registry of the upstream images given without a registry host
+const DOCKER_HUB: &str = "docker.io";
+// Registry API host of Docker Hub
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+
+// Manifest media types accepted by docker pull, the multi-platform index first
+// so that the digest is the one a mirroring tool copies
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.v2+json";
+
+// Upstream image of each mirrored repository, from the upstream_images section of
+// the configuration file, e.g. "mirror/nginx": "docker.io/library/nginx"
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamImages {
+    images: IndexMap<String, UpstreamImage>,
+    client: reqwest::Client,
+    // Pull tokens of the upstream repositories, requested once per run
+    tokens: Arc<Mutex<HashMap<String, String>>>,
+}
+
+// Image reference split into the registry API host and the repository name
+#[derive(Debug, Clone)]
+struct UpstreamImage {
+    host: String,
+    name: String,
+}
+
+impl UpstreamImages {
+    pub fn new(images: IndexMap<String, String>) -> Result<Self, String> {
+        let images = images
+            .into_iter()
+            .map(|(pattern, reference)| {
+                let image = UpstreamImage::parse(&reference)
+                    .ok_or_else(|| format!("'{}' is not an image reference", reference))?;
+                Ok((pattern, image))
+            })
+            .collect::<Result<_, String>>()?;
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("aws-ecr-scan-detail/", env!("CARGO_PKG_VERSION")))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+        Ok(UpstreamImages {
+            images,
+            client,
+            tokens: Arc::default(),
+        })
+    }
+
+    // True when the configuration file maps no repository to an upstream image
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    // Compare an image with the image of the same tag upstream: "same" when
+    // the digests match, "diverged" when they differ and "missing" when no tag
+    // of the image exists upstream, None for repositories without upstream image
+    // or untagged images
+    pub async fn compare(
+        &self,
+        repository_name: &str,
+        image_tags: &[String],
+        image_digest: &str,
+    ) -> Result<Option<&'static str>, Box<dyn Error>> {
+        let Some(image) = self
+            .images
+            .iter()
+            .find(|(pattern, _)| matches_pattern(pattern, repository_name))
+            .map(|(_, image)| image)
+        else {
+            return Ok(None);
+        };
+        if image_tags.is_empty() {
+            return Ok(None);
+        }
+        for tag in image_tags {
+            match self.manifest_digest(image, tag).await? {
+                Some(digest) if digest == image_digest => return Ok(Some("same")),
+                Some(_) => return Ok(Some("diverged")),
+                None => continue,
+            }
+        }
+        Ok(Some("missing"))
+    }
+
+    // Digest of a tag upstream, from a HEAD request on its manifest which does
+    // not count against the pull rate limits of Docker Hub
+    async fn manifest_digest(
+        &self,
+        image: &UpstreamImage,
+        tag: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let url = format!("{}/v2/{}/manifests/{}", image.base_url(), image.name, tag);
+        let key = format!("{}/{}", image.host, image.name);
+        let token = self
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .cloned();
+        let mut response = self.head_manifest(&url, token.as_deref()).await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            // Anonymous pull token from the realm of the authentication challenge
+            let challenge = response
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let token = self.pull_token(&challenge).await?;
+            response = self.head_manifest(&url, Some(&token)).await?;
+            self.tokens
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(key, token);
+        }
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(response
+                .headers()
+                .get("docker-content-digest")
+                .and_then(|h| h.to_str().ok())
+                .map(|digest| digest.to_string())),
+            status => Err(format!("{} answered {}", image.host, status).into()),
+        }
+    }
+
+    async fn head_manifest(
+        &self,
+        url: &str,
+        token: Option<&str>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self.client.head(url).header(ACCEPT, MANIFEST_TYPES);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request.send().await
+    }
+
+    // Request a token for a challenge, e.g.
+    // Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/nginx:pull"
+    async fn pull_token(&self, challenge: &str) -> Result<String, Box<dyn Error>> {
+        let parameters = challenge
+            .strip_prefix("Bearer ")
+            .ok_or_else(|| format!("unsupported authentication challenge '{}'", challenge))?;
+        let mut realm = None;
+        let mut query = Vec::new();
+        for parameter in parameters.split(',') {
+            if let Some((key, value)) = parameter.trim().split_once('=') {
+                let value = value.trim_matches('"');
+                match key {
+                    "realm" => realm = Some(value),
+                    _ => query.push((key, value)),
+                }
+            }
+        }
+        let realm = realm.ok_or("authentication challenge without realm")?;
+        let body = self
+            .client
+            .get(realm)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let response: serde_json::Value = serde_json::from_slice(&body)?;
+        response
+            .get("token")
+            .or_else(|| response.get("access_token"))
+            .and_then(|t| t.as_str())
+            .map(|t| t.to_string())
+            .ok_or_else(|| "no token in the authentication response".into())
+    }
+}
+
+impl UpstreamImage {
+    // Local registries are reached over plain HTTP, as docker does
+    fn base_url(&self) -> String {
+        let hostname = self.host.split(':').next().unwrap_or_default();
+        match hostname {
+            "localhost" | "127.0.0.1" => format!("http://{}", self.host),
+            _ => format!("https://{}", self.host),
+        }
+    }
+
+    // Parse an image reference without tag, e.g. nginx, bitnami/redis or
+    // ghcr.io/org/app, images without a registry host being on Docker Hub
+    fn parse(reference: &str) -> Option<Self> {
+        let reference = reference.trim();
+        if reference.is_empty()
+            || reference.contains('@')
+            || reference
+                .rsplit('/')
+                .next()
+                .is_some_and(|n| n.contains(':'))
+        {
+            return None;
+        }
+        let (host, name) = match reference.split_once('/') {
+            Some((host, name))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host, name.to_string())
+            }
+            _ => (DOCKER_HUB, reference.to_string()),
+        };
+        if host == DOCKER_HUB {
+            // Official images of Docker Hub are under library/
+            let name = match name.contains('/') {
+                true => name,
+                false => format!("library/{}", name),
+            };
+            return Some(UpstreamImage {
+                host: DOCKER_HUB_API.to_string(),
+                name,
+            });
+        }
+        Some(UpstreamImage {
+            host: host.to_string(),
+            name,
+        })
+    }
+}
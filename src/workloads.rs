@@ -0,0 +1,317 @@
+use crate::telemetry::{ecs_client, lambda_client};
+use aws_config::SdkConfig;
+use aws_sdk_ecs::types::DesiredStatus;
+use aws_sdk_lambda::types::PackageType;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read};
+
+// Prefixes of the image IDs of the container statuses, e.g. docker-pullable://
+const IMAGE_ID_SCHEMES: [&str; 2] = ["docker-pullable://", "docker://"];
+
+// Tasks described by one DescribeTasks call at most
+const MAX_TASKS_PER_REQUEST: usize = 100;
+
+// Services running the images, looked up with --usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageSource {
+    Ecs,
+    Lambda,
+}
+
+impl UsageSource {
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, String> {
+        let mut sources = Vec::new();
+        for name in value.split(',').map(|n| n.trim().to_lowercase()) {
+            let source = match name.as_str() {
+                "ecs" => UsageSource::Ecs,
+                "lambda" => UsageSource::Lambda,
+                _ => {
+                    return Err(format!(
+                        "unknown usage source '{}', expected 'ecs' or 'lambda'",
+                        name
+                    ))
+                }
+            };
+            if !sources.contains(&source) {
+                sources.push(source);
+            }
+        }
+        Ok(sources)
+    }
+}
+
+// Workloads running each image, by image digest, e.g. ecs:prod/payments-api
+// for the tasks of the payments-api service of the prod cluster
+#[derive(Debug, Clone, Default)]
+pub struct WorkloadUsage {
+    workloads: HashMap<String, Vec<String>>,
+}
+
+impl WorkloadUsage {
+    // Function to look up the workloads of the given sources, once per run
+    pub async fn collect(
+        sdk_config: &SdkConfig,
+        sources: &[UsageSource],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut usage = WorkloadUsage::default();
+        for source in sources {
+            match source {
+                UsageSource::Ecs => usage.add_ecs_tasks(sdk_config).await?,
+                UsageSource::Lambda => usage.add_lambda_functions(sdk_config).await?,
+            }
+        }
+        Ok(usage)
+    }
+
+    // Workloads running an image, sorted by name
+    pub fn workloads(&self, image_digest: &str) -> Vec<String> {
+        self.workloads
+            .get(image_digest)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn add(&mut self, image_digest: &str, workload: String) {
+        let workloads = self.workloads.entry(image_digest.to_string()).or_default();
+        if let Err(position) = workloads.binary_search(&workload) {
+            workloads.insert(position, workload);
+        }
+    }
+
+    // Running tasks of every ECS cluster of the region, by service or, for
+    // standalone tasks, by task group (family:name by default)
+    async fn add_ecs_tasks(&mut self, sdk_config: &SdkConfig) -> Result<(), aws_sdk_ecs::Error> {
+        let client = ecs_client(sdk_config);
+        let mut clusters = client.list_clusters().into_paginator().items().send();
+        while let Some(cluster_arn) = clusters.next().await {
+            let cluster_arn = cluster_arn?;
+            let cluster_name = cluster_arn
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let task_arns: Vec<String> = client
+                .list_tasks()
+                .cluster(&cluster_arn)
+                .desired_status(DesiredStatus::Running)
+                .into_paginator()
+                .items()
+                .send()
+                .collect::<Result<_, _>>()
+                .await?;
+            for chunk in task_arns.chunks(MAX_TASKS_PER_REQUEST) {
+                let response = client
+                    .describe_tasks()
+                    .cluster(&cluster_arn)
+                    .set_tasks(Some(chunk.to_vec()))
+                    .send()
+                    .await?;
+                for task in response.tasks() {
+                    let group = task.group().unwrap_or_default();
+                    let group = group.strip_prefix("service:").unwrap_or(group);
+                    for digest in task.containers().iter().filter_map(|c| c.image_digest()) {
+                        self.add(digest, format!("ecs:{}/{}", cluster_name, group));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Lambda functions of the region deployed as container images, whose
+    // resolved image URI gives the digest of their $LATEST version
+    async fn add_lambda_functions(
+        &mut self,
+        sdk_config: &SdkConfig,
+    ) -> Result<(), aws_sdk_lambda::Error> {
+        let client = lambda_client(sdk_config);
+        let mut functions = client.list_functions().into_paginator().items().send();
+        while let Some(function) = functions.next().await {
+            let function = function?;
+            if function.package_type() != Some(&PackageType::Image) {
+                continue;
+            }
+            let Some(function_name) = function.function_name() else {
+                continue;
+            };
+            let response = client
+                .get_function()
+                .function_name(function_name)
+                .send()
+                .await?;
+            let digest = response
+                .code()
+                .and_then(|code| code.resolved_image_uri())
+                .and_then(|uri| uri.split_once('@'))
+                .map(|(_, digest)| digest);
+            if let Some(digest) = digest {
+                self.add(digest, format!("lambda:{}", function_name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Images running in a cluster, given to k8s-correlate with --images-file, so
+// that only their findings are reported
+#[derive(Debug, Clone, Default)]
+pub struct RunningImages {
+    images: Vec<RunningImage>,
+    // References to other registries or regions, left out of the report
+    pub ignored: usize,
+}
+
+// Image of an ECR reference, e.g.
+// 123456789012.dkr.ecr.eu-west-1.amazonaws.com/payments-api:v2.3.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RunningImage {
+    registry_id: String,
+    repository_name: String,
+    tag: Option<String>,
+    digest: Option<String>,
+}
+
+impl RunningImages {
+    // Keep the ECR references of the region, every one of them without region
+    pub fn new(references: &[String], region: &str) -> Self {
+        let mut running = RunningImages::default();
+        for reference in references {
+            match RunningImage::parse(reference, region) {
+                Some(image) if !running.images.contains(&image) => running.images.push(image),
+                Some(_) => {}
+                None => running.ignored += 1,
+            }
+        }
+        running
+    }
+
+    // Repositories of the running images, in the order of the references
+    pub fn repositories(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for image in &self.images {
+            if !names.contains(&image.repository_name) {
+                names.push(image.repository_name.clone());
+            }
+        }
+        names
+    }
+
+    // Check if an image runs, from one of its tags or from its digest
+    pub fn runs(
+        &self,
+        registry_id: &str,
+        repository_name: &str,
+        image_tags: &[String],
+        image_digest: &str,
+    ) -> bool {
+        self.images.iter().any(|image| {
+            image.registry_id == registry_id
+                && image.repository_name == repository_name
+                && (image.digest.as_deref() == Some(image_digest)
+                    || image.digest.is_none()
+                        && image
+                            .tag
+                            .as_ref()
+                            .is_some_and(|tag| image_tags.contains(tag)))
+        })
+    }
+}
+
+impl RunningImage {
+    fn parse(reference: &str, region: &str) -> Option<Self> {
+        let mut reference = reference.trim();
+        for scheme in IMAGE_ID_SCHEMES {
+            reference = reference.strip_prefix(scheme).unwrap_or(reference);
+        }
+        let (host, path) = reference.split_once('/')?;
+        // Registry host of the account and region, e.g. 123456789012.dkr.ecr.eu-west-1.amazonaws.com
+        let mut labels = host.split('.');
+        let (registry_id, dkr, ecr, image_region) = (
+            labels.next()?,
+            labels.next()?,
+            labels.next()?,
+            labels.next()?,
+        );
+        if dkr != "dkr" || ecr != "ecr" || (!region.is_empty() && image_region != region) {
+            return None;
+        }
+        let (path, digest) = match path.split_once('@') {
+            Some((path, digest)) => (path, Some(digest.to_string())),
+            None => (path, None),
+        };
+        // A colon after the last slash separates the tag
+        let (repository_name, tag) = match path.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, Some(tag.to_string())),
+            _ => (path, None),
+        };
+        if repository_name.is_empty() {
+            return None;
+        }
+        Some(RunningImage {
+            registry_id: registry_id.to_string(),
+            repository_name: repository_name.to_string(),
+            // Without a tag nor digest, Kubernetes pulls the latest tag
+            tag: tag.or_else(|| digest.is_none().then(|| "latest".to_string())),
+            digest,
+        })
+    }
+}
+
+// Function to read the image references given with --images-file, from a file
+// or from stdin with '-': the pods of `kubectl get pods -o json`, whose container
+// statuses give the digests actually running, or one image URI per line,
+// ignoring blank lines and # comments
+pub fn read_image_references(path: &str) -> io::Result<Vec<String>> {
+    let mut content = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut content)?;
+    } else {
+        content = std::fs::read_to_string(path)?;
+    }
+    if !content.trim_start().starts_with('{') {
+        return Ok(content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect());
+    }
+    let document: Value = serde_json::from_str(&content)?;
+    // A pod list, or a single pod
+    let pods = match document.get("items").and_then(|items| items.as_array()) {
+        Some(items) => items.iter().collect(),
+        None => vec![&document],
+    };
+    let mut references = Vec::new();
+    for pod in pods {
+        let statuses = ["containerStatuses", "initContainerStatuses"]
+            .iter()
+            .filter_map(|field| pod.pointer(&format!("/status/{}", field)))
+            .filter_map(|statuses| statuses.as_array())
+            .flatten()
+            .collect::<Vec<_>>();
+        // Statuses give the digest pulled for a tag, containers not started yet
+        // only have their image
+        match statuses.is_empty() {
+            false => references.extend(
+                statuses
+                    .iter()
+                    .filter_map(|status| status.get("imageID").and_then(|id| id.as_str()))
+                    .filter(|id| !id.is_empty())
+                    .map(|id| id.to_string()),
+            ),
+            true => references.extend(
+                ["containers", "initContainers"]
+                    .iter()
+                    .filter_map(|field| pod.pointer(&format!("/spec/{}", field)))
+                    .filter_map(|containers| containers.as_array())
+                    .flatten()
+                    .filter_map(|container| container.get("image").and_then(|i| i.as_str()))
+                    .map(|image| image.to_string()),
+            ),
+        }
+    }
+    Ok(references)
+}